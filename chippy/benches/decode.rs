@@ -0,0 +1,23 @@
+use chippy::emu::instruction::Instruction;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn decode(c: &mut Criterion, name: &str, parse: fn(u16) -> Instruction) {
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            for opcode in 0..=u16::MAX {
+                black_box(parse(black_box(opcode)));
+            }
+        })
+    });
+}
+
+fn lookup_table(c: &mut Criterion) {
+    decode(c, "decode lookup table", Instruction::parse);
+}
+
+fn nibble_match(c: &mut Criterion) {
+    decode(c, "decode nibble match", Instruction::parse_nibbles);
+}
+
+criterion_group!(benches, lookup_table, nibble_match);
+criterion_main!(benches);
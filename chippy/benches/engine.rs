@@ -0,0 +1,32 @@
+use chippy::{
+    bench::WORKLOAD,
+    emu::{engine::Engine, vm::Vm},
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const CYCLES: usize = 10_000;
+
+fn run(c: &mut Criterion, name: &str, engine: Engine) {
+    let mut vm = Vm::new().with_engine(engine);
+    vm.load(WORKLOAD.to_vec());
+    c.bench_function(name, |b| b.iter(|| vm.run(CYCLES)));
+}
+
+fn match_engine(c: &mut Criterion) {
+    run(c, "match engine", Engine::Match);
+}
+
+fn table_engine(c: &mut Criterion) {
+    run(c, "table engine", Engine::Table);
+}
+
+#[cfg(feature = "cached-engine")]
+fn cached_engine(c: &mut Criterion) {
+    run(c, "cached engine", Engine::Cached);
+}
+
+#[cfg(not(feature = "cached-engine"))]
+criterion_group!(benches, match_engine, table_engine);
+#[cfg(feature = "cached-engine")]
+criterion_group!(benches, match_engine, table_engine, cached_engine);
+criterion_main!(benches);
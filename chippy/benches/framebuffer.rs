@@ -0,0 +1,71 @@
+use chippy::{
+    emu::framebuffer::Framebuffer,
+    render::{self, Palette},
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+
+fn display() -> Vec<bool> {
+    (0..WIDTH * HEIGHT).map(|index| index % 3 == 0).collect()
+}
+
+fn rgba(c: &mut Criterion) {
+    let display = display();
+    let frame = Framebuffer::from_display(&display, WIDTH, HEIGHT);
+    let palette = Palette::default();
+    let mut buffer = vec![0; frame.rgba_size()];
+
+    c.bench_function("rgba bool display", |b| {
+        b.iter(|| render::draw_rgba(black_box(&display), 1, palette, &mut buffer))
+    });
+    c.bench_function("rgba packed scalar", |b| {
+        b.iter(|| black_box(&frame).draw_rgba_scalar(palette, &mut buffer))
+    });
+    c.bench_function("rgba packed", |b| {
+        b.iter(|| black_box(&frame).draw_rgba(palette, &mut buffer))
+    });
+}
+
+fn diff(c: &mut Criterion) {
+    let display = display();
+    let a = Framebuffer::from_display(&display, WIDTH, HEIGHT);
+    let mut b = a.clone();
+    b.set(5, 40, true);
+
+    c.bench_function("diff bool display", |bench| {
+        bench.iter(|| {
+            black_box(&display)
+                .iter()
+                .zip(black_box(&display).iter())
+                .filter(|(a, b)| a != b)
+                .count()
+        })
+    });
+    c.bench_function("diff packed scalar", |bench| {
+        bench.iter(|| black_box(&a).changed_rows_scalar(black_box(&b)))
+    });
+    c.bench_function("diff packed", |bench| {
+        bench.iter(|| black_box(&a).changed_rows(black_box(&b)))
+    });
+}
+
+fn clear_and_scroll(c: &mut Criterion) {
+    let mut display = display();
+    let mut frame = Framebuffer::from_display(&display, WIDTH, HEIGHT);
+
+    c.bench_function("clear bool display", |b| {
+        b.iter(|| black_box(&mut display).fill(false))
+    });
+    c.bench_function("clear packed", |b| b.iter(|| black_box(&mut frame).clear()));
+    c.bench_function("scroll bool display", |b| {
+        b.iter(|| black_box(&mut display).copy_within(0..WIDTH * (HEIGHT - 4), WIDTH * 4))
+    });
+    c.bench_function("scroll packed", |b| {
+        b.iter(|| black_box(&mut frame).scroll_down(4))
+    });
+}
+
+criterion_group!(benches, rgba, diff, clear_and_scroll);
+criterion_main!(benches);
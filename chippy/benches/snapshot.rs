@@ -0,0 +1,86 @@
+use chippy::emu::{compress::SnapshotHistory, state::ENCODED_SIZE, vm::Vm};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::collections::VecDeque;
+
+const HISTORY: usize = 600;
+const CYCLES_PER_SNAPSHOT: usize = 10;
+
+fn vm() -> Vm {
+    let mut vm = Vm::new();
+    vm.load(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../roms/pong.ch8")).unwrap());
+    vm
+}
+
+fn run(vm: &mut Vm) {
+    for _ in 0..CYCLES_PER_SNAPSHOT {
+        vm.cycle().unwrap();
+    }
+}
+
+fn full_snapshots(c: &mut Criterion) {
+    c.bench_function("full snapshots", |b| {
+        b.iter_batched(
+            vm,
+            |mut vm| {
+                let mut history = VecDeque::with_capacity(HISTORY);
+                for _ in 0..HISTORY {
+                    run(&mut vm);
+                    history.push_back(vm.snapshot().encode());
+                }
+                history
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn compressed_snapshots(c: &mut Criterion) {
+    c.bench_function("compressed snapshots", |b| {
+        b.iter_batched(
+            vm,
+            |mut vm| {
+                let mut history = SnapshotHistory::new(HISTORY, 60);
+                for _ in 0..HISTORY {
+                    run(&mut vm);
+                    history.push(&vm.snapshot());
+                }
+                history
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let mut vm = vm();
+    let mut history = SnapshotHistory::new(HISTORY, 60);
+    for _ in 0..HISTORY {
+        run(&mut vm);
+        history.push(&vm.snapshot());
+    }
+    println!(
+        "{} snapshots: full {} bytes, compressed {} bytes",
+        HISTORY,
+        HISTORY * ENCODED_SIZE,
+        history.compressed_size()
+    );
+}
+
+fn restore_snapshot(c: &mut Criterion) {
+    let mut vm = vm();
+    let mut history = SnapshotHistory::new(HISTORY, 60);
+    for _ in 0..HISTORY {
+        run(&mut vm);
+        history.push(&vm.snapshot());
+    }
+
+    c.bench_function("restore compressed snapshot", |b| {
+        b.iter(|| history.get(30).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    full_snapshots,
+    compressed_snapshots,
+    restore_snapshot
+);
+criterion_main!(benches);
@@ -0,0 +1,117 @@
+//! Buzzer samples for audio callbacks that pull blocks of samples, such as a WebAudio worklet
+//! asking for 128 frames at a time. The buzzer plays the square wave of `wav::TONE`, or an XO-CHIP
+//! audio pattern of 128 bits played at the rate set by its pitch.
+
+use crate::wav::TONE;
+
+/// Pitch at which a pattern plays 4000 bits per second
+pub const DEFAULT_PITCH: u8 = 64;
+const DEFAULT_VOLUME: f32 = 0.25;
+
+/// Bits per second at which XO-CHIP plays a pattern of `pitch`
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// Streaming generator of the buzzer, keeping its phase across blocks so the tone does not click.
+#[derive(Debug, Clone)]
+pub struct Beeper {
+    sample_rate: f32,
+    volume: f32,
+    /// Pattern played instead of the tone, and its rate in bits per second
+    pattern: Option<([u8; 16], f32)>,
+    /// Position in the tone period, from 0 to 1, or in the pattern, from 0 to 128
+    phase: f32,
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1) as f32,
+            volume: DEFAULT_VOLUME,
+            pattern: None,
+            phase: 0.0,
+        }
+    }
+
+    /// Amplitude of the samples, from 0 to 1
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Play `pattern` at `pitch` instead of the tone
+    pub fn with_pattern(mut self, pattern: [u8; 16], pitch: u8) -> Self {
+        self.set_pattern(Some((pattern, pitch)));
+        self
+    }
+
+    /// Change the pattern, or go back to the tone with `None`
+    pub fn set_pattern(&mut self, pattern: Option<([u8; 16], u8)>) {
+        self.pattern = pattern.map(|(bits, pitch)| (bits, pattern_rate(pitch)));
+        self.phase = 0.0;
+    }
+
+    /// Fill `out` with the buzzer while `sound` is true and with silence otherwise
+    pub fn fill(&mut self, sound: bool, out: &mut [f32]) {
+        let (step, period) = match self.pattern {
+            Some((_, rate)) => (rate / self.sample_rate, 128.0),
+            None => (TONE as f32 / self.sample_rate, 1.0),
+        };
+        for sample in out.iter_mut() {
+            let high = match &self.pattern {
+                Some((bits, _)) => {
+                    let bit = self.phase as usize;
+                    bits[bit / 8] & (0x80 >> (bit % 8)) != 0
+                }
+                None => self.phase < 0.5,
+            };
+            *sample = match (sound, high) {
+                (false, _) => 0.0,
+                (true, true) => self.volume,
+                (true, false) => -self.volume,
+            };
+            self.phase = (self.phase + step) % period;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_blocks() {
+        let mut block = [1.0; 128];
+        Beeper::new(44000).fill(false, &mut block);
+        assert!(block.iter().all(|s| *s == 0.0));
+
+        // 100 samples per period, the second block carries on from the first
+        let mut beeper = Beeper::new(44000).with_volume(0.5);
+        let mut first = [0.0; 75];
+        let mut second = [0.0; 75];
+        beeper.fill(true, &mut first);
+        beeper.fill(true, &mut second);
+        assert_eq!((first[0], first[30], first[60]), (0.5, 0.5, -0.5));
+        assert_eq!((second[10], second[40]), (-0.5, 0.5));
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(pattern_rate(DEFAULT_PITCH), 4000.0);
+        assert!((pattern_rate(112) - 8000.0).abs() < 0.01);
+
+        // One bit per sample
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1010_0000;
+        let mut beeper = Beeper::new(4000).with_pattern(pattern, DEFAULT_PITCH);
+        let mut block = [0.0; 130];
+        beeper.fill(true, &mut block);
+        assert_eq!(block[..4], [0.25, -0.25, 0.25, -0.25]);
+        assert_eq!(block[128..], [0.25, -0.25]);
+
+        beeper.set_pattern(None);
+        beeper.fill(true, &mut block[..1]);
+        assert_eq!(block[0], 0.25);
+    }
+}
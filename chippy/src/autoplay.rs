@@ -0,0 +1,277 @@
+//! Input played without a player, for attract modes and demos: random key presses or a recording.
+//!
+//! A recording is a text file with one line per change of the keypad, giving the frame number and
+//! the held keys as a hex bitmask with one bit per key.
+//!
+//! ```text
+//! 0 0000
+//! 30 0020
+//! 45 0000
+//! ```
+//!
+//! Recordings can also be written by hand as an input script, compiled by `compile_script`.
+//! Statements are separated by `;` or new lines, `#` starts a comment. A statement gives the
+//! frame, absolute or `+N` after the previous statement, and a command: `press` or `release`
+//! keys, `release all`, or `tap` keys for one frame or `for N` frames.
+//!
+//! ```text
+//! frame 120: press 5; frame 130: release 5
+//! frame +10: tap 4 6 for 3   # turn for three frames
+//! ```
+
+use crate::{emu::input::Input, netplay::InputFrame, soak::Rng};
+use std::fmt::Write;
+
+/// Frames a random key is held for
+pub const RANDOM_HOLD_FRAMES: u64 = 12;
+
+#[derive(Debug, Clone)]
+pub enum Autoplay {
+    /// A random key, or none, held for `RANDOM_HOLD_FRAMES` frames at a time
+    Random { rng: Rng, keys: u16 },
+    /// Keypad changes ordered by frame
+    Recorded(Vec<InputFrame>),
+}
+
+impl Autoplay {
+    pub fn random(seed: u64) -> Self {
+        Autoplay::Random {
+            rng: Rng::new(seed),
+            keys: 0,
+        }
+    }
+
+    /// Parse a recording, the error is the number of the first invalid line
+    pub fn parse(text: &str) -> Result<Self, usize> {
+        let mut frames = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let (frame, keys) = line.trim().split_once(' ').ok_or(number + 1)?;
+                Ok(InputFrame {
+                    frame: frame.parse().map_err(|_| number + 1)?,
+                    keys: u16::from_str_radix(keys.trim(), 16).map_err(|_| number + 1)?,
+                })
+            })
+            .collect::<Result<Vec<_>, usize>>()?;
+        frames.sort_by_key(|frame| frame.frame);
+        Ok(Autoplay::Recorded(frames))
+    }
+
+    /// Compile an input script, the error is the number of the first invalid line
+    pub fn from_script(script: &str) -> Result<Self, usize> {
+        compile_script(script).map(Autoplay::Recorded)
+    }
+
+    /// Keys held during `frame`. Random input must be asked for every frame in order.
+    pub fn keys(&mut self, frame: u64) -> u16 {
+        match self {
+            Autoplay::Random { rng, keys } => {
+                if frame.is_multiple_of(RANDOM_HOLD_FRAMES) {
+                    *keys = match rng.next_u64() % 17 {
+                        16 => 0,
+                        key => 1 << key,
+                    };
+                }
+                *keys
+            }
+            Autoplay::Recorded(frames) => frames
+                .iter()
+                .take_while(|recorded| recorded.frame <= frame)
+                .last()
+                .map_or(0, |recorded| recorded.keys),
+        }
+    }
+
+    /// Replace the keypad state of `input` with the keys of `frame`
+    pub fn apply(&mut self, frame: u64, input: &mut Input) {
+        let keys = self.keys(frame);
+        InputFrame { frame, keys }.apply(input);
+    }
+}
+
+/// Compile an input script into the keypad changes of a recording, the error is the number of
+/// the first invalid line
+pub fn compile_script(script: &str) -> Result<Vec<InputFrame>, usize> {
+    // Keys pressed and released at a frame, in the order of the script
+    let mut events: Vec<(u64, u16, u16)> = Vec::new();
+    let mut last = 0;
+    for (number, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        for statement in line.split(';').map(str::trim) {
+            if statement.is_empty() {
+                continue;
+            }
+            let (frame, command) = parse_statement(statement, last).ok_or(number + 1)?;
+            last = frame;
+            let mut words = command.split_whitespace();
+            let action = words.next().ok_or(number + 1)?;
+            let mut keys = 0;
+            let mut hold = 1;
+            while let Some(word) = words.next() {
+                match word {
+                    "all" if action == "release" => keys = u16::MAX,
+                    "for" if action == "tap" => {
+                        hold = words
+                            .next()
+                            .and_then(|hold| hold.parse().ok())
+                            .filter(|hold| *hold > 0)
+                            .ok_or(number + 1)?;
+                    }
+                    key => match u8::from_str_radix(key, 16) {
+                        Ok(key) if key < 16 => keys |= 1 << key,
+                        _ => return Err(number + 1),
+                    },
+                }
+            }
+            if keys == 0 {
+                return Err(number + 1);
+            }
+            match action {
+                "press" => events.push((frame, keys, 0)),
+                "release" => events.push((frame, 0, keys)),
+                "tap" => {
+                    events.push((frame, keys, 0));
+                    events.push((frame + hold, 0, keys));
+                }
+                _ => return Err(number + 1),
+            }
+        }
+    }
+
+    events.sort_by_key(|(frame, _, _)| *frame);
+    let mut frames: Vec<InputFrame> = Vec::new();
+    let mut keys = 0;
+    for (frame, press, release) in events {
+        keys = (keys | press) & !release;
+        match frames.last_mut() {
+            Some(last) if last.frame == frame => last.keys = keys,
+            _ => frames.push(InputFrame { frame, keys }),
+        }
+    }
+    // Drop the frames that do not change the keypad
+    let mut held = 0;
+    frames.retain(|frame| {
+        let changed = frame.keys != held;
+        held = frame.keys;
+        changed
+    });
+    Ok(frames)
+}
+
+/// Split `frame N: command` into the frame and the command, `+N` counts from `last`
+fn parse_statement(statement: &str, last: u64) -> Option<(u64, &str)> {
+    let (frame, command) = statement.strip_prefix("frame")?.split_once(':')?;
+    let frame = frame.trim();
+    let frame = match frame.strip_prefix('+') {
+        Some(offset) => last.checked_add(offset.parse().ok()?)?,
+        None => frame.parse().ok()?,
+    };
+    Some((frame, command.trim()))
+}
+
+/// Write keypad changes in the recording format read by `Autoplay::parse`
+pub fn write_recording(frames: &[InputFrame]) -> String {
+    let mut text = String::new();
+    for frame in frames {
+        let _ = writeln!(text, "{} {:04x}", frame.frame, frame.keys);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded() {
+        let mut autoplay = Autoplay::parse("30 0020\n0 0000\n\n45 0000\n").unwrap();
+        assert_eq!(autoplay.keys(0), 0);
+        assert_eq!(autoplay.keys(30), 0x20);
+        assert_eq!(autoplay.keys(44), 0x20);
+        assert_eq!(autoplay.keys(45), 0);
+
+        let mut input = Input::new();
+        Autoplay::parse("0 0003").unwrap().apply(5, &mut input);
+        assert!(input.keys[0] && input.keys[1] && !input.keys[2]);
+
+        assert_eq!(Autoplay::parse("0 0000\n12\n").unwrap_err(), 2);
+        assert_eq!(Autoplay::parse("x 0000").unwrap_err(), 1);
+    }
+
+    #[test]
+    fn script() {
+        let frames = compile_script("frame 120: press 5; frame 130: release 5").unwrap();
+        assert_eq!(
+            frames,
+            vec![
+                InputFrame {
+                    frame: 120,
+                    keys: 0x20
+                },
+                InputFrame {
+                    frame: 130,
+                    keys: 0
+                },
+            ]
+        );
+
+        let script = "
+            # hold 1 and tap a
+            frame 10: press 1
+            frame +5: tap a for 2; frame +0: press 1
+            frame 40: release all
+        ";
+        let mut autoplay = Autoplay::from_script(script).unwrap();
+        let keys: Vec<u16> = [9, 10, 15, 16, 17, 40]
+            .iter()
+            .map(|f| autoplay.keys(*f))
+            .collect();
+        assert_eq!(keys, [0, 0x2, 0x402, 0x402, 0x2, 0]);
+
+        let recording = write_recording(&compile_script(script).unwrap());
+        assert_eq!(recording, "10 0002\n15 0402\n17 0002\n40 0000\n");
+        let mut parsed = Autoplay::parse(&recording).unwrap();
+        assert_eq!(parsed.keys(16), 0x402);
+    }
+
+    #[test]
+    fn script_errors() {
+        assert_eq!(
+            compile_script("frame 1: press 5\nframe 2 release 5"),
+            Err(2)
+        );
+        assert_eq!(compile_script("frame x: press 5"), Err(1));
+        assert_eq!(compile_script("frame 1: hold 5"), Err(1));
+        assert_eq!(compile_script("frame 1: press 10"), Err(1));
+        assert_eq!(compile_script("frame 1: press"), Err(1));
+        assert_eq!(compile_script("frame 1: press all"), Err(1));
+        assert_eq!(compile_script("frame 1: tap 5 for 0"), Err(1));
+        assert_eq!(
+            compile_script("frame 1: press 5 ; ; # done"),
+            Ok(vec![InputFrame {
+                frame: 1,
+                keys: 0x20
+            }])
+        );
+    }
+
+    #[test]
+    fn random_holds_keys() {
+        let mut autoplay = Autoplay::random(7);
+        let keys: Vec<u16> = (0..RANDOM_HOLD_FRAMES * 4)
+            .map(|frame| autoplay.keys(frame))
+            .collect();
+        for held in keys.chunks(RANDOM_HOLD_FRAMES as usize) {
+            assert!(held.iter().all(|keys| *keys == held[0]));
+            assert!(held[0].count_ones() <= 1);
+        }
+
+        let mut again = Autoplay::random(7);
+        let replayed: Vec<u16> = (0..keys.len() as u64)
+            .map(|frame| again.keys(frame))
+            .collect();
+        assert_eq!(replayed, keys);
+    }
+}
@@ -0,0 +1,189 @@
+//! Throughput of the interpreter, reported as JSON and compared against a baseline report so
+//! automated runs can fail on performance regressions.
+//!
+//! ```text
+//! {"cycles_per_second": 81234567, "decodes_per_second": 301234567, "snapshots_per_second": 412345}
+//! ```
+
+use crate::emu::{
+    instruction::Instruction,
+    json::{self, Value},
+    vm::Vm,
+};
+use std::{
+    fmt,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// Rom run when no rom is given: an endless loop of arithmetic, draws, calls and returns
+pub const WORKLOAD: [u8; 18] = [
+    0x70, 0x01, // add v0, 0x01
+    0x81, 0x04, // add v1, v0
+    0xF0, 0x29, // ld f, v0
+    0xD1, 0x25, // drw v1, v2, 5
+    0x22, 0x10, // call 0x210
+    0x12, 0x00, // jp 0x200
+    0x00, 0x00, 0x00, 0x00, // padding
+    0x00, 0xEE, // ret
+];
+
+/// Operations of a kind completed per second, higher is better
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub name: String,
+    pub value: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub metrics: Vec<Metric>,
+}
+
+impl Report {
+    /// Measure every metric for `duration`, running `rom`
+    pub fn run(rom: &[u8], duration: Duration) -> Self {
+        let mut vm = Vm::new();
+        vm.load(rom.to_vec());
+        let cycles = measure(duration, || {
+            for _ in 0..1000 {
+                let _ = vm.cycle();
+            }
+            1000
+        });
+        let decodes = measure(duration, || {
+            for opcode in 0..=u16::MAX {
+                black_box(Instruction::parse(black_box(opcode)));
+            }
+            0x10000
+        });
+        let snapshots = measure(duration, || {
+            black_box(vm.snapshot().encode());
+            1
+        });
+
+        let metric = |name: &str, value| Metric {
+            name: name.to_string(),
+            value,
+        };
+        Self {
+            metrics: vec![
+                metric("cycles_per_second", cycles),
+                metric("decodes_per_second", decodes),
+                metric("snapshots_per_second", snapshots),
+            ],
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.metrics
+            .iter()
+            .find(|metric| metric.name == name)
+            .map(|metric| metric.value)
+    }
+
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .metrics
+            .iter()
+            .map(|metric| format!("\"{}\": {}", metric.name, metric.value))
+            .collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    /// Read a report written by `Report::to_json`
+    pub fn from_json(src: &str) -> Result<Self, String> {
+        let fields = json::parse_object(src)
+            .map_err(|offset| format!("Invalid JSON at offset {}", offset))?;
+        let metrics = fields
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::Number(value) => Ok(Metric { name, value }),
+                Value::Array(_) => Err(format!("Invalid value for metric: {}", name)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { metrics })
+    }
+
+    /// Change of every metric also found in `baseline`
+    pub fn compare(&self, baseline: &Report) -> Vec<Delta> {
+        self.metrics
+            .iter()
+            .filter_map(|metric| {
+                Some(Delta {
+                    name: metric.name.clone(),
+                    baseline: baseline.get(&metric.name)?,
+                    current: metric.value,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A metric of a report and of its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub name: String,
+    pub baseline: u64,
+    pub current: u64,
+}
+
+impl Delta {
+    /// Change from the baseline in percent, negative when slower
+    pub fn percent(&self) -> f64 {
+        match self.baseline {
+            0 => 0.0,
+            baseline => (self.current as f64 - baseline as f64) / baseline as f64 * 100.0,
+        }
+    }
+
+    /// True if the metric got worse by more than `threshold` percent
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        self.percent() < -threshold
+    }
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<22} {:>12} -> {:>12} {:+7.1}%",
+            self.name,
+            self.baseline,
+            self.current,
+            self.percent()
+        )
+    }
+}
+
+/// Operations per second done by `step`, which returns the number of operations it did
+fn measure(duration: Duration, mut step: impl FnMut() -> u64) -> u64 {
+    let start = Instant::now();
+    let mut operations = 0;
+    while start.elapsed() < duration {
+        operations += step();
+    }
+    (operations as f64 / start.elapsed().as_secs_f64()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_and_compare() {
+        let report = Report::run(&WORKLOAD, Duration::from_millis(5));
+        assert_eq!(report.metrics.len(), 3);
+        assert!(report.get("cycles_per_second").unwrap() > 0);
+        assert_eq!(Report::from_json(&report.to_json()), Ok(report.clone()));
+
+        let baseline = Report::from_json(r#"{"cycles_per_second": 100, "removed": 1}"#).unwrap();
+        let current = Report::from_json(r#"{"cycles_per_second": 90, "added": 1}"#).unwrap();
+        let deltas = current.compare(&baseline);
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].percent(), -10.0);
+        assert!(deltas[0].is_regression(5.0));
+        assert!(!deltas[0].is_regression(10.0));
+        assert!(Report::from_json(r#"{"cycles_per_second": [1]}"#).is_err());
+    }
+}
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+pub type ExprResult<T> = std::result::Result<T, ExprError>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ExprError {
+    #[error("Invalid number: {0}")]
+    InvalidNumber(String),
+
+    #[error("Unknown identifier: {0}")]
+    UnknownIdentifier(String),
+
+    #[error("Unexpected character: {0}")]
+    UnexpectedCharacter(char),
+
+    #[error("Unexpected token: {0}")]
+    UnexpectedToken(String),
+
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+}
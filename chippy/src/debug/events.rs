@@ -0,0 +1,148 @@
+//! Machine events written as JSON lines, one object per event, for external analysis.
+//!
+//! ```text
+//! {"event":"draw","cycle":412,"frame":412,"address":522}
+//! ```
+
+use super::{trigger::Trigger, Inspect};
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A draw instruction was executed
+    Draw,
+    /// The sound timer started
+    Beep,
+    /// The program checked or waited for a key
+    KeyPoll,
+    /// The program stopped
+    Halt,
+    /// The debugger stopped on a breakpoint
+    Breakpoint,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventKind::Draw => "draw",
+            EventKind::Beep => "beep",
+            EventKind::KeyPoll => "key_poll",
+            EventKind::Halt => "halt",
+            EventKind::Breakpoint => "breakpoint",
+        }
+    }
+
+    /// Events fired by the step from `before` to `after`
+    pub fn of_step(before: &impl Inspect, after: &impl Inspect) -> Vec<EventKind> {
+        [
+            (Trigger::Draw, EventKind::Draw),
+            (Trigger::Sound, EventKind::Beep),
+            (Trigger::KeyPolled, EventKind::KeyPoll),
+        ]
+        .iter()
+        .filter(|(trigger, _)| trigger.is_hit(before, after))
+        .map(|(_, kind)| *kind)
+        .collect()
+    }
+
+    /// Events the instruction at the program counter of `vm` fires when executed
+    pub fn of_next(vm: &impl Inspect) -> Vec<EventKind> {
+        // None of these triggers look at the machine after the step
+        EventKind::of_step(vm, vm)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    /// Instructions executed before the event
+    pub cycle: u64,
+    pub frame: u64,
+    /// Address of the instruction that fired the event
+    pub address: u16,
+}
+
+impl fmt::Display for Event {
+    /// The event as a single line JSON object
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r#"{{"event":"{}","cycle":{},"frame":{},"address":{}}}"#,
+            self.kind.as_str(),
+            self.cycle,
+            self.frame,
+            self.address
+        )
+    }
+}
+
+/// Writer of JSON line events.
+pub struct EventLog<W: Write> {
+    out: W,
+}
+
+impl<W: Write> EventLog<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn write(&mut self, event: &Event) -> io::Result<()> {
+        writeln!(self.out, "{}", event)
+    }
+
+    /// Write the events fired by the step from `before` to `after`
+    pub fn step(
+        &mut self,
+        cycle: u64,
+        frame: u64,
+        before: &impl Inspect,
+        after: &impl Inspect,
+    ) -> io::Result<()> {
+        for kind in EventKind::of_step(before, after) {
+            self.write(&Event {
+                kind,
+                cycle,
+                frame,
+                address: before.program_counter(),
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn step_events() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 5
+            0xF0, 0x18, // ld st, v0
+            0xD0, 0x05, // drw v0, v0, 5
+            0xE0, 0x9E, // skp v0
+        ]);
+
+        let mut log = EventLog::new(Vec::new());
+        for cycle in 0..4 {
+            let before = vm.snapshot();
+            vm.cycle().unwrap();
+            log.step(cycle, cycle / 2, &before, &vm).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(log.out).unwrap(),
+            "{\"event\":\"beep\",\"cycle\":1,\"frame\":0,\"address\":514}\n\
+             {\"event\":\"draw\",\"cycle\":2,\"frame\":1,\"address\":516}\n\
+             {\"event\":\"key_poll\",\"cycle\":3,\"frame\":1,\"address\":518}\n"
+        );
+    }
+}
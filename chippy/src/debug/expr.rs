@@ -0,0 +1,430 @@
+//! Expressions over the machine state, used for breakpoint conditions.
+//!
+//! ```text
+//! v3 == 0x1F && I > 0x300
+//! [i + 2] != 0 || (dt == 0 && !st)
+//! ```
+//!
+//! Operands are numbers (decimal, `0x` hex or `0b` binary), the registers `v0` to `vf`, `i`,
+//! `pc`, `sp`, `dt`, `st` and memory bytes `[address]`. Comparisons and logical operators
+//! evaluate to 1 or 0.
+
+use super::{
+    error::{ExprError, ExprResult},
+    Inspect,
+};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+impl BinaryOp {
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Eq | BinaryOp::Ne => 6,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 7,
+            BinaryOp::Add | BinaryOp::Sub => 8,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+        }
+    }
+
+    fn apply(&self, lhs: u32, rhs: u32) -> u32 {
+        match self {
+            BinaryOp::Or => (lhs != 0 || rhs != 0) as u32,
+            BinaryOp::And => (lhs != 0 && rhs != 0) as u32,
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::Eq => (lhs == rhs) as u32,
+            BinaryOp::Ne => (lhs != rhs) as u32,
+            BinaryOp::Lt => (lhs < rhs) as u32,
+            BinaryOp::Le => (lhs <= rhs) as u32,
+            BinaryOp::Gt => (lhs > rhs) as u32,
+            BinaryOp::Ge => (lhs >= rhs) as u32,
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(u32),
+    Register(u8),
+    Index,
+    ProgramCounter,
+    StackPointer,
+    DelayTimer,
+    SoundTimer,
+    /// Byte in memory at the address
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(src: &str) -> ExprResult<Expr> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr(0)?;
+        match parser.peek() {
+            Some(token) => Err(ExprError::UnexpectedToken(token.to_string())),
+            None => Ok(expr),
+        }
+    }
+
+    pub fn eval(&self, target: &impl Inspect) -> u32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => target.register(*register) as u32,
+            Expr::Index => target.index() as u32,
+            Expr::ProgramCounter => target.program_counter() as u32,
+            Expr::StackPointer => target.stack_pointer() as u32,
+            Expr::DelayTimer => target.delay_timer() as u32,
+            Expr::SoundTimer => target.sound_timer() as u32,
+            Expr::Memory(address) => target.memory(address.eval(target) as u16) as u32,
+            Expr::Unary(UnaryOp::Not, expr) => (expr.eval(target) == 0) as u32,
+            Expr::Unary(UnaryOp::Neg, expr) => expr.eval(target).wrapping_neg(),
+            Expr::Binary(op, lhs, rhs) => op.apply(lhs.eval(target), rhs.eval(target)),
+        }
+    }
+
+    /// Evaluate the expression as a condition, any nonzero value is true
+    pub fn is_true(&self, target: &impl Inspect) -> bool {
+        self.eval(target) != 0
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expr::parse(s)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(value) => write!(f, "0x{:X}", value),
+            Expr::Register(register) => write!(f, "v{:X}", register),
+            Expr::Index => write!(f, "i"),
+            Expr::ProgramCounter => write!(f, "pc"),
+            Expr::StackPointer => write!(f, "sp"),
+            Expr::DelayTimer => write!(f, "dt"),
+            Expr::SoundTimer => write!(f, "st"),
+            Expr::Memory(address) => write!(f, "[{}]", address),
+            Expr::Unary(UnaryOp::Not, expr) => write!(f, "!{}", expr),
+            Expr::Unary(UnaryOp::Neg, expr) => write!(f, "-{}", expr),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.as_str(), rhs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(u32),
+    Ident(String),
+    Op(BinaryOp),
+    Not,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Ident(ident) => write!(f, "{}", ident),
+            Token::Op(op) => write!(f, "{}", op.as_str()),
+            Token::Not => write!(f, "!"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+        }
+    }
+}
+
+fn tokenize(src: &str) -> ExprResult<Vec<Token>> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let next = chars.get(pos + 1).copied();
+
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            let word: String = chars[start..pos].iter().collect::<String>().to_lowercase();
+            tokens.push(match c.is_ascii_digit() {
+                true => Token::Number(parse_number(&word)?),
+                false => Token::Ident(word),
+            });
+            continue;
+        }
+
+        let (token, len) = match (c, next) {
+            ('|', Some('|')) => (Token::Op(BinaryOp::Or), 2),
+            ('&', Some('&')) => (Token::Op(BinaryOp::And), 2),
+            ('=', Some('=')) => (Token::Op(BinaryOp::Eq), 2),
+            ('!', Some('=')) => (Token::Op(BinaryOp::Ne), 2),
+            ('<', Some('=')) => (Token::Op(BinaryOp::Le), 2),
+            ('>', Some('=')) => (Token::Op(BinaryOp::Ge), 2),
+            ('|', _) => (Token::Op(BinaryOp::BitOr), 1),
+            ('^', _) => (Token::Op(BinaryOp::BitXor), 1),
+            ('&', _) => (Token::Op(BinaryOp::BitAnd), 1),
+            ('<', _) => (Token::Op(BinaryOp::Lt), 1),
+            ('>', _) => (Token::Op(BinaryOp::Gt), 1),
+            ('+', _) => (Token::Op(BinaryOp::Add), 1),
+            ('-', _) => (Token::Op(BinaryOp::Sub), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('[', _) => (Token::LBracket, 1),
+            (']', _) => (Token::RBracket, 1),
+            (c, _) => return Err(ExprError::UnexpectedCharacter(c)),
+        };
+        tokens.push(token);
+        pos += len;
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> ExprResult<u32> {
+    let result = if let Some(hex) = word.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(bin) = word.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2)
+    } else {
+        word.parse()
+    };
+    result.map_err(|_| ExprError::InvalidNumber(word.to_string()))
+}
+
+fn parse_ident(ident: &str) -> ExprResult<Expr> {
+    let expr = match ident {
+        "i" => Expr::Index,
+        "pc" => Expr::ProgramCounter,
+        "sp" => Expr::StackPointer,
+        "dt" => Expr::DelayTimer,
+        "st" => Expr::SoundTimer,
+        _ => {
+            let register = ident
+                .strip_prefix('v')
+                .filter(|r| r.len() == 1)
+                .and_then(|r| u8::from_str_radix(r, 16).ok())
+                .ok_or_else(|| ExprError::UnknownIdentifier(ident.to_string()))?;
+            Expr::Register(register)
+        }
+    };
+    Ok(expr)
+}
+
+/// Precedence climbing parser over the token list
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> ExprResult<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token.ok_or(ExprError::UnexpectedEnd)
+    }
+
+    fn expect(&mut self, expected: Token) -> ExprResult<()> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(ExprError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn expr(&mut self, min_precedence: u8) -> ExprResult<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            if op.precedence() <= min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(op.precedence())?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> ExprResult<Expr> {
+        match self.next()? {
+            Token::Not => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Token::Op(BinaryOp::Sub) => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?))),
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Ident(ident) => parse_ident(&ident),
+            Token::LParen => {
+                let expr = self.expr(0)?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Token::LBracket => {
+                let expr = self.expr(0)?;
+                self.expect(Token::RBracket)?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            token => Err(ExprError::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::state::VmState;
+
+    fn state() -> VmState {
+        let mut memory = vec![0; 4096];
+        memory[0x302] = 0x42;
+        let mut registers = [0; 16];
+        registers[0x3] = 0x1F;
+        registers[0xF] = 1;
+
+        VmState {
+            memory,
+            registers,
+            stack: [0; 16],
+            stack_pointer: 2,
+            index: 0x300,
+            program_counter: 0x21A,
+            delay_timer: 0,
+            sound_timer: 5,
+            wait_for_key: None,
+            flags: [0; 8],
+            display: vec![false; 64 * 32],
+            keys: [false; 16],
+        }
+    }
+
+    fn eval(src: &str) -> u32 {
+        Expr::parse(src).unwrap().eval(&state())
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(eval("42"), 42);
+        assert_eq!(eval("0x2A"), 42);
+        assert_eq!(eval("0b101010"), 42);
+        assert_eq!(eval("v3"), 0x1F);
+        assert_eq!(eval("VF"), 1);
+        assert_eq!(eval("I"), 0x300);
+        assert_eq!(eval("pc"), 0x21A);
+        assert_eq!(eval("sp"), 2);
+        assert_eq!(eval("st"), 5);
+        assert_eq!(eval("[i + 2]"), 0x42);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 == 3"), 1);
+        assert_eq!(eval("1 | 2 & 0"), 1);
+        assert_eq!(eval("(1 | 2) & 3"), 3);
+        assert_eq!(eval("0 || 1 && 0"), 0);
+        assert_eq!(eval("!0 && !dt"), 1);
+        assert_eq!(eval("10 - 3 - 2"), 5);
+    }
+
+    #[test]
+    fn conditions() {
+        let target = state();
+        let condition = Expr::parse("v3 == 0x1F && I > 0x2FF").unwrap();
+        assert!(condition.is_true(&target));
+
+        let condition = Expr::parse("v3 != 0x1F || [0x302] < 0x42").unwrap();
+        assert!(!condition.is_true(&target));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Expr::parse("v3 =="), Err(ExprError::UnexpectedEnd));
+        assert_eq!(
+            Expr::parse("vx == 1"),
+            Err(ExprError::UnknownIdentifier("vx".to_string()))
+        );
+        assert_eq!(
+            Expr::parse("0xZZ"),
+            Err(ExprError::InvalidNumber("0xzz".to_string()))
+        );
+        assert_eq!(
+            Expr::parse("v1 = 2"),
+            Err(ExprError::UnexpectedCharacter('='))
+        );
+        assert_eq!(Expr::parse("(v1 == 2"), Err(ExprError::UnexpectedEnd));
+        assert_eq!(
+            Expr::parse("v1 v2"),
+            Err(ExprError::UnexpectedToken("v2".to_string()))
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let expr = Expr::parse("v3 == 0x1F && [i + 1] > 3 || !dt").unwrap();
+        assert_eq!(Expr::parse(&expr.to_string()), Ok(expr));
+    }
+}
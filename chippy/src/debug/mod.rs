@@ -0,0 +1,162 @@
+//! Debugging support shared by the frontends.
+
+use crate::emu::{state::VmState, vm::MEMORY_SIZE};
+use error::ExprResult;
+use expr::Expr;
+use std::collections::BTreeMap;
+
+pub mod error;
+pub mod events;
+pub mod expr;
+pub mod trigger;
+pub mod watch;
+
+/// Read access to the machine state, used to evaluate expressions.
+pub trait Inspect {
+    fn register(&self, register: u8) -> u8;
+    fn index(&self) -> u16;
+    fn program_counter(&self) -> u16;
+    fn stack_pointer(&self) -> usize;
+    fn delay_timer(&self) -> u8;
+    fn sound_timer(&self) -> u8;
+    /// Byte at `address`, wrapping around the end of memory
+    fn memory(&self, address: u16) -> u8;
+}
+
+impl Inspect for VmState {
+    fn register(&self, register: u8) -> u8 {
+        self.registers[register as usize & 0xF]
+    }
+
+    fn index(&self) -> u16 {
+        self.index
+    }
+
+    fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    fn stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    fn memory(&self, address: u16) -> u8 {
+        self.memory[address as usize % MEMORY_SIZE]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub address: u16,
+    /// Only break if the condition is true
+    pub condition: Option<Expr>,
+}
+
+impl Breakpoint {
+    /// True if the program counter is on the breakpoint and the condition holds
+    pub fn is_hit(&self, target: &impl Inspect) -> bool {
+        target.program_counter() == self.address
+            && match &self.condition {
+                Some(condition) => condition.is_true(target),
+                None => true,
+            }
+    }
+}
+
+/// Set of breakpoints, at most one per address.
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    breakpoints: BTreeMap<u16, Breakpoint>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.breakpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// Add or replace the breakpoint at `address`, with an optional condition expression
+    pub fn add(&mut self, address: u16, condition: Option<&str>) -> ExprResult<()> {
+        let condition = condition.map(Expr::parse).transpose()?;
+        self.breakpoints
+            .insert(address, Breakpoint { address, condition });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, address: u16) -> Option<Breakpoint> {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn get(&self, address: u16) -> Option<&Breakpoint> {
+        self.breakpoints.get(&address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> + '_ {
+        self.breakpoints.values()
+    }
+
+    /// The breakpoint the machine is stopped on, if any
+    pub fn hit(&self, target: &impl Inspect) -> Option<&Breakpoint> {
+        self.breakpoints
+            .get(&target.program_counter())
+            .filter(|breakpoint| breakpoint.is_hit(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn conditional_breakpoint() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x70, 0x01, // add v0, 0x01
+            0x12, 0x00, // jp 0x200
+        ]);
+
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.add(0x202, Some("v0 == 3")).unwrap();
+        assert!(breakpoints.add(0x200, Some("v0 ==")).is_err());
+        assert_eq!(breakpoints.len(), 1);
+
+        let mut cycles = 0;
+        while breakpoints.hit(&vm).is_none() {
+            vm.cycle().unwrap();
+            cycles += 1;
+        }
+        assert_eq!(cycles, 5);
+        assert_eq!(vm.snapshot().registers[0], 3);
+    }
+
+    #[test]
+    fn unconditional_breakpoint() {
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.add(0x200, None).unwrap();
+
+        let vm = Vm::new();
+        assert_eq!(breakpoints.hit(&vm).map(|b| b.address), Some(0x200));
+        assert!(breakpoints.remove(0x200).is_some());
+        assert!(breakpoints.hit(&vm).is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//! Breaks on machine events rather than addresses.
+
+use super::Inspect;
+use crate::emu::instruction::Instruction;
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trigger {
+    /// A draw instruction was executed
+    Draw,
+    /// A draw instruction erased a pixel and set vf
+    Collision,
+    /// The sound timer went from zero to nonzero
+    Sound,
+    /// The program checked or waited for a key
+    KeyPolled,
+}
+
+impl Trigger {
+    pub const VARIANTS: [Trigger; 4] = [
+        Trigger::Draw,
+        Trigger::Collision,
+        Trigger::Sound,
+        Trigger::KeyPolled,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Trigger::Draw => "draw",
+            Trigger::Collision => "collision",
+            Trigger::Sound => "sound",
+            Trigger::KeyPolled => "key",
+        }
+    }
+
+    /// True if the step from `before` to `after` fired the trigger
+    pub fn is_hit(&self, before: &impl Inspect, after: &impl Inspect) -> bool {
+        let pc = before.program_counter();
+        let opcode = u16::from_be_bytes([before.memory(pc), before.memory(pc.wrapping_add(1))]);
+        match (self, Instruction::parse(opcode)) {
+            (Trigger::Draw, Instruction::Draw { .. }) => true,
+            (Trigger::Collision, Instruction::Draw { .. }) => after.register(0xF) == 1,
+            (Trigger::Sound, Instruction::SetSTAsX(register)) => {
+                before.sound_timer() == 0 && before.register(register) > 0
+            }
+            (Trigger::KeyPolled, Instruction::SkipIfKeyPressed(_))
+            | (Trigger::KeyPolled, Instruction::SkipIfNotKeyPressed(_))
+            | (Trigger::KeyPolled, Instruction::WaitInputStoreIn(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Trigger::VARIANTS
+            .iter()
+            .find(|trigger| trigger.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown trigger: {}", s))
+    }
+}
+
+/// Set of enabled triggers.
+#[derive(Debug, Clone, Default)]
+pub struct Triggers {
+    enabled: BTreeSet<Trigger>,
+}
+
+impl Triggers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self, trigger: Trigger) -> bool {
+        self.enabled.contains(&trigger)
+    }
+
+    /// Enable or disable `trigger`, returns true if it is now enabled
+    pub fn toggle(&mut self, trigger: Trigger) -> bool {
+        if self.enabled.remove(&trigger) {
+            return false;
+        }
+        self.enabled.insert(trigger);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.enabled.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Trigger> + '_ {
+        self.enabled.iter().copied()
+    }
+
+    /// The first enabled trigger fired by the step from `before` to `after`
+    pub fn hit(&self, before: &impl Inspect, after: &impl Inspect) -> Option<Trigger> {
+        self.iter().find(|trigger| trigger.is_hit(before, after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    fn run_until(vm: &mut Vm, triggers: &Triggers, limit: usize) -> Option<(Trigger, u16)> {
+        for _ in 0..limit {
+            let before = vm.snapshot();
+            vm.cycle().unwrap();
+            if let Some(trigger) = triggers.hit(&before, vm) {
+                return Some((trigger, before.program_counter));
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn draw_and_collision() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x00, // ld v0, 0x00
+            0xF0, 0x29, // ld f, v0
+            0xD0, 0x05, // drw v0, v0, 5
+            0xD0, 0x05, // drw v0, v0, 5
+        ]);
+
+        let mut triggers = Triggers::new();
+        assert!(triggers.toggle(Trigger::Collision));
+        assert_eq!(
+            run_until(&mut vm, &triggers, 8),
+            Some((Trigger::Collision, 0x206))
+        );
+
+        vm.reset();
+        vm.load(vec![0x00, 0xE0, 0xD0, 0x05]);
+        triggers.toggle(Trigger::Draw);
+        assert_eq!(
+            run_until(&mut vm, &triggers, 8),
+            Some((Trigger::Draw, 0x202))
+        );
+    }
+
+    #[test]
+    fn sound_and_key() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0xE0, 0xA1, // sknp v0
+            0x00, 0xE0, // cls
+            0xF0, 0x18, // ld st, v0
+            0xF0, 0x18, // ld st, v0
+        ]);
+
+        let mut triggers = Triggers::new();
+        triggers.toggle(Trigger::Sound);
+        triggers.toggle(Trigger::KeyPolled);
+        assert_eq!(
+            run_until(&mut vm, &triggers, 8),
+            Some((Trigger::KeyPolled, 0x202))
+        );
+
+        assert!(!triggers.toggle(Trigger::KeyPolled));
+        assert_eq!(
+            run_until(&mut vm, &triggers, 8),
+            Some((Trigger::Sound, 0x206))
+        );
+        // The timer is already running
+        assert_eq!(run_until(&mut vm, &triggers, 1), None);
+    }
+
+    #[test]
+    fn parse_trigger() {
+        for trigger in Trigger::VARIANTS.iter() {
+            assert_eq!(trigger.as_str().parse(), Ok(*trigger));
+        }
+        assert!("jump".parse::<Trigger>().is_err());
+    }
+}
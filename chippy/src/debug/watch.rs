@@ -0,0 +1,216 @@
+//! Values pinned by the user and refreshed after every frame, with the values that changed since
+//! the previous refresh marked.
+//!
+//! ```text
+//! v3
+//! [i + 2]
+//! 0x300..0x340
+//! i..i+8
+//! ```
+//!
+//! A watch is an expression (see `expr`) or a range of memory `START..END`, the bounds of a
+//! range are expressions too so a range can follow a register.
+
+use super::{
+    error::{ExprError, ExprResult},
+    expr::Expr,
+    Inspect,
+};
+use std::{fmt, str::FromStr};
+
+/// Most bytes shown for a single range
+pub const MAX_RANGE: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Watch {
+    Expr(Expr),
+    /// Memory bytes from `start` up to, not including, `end`
+    Range {
+        start: Expr,
+        end: Expr,
+    },
+}
+
+impl Watch {
+    /// Current values, one for an expression and one per byte for a range
+    pub fn values(&self, target: &impl Inspect) -> Vec<u32> {
+        match self {
+            Watch::Expr(expr) => vec![expr.eval(target)],
+            Watch::Range { start, end } => {
+                let start = start.eval(target);
+                let len = end.eval(target).saturating_sub(start) as usize;
+                (0..len.min(MAX_RANGE))
+                    .map(|offset| target.memory((start as usize + offset) as u16) as u32)
+                    .collect()
+            }
+        }
+    }
+
+    /// Address of the first byte of a range
+    pub fn start(&self, target: &impl Inspect) -> Option<u16> {
+        match self {
+            Watch::Expr(_) => None,
+            Watch::Range { start, .. } => Some(start.eval(target) as u16),
+        }
+    }
+}
+
+impl FromStr for Watch {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once("..") {
+            Some((start, end)) => Ok(Watch::Range {
+                start: Expr::parse(start)?,
+                end: Expr::parse(end)?,
+            }),
+            None => Ok(Watch::Expr(Expr::parse(s)?)),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Expr(expr) => write!(f, "{}", expr),
+            Watch::Range { start, end } => write!(f, "{}..{}", start, end),
+        }
+    }
+}
+
+/// A watch and its values at the last refresh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchEntry {
+    pub watch: Watch,
+    /// Address of the first value for a range
+    pub start: Option<u16>,
+    pub values: Vec<u32>,
+    /// Values that differ from the previous refresh
+    pub changed: Vec<bool>,
+}
+
+/// List of watches in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct Watches {
+    entries: Vec<WatchEntry>,
+}
+
+impl Watches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Parse and add a watch, its values are read on the next `update`
+    pub fn add(&mut self, src: &str) -> ExprResult<&Watch> {
+        let watch = src.parse()?;
+        self.entries.push(WatchEntry {
+            watch,
+            start: None,
+            values: Vec::new(),
+            changed: Vec::new(),
+        });
+        Ok(&self.entries[self.entries.len() - 1].watch)
+    }
+
+    /// Remove the watch at `index` in the list
+    pub fn remove(&mut self, index: usize) -> Option<Watch> {
+        match index < self.entries.len() {
+            true => Some(self.entries.remove(index).watch),
+            false => None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WatchEntry> + '_ {
+        self.entries.iter()
+    }
+
+    /// Read the values of every watch and mark the ones that changed
+    pub fn update(&mut self, target: &impl Inspect) {
+        for entry in self.entries.iter_mut() {
+            let values = entry.watch.values(target);
+            let start = entry.watch.start(target);
+            let moved = entry.start != start;
+            entry.changed = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| !moved && entry.values.get(i).is_some_and(|old| old != value))
+                .collect();
+            entry.values = values;
+            entry.start = start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "0x300..0x304".parse(),
+            Ok(Watch::Range {
+                start: Expr::Number(0x300),
+                end: Expr::Number(0x304)
+            })
+        );
+        assert_eq!("v3".parse(), Ok(Watch::Expr(Expr::Register(3))));
+        assert!("0x300..".parse::<Watch>().is_err());
+        assert_eq!(
+            "i..i+8".parse::<Watch>().unwrap().to_string(),
+            "i..(i + 0x8)"
+        );
+    }
+
+    #[test]
+    fn changed_values() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0xA3, 0x00, // ld i, 0x300
+            0x60, 0x07, // ld v0, 0x07
+            0xF1, 0x55, // ld [i], v1
+        ]);
+
+        let mut watches = Watches::new();
+        watches.add("0x300..0x302").unwrap();
+        watches.add("v0").unwrap();
+        watches.add("i..i+1").unwrap();
+        assert!(watches.add("v0 ==").is_err());
+        watches.update(&vm);
+
+        vm.cycle().unwrap();
+        watches.update(&vm);
+        let entries: Vec<&WatchEntry> = watches.iter().collect();
+        assert_eq!(entries[0].start, Some(0x300));
+        assert_eq!(entries[0].changed, vec![false, false]);
+        assert_eq!(entries[1].values, vec![0]);
+        // The range moved with i, nothing is marked changed
+        assert_eq!(entries[2].changed, vec![false]);
+
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
+        watches.update(&vm);
+        let entries: Vec<&WatchEntry> = watches.iter().collect();
+        assert_eq!(entries[0].values, vec![0x07, 0x00]);
+        assert_eq!(entries[0].changed, vec![true, false]);
+        assert_eq!(entries[1].changed, vec![true]);
+        assert_eq!(entries[2].start, Some(0x302));
+
+        assert_eq!(watches.remove(1), Some(Watch::Expr(Expr::Register(0))));
+        assert_eq!(watches.remove(5), None);
+        assert_eq!(watches.len(), 2);
+    }
+}
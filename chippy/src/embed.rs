@@ -0,0 +1,144 @@
+//! Settings of a player embedded in a web page with one tag, read from the attributes of the
+//! custom element so documentation sites can show playable demos.
+//!
+//! ```html
+//! <chippy-player rom="roms/pong.ch8" palette="white" scale="4" speed="15" autoplay></chippy-player>
+//! ```
+
+use crate::render::Palette;
+
+/// Name of the custom element
+pub const TAG: &str = "chippy-player";
+/// Screen pixels per side of a chip8 pixel
+pub const DEFAULT_SCALE: usize = 8;
+/// Instructions per frame
+pub const DEFAULT_SPEED: usize = 11;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerConfig {
+    /// URL of the rom
+    pub rom: String,
+    pub palette: Option<Palette>,
+    pub scale: usize,
+    pub speed: usize,
+    /// Start running once the rom is loaded instead of waiting for a click
+    pub autoplay: bool,
+}
+
+impl PlayerConfig {
+    /// Attributes the element reacts to, its `observedAttributes`
+    pub const ATTRIBUTES: [&'static str; 5] = ["rom", "palette", "scale", "speed", "autoplay"];
+
+    pub fn new(rom: impl Into<String>) -> Self {
+        Self {
+            rom: rom.into(),
+            palette: None,
+            scale: DEFAULT_SCALE,
+            speed: DEFAULT_SPEED,
+            autoplay: false,
+        }
+    }
+
+    /// Read the attributes of an element, which must name a rom
+    pub fn from_attributes<'a>(
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let mut config = Self::new("");
+        for (name, value) in attributes {
+            config.set_attribute(name, Some(value))?;
+        }
+        match config.rom.is_empty() {
+            true => Err(format!("<{}> needs a rom attribute", TAG)),
+            false => Ok(config),
+        }
+    }
+
+    /// Apply an attribute set to `value`, or removed with `None`, as in
+    /// `attributeChangedCallback`. Unknown attributes are left to the element.
+    pub fn set_attribute(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let number = |value: Option<&str>, default: usize| match value {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| format!("Invalid {}: {}", name, value)),
+            None => Ok(default),
+        };
+        match name {
+            "rom" => self.rom = value.unwrap_or_default().to_string(),
+            "palette" => self.palette = value.map(str::parse).transpose()?,
+            "scale" => self.scale = number(value, DEFAULT_SCALE)?,
+            "speed" => self.speed = number(value, DEFAULT_SPEED)?,
+            // A boolean attribute, set whatever its value
+            "autoplay" => self.autoplay = value.is_some(),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// The tag embedding a player with these settings
+    pub fn to_html(&self) -> String {
+        let mut html = format!("<{} rom=\"{}\"", TAG, escape(&self.rom));
+        if let Some(palette) = &self.palette {
+            html += &format!(" palette=\"{}\"", palette);
+        }
+        if self.scale != DEFAULT_SCALE {
+            html += &format!(" scale=\"{}\"", self.scale);
+        }
+        if self.speed != DEFAULT_SPEED {
+            html += &format!(" speed=\"{}\"", self.speed);
+        }
+        if self.autoplay {
+            html += " autoplay";
+        }
+        html + &format!("></{}>", TAG)
+    }
+}
+
+/// Escape `src` for an attribute value
+fn escape(src: &str) -> String {
+    src.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes() {
+        let config = PlayerConfig::from_attributes(vec![
+            ("rom", "roms/pong.ch8"),
+            ("palette", "ffb000:281800"),
+            ("speed", "15"),
+            ("autoplay", ""),
+            ("class", "demo"),
+        ])
+        .unwrap();
+        assert_eq!(config.rom, "roms/pong.ch8");
+        assert_eq!(config.scale, DEFAULT_SCALE);
+        assert_eq!(config.speed, 15);
+        assert!(config.autoplay);
+        assert_eq!(
+            config.to_html(),
+            "<chippy-player rom=\"roms/pong.ch8\" palette=\"ffb000:281800\" speed=\"15\" \
+             autoplay></chippy-player>"
+        );
+
+        let mut config = config;
+        config.set_attribute("autoplay", None).unwrap();
+        config.set_attribute("speed", None).unwrap();
+        assert_eq!(config.speed, DEFAULT_SPEED);
+        assert!(!config.autoplay);
+        assert!(config.set_attribute("scale", Some("0")).is_err());
+        assert!(config.set_attribute("palette", Some("blurple")).is_err());
+
+        assert!(PlayerConfig::from_attributes(vec![("scale", "4")]).is_err());
+        assert_eq!(
+            PlayerConfig::new("a\"b.ch8").to_html(),
+            "<chippy-player rom=\"a&quot;b.ch8\"></chippy-player>"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! Address space seen by the cpu. The vm only reaches memory through `Bus`, so other memory
+//! models (a 64K XO-CHIP address space, banked memory, memory mapped peripherals) can be plugged
+//! in with `Vm::with_memory` without changing how instructions are executed.
+
+use super::memory::Memory;
+
+pub trait Bus {
+    /// Number of addressable bytes
+    fn size(&self) -> usize;
+
+    fn read(&self, address: u16) -> u8;
+
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Write `bytes` starting at `address`
+    fn load(&mut self, address: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.write(address.wrapping_add(offset as u16), *byte);
+        }
+    }
+
+    /// Big endian word at `address`
+    fn read_u16(&self, address: u16) -> u16 {
+        u16::from_be_bytes([self.read(address), self.read(address.wrapping_add(1))])
+    }
+}
+
+impl Bus for Memory {
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        self[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        Memory::write(self, address as usize, value)
+    }
+
+    fn load(&mut self, address: u16, bytes: &[u8]) {
+        let start = address as usize;
+        self.as_mut_slice()[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::VecDeque;
+
+use super::{
+    state::{VmState, ENCODED_SIZE},
+    vm::MEMORY_SIZE,
+};
+
+/// Encode `next` relative to `base`. The two buffers are xor'd together so unchanged bytes become
+/// zero, then the result is run length encoded as a list of `(zero run, literal run, literals)`
+/// tokens with the lengths stored as LEB128 varints.
+///
+/// Passing an all zero `base` produces a compressed keyframe.
+pub fn encode_delta(base: &[u8], next: &[u8]) -> Vec<u8> {
+    assert_eq!(
+        base.len(),
+        next.len(),
+        "Delta buffers must be the same size"
+    );
+
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < next.len() {
+        let zeros = next[pos..]
+            .iter()
+            .zip(&base[pos..])
+            .take_while(|(n, b)| n == b)
+            .count();
+        pos += zeros;
+
+        let start = pos;
+        while pos < next.len() {
+            // A single matching byte is cheaper to store as a literal than to start a new token
+            let run_ends =
+                next[pos] == base[pos] && (pos + 1 == next.len() || next[pos + 1] == base[pos + 1]);
+            if run_ends {
+                break;
+            }
+            pos += 1;
+        }
+
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, pos - start);
+        out.extend(
+            next[start..pos]
+                .iter()
+                .zip(&base[start..pos])
+                .map(|(n, b)| n ^ b),
+        );
+    }
+    out
+}
+
+/// Apply a delta created by `encode_delta` on top of `base`. Returns `None` if the delta is
+/// malformed or does not fit the base buffer.
+pub fn decode_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut out = base.to_vec();
+    let mut pos = 0;
+    let mut cursor = delta;
+    while !cursor.is_empty() {
+        pos += read_varint(&mut cursor)?;
+        let literals = read_varint(&mut cursor)?;
+        if literals > cursor.len() || pos + literals > out.len() {
+            return None;
+        }
+
+        let (bytes, rest) = cursor.split_at(literals);
+        for (target, value) in out[pos..pos + literals].iter_mut().zip(bytes) {
+            *target ^= value;
+        }
+        pos += literals;
+        cursor = rest;
+    }
+
+    if pos > out.len() {
+        return None;
+    }
+    Some(out)
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn read_varint(cursor: &mut &[u8]) -> Option<usize> {
+    let mut value = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (byte, rest) = cursor.split_first()?;
+        *cursor = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+enum Entry {
+    /// State compressed against an empty buffer
+    Key(Vec<u8>),
+    /// State compressed against the entry before it
+    Delta(Vec<u8>),
+}
+
+impl Entry {
+    fn len(&self) -> usize {
+        match self {
+            Entry::Key(bytes) | Entry::Delta(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// Bounded history of snapshots stored as a chain of compressed deltas. Every
+/// `keyframe_interval` entries a keyframe is stored so that restoring an older state does not
+/// have to replay the whole history.
+pub struct SnapshotHistory {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    keyframe_interval: usize,
+    since_keyframe: usize,
+    last: Option<Vec<u8>>,
+}
+
+impl SnapshotHistory {
+    pub fn new(capacity: usize, keyframe_interval: usize) -> Self {
+        assert!(capacity > 0, "History capacity must be greater than zero");
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            keyframe_interval: keyframe_interval.max(1),
+            since_keyframe: 0,
+            last: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Total number of bytes used by the compressed entries.
+    pub fn compressed_size(&self) -> usize {
+        self.entries.iter().map(Entry::len).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.since_keyframe = 0;
+        self.last = None;
+    }
+
+    pub fn push(&mut self, state: &VmState) {
+        let raw = state.encode();
+        let entry = match &self.last {
+            Some(last) if self.since_keyframe < self.keyframe_interval => {
+                self.since_keyframe += 1;
+                Entry::Delta(encode_delta(last, &raw))
+            }
+            _ => {
+                self.since_keyframe = 1;
+                Entry::Key(encode_delta(&[0; ENCODED_SIZE], &raw))
+            }
+        };
+
+        if self.entries.len() == self.capacity {
+            self.evict_oldest();
+        }
+        self.entries.push_back(entry);
+        self.last = Some(raw);
+    }
+
+    /// Get the state `back` entries from the most recent one, where 0 is the latest state.
+    pub fn get(&self, back: usize) -> Option<VmState> {
+        let index = self.entries.len().checked_sub(back + 1)?;
+        self.raw(index).and_then(|raw| VmState::decode(&raw).ok())
+    }
+
+    /// Remove and return the most recent state.
+    pub fn pop(&mut self) -> Option<VmState> {
+        let state = self.get(0)?;
+        self.entries.pop_back();
+        self.last = self.entries.len().checked_sub(1).and_then(|i| self.raw(i));
+        // Count from the nearest remaining keyframe, the popped entry may have been one
+        self.since_keyframe = self
+            .entries
+            .iter()
+            .rev()
+            .position(|entry| matches!(entry, Entry::Key(_)))
+            .map_or(0, |delta| delta + 1);
+        Some(state)
+    }
+
+    /// States from the oldest to the most recent, decoding every entry once
+    pub fn iter(&self) -> impl Iterator<Item = VmState> + '_ {
+        let mut raw = vec![0; ENCODED_SIZE];
+        self.entries.iter().map_while(move |entry| {
+            raw = match entry {
+                Entry::Key(bytes) => decode_delta(&[0; ENCODED_SIZE], bytes)?,
+                Entry::Delta(bytes) => decode_delta(&raw, bytes)?,
+            };
+            VmState::decode(&raw).ok()
+        })
+    }
+
+    /// Number of entries back, like `get`, of the last state before memory at `address` changed,
+    /// with `latest` the state following the most recent entry. `None` if the byte kept its
+    /// value over the whole history.
+    pub fn last_memory_change(&self, address: u16, latest: &VmState) -> Option<usize> {
+        let address = address as usize % MEMORY_SIZE;
+        let mut values: Vec<u8> = self.iter().map(|state| state.memory[address]).collect();
+        values.push(latest.memory[address]);
+        let changed = values.windows(2).rposition(|pair| pair[0] != pair[1])?;
+        Some(self.entries.len() - 1 - changed)
+    }
+
+    fn raw(&self, index: usize) -> Option<Vec<u8>> {
+        let key = (0..=index)
+            .rev()
+            .find(|i| matches!(self.entries[*i], Entry::Key(_)))?;
+
+        let mut raw = vec![0; ENCODED_SIZE];
+        for entry in self.entries.range(key..=index) {
+            raw = match entry {
+                Entry::Key(bytes) => decode_delta(&[0; ENCODED_SIZE], bytes)?,
+                Entry::Delta(bytes) => decode_delta(&raw, bytes)?,
+            };
+        }
+        Some(raw)
+    }
+
+    fn evict_oldest(&mut self) {
+        // The entry after the evicted keyframe becomes the new start of the chain so it has to be
+        // promoted to a keyframe first.
+        if let Some(Entry::Delta(_)) = self.entries.get(1) {
+            if let Some(raw) = self.raw(1) {
+                self.entries[1] = Entry::Key(encode_delta(&[0; ENCODED_SIZE], &raw));
+            }
+        }
+        self.entries.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(value: u8) -> VmState {
+        let mut memory = vec![0; 4096];
+        memory[0x200] = value;
+        memory[0x800] = value.wrapping_mul(3);
+        VmState {
+            memory,
+            registers: [value; 16],
+            stack: [0; 16],
+            stack_pointer: 0,
+            index: value as u16,
+            program_counter: 0x200 + value as u16 * 2,
+            delay_timer: 0,
+            sound_timer: 0,
+            wait_for_key: None,
+            flags: [0; 8],
+            display: vec![false; 64 * 32],
+            keys: [false; 16],
+        }
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let base = state(1).encode();
+        let next = state(2).encode();
+        let delta = encode_delta(&base, &next);
+        assert!(delta.len() < next.len() / 10);
+        assert_eq!(decode_delta(&base, &delta), Some(next));
+    }
+
+    #[test]
+    fn delta_of_identical_buffers() {
+        let base = state(1).encode();
+        let delta = encode_delta(&base, &base);
+        assert_eq!(decode_delta(&base, &delta), Some(base));
+    }
+
+    #[test]
+    fn malformed_delta() {
+        let base = vec![0u8; 4];
+        assert_eq!(decode_delta(&base, &[0x02, 0x05, 0x01]), None);
+        assert_eq!(decode_delta(&base, &[0x80]), None);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, 4096, usize::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            assert_eq!(read_varint(&mut out.as_slice()), Some(value));
+        }
+    }
+
+    #[test]
+    fn history_get_and_pop() {
+        let mut history = SnapshotHistory::new(8, 3);
+        for i in 0..5 {
+            history.push(&state(i));
+        }
+
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.get(0), Some(state(4)));
+        assert_eq!(history.get(4), Some(state(0)));
+        assert_eq!(history.get(5), None);
+
+        assert_eq!(history.pop(), Some(state(4)));
+        history.push(&state(9));
+        assert_eq!(history.get(0), Some(state(9)));
+        assert_eq!(history.get(1), Some(state(3)));
+    }
+
+    #[test]
+    fn history_pop_keyframe() {
+        let mut history = SnapshotHistory::new(8, 2);
+        for i in 0..3 {
+            history.push(&state(i));
+        }
+        assert_eq!(history.pop(), Some(state(2)));
+        history.push(&state(3));
+        history.push(&state(4));
+
+        let keyframes: Vec<bool> = history
+            .entries
+            .iter()
+            .map(|entry| matches!(entry, Entry::Key(_)))
+            .collect();
+        assert_eq!(keyframes, vec![true, false, true, false]);
+        let states: Vec<VmState> = history.iter().collect();
+        assert_eq!(states, vec![state(0), state(1), state(3), state(4)]);
+    }
+
+    #[test]
+    fn history_memory_change() {
+        let mut history = SnapshotHistory::new(8, 2);
+        for i in [1, 1, 2, 2, 2] {
+            history.push(&state(i));
+        }
+        let states: Vec<VmState> = history.iter().collect();
+        assert_eq!(states.len(), 5);
+        assert_eq!(states[2], state(2));
+
+        assert_eq!(history.last_memory_change(0x200, &state(2)), Some(3));
+        assert_eq!(history.last_memory_change(0x200, &state(5)), Some(0));
+        assert_eq!(history.last_memory_change(0x300, &state(2)), None);
+    }
+
+    #[test]
+    fn history_evicts_oldest() {
+        let mut history = SnapshotHistory::new(4, 3);
+        for i in 0..10 {
+            history.push(&state(i));
+        }
+
+        assert_eq!(history.len(), 4);
+        for back in 0..4 {
+            assert_eq!(history.get(back), Some(state(9 - back as u8)));
+        }
+        assert!(history.compressed_size() < ENCODED_SIZE * 4);
+    }
+}
@@ -0,0 +1,135 @@
+//! Fault dumps written when a program fails, holding everything needed to inspect the failure
+//! in the debugger: the machine state, the last executed instructions and the rom checksum.
+
+use super::{
+    history::HistoryEntry,
+    state::{VmState, ENCODED_SIZE},
+    vm::Vm,
+};
+
+/// Extension of dump files
+pub const EXTENSION: &str = "c8dump";
+/// Instructions kept in the trace of a dump
+pub const TRACE_SIZE: usize = 256;
+
+const MAGIC: &[u8] = b"C8DUMP1\n";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dump {
+    /// Checksum of the rom, see `crate::rom::checksum`
+    pub rom_checksum: u32,
+    /// Description of the failure
+    pub error: String,
+    pub state: VmState,
+    /// Executed instructions, oldest first
+    pub trace: Vec<HistoryEntry>,
+}
+
+impl Dump {
+    /// Capture the state of a failed vm, `rom_checksum` identifies the rom it was running
+    pub fn capture(vm: &Vm, rom_checksum: u32, error: impl ToString) -> Self {
+        Self {
+            rom_checksum,
+            error: error.to_string(),
+            state: vm.snapshot(),
+            trace: vm.history().iter().copied().collect(),
+        }
+    }
+
+    /// Restore the state into `vm` with the trace as its instruction history
+    pub fn restore(&self, vm: &mut Vm) {
+        vm.restore(&self.state);
+        let capacity = vm.history().capacity().max(self.trace.len());
+        vm.set_history_capacity(capacity);
+        let history = vm.history_mut();
+        history.clear();
+        for entry in self.trace.iter() {
+            history.push(entry.address, entry.opcode);
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&self.rom_checksum.to_be_bytes());
+        bytes.extend_from_slice(&(self.error.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(self.error.as_bytes());
+        bytes.extend(self.state.encode());
+        bytes.extend_from_slice(&(self.trace.len() as u32).to_be_bytes());
+        for entry in self.trace.iter() {
+            bytes.extend_from_slice(&entry.address.to_be_bytes());
+            bytes.extend_from_slice(&entry.opcode.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Decode a dump created by `Dump::encode`, `None` if the bytes are not a valid dump
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(MAGIC)?;
+        let (rom_checksum, rest) = take_u32(rest)?;
+        let (len, rest) = take_u32(rest)?;
+        let error = String::from_utf8(rest.get(..len as usize)?.to_vec()).ok()?;
+        let rest = &rest[len as usize..];
+        let state = VmState::decode(rest.get(..ENCODED_SIZE)?).ok()?;
+        let (len, rest) = take_u32(&rest[ENCODED_SIZE..])?;
+        if rest.len() != len as usize * 4 {
+            return None;
+        }
+        let trace = rest
+            .chunks_exact(4)
+            .map(|entry| HistoryEntry {
+                address: u16::from_be_bytes([entry[0], entry[1]]),
+                opcode: u16::from_be_bytes([entry[2], entry[3]]),
+            })
+            .collect();
+
+        Some(Self {
+            rom_checksum,
+            error,
+            state,
+            trace,
+        })
+    }
+}
+
+fn take_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let value = bytes.get(..4)?;
+    Some((
+        u32::from_be_bytes([value[0], value[1], value[2], value[3]]),
+        &bytes[4..],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom;
+
+    #[test]
+    fn round_trip() {
+        let rom = vec![
+            0x60, 0x05, // ld v0, 5
+            0x70, 0x01, // add v0, 1
+            0x1F, 0xFF, // jp 0xFFF
+        ];
+        let mut vm = Vm::new();
+        vm.set_history_capacity(TRACE_SIZE);
+        vm.load(rom.clone());
+        let error = match vm.frames().cycles_per_frame(4).next() {
+            Some(Err(error)) => error,
+            _ => panic!("expected the program counter to go out of range"),
+        };
+
+        let dump = Dump::capture(&vm, rom::checksum(&rom), error);
+        assert_eq!(dump.error, "Program counter out of range: 0x0FFF");
+        assert_eq!(dump.trace.len(), 3);
+        let decoded = Dump::decode(&dump.encode()).unwrap();
+        assert_eq!(decoded, dump);
+        assert!(Dump::decode(&dump.encode()[1..]).is_none());
+
+        let mut replay = Vm::new();
+        decoded.restore(&mut replay);
+        assert_eq!(replay.snapshot(), vm.snapshot());
+        let trace: Vec<u16> = replay.history().iter().map(|e| e.opcode).collect();
+        assert_eq!(trace, vec![0x6005, 0x7001, 0x1FFF]);
+    }
+}
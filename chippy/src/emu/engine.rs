@@ -0,0 +1,412 @@
+//! Ways of executing instructions, picked with `Vm::with_engine`.
+//!
+//! `Engine::Match` decodes every opcode into an `Instruction` and executes it with a match.
+//! `Engine::Table`, enabled with the `table-engine` feature, skips building the `Instruction` and
+//! calls the handler of the decoded kind of opcode from a table of function pointers.
+//! `Engine::Cached`, enabled with the `cached-engine` feature, compiles runs of straight line
+//! instructions into blocks of handlers that `Vm::run` executes without fetching, decoding or
+//! moving the program counter between them. Blocks are dropped when the program writes to them.
+//!
+//! The engines share the code of the instructions so they always behave the same.
+
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Engine {
+    #[default]
+    Match,
+    #[cfg(feature = "table-engine")]
+    Table,
+    #[cfg(feature = "cached-engine")]
+    Cached,
+}
+
+impl Engine {
+    #[cfg(not(feature = "table-engine"))]
+    pub const VARIANTS: &'static [&'static str] = &["match"];
+    #[cfg(all(feature = "table-engine", not(feature = "cached-engine")))]
+    pub const VARIANTS: &'static [&'static str] = &["match", "table"];
+    #[cfg(feature = "cached-engine")]
+    pub const VARIANTS: &'static [&'static str] = &["match", "table", "cached"];
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "match" => Ok(Engine::Match),
+            #[cfg(feature = "table-engine")]
+            "table" => Ok(Engine::Table),
+            #[cfg(feature = "cached-engine")]
+            "cached" => Ok(Engine::Cached),
+            _ => Err(format!("Unknown engine: {}", s)),
+        }
+    }
+}
+
+#[cfg(feature = "table-engine")]
+mod table {
+    use crate::{
+        debug::Inspect,
+        emu::{
+            bus::Bus,
+            error::{VmError, VmResult},
+            instruction::{Kind, KIND_COUNT},
+            vm::{ProgramCounter, StopReason, Vm},
+        },
+    };
+
+    pub(crate) type Handler<B> = fn(&mut Vm<B>, u16) -> VmResult<ProgramCounter>;
+
+    fn x(opcode: u16) -> u8 {
+        ((opcode >> 8) & 0xF) as u8
+    }
+
+    fn y(opcode: u16) -> u8 {
+        ((opcode >> 4) & 0xF) as u8
+    }
+
+    fn kk(opcode: u16) -> u8 {
+        (opcode & 0xFF) as u8
+    }
+
+    fn nnn(opcode: u16) -> u16 {
+        opcode & 0xFFF
+    }
+
+    fn skip_if(condition: bool) -> ProgramCounter {
+        match condition {
+            true => ProgramCounter::Skip,
+            false => ProgramCounter::Next,
+        }
+    }
+
+    impl<B: Bus> Vm<B> {
+        /// Handler of every `Kind`, in the order of its variants
+        pub(crate) const HANDLERS: [Handler<B>; KIND_COUNT] = [
+            |vm, _| Ok(vm.op_cls()),
+            |vm, _| vm.op_ret(),
+            |_, _| Ok(ProgramCounter::Stop(StopReason::Exit)),
+            |vm, op| Ok(vm.op_sys(nnn(op))),
+            |_, op| Ok(ProgramCounter::Jump(nnn(op))),
+            |vm, op| vm.op_call(nnn(op)),
+            |vm, op| Ok(skip_if(vm.get_register(x(op)) == kk(op))),
+            |vm, op| Ok(skip_if(vm.get_register(x(op)) != kk(op))),
+            |vm, op| Ok(skip_if(vm.get_register(x(op)) == vm.get_register(y(op)))),
+            |vm, op| Ok(vm.op_set_reg(x(op), kk(op))),
+            |vm, op| Ok(vm.op_add_value(x(op), kk(op))),
+            |vm, op| Ok(vm.op_set_reg(x(op), vm.get_register(y(op)))),
+            |vm, op| Ok(vm.op_or(x(op), y(op))),
+            |vm, op| Ok(vm.op_and(x(op), y(op))),
+            |vm, op| Ok(vm.op_xor(x(op), y(op))),
+            |vm, op| Ok(vm.op_add(x(op), y(op))),
+            |vm, op| Ok(vm.op_sub(x(op), y(op))),
+            |vm, op| Ok(vm.op_shr(x(op), y(op))),
+            |vm, op| Ok(vm.op_subn(x(op), y(op))),
+            |vm, op| Ok(vm.op_shl(x(op), y(op))),
+            |vm, op| Ok(skip_if(vm.get_register(x(op)) != vm.get_register(y(op)))),
+            |vm, op| Ok(vm.op_set_i(nnn(op))),
+            |vm, op| Ok(vm.op_jump_v0(nnn(op))),
+            |vm, op| Ok(vm.op_random(x(op), kk(op))),
+            |vm, op| Ok(vm.op_draw(x(op), y(op), (op & 0xF) as u8)),
+            |vm, op| Ok(skip_if(vm.input.poll(vm.get_register(x(op))))),
+            |vm, op| Ok(skip_if(!vm.input.poll(vm.get_register(x(op))))),
+            |vm, op| Ok(vm.op_set_reg(x(op), vm.delay_timer())),
+            |vm, op| Ok(vm.op_wait_key(x(op))),
+            |vm, op| Ok(vm.op_set_delay_timer(x(op))),
+            |vm, op| Ok(vm.op_set_sound_timer(x(op))),
+            |vm, op| Ok(vm.op_add_i(x(op))),
+            |vm, op| Ok(vm.op_font(x(op))),
+            |vm, op| Ok(vm.op_bcd(x(op))),
+            |vm, op| Ok(vm.op_dump_registers(x(op))),
+            |vm, op| Ok(vm.op_load_registers(x(op))),
+            |vm, op| Ok(vm.op_scroll_down((op & 0xF) as u8)),
+            |vm, _| Ok(vm.op_scroll_right()),
+            |vm, _| Ok(vm.op_scroll_left()),
+            |vm, _| Ok(vm.op_set_hires(false)),
+            |vm, _| Ok(vm.op_set_hires(true)),
+            |vm, op| Ok(vm.op_big_font(x(op))),
+            |vm, op| Ok(vm.op_store_flags(x(op))),
+            |vm, op| Ok(vm.op_load_flags(x(op))),
+            |vm, op| Err(VmError::InvalidOpcode(vm.program_counter(), op)),
+        ];
+
+        /// Execute `opcode` with `Engine::Table`
+        pub(crate) fn execute_table(&mut self, opcode: u16) -> VmResult<ProgramCounter> {
+            Self::HANDLERS[Kind::of(opcode) as usize](self, opcode)
+        }
+    }
+}
+
+#[cfg(feature = "cached-engine")]
+pub(crate) use cached::BlockCache;
+
+#[cfg(feature = "cached-engine")]
+mod cached {
+    use super::table::Handler;
+    use crate::{
+        debug::Inspect,
+        emu::{
+            bus::Bus,
+            instruction::Kind,
+            vm::{ProgramState, Vm},
+        },
+    };
+    /// Most instructions in a block
+    const MAX_BLOCK: usize = 64;
+
+    struct Op<B: Bus> {
+        handler: Handler<B>,
+        opcode: u16,
+        /// The instruction accesses memory, see `Vm::check_memory_access`
+        checked: bool,
+    }
+
+    /// Straight line instructions, only the last one can branch or use the timers
+    struct Block<B: Bus> {
+        ops: Vec<Op<B>>,
+        ends_with_branch: bool,
+    }
+
+    /// Compiled blocks by start address.
+    pub(crate) struct BlockCache<B: Bus> {
+        blocks: Vec<Option<Box<Block<B>>>>,
+        /// Addresses covered by a cached block
+        code: Vec<bool>,
+        /// Set when the program wrote to a cached block
+        dirty: bool,
+    }
+
+    impl<B: Bus> BlockCache<B> {
+        pub(crate) fn new() -> Self {
+            Self {
+                blocks: Vec::new(),
+                code: Vec::new(),
+                dirty: false,
+            }
+        }
+
+        pub(crate) fn clear(&mut self) {
+            self.blocks.clear();
+            self.code.clear();
+        }
+
+        /// Drop every block if `address` is part of one
+        pub(crate) fn invalidate(&mut self, address: u16) {
+            if self.code.get(address as usize) == Some(&true) {
+                self.clear();
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// True for the instructions that end a block: the ones that move the program counter or
+    /// can stay on it, `sys` whose policy may do both, the ones that use the timers, which are
+    /// only counted down at the end of a block, and invalid opcodes whose error gives the address
+    fn ends_block(kind: Kind) -> bool {
+        matches!(
+            kind,
+            Kind::Invalid
+                | Kind::Return
+                | Kind::Exit
+                | Kind::CallMachineCode
+                | Kind::Jump
+                | Kind::Call
+                | Kind::SkipIfEq
+                | Kind::SkipIfNeq
+                | Kind::SkipIfRegEq
+                | Kind::SkipIfDifferent
+                | Kind::JumpNPlusPC
+                | Kind::SkipIfKeyPressed
+                | Kind::SkipIfNotKeyPressed
+                | Kind::WaitInputStoreIn
+                | Kind::SetXAsDT
+                | Kind::SetDTAsX
+                | Kind::SetSTAsX
+        )
+    }
+
+    impl<B: Bus> Vm<B> {
+        fn compile(&self, start: u16) -> Box<Block<B>> {
+            let size = self.memory_size();
+
+            let mut block = Block {
+                ops: Vec::new(),
+                ends_with_branch: false,
+            };
+            let mut address = start as usize;
+            while block.ops.len() < MAX_BLOCK && address + 1 < size {
+                let opcode = self.fetch(address as u16);
+                let kind = Kind::of(opcode);
+                block.ops.push(Op {
+                    handler: Self::HANDLERS[kind as usize],
+                    opcode,
+                    checked: matches!(
+                        kind,
+                        Kind::Draw | Kind::StoreBCD | Kind::DumpRegisters | Kind::LoadRegisters
+                    ),
+                });
+                address += 2;
+                if ends_block(kind) {
+                    block.ends_with_branch = true;
+                    break;
+                }
+            }
+
+            Box::new(block)
+        }
+
+        /// Run the block at the program counter, at most `cycles` instructions of it. Returns the
+        /// number of executed instructions and the resulting state.
+        pub(crate) fn run_block(&mut self, cycles: usize) -> (usize, ProgramState) {
+            let start = self.program_counter();
+            let size = self.memory_size();
+            if start as usize + 1 >= size {
+                return (1, self.cycle().unwrap_or_else(ProgramState::Error));
+            }
+            if self.cache.blocks.len() != size {
+                self.cache.blocks = (0..size).map(|_| None).collect();
+                self.cache.code = vec![false; size];
+            }
+
+            // The block is taken out of the cache while it runs and put back unless the program
+            // wrote to it
+            let block = match self.cache.blocks[start as usize].take() {
+                Some(block) => block,
+                None => {
+                    let block = self.compile(start);
+                    let end = start as usize + 2 * block.ops.len();
+                    self.cache.code[start as usize..end].fill(true);
+                    block
+                }
+            };
+            let (executed, state) = self.run_compiled(&block, cycles);
+            if let Some(slot) = self.cache.blocks.get_mut(start as usize) {
+                *slot = Some(block);
+            }
+            (executed, state)
+        }
+
+        fn run_compiled(&mut self, block: &Block<B>, cycles: usize) -> (usize, ProgramState) {
+            self.cache.dirty = false;
+            let straight = block.ops.len() - block.ends_with_branch as usize;
+            let mut executed = 0;
+            for op in block.ops[..straight].iter().take(cycles) {
+                if op.checked {
+                    if let Err(err) = self.check_memory_access(op.opcode) {
+                        self.skip_instructions(executed);
+                        return (executed, ProgramState::Error(err));
+                    }
+                }
+                // Only the last instruction of a block can fail otherwise
+                let _ = (op.handler)(self, op.opcode);
+                executed += 1;
+                if self.cache.dirty {
+                    break;
+                }
+            }
+            self.skip_instructions(executed);
+
+            if self.cache.dirty
+                || executed < straight
+                || executed == cycles
+                || !block.ends_with_branch
+            {
+                return (executed, ProgramState::Continue);
+            }
+            let last = &block.ops[straight];
+            let next = self
+                .check_memory_access(last.opcode)
+                .and_then(|()| (last.handler)(self, last.opcode));
+            match next {
+                Ok(next) => (executed + 1, self.finish_instruction(next)),
+                Err(err) => (executed, ProgramState::Error(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for variant in Engine::VARIANTS {
+            assert!(variant.parse::<Engine>().is_ok());
+        }
+        assert!("jit".parse::<Engine>().is_err());
+    }
+
+    #[cfg(feature = "table-engine")]
+    #[test]
+    fn table_engine_agrees() {
+        use crate::{emu::vm::Vm, soak::random_rom};
+
+        for seed in 0..200 {
+            let mut by_match = Vm::new().with_engine(Engine::Match);
+            let mut by_table = Vm::new().with_engine(Engine::Table);
+            by_match.load(random_rom(seed));
+            by_table.load(random_rom(seed));
+            for _ in 0..500 {
+                if by_match.check_program_counter().is_err() {
+                    break;
+                }
+                assert_eq!(by_match.cycle(), by_table.cycle(), "seed {}", seed);
+            }
+            assert_eq!(by_match.snapshot(), by_table.snapshot(), "seed {}", seed);
+        }
+    }
+
+    #[cfg(feature = "cached-engine")]
+    #[test]
+    fn cached_engine_agrees() {
+        use crate::{bench::WORKLOAD, emu::vm::Vm};
+
+        let mut by_match = Vm::new();
+        let mut cached = Vm::new().with_engine(Engine::Cached);
+        by_match.load(WORKLOAD.to_vec());
+        cached.load(WORKLOAD.to_vec());
+        for cycles in [1, 7, 64, 1000].iter().cycle().take(40) {
+            assert_eq!(by_match.run(*cycles), cached.run(*cycles));
+            assert_eq!(by_match.snapshot(), cached.snapshot());
+        }
+    }
+
+    #[cfg(feature = "cached-engine")]
+    #[test]
+    fn self_modifying_code() {
+        use crate::{
+            debug::Inspect,
+            emu::vm::{ProgramState, Vm},
+        };
+
+        let program = vec![
+            0x60, 0x70, // ld v0, 0x70
+            0x61, 0x05, // ld v1, 0x05
+            0xA2, 0x0A, // ld i, 0x20A
+            0xF1, 0x55, // ld [i], v1, replaces the cls below with add v0, 0x05
+            0x62, 0x01, // ld v2, 0x01
+            0x00, 0xE0, // cls
+            0x12, 0x0C, // jp 0x20C
+        ];
+        for engine in Engine::VARIANTS {
+            let mut vm = Vm::new().with_engine(engine.parse().unwrap());
+            vm.load(program.clone());
+            assert_eq!(vm.run(100), ProgramState::Halt, "{}", engine);
+            assert_eq!(vm.register(0), 0x75, "{}", engine);
+            assert_eq!(vm.register(2), 0x01, "{}", engine);
+
+            // Code loaded from outside is seen too
+            vm.restore(&{
+                let mut state = vm.snapshot();
+                state.program_counter = 0x208;
+                state
+            });
+            vm.write_memory(0x20A, &[0x70, 0x10]).unwrap();
+            vm.run(100);
+            assert_eq!(vm.register(0), 0x85, "{}", engine);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use thiserror::Error;
+
+pub type VmResult<T> = std::result::Result<T, VmError>;
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    #[error("Program counter out of range: 0x{0:04X}")]
+    PcOutOfRange(u16),
+
+    #[error("Memory access out of range: 0x{0:04X}..0x{1:04X}")]
+    MemoryOutOfRange(usize, usize),
+
+    #[error("Stack overflow calling from 0x{0:04X}")]
+    StackOverflow(u16),
+
+    #[error("Stack underflow returning from 0x{0:04X}")]
+    StackUnderflow(u16),
+
+    #[error("Invalid opcode {1:04X} at 0x{0:04X}")]
+    InvalidOpcode(u16, u16),
+
+    #[error("Rom of {0} bytes does not fit in the {1} bytes of program memory")]
+    RomTooLarge(usize, usize),
+
+    #[error("Rom has an odd length of {0} bytes")]
+    OddRomLength(usize),
+}
+
+pub type StateResult<T> = std::result::Result<T, StateError>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StateError {
+    #[error("Invalid JSON at offset {0}")]
+    Syntax(usize),
+
+    #[error("Missing field: {0}")]
+    MissingField(&'static str),
+
+    #[error("Unknown field: {0}")]
+    UnknownField(String),
+
+    #[error("Invalid value for field: {0}")]
+    InvalidValue(String),
+
+    #[error("Snapshot of {0} bytes instead of {1}")]
+    Size(usize, usize),
+
+    #[error("Snapshot format version {0} is not supported, expected {1}")]
+    Version(u8, u8),
+}
@@ -16,3 +16,23 @@ pub const FONT_SET: [u8; 80] = [
     0xf0, 0x80, 0xf0, 0x80, 0xf0, // e
     0xf0, 0x80, 0xf0, 0x80, 0x80, // f
 ];
+
+/// Address of `BIG_FONT_SET`, right after `FONT_SET`
+pub const BIG_FONT_START: usize = FONT_SET.len();
+
+/// Bytes of a `BIG_FONT_SET` sprite
+pub const BIG_FONT_SPRITE_SIZE: usize = 10;
+
+/// SUPER-CHIP 8x10 sprites of the digits 0 to 9, used by `ld hf, vx`
+pub const BIG_FONT_SET: [u8; 100] = [
+    0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, // 1
+    0x3e, 0x7f, 0xc3, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xff, 0xff, // 2
+    0x3c, 0x7e, 0xc3, 0x03, 0x0e, 0x0e, 0x03, 0xc3, 0x7e, 0x3c, // 3
+    0x06, 0x0e, 0x1e, 0x36, 0x66, 0xc6, 0xff, 0xff, 0x06, 0x06, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfe, 0x03, 0xc3, 0x7e, 0x3c, // 5
+    0x3e, 0x7c, 0xc0, 0xc0, 0xfc, 0xfe, 0xc3, 0xc3, 0x7e, 0x3c, // 6
+    0xff, 0xff, 0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7e, 0x7e, 0xc3, 0xc3, 0x7e, 0x3c, // 8
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7f, 0x3f, 0x03, 0x03, 0x3e, 0x7c, // 9
+];
@@ -0,0 +1,233 @@
+use super::{
+    bus::Bus,
+    error::VmResult,
+    memory::Memory,
+    vm::{ProgramState, Vm, TIMER_PERIOD},
+};
+use std::time::Duration;
+
+/// Instructions per 60 Hz frame of the COSMAC VIP interpreter
+pub const VIP_CYCLES_PER_FRAME: usize = 11;
+
+/// Instructions per 60 Hz frame that SUPER-CHIP games are usually tuned for
+pub const SCHIP_CYCLES_PER_FRAME: usize = 30;
+
+/// Number of cycles executed per frame unless configured with `Frames::cycles_per_frame`
+pub const DEFAULT_CYCLES_PER_FRAME: usize = VIP_CYCLES_PER_FRAME;
+
+/// A completed frame produced by `Vm::frames`.
+#[derive(Clone)]
+pub struct Frame {
+    /// Frame number starting at 0 for the first frame produced by the iterator
+    pub number: usize,
+    /// Pixels of the display row by row, `width` pixels per row, see `Gpu::display`
+    pub display: Vec<bool>,
+    pub width: usize,
+    pub height: usize,
+    /// True while the sound timer is active and the buzzer should be playing
+    pub sound: bool,
+    /// True if the frame ended early because the vm stopped on a breakpoint or a watchpoint, see
+    /// `Vm::break_hit`. The timers are not counted for such a frame.
+    pub breakpoint: bool,
+}
+
+impl Frame {
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.display[(y % self.height) * self.width + (x % self.width)]
+    }
+}
+
+/// Iterator over completed frames. Ends when the program stops and after the first error.
+pub struct Frames<'a, B: Bus = Memory> {
+    vm: &'a mut Vm<B>,
+    cycles_per_frame: usize,
+    period: Duration,
+    number: usize,
+    done: bool,
+}
+
+impl<'a, B: Bus> Frames<'a, B> {
+    pub(crate) fn new(vm: &'a mut Vm<B>) -> Self {
+        Self {
+            vm,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            period: TIMER_PERIOD,
+            number: 0,
+            done: false,
+        }
+    }
+
+    pub fn cycles_per_frame(mut self, cycles: usize) -> Self {
+        self.cycles_per_frame = cycles.max(1);
+        self
+    }
+
+    /// Time a frame lasts for the timers of a `TimerClock::Realtime` vm, 1/60 s by default
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+}
+
+impl<'a, B: Bus> Iterator for Frames<'a, B> {
+    type Item = VmResult<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut breakpoint = false;
+        for _ in 0..self.cycles_per_frame {
+            match self.vm.cycle() {
+                Ok(ProgramState::Halted(_)) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(ProgramState::BreakpointHit) => {
+                    breakpoint = true;
+                    break;
+                }
+                Ok(_) => (),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if !breakpoint {
+            self.vm.tick(self.period);
+        }
+
+        let frame = Frame {
+            number: self.number,
+            display: self.vm.gpu.display().to_vec(),
+            width: self.vm.gpu.width(),
+            height: self.vm.gpu.height(),
+            sound: self.vm.sound_active(),
+            breakpoint,
+        };
+        self.number += 1;
+        Some(Ok(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        debug::Inspect,
+        emu::{error::VmError, vm::TimerClock},
+    };
+
+    #[test]
+    fn frames_are_numbered_and_drawn() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0xF0, 0x29, // ld f, v0
+            0xD0, 0x05, // drw v0, v0, 5
+            0x12, 0x04, // jp 0x204
+        ]);
+
+        let frames: Vec<Frame> = vm
+            .frames()
+            .cycles_per_frame(2)
+            .take(3)
+            .collect::<VmResult<_>>()
+            .unwrap();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].number, 2);
+        assert!(frames[0].get(0, 0));
+        assert!(!frames[0].sound);
+    }
+
+    #[test]
+    fn frames_draw_hires() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x00, 0xFF, // 200: high
+            0x60, 0x00, // 202: ld v0, 0
+            0xF0, 0x29, // 204: ld f, v0
+            0x61, 0x7C, // 206: ld v1, 124
+            0x62, 0x3B, // 208: ld v2, 59
+            0xD1, 0x25, // 20A: drw v1, v2, 5
+            0x12, 0x0C, // 20C: jp 0x20C
+        ]);
+
+        let frame = vm.frames().cycles_per_frame(6).next().unwrap().unwrap();
+        assert_eq!((frame.width, frame.height), (128, 64));
+        assert_eq!(frame.display.len(), 128 * 64);
+        assert!(frame.get(124, 59));
+        assert!(frame.get(127, 63));
+        assert!(!frame.get(62, 29));
+    }
+
+    #[test]
+    fn frames_report_sound() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0xF0, 0x18, // ld st, v0
+            0x12, 0x04, // jp 0x204
+        ]);
+
+        let sound: Vec<bool> = vm
+            .frames()
+            .cycles_per_frame(1)
+            .take(3)
+            .map(|f| f.unwrap().sound)
+            .collect();
+        assert_eq!(sound, vec![false, true, true]);
+    }
+
+    #[test]
+    fn frames_tick_realtime_timers() {
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Realtime);
+        vm.load(vec![
+            0x60, 0x02, // ld v0, 0x02
+            0xF0, 0x18, // ld st, v0
+            0x12, 0x04, // jp 0x204
+        ]);
+
+        let sound: Vec<bool> = vm
+            .frames()
+            .cycles_per_frame(100)
+            .take(3)
+            .map(|f| f.unwrap().sound)
+            .collect();
+        assert_eq!(sound, vec![true, false, false]);
+    }
+
+    #[test]
+    fn frames_end_on_breakpoints() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x70, 0x01, // add v0, 1
+            0x70, 0x01, // add v0, 1
+            0x12, 0x00, // jp 0x200
+        ]);
+        vm.add_breakpoint(0x202);
+
+        for _ in 0..2 {
+            let frame = vm.frames().cycles_per_frame(10).next().unwrap().unwrap();
+            assert!(frame.breakpoint);
+        }
+        assert_eq!(vm.program_counter(), 0x202);
+        assert_eq!(vm.register(0), 3);
+    }
+
+    #[test]
+    fn frames_stop_on_error() {
+        let mut vm = Vm::new();
+        vm.load(vec![0x1F, 0xFF]); // jp 0xFFF
+
+        let mut frames = vm.frames().cycles_per_frame(1);
+        assert!(frames.next().unwrap().is_ok());
+        assert_eq!(
+            frames.next().unwrap().err(),
+            Some(VmError::PcOutOfRange(0xFFF))
+        );
+        assert!(frames.next().is_none());
+    }
+}
@@ -0,0 +1,316 @@
+//! Display packed one bit per pixel, one `u128` per row.
+//!
+//! Rows are up to `MAX_WIDTH` pixels wide so the same representation holds the 64x32 chip8
+//! display and 128x64 high resolution displays. Pixel `x` of a row is bit `x` of the row.
+//! Clearing and scrolling work on whole rows at once. RGBA conversion and frame diffing use
+//! SSE2 on x86_64 when the `simd` feature is enabled and a portable version otherwise.
+
+use crate::render::Palette;
+
+/// Widest supported display, the bits of a row
+pub const MAX_WIDTH: usize = 128;
+
+/// Tallest supported display, also the bits of the mask returned by `Framebuffer::changed_rows`
+pub const MAX_HEIGHT: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    rows: [u128; MAX_HEIGHT],
+}
+
+impl Framebuffer {
+    /// Blank display of `width` by `height` pixels, at most `MAX_WIDTH` by `MAX_HEIGHT`
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            (1..=MAX_WIDTH).contains(&width) && (1..=MAX_HEIGHT).contains(&height),
+            "Unsupported display size {}x{}",
+            width,
+            height
+        );
+        Self {
+            width,
+            height,
+            rows: [0; MAX_HEIGHT],
+        }
+    }
+
+    /// Pack a display of one `bool` per pixel, row by row
+    pub fn from_display(display: &[bool], width: usize, height: usize) -> Self {
+        let mut frame = Self::new(width, height);
+        for (y, row) in display.chunks(width).take(height).enumerate() {
+            frame.rows[y] = row
+                .iter()
+                .enumerate()
+                .filter(|(_, pixel)| **pixel)
+                .fold(0, |bits, (x, _)| bits | 1 << x);
+        }
+        frame
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Pixels of the visible rows
+    pub fn rows(&self) -> &[u128] {
+        &self.rows[..self.height]
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y % self.height] >> (x % self.width) & 1 != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        let bit = 1 << (x % self.width);
+        let row = &mut self.rows[y % self.height];
+        match value {
+            true => *row |= bit,
+            false => *row &= !bit,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; MAX_HEIGHT];
+    }
+
+    /// Bits of the pixels inside the display width
+    fn row_mask(&self) -> u128 {
+        u128::MAX >> (MAX_WIDTH - self.width)
+    }
+
+    /// Move the display down by `lines`, the top lines become blank
+    pub fn scroll_down(&mut self, lines: usize) {
+        let lines = lines.min(self.height);
+        self.rows.copy_within(0..self.height - lines, lines);
+        self.rows[..lines].fill(0);
+    }
+
+    /// Move the display up by `lines`, the bottom lines become blank
+    pub fn scroll_up(&mut self, lines: usize) {
+        let lines = lines.min(self.height);
+        self.rows.copy_within(lines..self.height, 0);
+        self.rows[self.height - lines..self.height].fill(0);
+    }
+
+    /// Move the display left by `pixels`, the right columns become blank
+    pub fn scroll_left(&mut self, pixels: usize) {
+        for row in self.rows[..self.height].iter_mut() {
+            *row = row.checked_shr(pixels as u32).unwrap_or(0);
+        }
+    }
+
+    /// Move the display right by `pixels`, the left columns become blank
+    pub fn scroll_right(&mut self, pixels: usize) {
+        let mask = self.row_mask();
+        for row in self.rows[..self.height].iter_mut() {
+            *row = row.checked_shl(pixels as u32).unwrap_or(0) & mask;
+        }
+    }
+
+    /// Size in bytes of the RGBA image of the display drawn by `draw_rgba`
+    pub fn rgba_size(&self) -> usize {
+        self.width * self.height * 4
+    }
+
+    /// Draw the display into `buffer`, an RGBA image with one pixel per display pixel.
+    /// Rows past the end of the buffer are not drawn.
+    pub fn draw_rgba(&self, palette: Palette, buffer: &mut [u8]) {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        return sse2::draw_rgba(self, palette, buffer);
+        #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+        return self.draw_rgba_scalar(palette, buffer);
+    }
+
+    /// Portable version of `draw_rgba`
+    #[doc(hidden)]
+    pub fn draw_rgba_scalar(&self, palette: Palette, buffer: &mut [u8]) {
+        for (row, line) in self.rows().iter().zip(buffer.chunks_mut(self.width * 4)) {
+            for (x, pixel) in line.chunks_exact_mut(4).enumerate() {
+                let color = match row >> x & 1 {
+                    1 => palette.on,
+                    _ => palette.off,
+                };
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    pub fn to_rgba(&self, palette: Palette) -> Vec<u8> {
+        let mut buffer = vec![0; self.rgba_size()];
+        self.draw_rgba(palette, &mut buffer);
+        buffer
+    }
+
+    /// Rows that differ from `other`, bit `y` is set when row `y` changed. Frames of different
+    /// sizes differ on every row.
+    pub fn changed_rows(&self, other: &Framebuffer) -> u64 {
+        if self.width != other.width || self.height != other.height {
+            return u64::MAX >> (MAX_HEIGHT - self.height.max(other.height));
+        }
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        return sse2::changed_rows(&self.rows, &other.rows);
+        #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+        return self.changed_rows_scalar(other);
+    }
+
+    /// Portable version of `changed_rows` for frames of the same size
+    #[doc(hidden)]
+    pub fn changed_rows_scalar(&self, other: &Framebuffer) -> u64 {
+        self.rows
+            .iter()
+            .zip(other.rows.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .fold(0, |mask, (y, _)| mask | 1 << y)
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    //! SSE2 is part of every x86_64 cpu so no runtime detection is needed.
+
+    use super::{Framebuffer, MAX_HEIGHT};
+    use crate::render::Palette;
+    use std::arch::x86_64::*;
+
+    pub(super) fn draw_rgba(frame: &Framebuffer, palette: Palette, buffer: &mut [u8]) {
+        let width = frame.width;
+        // SAFETY: SSE2 is always available on x86_64, loads and stores are unaligned and stay
+        // inside `line`, pixels past the last group of four use the scalar code
+        unsafe {
+            let on = _mm_set1_epi32(i32::from_ne_bytes(palette.on));
+            let off = _mm_set1_epi32(i32::from_ne_bytes(palette.off));
+            let lanes = _mm_set_epi32(8, 4, 2, 1);
+            for (row, line) in frame.rows().iter().zip(buffer.chunks_mut(width * 4)) {
+                let groups = line.len() / 16;
+                for group in 0..groups {
+                    let bits = _mm_set1_epi32((row >> (group * 4)) as i32 & 0xF);
+                    let mask = _mm_cmpeq_epi32(_mm_and_si128(bits, lanes), lanes);
+                    let color = _mm_or_si128(_mm_and_si128(mask, on), _mm_andnot_si128(mask, off));
+                    _mm_storeu_si128(line.as_mut_ptr().add(group * 16) as *mut __m128i, color);
+                }
+                for (x, pixel) in line[groups * 16..].chunks_exact_mut(4).enumerate() {
+                    let color = match row >> (groups * 4 + x) & 1 {
+                        1 => palette.on,
+                        _ => palette.off,
+                    };
+                    pixel.copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    pub(super) fn changed_rows(a: &[u128; MAX_HEIGHT], b: &[u128; MAX_HEIGHT]) -> u64 {
+        let mut mask = 0;
+        // SAFETY: SSE2 is always available on x86_64 and every load reads one row
+        unsafe {
+            for y in 0..MAX_HEIGHT {
+                let row_a = _mm_loadu_si128(a.as_ptr().add(y) as *const __m128i);
+                let row_b = _mm_loadu_si128(b.as_ptr().add(y) as *const __m128i);
+                let same = _mm_movemask_epi8(_mm_cmpeq_epi8(row_a, row_b)) == 0xFFFF;
+                mask |= (!same as u64) << y;
+            }
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::gpu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    fn pattern(width: usize, height: usize) -> Framebuffer {
+        let mut frame = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                frame.set(x, y, (x * 7 + y * 3) % 5 == 0);
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn pack_display() {
+        let mut display = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        display[0] = true;
+        display[SCREEN_WIDTH * 2 + 63] = true;
+        let frame = Framebuffer::from_display(&display, SCREEN_WIDTH, SCREEN_HEIGHT);
+        assert!(frame.get(0, 0));
+        assert!(frame.get(63, 2));
+        assert!(frame.get(64 + 63, 2 + 32));
+        assert_eq!(
+            frame.rows().iter().map(|row| row.count_ones()).sum::<u32>(),
+            2
+        );
+    }
+
+    #[test]
+    fn scroll() {
+        let mut frame = Framebuffer::new(128, 64);
+        frame.set(0, 0, true);
+        frame.set(127, 63, true);
+
+        frame.scroll_down(4);
+        assert!(frame.get(0, 4));
+        assert_eq!(frame.rows()[63], 0);
+        frame.scroll_up(4);
+        assert!(frame.get(0, 0));
+        assert_eq!(frame.rows()[63], 0);
+
+        frame.scroll_right(4);
+        assert!(frame.get(4, 0));
+        frame.set(127, 1, true);
+        frame.scroll_right(4);
+        assert_eq!(frame.rows()[1], 0);
+        frame.scroll_left(8);
+        assert!(frame.get(0, 0));
+        frame.scroll_left(128);
+        assert_eq!(frame, Framebuffer::new(128, 64));
+
+        let mut lores = pattern(64, 32);
+        lores.scroll_right(1);
+        assert!(lores.rows().iter().all(|row| row >> 64 == 0));
+    }
+
+    #[test]
+    fn rgba_matches_scalar() {
+        let palette = Palette::default();
+        for &(width, height) in [(64, 32), (128, 64), (30, 5)].iter() {
+            let frame = pattern(width, height);
+            let mut scalar = vec![0; frame.rgba_size()];
+            frame.draw_rgba_scalar(palette, &mut scalar);
+            assert_eq!(frame.to_rgba(palette), scalar);
+            for (index, pixel) in scalar.chunks(4).enumerate() {
+                let color = match frame.get(index % width, index / width) {
+                    true => palette.on,
+                    false => palette.off,
+                };
+                assert_eq!(pixel, color);
+            }
+        }
+
+        // The buffer can be shorter than the image
+        let mut short = vec![0; 64 * 4 + 8];
+        pattern(64, 32).draw_rgba(palette, &mut short);
+        assert_eq!(short[..64 * 4], pattern(64, 32).to_rgba(palette)[..64 * 4]);
+    }
+
+    #[test]
+    fn changed_rows() {
+        let a = pattern(128, 64);
+        let mut b = a.clone();
+        assert_eq!(a.changed_rows(&b), 0);
+        b.set(127, 0, !b.get(127, 0));
+        b.set(3, 63, !b.get(3, 63));
+        assert_eq!(a.changed_rows(&b), 1 | 1 << 63);
+        assert_eq!(a.changed_rows(&b), a.changed_rows_scalar(&b));
+        assert_eq!(a.changed_rows(&pattern(64, 32)), u64::MAX);
+    }
+}
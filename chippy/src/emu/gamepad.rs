@@ -0,0 +1,189 @@
+//! Gamepads mapped to the keypad. Buttons are numbered like the standard layout of the browser
+//! Gamepad API, which SDL and gilrs follow as well, so any frontend reading a controller can hand
+//! over its button and axis state as is.
+
+use super::input::{Input, Key};
+use std::fmt;
+
+/// Stick deflection below which the stick counts as centered
+pub const DEFAULT_DEADZONE: f32 = 0.5;
+
+/// Buttons of the standard layout, in the order of their index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    /// A on Xbox controllers, cross on PlayStation controllers
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Button {
+    pub const ALL: [Button; 16] = [
+        Button::South,
+        Button::East,
+        Button::West,
+        Button::North,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::LeftTrigger,
+        Button::RightTrigger,
+        Button::Select,
+        Button::Start,
+        Button::LeftStick,
+        Button::RightStick,
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Button::South => "south",
+            Button::East => "east",
+            Button::West => "west",
+            Button::North => "north",
+            Button::LeftShoulder => "lb",
+            Button::RightShoulder => "rb",
+            Button::LeftTrigger => "lt",
+            Button::RightTrigger => "rt",
+            Button::Select => "select",
+            Button::Start => "start",
+            Button::LeftStick => "ls",
+            Button::RightStick => "rs",
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Left => "left",
+            Button::Right => "right",
+        }
+    }
+}
+
+/// Keypad key of every button. The left stick moves like the d-pad.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadMap {
+    keys: [Option<Key>; 16],
+    deadzone: f32,
+}
+
+impl Default for GamepadMap {
+    /// The d-pad on 2, 4, 6 and 8, the directions of most chip8 games, and the face buttons on
+    /// 5, 0, A and B
+    fn default() -> Self {
+        Self::new()
+            .with_button(Button::Up, Some(Key::Two))
+            .with_button(Button::Down, Some(Key::Eight))
+            .with_button(Button::Left, Some(Key::Four))
+            .with_button(Button::Right, Some(Key::Six))
+            .with_button(Button::South, Some(Key::Five))
+            .with_button(Button::East, Some(Key::Zero))
+            .with_button(Button::West, Some(Key::A))
+            .with_button(Button::North, Some(Key::B))
+    }
+}
+
+impl GamepadMap {
+    /// Map without any button bound
+    pub fn new() -> Self {
+        Self {
+            keys: [None; 16],
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+
+    /// Press `key` with `button`, or nothing
+    pub fn with_button(mut self, button: Button, key: Option<Key>) -> Self {
+        self.keys[button as usize] = key;
+        self
+    }
+
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn key(&self, button: Button) -> Option<Key> {
+        self.keys[button as usize]
+    }
+
+    /// Buttons held by standard index, the left stick holding the d-pad. The y axis points down.
+    pub fn pressed(&self, buttons: &[bool], axes: &[f32]) -> [bool; 16] {
+        let mut pressed = [false; 16];
+        for (held, button) in pressed.iter_mut().zip(buttons.iter()) {
+            *held = *button;
+        }
+        let axis = |index: usize| axes.get(index).copied().unwrap_or(0.0);
+        let (x, y) = (axis(0), axis(1));
+        pressed[Button::Up as usize] |= y < -self.deadzone;
+        pressed[Button::Down as usize] |= y > self.deadzone;
+        pressed[Button::Left as usize] |= x < -self.deadzone;
+        pressed[Button::Right as usize] |= x > self.deadzone;
+        pressed
+    }
+
+    /// Hold the keys of the pressed `buttons` and of the left stick, from the first two `axes`
+    pub fn apply(&self, buttons: &[bool], axes: &[f32], input: &mut Input) {
+        let pressed = self.pressed(buttons, axes);
+        for (key, pressed) in self.keys.iter().zip(pressed.iter()) {
+            if let (Some(key), true) = (key, pressed) {
+                input.key_down(*key);
+            }
+        }
+    }
+}
+
+impl fmt::Display for GamepadMap {
+    /// The bound buttons and their key, one `button key` pair per line, for a mapping overlay
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for button in Button::ALL.iter() {
+            if let Some(key) = self.key(*button) {
+                writeln!(f, "{:<6} {}", button.as_str(), key.as_str())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons_and_stick() {
+        let map = GamepadMap::default().with_button(Button::Start, Some(Key::F));
+        let mut buttons = [false; 17];
+        buttons[0] = true;
+        buttons[9] = true;
+
+        let mut input = Input::new();
+        map.apply(&buttons, &[0.9, -0.2], &mut input);
+        let held: Vec<usize> = (0..16).filter(|key| input.keys[*key]).collect();
+        assert_eq!(held, vec![0x5, 0x6, 0xF]);
+
+        // A short axis list and a stick in the deadzone press nothing more
+        let mut input = Input::new();
+        map.apply(&[], &[0.4], &mut input);
+        assert_eq!(input.keys, [false; 16]);
+    }
+
+    #[test]
+    fn overlay() {
+        let map = GamepadMap::new()
+            .with_button(Button::Up, Some(Key::Two))
+            .with_button(Button::South, Some(Key::Five));
+        assert_eq!(map.to_string(), "south  5\nup     2\n");
+    }
+}
@@ -1,8 +1,20 @@
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
+/// Size of the SUPER-CHIP high resolution display
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Pixels moved by the SUPER-CHIP horizontal scroll instructions
+pub const SCROLL_PIXELS: usize = 4;
+
 pub struct Gpu {
+    /// The 64x32 display. In high resolution mode a pixel is set when any of the 2x2 high
+    /// resolution pixels it covers is set, so frontends drawing 64x32 still show the game.
     pub memory: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    /// The 128x64 display, only used in high resolution mode
+    pub hires_memory: [bool; HIRES_WIDTH * HIRES_HEIGHT],
+    hires: bool,
     pub pending_draw: bool,
 }
 
@@ -10,31 +22,154 @@ fn index(x: usize, y: usize) -> usize {
     (y % SCREEN_HEIGHT) * SCREEN_WIDTH + (x % SCREEN_WIDTH)
 }
 
+fn hires_index(x: usize, y: usize) -> usize {
+    (y % HIRES_HEIGHT) * HIRES_WIDTH + (x % HIRES_WIDTH)
+}
+
 impl Gpu {
     pub fn new() -> Self {
         Self {
             memory: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            hires_memory: [false; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
             pending_draw: false,
         }
     }
 
     pub fn clear(&mut self) {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                self.set(x, y, false);
+        self.memory = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.hires_memory = [false; HIRES_WIDTH * HIRES_HEIGHT];
+        self.pending_draw = false;
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switch between the 64x32 and 128x64 displays, clearing the display
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear();
+        self.pending_draw = true;
+    }
+
+    /// Width of the current display
+    pub fn width(&self) -> usize {
+        match self.hires {
+            true => HIRES_WIDTH,
+            false => SCREEN_WIDTH,
+        }
+    }
+
+    /// Height of the current display
+    pub fn height(&self) -> usize {
+        match self.hires {
+            true => HIRES_HEIGHT,
+            false => SCREEN_HEIGHT,
+        }
+    }
+
+    /// Pixels of the current display row by row, `width()` pixels per row
+    pub fn display(&self) -> &[bool] {
+        match self.hires {
+            true => &self.hires_memory,
+            false => &self.memory,
+        }
+    }
+
+    /// Replace the display, switching to high resolution if `display` has 128x64 pixels
+    pub fn load(&mut self, display: &[bool]) {
+        self.hires = display.len() == self.hires_memory.len();
+        match self.hires {
+            true => {
+                self.hires_memory.copy_from_slice(display);
+                self.update_lores();
             }
+            false => self.memory.copy_from_slice(display),
         }
-        self.pending_draw = false;
+        self.pending_draw = true;
     }
 
     pub fn get(&self, x: usize, y: usize) -> bool {
-        self.memory[index(x, y)]
+        match self.hires {
+            true => self.hires_memory[hires_index(x, y)],
+            false => self.memory[index(x, y)],
+        }
     }
 
     pub fn set(&mut self, x: usize, y: usize, value: bool) {
-        let index = index(x, y);
-        self.pending_draw |= self.memory[index] != value;
-        self.memory[index] = value;
+        if !self.hires {
+            let index = index(x, y);
+            self.pending_draw |= self.memory[index] != value;
+            self.memory[index] = value;
+            return;
+        }
+
+        let index = hires_index(x, y);
+        if self.hires_memory[index] != value {
+            self.pending_draw = true;
+            self.hires_memory[index] = value;
+            let (x, y) = ((index % HIRES_WIDTH) & !1, (index / HIRES_WIDTH) & !1);
+            self.memory[(y / 2) * SCREEN_WIDTH + x / 2] = self.hires_block(x, y);
+        }
+    }
+
+    /// True if any pixel of the 2x2 block of the high resolution display at x,y is set
+    fn hires_block(&self, x: usize, y: usize) -> bool {
+        let top = y * HIRES_WIDTH + x;
+        let bottom = top + HIRES_WIDTH;
+        self.hires_memory[top..top + 2]
+            .iter()
+            .chain(self.hires_memory[bottom..bottom + 2].iter())
+            .any(|pixel| *pixel)
+    }
+
+    /// Rebuild the 64x32 view of the high resolution display
+    fn update_lores(&mut self) {
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                self.memory[y * SCREEN_WIDTH + x] = self.hires_block(x * 2, y * 2);
+            }
+        }
+    }
+
+    /// Move the current display by `dx`, `dy` pixels, the uncovered pixels are cleared
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let buffer = match self.hires {
+            true => &mut self.hires_memory[..],
+            false => &mut self.memory[..],
+        };
+        // Walk against the move so every pixel is read before it is overwritten
+        for row in 0..height {
+            let y = if dy > 0 { height - 1 - row } else { row };
+            for column in 0..width {
+                let x = if dx > 0 { width - 1 - column } else { column };
+                let (from_x, from_y) = (x - dx, y - dy);
+                let inside = (0..width).contains(&from_x) && (0..height).contains(&from_y);
+                buffer[(y * width + x) as usize] =
+                    inside && buffer[(from_y * width + from_x) as usize];
+            }
+        }
+        if self.hires {
+            self.update_lores();
+        }
+        self.pending_draw = true;
+    }
+
+    /// Scroll the display down by `lines`
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll(0, lines as isize);
+    }
+
+    /// Scroll the display right by `SCROLL_PIXELS`
+    pub fn scroll_right(&mut self) {
+        self.scroll(SCROLL_PIXELS as isize, 0);
+    }
+
+    /// Scroll the display left by `SCROLL_PIXELS`
+    pub fn scroll_left(&mut self) {
+        self.scroll(-(SCROLL_PIXELS as isize), 0);
     }
 
     /// Toggle pixel at location x,y. Returns true if pixel was set
@@ -65,24 +200,34 @@ impl Gpu {
             false => 0,
         }
     }
+
+    /// Draw a 16x16 sprite, one `u16` per row with the leftmost pixel in the highest bit
+    pub fn draw_large(&mut self, x: usize, y: usize, rows: &[u16; 16]) -> u8 {
+        let mut collision = false;
+        for (yy, row) in rows.iter().enumerate() {
+            for xx in 0..16 {
+                let bit = (row >> (15 - xx)) & 1 != 0;
+                collision |= self.toggle(x + xx, y + yy, bit);
+            }
+        }
+        collision as u8
+    }
 }
 
 impl std::fmt::Display for Gpu {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut result = String::new();
-        result.push_str("\n");
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let bit = self.get(x as usize, y as usize);
-                let s = match bit {
+        writeln!(f)?;
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let s = match self.get(x, y) {
                     true => "█",
                     false => "·",
                 };
-                result.push_str(s.as_ref());
+                f.write_str(s)?;
             }
-            result.push_str("\n");
+            writeln!(f)?;
         }
-        write!(f, "{}", result)
+        Ok(())
     }
 }
 
@@ -0,0 +1,108 @@
+use super::instruction::Instruction;
+use std::collections::VecDeque;
+
+/// An executed instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryEntry {
+    pub address: u16,
+    pub opcode: u16,
+}
+
+impl HistoryEntry {
+    pub fn instruction(&self) -> Instruction {
+        Instruction::parse(self.opcode)
+    }
+}
+
+/// Ring buffer of the most recently executed instructions. Recording is disabled while the
+/// capacity is zero.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, dropping the oldest entries if there are too many. The memory for
+    /// the entries is reserved here so recording never allocates.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+        self.entries.reserve_exact(capacity - self.entries.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn push(&mut self, address: u16, opcode: u16) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry { address, opcode });
+    }
+
+    /// Get the entry `back` instructions ago, where 0 is the last executed instruction
+    pub fn get(&self, back: usize) -> Option<&HistoryEntry> {
+        let index = self.entries.len().checked_sub(back + 1)?;
+        self.entries.get(index)
+    }
+
+    /// Iterate from the oldest to the most recent entry
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> + '_ {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_by_default() {
+        let mut history = History::default();
+        history.push(0x200, 0x00E0);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn keeps_most_recent_entries() {
+        let mut history = History::new(3);
+        for i in 0..5 {
+            history.push(0x200 + i * 2, 0x6000 + i);
+        }
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(0).map(|e| e.address), Some(0x208));
+        assert_eq!(history.get(2).map(|e| e.address), Some(0x204));
+        assert_eq!(history.get(3), None);
+
+        history.set_capacity(1);
+        let addresses: Vec<u16> = history.iter().map(|e| e.address).collect();
+        assert_eq!(addresses, vec![0x208]);
+    }
+}
@@ -3,6 +3,10 @@ const KEYPAD_SIZE: usize = 16;
 #[derive(Debug, PartialEq)]
 pub struct Input {
     pub keys: [bool; KEYPAD_SIZE],
+    /// Keys checked by the program since the last `take_polled`
+    polled: [bool; KEYPAD_SIZE],
+    /// Key presses are ignored while locked
+    locked: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -71,6 +75,8 @@ impl Input {
     pub fn new() -> Self {
         Self {
             keys: [false; KEYPAD_SIZE],
+            polled: [false; KEYPAD_SIZE],
+            locked: false,
         }
     }
 
@@ -78,6 +84,27 @@ impl Input {
         self.keys[key as usize]
     }
 
+    /// Check if a key is pressed on behalf of the program, recording the key as polled
+    pub(crate) fn poll(&mut self, key: u8) -> bool {
+        self.polled[key as usize] = true;
+        self.is_pressed(key)
+    }
+
+    /// Record that the program is waiting for any key
+    pub(crate) fn poll_any(&mut self) {
+        self.polled = [true; KEYPAD_SIZE];
+    }
+
+    /// Keys polled by the program since the last call to `take_polled`
+    pub fn polled(&self) -> &[bool; KEYPAD_SIZE] {
+        &self.polled
+    }
+
+    /// Return the polled keys and reset the tracking
+    pub fn take_polled(&mut self) -> [bool; KEYPAD_SIZE] {
+        std::mem::replace(&mut self.polled, [false; KEYPAD_SIZE])
+    }
+
     pub fn clear(&mut self) {
         self.keys = [false; KEYPAD_SIZE];
     }
@@ -87,7 +114,17 @@ impl Input {
     }
 
     pub fn key_down(&mut self, key: Key) {
-        self.keys[key as usize] = true;
+        if !self.locked {
+            self.keys[key as usize] = true;
+        }
+    }
+
+    /// Ignore key presses until unlocked, releasing the keys held when locking
+    pub(crate) fn set_locked(&mut self, locked: bool) {
+        if locked {
+            self.clear();
+        }
+        self.locked = locked;
     }
 }
 
@@ -127,4 +164,34 @@ mod tests {
         input.key_up(key);
         assert!(!input.is_pressed(key as u8));
     }
+
+    #[test]
+    fn locked_ignores_presses() {
+        let mut input = Input::new();
+        input.key_down(Key::A);
+        input.set_locked(true);
+        assert!(!input.is_pressed(Key::A as u8));
+        input.key_down(Key::B);
+        assert!(!input.is_pressed(Key::B as u8));
+
+        input.set_locked(false);
+        input.key_down(Key::B);
+        assert!(input.is_pressed(Key::B as u8));
+    }
+
+    #[test]
+    fn poll_tracking() {
+        let mut input = Input::new();
+        input.key_down(Key::Five);
+        assert!(input.poll(Key::Five as u8));
+        assert!(!input.poll(Key::A as u8));
+
+        let polled = input.take_polled();
+        assert!(polled[0x5] && polled[0xA]);
+        assert_eq!(polled.iter().filter(|p| **p).count(), 2);
+        assert!(input.polled().iter().all(|p| !p));
+
+        input.poll_any();
+        assert!(input.take_polled().iter().all(|p| *p));
+    }
 }
@@ -1,16 +1,16 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TargetSourcePair {
     pub target: u8,
     pub source: u8,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RegisterValuePair {
     pub register: u8,
     pub value: u8,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Instruction {
     /// 0nnn - SYS addr Jump to a machine code routine at nnn.  This instruction is only used on
     /// the old computers on which Chip-8 was originally implemented. It is ignored by modern
@@ -24,6 +24,24 @@ pub enum Instruction {
     /// address at the top of the stack, then subtracts 1 from the stack pointer.
     Return,
 
+    /// 00Cn - SCD nibble Scroll the display down by n lines (SUPER-CHIP).
+    ScrollDown(u8),
+
+    /// 00FB - SCR Scroll the display right by 4 pixels (SUPER-CHIP).
+    ScrollRight,
+
+    /// 00FC - SCL Scroll the display left by 4 pixels (SUPER-CHIP).
+    ScrollLeft,
+
+    /// 00FD - EXIT Exit the interpreter (SUPER-CHIP).
+    Exit,
+
+    /// 00FE - LOW Switch to the 64x32 low resolution display (SUPER-CHIP).
+    LowRes,
+
+    /// 00FF - HIGH Switch to the 128x64 high resolution display (SUPER-CHIP).
+    HighRes,
+
     /// 1nnn - JP addr Jump to location nnn.  The interpreter sets the program counter to nnn.
     Jump(u16),
 
@@ -80,8 +98,8 @@ pub enum Instruction {
     SubYFromX(TargetSourcePair),
 
     /// 8xy6 - SHR Vx {, Vy} Set Vx = Vx SHR 1.  If the least-significant bit of Vx is 1, then VF
-    /// is set to 1, otherwise 0. Then Vx is divided by 2. NOTE: there is no information on what y
-    /// is set to
+    /// is set to 1, otherwise 0. Then Vx is divided by 2. y is kept when encoding so roms that
+    /// put a value there are not changed by a round trip
     ShiftRight(TargetSourcePair),
 
     /// 8xy7 - SUBN Vx, Vy Set Vx = Vy - Vx, set VF = NOT borrow.  If Vy > Vx, then VF is set to 1,
@@ -89,8 +107,8 @@ pub enum Instruction {
     SubXFromYIntoX(TargetSourcePair),
 
     /// 8xyE - SHL Vx {, Vy} Set Vx = Vx SHL 1.  If the most-significant bit of Vx is 1, then VF is
-    /// set to 1, otherwise to 0. Then Vx is multiplied by 2. NOTE: there is no information on what
-    /// y is set to
+    /// set to 1, otherwise to 0. Then Vx is multiplied by 2. y is kept when encoding like for
+    /// `ShiftRight`
     ShiftLeft(TargetSourcePair),
 
     /// 9xy0 - SNE Vx, Vy Skip next instruction if Vx != Vy.  The values of Vx and Vy are compared,
@@ -116,7 +134,8 @@ pub enum Instruction {
     /// set to 1, otherwise it is set to 0. If the sprite is positioned so part of it is outside
     /// the coordinates of the display, it wraps around to the opposite side of the screen. See
     /// instruction 8xy3 for more information on XOR, and section 2.4, Display, for more
-    /// information on the Chip-8 screen and sprites.
+    /// information on the Chip-8 screen and sprites. With n = 0 a 16x16 sprite of 32 bytes, two
+    /// bytes per row, is drawn (SUPER-CHIP).
     Draw { x: u8, y: u8, n: u8 }, // TODO
 
     /// Ex9E - SKP Vx Skip next instruction if key with the value of Vx is pressed.  Checks the
@@ -151,6 +170,10 @@ pub enum Instruction {
     /// Display, for more information on the Chip-8 hexadecimal font.
     SetIToFontSprite(u8),
 
+    /// Fx30 - LD HF, Vx Set I = location of the 10 byte high resolution sprite for digit Vx
+    /// (SUPER-CHIP).
+    SetIToBigFontSprite(u8),
+
     /// Fx33 - LD B, Vx Store BCD representation of Vx in memory locations I, I+1, and I+2.  The
     /// interpreter takes the decimal value of Vx, and places the hundreds digit in memory at
     /// location in I, the tens digit at location I+1, and the ones digit at location I+2.
@@ -165,6 +188,13 @@ pub enum Instruction {
     /// interpreter reads values from memory starting at location I into registers V0 through Vx.
     LoadRegisters(u8),
 
+    /// Fx75 - LD R, Vx Store registers V0 through Vx in the RPL user flags, x <= 7 (SUPER-CHIP).
+    StoreFlags(u8),
+
+    /// Fx85 - LD Vx, R Read registers V0 through Vx from the RPL user flags, x <= 7
+    /// (SUPER-CHIP).
+    LoadFlags(u8),
+
     /// Unknown opcode
     Invalid(u16),
 }
@@ -184,7 +214,7 @@ fn as_nnn(opcode: u16) -> u16 {
     opcode & 0xFFF
 }
 
-fn as_nibble_array(opcode: u16) -> [u8; 4] {
+const fn as_nibble_array(opcode: u16) -> [u8; 4] {
     let first = ((opcode & 0xF000) >> 12) as u8;
     let second = ((opcode & 0x0F00) >> 8) as u8;
     let third = ((opcode & 0x00F0) >> 4) as u8;
@@ -192,12 +222,146 @@ fn as_nibble_array(opcode: u16) -> [u8; 4] {
     [first, second, third, fourth]
 }
 
+/// Kind of instruction of an opcode, the fields are read from the opcode once the kind is known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    ClearDisplay,
+    Return,
+    Exit,
+    CallMachineCode,
+    Jump,
+    Call,
+    SkipIfEq,
+    SkipIfNeq,
+    SkipIfRegEq,
+    SetReg,
+    AddValueToReg,
+    SetRegXToRegY,
+    BitXOrY,
+    BitXAndY,
+    BitXXorY,
+    AddYToX,
+    SubYFromX,
+    ShiftRight,
+    SubXFromYIntoX,
+    ShiftLeft,
+    SkipIfDifferent,
+    SetI,
+    JumpNPlusPC,
+    Random,
+    Draw,
+    SkipIfKeyPressed,
+    SkipIfNotKeyPressed,
+    SetXAsDT,
+    WaitInputStoreIn,
+    SetDTAsX,
+    SetSTAsX,
+    AddXToI,
+    SetIToFontSprite,
+    StoreBCD,
+    DumpRegisters,
+    LoadRegisters,
+    ScrollDown,
+    ScrollRight,
+    ScrollLeft,
+    LowRes,
+    HighRes,
+    SetIToBigFontSprite,
+    StoreFlags,
+    LoadFlags,
+    Invalid,
+}
+
+/// Number of `Kind`s
+pub(crate) const KIND_COUNT: usize = Kind::Invalid as usize + 1;
+
+impl Kind {
+    pub(crate) fn of(opcode: u16) -> Kind {
+        // The low byte alone decides the kind of 0x0 opcodes, they must also have x = 0
+        match opcode & 0xFF00 {
+            0x0000 => DECODE[(opcode & 0xFF) as usize],
+            0x0001..=0x0FFF => Kind::CallMachineCode,
+            _ => DECODE[(((opcode >> 4) & 0xF00) | (opcode & 0xFF)) as usize],
+        }
+    }
+}
+
+/// Kind of every opcode indexed by its first nibble and low byte, built at compile time. The x
+/// nibble never changes the kind except in 0x0 opcodes, see `Kind::of`.
+static DECODE: [Kind; 4096] = decode_table();
+
+const fn decode_table() -> [Kind; 4096] {
+    let mut table = [Kind::Invalid; 4096];
+    let mut index = 0;
+    while index < table.len() {
+        let opcode = (((index & 0xF00) << 4) | (index & 0xFF)) as u16;
+        table[index] = kind_of(opcode);
+        index += 1;
+    }
+    table
+}
+
+const fn kind_of(opcode: u16) -> Kind {
+    match as_nibble_array(opcode) {
+        [0x0, 0x0, 0xE, 0x0] => Kind::ClearDisplay,
+        [0x0, 0x0, 0xE, 0xE] => Kind::Return,
+        [0x0, 0x0, 0xC, _] => Kind::ScrollDown,
+        [0x0, 0x0, 0xF, 0xB] => Kind::ScrollRight,
+        [0x0, 0x0, 0xF, 0xC] => Kind::ScrollLeft,
+        [0x0, 0x0, 0xF, 0xD] => Kind::Exit,
+        [0x0, 0x0, 0xF, 0xE] => Kind::LowRes,
+        [0x0, 0x0, 0xF, 0xF] => Kind::HighRes,
+        [0x0, _, _, _] => Kind::CallMachineCode,
+        [0x1, _, _, _] => Kind::Jump,
+        [0x2, _, _, _] => Kind::Call,
+        [0x3, _, _, _] => Kind::SkipIfEq,
+        [0x4, _, _, _] => Kind::SkipIfNeq,
+        [0x5, _, _, 0x0] => Kind::SkipIfRegEq,
+        [0x6, _, _, _] => Kind::SetReg,
+        [0x7, _, _, _] => Kind::AddValueToReg,
+        [0x8, _, _, 0x0] => Kind::SetRegXToRegY,
+        [0x8, _, _, 0x1] => Kind::BitXOrY,
+        [0x8, _, _, 0x2] => Kind::BitXAndY,
+        [0x8, _, _, 0x3] => Kind::BitXXorY,
+        [0x8, _, _, 0x4] => Kind::AddYToX,
+        [0x8, _, _, 0x5] => Kind::SubYFromX,
+        [0x8, _, _, 0x6] => Kind::ShiftRight,
+        [0x8, _, _, 0x7] => Kind::SubXFromYIntoX,
+        [0x8, _, _, 0xE] => Kind::ShiftLeft,
+        [0x9, _, _, 0x0] => Kind::SkipIfDifferent,
+        [0xA, _, _, _] => Kind::SetI,
+        [0xB, _, _, _] => Kind::JumpNPlusPC,
+        [0xC, _, _, _] => Kind::Random,
+        [0xD, _, _, _] => Kind::Draw,
+        [0xE, _, 0x9, 0xE] => Kind::SkipIfKeyPressed,
+        [0xE, _, 0xA, 0x1] => Kind::SkipIfNotKeyPressed,
+        [0xF, _, 0x0, 0x7] => Kind::SetXAsDT,
+        [0xF, _, 0x0, 0xA] => Kind::WaitInputStoreIn,
+        [0xF, _, 0x1, 0x5] => Kind::SetDTAsX,
+        [0xF, _, 0x1, 0x8] => Kind::SetSTAsX,
+        [0xF, _, 0x1, 0xE] => Kind::AddXToI,
+        [0xF, _, 0x2, 0x9] => Kind::SetIToFontSprite,
+        [0xF, _, 0x3, 0x0] => Kind::SetIToBigFontSprite,
+        [0xF, _, 0x3, 0x3] => Kind::StoreBCD,
+        [0xF, _, 0x5, 0x5] => Kind::DumpRegisters,
+        [0xF, _, 0x6, 0x5] => Kind::LoadRegisters,
+        [0xF, _, 0x7, 0x5] => Kind::StoreFlags,
+        [0xF, _, 0x8, 0x5] => Kind::LoadFlags,
+        _ => Kind::Invalid,
+    }
+}
+
+/// Address of an `nnn` opcode, fields wider than the opcode are truncated
+fn pack_nnn(addr: u16) -> u16 {
+    addr & 0xFFF
+}
+
 fn pack_xkk(rv: &RegisterValuePair) -> u16 {
     (((rv.register & 0xF) as u16) << 8) + (rv.value as u16)
 }
 
 fn pack_xyn(x: u8, y: u8, n: u8) -> u16 {
-    ((x as u16) << 8) + ((y as u16) << 4) + (n as u16)
+    (((x & 0xF) as u16) << 8) + (((y & 0xF) as u16) << 4) + ((n & 0xF) as u16)
 }
 
 fn pack_tsn(ts: &TargetSourcePair, n: u8) -> u16 {
@@ -206,10 +370,71 @@ fn pack_tsn(ts: &TargetSourcePair, n: u8) -> u16 {
 
 impl Instruction {
     pub fn parse(opcode: u16) -> Instruction {
+        let [_, x, y, n] = as_nibble_array(opcode);
+        let (register, c1, c2) = (x, y, n);
+        match Kind::of(opcode) {
+            Kind::ClearDisplay => Instruction::ClearDisplay,
+            Kind::Return => Instruction::Return,
+            Kind::Exit => Instruction::Exit,
+            Kind::CallMachineCode => Instruction::CallMachineCode(as_nnn(opcode)),
+            Kind::Jump => Instruction::Jump(as_nnn(opcode)),
+            Kind::Call => Instruction::Call(as_nnn(opcode)),
+            Kind::SkipIfEq => Instruction::SkipIfEq(as_rv_pair(register, c1, c2)),
+            Kind::SkipIfNeq => Instruction::SkipIfNeq(as_rv_pair(register, c1, c2)),
+            Kind::SkipIfRegEq => Instruction::SkipIfRegEq(as_ts_pair(x, y)),
+            Kind::SetReg => Instruction::SetReg(as_rv_pair(register, c1, c2)),
+            Kind::AddValueToReg => Instruction::AddValueToReg(as_rv_pair(register, c1, c2)),
+            Kind::SetRegXToRegY => Instruction::SetRegXToRegY(as_ts_pair(x, y)),
+            Kind::BitXOrY => Instruction::BitXOrY(as_ts_pair(x, y)),
+            Kind::BitXAndY => Instruction::BitXAndY(as_ts_pair(x, y)),
+            Kind::BitXXorY => Instruction::BitXXorY(as_ts_pair(x, y)),
+            Kind::AddYToX => Instruction::AddYToX(as_ts_pair(x, y)),
+            Kind::SubYFromX => Instruction::SubYFromX(as_ts_pair(x, y)),
+            Kind::ShiftRight => Instruction::ShiftRight(as_ts_pair(x, y)),
+            Kind::SubXFromYIntoX => Instruction::SubXFromYIntoX(as_ts_pair(x, y)),
+            Kind::ShiftLeft => Instruction::ShiftLeft(as_ts_pair(x, y)),
+            Kind::SkipIfDifferent => Instruction::SkipIfDifferent(as_ts_pair(x, y)),
+            Kind::SetI => Instruction::SetI(as_nnn(opcode)),
+            Kind::JumpNPlusPC => Instruction::JumpNPlusPC(as_nnn(opcode)),
+            Kind::Random => Instruction::Random(as_rv_pair(register, c1, c2)),
+            Kind::Draw => Instruction::Draw { x, y, n },
+            Kind::SkipIfKeyPressed => Instruction::SkipIfKeyPressed(x),
+            Kind::SkipIfNotKeyPressed => Instruction::SkipIfNotKeyPressed(x),
+            Kind::SetXAsDT => Instruction::SetXAsDT(x),
+            Kind::WaitInputStoreIn => Instruction::WaitInputStoreIn(x),
+            Kind::SetDTAsX => Instruction::SetDTAsX(x),
+            Kind::SetSTAsX => Instruction::SetSTAsX(x),
+            Kind::AddXToI => Instruction::AddXToI(x),
+            Kind::SetIToFontSprite => Instruction::SetIToFontSprite(x),
+            Kind::StoreBCD => Instruction::StoreBCD(x),
+            Kind::DumpRegisters => Instruction::DumpRegisters(x),
+            Kind::LoadRegisters => Instruction::LoadRegisters(x),
+            Kind::ScrollDown => Instruction::ScrollDown(n),
+            Kind::ScrollRight => Instruction::ScrollRight,
+            Kind::ScrollLeft => Instruction::ScrollLeft,
+            Kind::LowRes => Instruction::LowRes,
+            Kind::HighRes => Instruction::HighRes,
+            Kind::SetIToBigFontSprite => Instruction::SetIToBigFontSprite(x),
+            Kind::StoreFlags => Instruction::StoreFlags(x),
+            Kind::LoadFlags => Instruction::LoadFlags(x),
+            Kind::Invalid => Instruction::Invalid(opcode),
+        }
+    }
+
+    /// Decode by matching the nibbles of the opcode. This is the reference `parse` is checked and
+    /// benchmarked against.
+    #[doc(hidden)]
+    pub fn parse_nibbles(opcode: u16) -> Instruction {
         let nibbles = as_nibble_array(opcode);
         match nibbles {
             [0x0, 0x0, 0xE, 0x0] => Instruction::ClearDisplay,
             [0x0, 0x0, 0xE, 0xE] => Instruction::Return,
+            [0x0, 0x0, 0xC, n] => Instruction::ScrollDown(n),
+            [0x0, 0x0, 0xF, 0xB] => Instruction::ScrollRight,
+            [0x0, 0x0, 0xF, 0xC] => Instruction::ScrollLeft,
+            [0x0, 0x0, 0xF, 0xD] => Instruction::Exit,
+            [0x0, 0x0, 0xF, 0xE] => Instruction::LowRes,
+            [0x0, 0x0, 0xF, 0xF] => Instruction::HighRes,
             [0x0, _, _, _] => Instruction::CallMachineCode(as_nnn(opcode)),
             [0x1, _, _, _] => Instruction::Jump(as_nnn(opcode)),
             [0x2, _, _, _] => Instruction::Call(as_nnn(opcode)),
@@ -240,9 +465,12 @@ impl Instruction {
             [0xF, x, 0x1, 0x8] => Instruction::SetSTAsX(x),
             [0xF, x, 0x1, 0xE] => Instruction::AddXToI(x),
             [0xF, x, 0x2, 0x9] => Instruction::SetIToFontSprite(x),
+            [0xF, x, 0x3, 0x0] => Instruction::SetIToBigFontSprite(x),
             [0xF, x, 0x3, 0x3] => Instruction::StoreBCD(x),
             [0xF, x, 0x5, 0x5] => Instruction::DumpRegisters(x),
             [0xF, x, 0x6, 0x5] => Instruction::LoadRegisters(x),
+            [0xF, x, 0x7, 0x5] => Instruction::StoreFlags(x),
+            [0xF, x, 0x8, 0x5] => Instruction::LoadFlags(x),
             _ => Instruction::Invalid(opcode),
         }
     }
@@ -260,6 +488,14 @@ impl Instruction {
             Instruction::Return => {
                 format!("ret")
             }
+            Instruction::ScrollDown(n) => {
+                format!("scd 0x{:X}", n)
+            }
+            Instruction::ScrollRight => "scr".to_string(),
+            Instruction::ScrollLeft => "scl".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::LowRes => "low".to_string(),
+            Instruction::HighRes => "high".to_string(),
             Instruction::Jump(addr) => {
                 format!("jp 0x{:03X}", addr)
             }
@@ -347,6 +583,9 @@ impl Instruction {
             Instruction::SetIToFontSprite(register) => {
                 format!("ld f, v{:x}", register)
             }
+            Instruction::SetIToBigFontSprite(register) => {
+                format!("ld hf, v{:x}", register)
+            }
             Instruction::StoreBCD(register) => {
                 format!("ld b, v{:x}", register)
             }
@@ -356,19 +595,33 @@ impl Instruction {
             Instruction::LoadRegisters(register) => {
                 format!("ld v{:x}, [i]", register)
             }
+            Instruction::StoreFlags(register) => {
+                format!("ld r, v{:x}", register)
+            }
+            Instruction::LoadFlags(register) => {
+                format!("ld v{:x}, r", register)
+            }
             Instruction::Invalid(value) => {
                 format!("raw 0x{:04X}", value)
             }
         }
     }
 
+    /// Encode the instruction. Fields wider than their place in the opcode are truncated, so
+    /// `Instruction::parse(i.to_u16())` can differ from `i`, see `normalize`
     pub fn to_u16(&self) -> u16 {
         match self {
-            Instruction::CallMachineCode(addr) => (0x0u16 << 12) + addr,
+            Instruction::CallMachineCode(addr) => (0x0u16 << 12) + pack_nnn(*addr),
             Instruction::ClearDisplay => 0x00E0,
             Instruction::Return => 0x00EE,
-            Instruction::Jump(addr) => (0x1u16 << 12) + addr,
-            Instruction::Call(addr) => (0x2u16 << 12) + addr,
+            Instruction::ScrollDown(n) => 0x00C0 + (*n & 0xF) as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Jump(addr) => (0x1u16 << 12) + pack_nnn(*addr),
+            Instruction::Call(addr) => (0x2u16 << 12) + pack_nnn(*addr),
             Instruction::SkipIfEq(rv) => (0x3u16 << 12) + pack_xkk(rv),
             Instruction::SkipIfNeq(rv) => (0x4u16 << 12) + pack_xkk(rv),
             Instruction::SkipIfRegEq(ts) => (0x5u16 << 12) + pack_tsn(ts, 0),
@@ -384,8 +637,8 @@ impl Instruction {
             Instruction::SubXFromYIntoX(ts) => (0x8u16 << 12) + pack_tsn(ts, 7),
             Instruction::ShiftLeft(ts) => (0x8u16 << 12) + pack_tsn(ts, 0xE),
             Instruction::SkipIfDifferent(ts) => (0x9u16 << 12) + pack_tsn(ts, 0),
-            Instruction::SetI(addr) => (0xAu16 << 12) + addr,
-            Instruction::JumpNPlusPC(addr) => (0xBu16 << 12) + addr,
+            Instruction::SetI(addr) => (0xAu16 << 12) + pack_nnn(*addr),
+            Instruction::JumpNPlusPC(addr) => (0xBu16 << 12) + pack_nnn(*addr),
             Instruction::Random(rv) => (0xCu16 << 12) + pack_xkk(rv),
             Instruction::Draw { x, y, n } => (0xDu16 << 12) + pack_xyn(*x, *y, *n),
             Instruction::SkipIfKeyPressed(register) => {
@@ -404,12 +657,30 @@ impl Instruction {
             Instruction::SetIToFontSprite(register) => {
                 (0xFu16 << 12) + pack_xyn(*register, 0x2, 0x9)
             }
+            Instruction::SetIToBigFontSprite(register) => {
+                (0xFu16 << 12) + pack_xyn(*register, 0x3, 0x0)
+            }
             Instruction::StoreBCD(register) => (0xFu16 << 12) + pack_xyn(*register, 0x3, 0x3),
             Instruction::DumpRegisters(register) => (0xFu16 << 12) + pack_xyn(*register, 0x5, 0x5),
             Instruction::LoadRegisters(register) => (0xFu16 << 12) + pack_xyn(*register, 0x6, 0x5),
+            Instruction::StoreFlags(register) => (0xFu16 << 12) + pack_xyn(*register, 0x7, 0x5),
+            Instruction::LoadFlags(register) => (0xFu16 << 12) + pack_xyn(*register, 0x8, 0x5),
             Instruction::Invalid(code) => *code,
         }
     }
+
+    /// Canonical form of the instruction, the one `parse` gives for its opcode. Every opcode
+    /// decodes to exactly one instruction and encodes back to itself, the rules below only matter
+    /// for instructions built by hand:
+    ///
+    /// - registers and nibbles are truncated to 4 bits and addresses to 12 bits
+    /// - `sys` of the address of a `0x00nn` instruction such as `sys 0x0E0` becomes that
+    ///   instruction, `cls` here
+    /// - `Invalid` holding the opcode of a known instruction becomes that instruction
+    /// - shifts keep their source register, `shr vx` without a source is `8x06`
+    pub fn normalize(&self) -> Instruction {
+        Instruction::parse(self.to_u16())
+    }
 }
 
 #[cfg(test)]
@@ -422,6 +693,18 @@ mod tests {
         assert_eq!(result, [0xD, 0xE, 0xA, 0xF]);
     }
 
+    #[test]
+    fn decode_table_matches_nibbles() {
+        for opcode in 0..=u16::MAX {
+            assert_eq!(
+                Instruction::parse(opcode),
+                Instruction::parse_nibbles(opcode),
+                "{:04X}",
+                opcode
+            );
+        }
+    }
+
     #[test]
     fn call_machine_code() {
         assert_eq!(
@@ -715,11 +998,34 @@ mod tests {
         assert_eq!(Instruction::LoadRegisters(0xA), Instruction::parse(0xFA65));
     }
 
+    #[test]
+    fn super_chip() {
+        use Instruction::*;
+        assert_eq!(Instruction::parse(0x00C5), ScrollDown(5));
+        assert_eq!(Instruction::parse(0x00FB), ScrollRight);
+        assert_eq!(Instruction::parse(0x00FC), ScrollLeft);
+        assert_eq!(Instruction::parse(0x00FD), Exit);
+        assert_eq!(Instruction::parse(0x00FE), LowRes);
+        assert_eq!(Instruction::parse(0x00FF), HighRes);
+        assert_eq!(Instruction::parse(0xFA30), SetIToBigFontSprite(0xA));
+        assert_eq!(Instruction::parse(0xF775), StoreFlags(7));
+        assert_eq!(Instruction::parse(0xF785), LoadFlags(7));
+        assert_eq!(Instruction::parse(0xD120), Draw { x: 1, y: 2, n: 0 });
+        // Only the x = 0 forms are SUPER-CHIP instructions
+        assert_eq!(Instruction::parse(0x01FF), CallMachineCode(0x1FF));
+    }
+
     #[test]
     fn asm_output() {
         let pairs = vec![
             (0x00E0, "cls"),
             (0x00EE, "ret"),
+            (0x00C4, "scd 0x4"),
+            (0x00FB, "scr"),
+            (0x00FC, "scl"),
+            (0x00FD, "exit"),
+            (0x00FE, "low"),
+            (0x00FF, "high"),
             (0x0246, "sys 0x246"),
             (0x1246, "jp 0x246"),
             (0x2357, "call 0x357"),
@@ -750,9 +1056,12 @@ mod tests {
             (0xF118, "ld st, v1"),
             (0xF11E, "add i, v1"),
             (0xF129, "ld f, v1"),
+            (0xF130, "ld hf, v1"),
             (0xF133, "ld b, v1"),
             (0xF155, "ld [i], v1"),
             (0xF165, "ld v1, [i]"),
+            (0xF175, "ld r, v1"),
+            (0xF185, "ld v1, r"),
             (0xF169, "raw 0xF169"),
         ];
 
@@ -765,10 +1074,9 @@ mod tests {
 
     #[test]
     fn code_to_u16() {
-        // NOTE: for 0x8xy6 (shift left) 0x8xyE (shift right) dont store y to set them to 0 for the test
         let code_list = vec![
             0x00E0, 0x00EE, 0x0246, 0x1246, 0x2357, 0x32DE, 0x42DE, 0x5210, 0x6218, 0x70E3, 0x8120,
-            0x8121, 0x8122, 0x8123, 0x8124, 0x8125, 0x8106, 0x8127, 0x810E, 0x93E0, 0xA123, 0xB123,
+            0x8121, 0x8122, 0x8123, 0x8124, 0x8125, 0x8126, 0x8127, 0x812E, 0x93E0, 0xA123, 0xB123,
             0xC123, 0xD123, 0xE19E, 0xE1A1, 0xF107, 0xF10A, 0xF115, 0xF118, 0xF11E, 0xF129, 0xF133,
             0xF155, 0xF165, 0xF169,
         ];
@@ -780,6 +1088,47 @@ mod tests {
         }
     }
 
+    #[test]
+    fn every_opcode_round_trips() {
+        for opcode in 0..=u16::MAX {
+            let instruction = Instruction::parse(opcode);
+            assert_eq!(instruction.to_u16(), opcode, "{:?}", instruction);
+            assert_eq!(instruction.normalize(), instruction);
+        }
+    }
+
+    #[test]
+    fn normalization() {
+        use Instruction::*;
+        let pairs = vec![
+            (Jump(0x1FFF), Jump(0xFFF)),
+            (CallMachineCode(0xE0), ClearDisplay),
+            (CallMachineCode(0xEE), Return),
+            (Invalid(0x8126), ShiftRight(as_ts_pair(1, 2))),
+            (
+                ShiftLeft(as_ts_pair(0x11, 0x12)),
+                ShiftLeft(as_ts_pair(1, 2)),
+            ),
+            (
+                Draw {
+                    x: 0,
+                    y: 0,
+                    n: 0x1F,
+                },
+                Draw { x: 0, y: 0, n: 0xF },
+            ),
+            (
+                SetReg(as_rv_pair(0x13, 0x4, 0x2)),
+                SetReg(as_rv_pair(3, 0x4, 0x2)),
+            ),
+        ];
+        for (instruction, normalized) in pairs {
+            assert_eq!(instruction.normalize(), normalized, "{:?}", instruction);
+            assert_eq!(normalized.to_u16(), instruction.to_u16());
+            assert_eq!(normalized.normalize(), normalized);
+        }
+    }
+
     #[test]
     fn packing_xkk() {
         let rv = RegisterValuePair {
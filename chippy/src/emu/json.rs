@@ -0,0 +1,111 @@
+//! Reader for the small subset of JSON used by `VmState::from_json`: a single object whose values
+//! are unsigned integers or arrays of unsigned integers.
+
+/// Value of an object field
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Number(u64),
+    Array(Vec<u64>),
+}
+
+/// Fields of the object in `src`, in order. The error is the byte offset of the first invalid
+/// character.
+pub(crate) fn parse_object(src: &str) -> Result<Vec<(String, Value)>, usize> {
+    let mut reader = Reader { src, offset: 0 };
+    let mut fields = Vec::new();
+
+    reader.expect(b'{')?;
+    if !reader.accept(b'}') {
+        loop {
+            let name = reader.string()?;
+            reader.expect(b':')?;
+            fields.push((name, reader.value()?));
+            if reader.accept(b'}') {
+                break;
+            }
+            reader.expect(b',')?;
+        }
+    }
+
+    reader.skip_whitespace();
+    match reader.offset == src.len() {
+        true => Ok(fields),
+        false => Err(reader.offset),
+    }
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.offset..];
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.src.as_bytes().get(self.offset).copied()
+    }
+
+    /// Consume `byte` if it is next
+    fn accept(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.offset += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), usize> {
+        match self.accept(byte) {
+            true => Ok(()),
+            false => Err(self.offset),
+        }
+    }
+
+    /// A string without escapes
+    fn string(&mut self) -> Result<String, usize> {
+        self.expect(b'"')?;
+        let rest = &self.src[self.offset..];
+        match rest.find(['"', '\\']) {
+            Some(end) if rest.as_bytes()[end] == b'"' => {
+                self.offset += end + 1;
+                Ok(rest[..end].to_string())
+            }
+            Some(end) => Err(self.offset + end),
+            None => Err(self.src.len()),
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, usize> {
+        self.skip_whitespace();
+        let rest = &self.src[self.offset..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number = rest[..len].parse().map_err(|_| self.offset)?;
+        self.offset += len;
+        Ok(number)
+    }
+
+    fn value(&mut self) -> Result<Value, usize> {
+        if !self.accept(b'[') {
+            return Ok(Value::Number(self.number()?));
+        }
+
+        let mut values = Vec::new();
+        if !self.accept(b']') {
+            loop {
+                values.push(self.number()?);
+                if self.accept(b']') {
+                    break;
+                }
+                self.expect(b',')?;
+            }
+        }
+        Ok(Value::Array(values))
+    }
+}
@@ -0,0 +1,112 @@
+use super::{
+    error::{VmError, VmResult},
+    font::{BIG_FONT_SET, BIG_FONT_START, FONT_SET},
+    vm::{MEMORY_SIZE, MEMORY_START},
+};
+use std::{ops::Deref, sync::Arc};
+
+/// The 4K address space of the vm. The bytes are reference counted so that many vms can share a
+/// single loaded rom image, a vm gets its own copy on the first write (self modifying code,
+/// `ld [i], vx`, `ld b, vx`).
+#[derive(Clone)]
+pub struct Memory {
+    bytes: Arc<[u8]>,
+}
+
+impl Memory {
+    /// Empty memory with the font sets loaded
+    pub fn new() -> Self {
+        let mut bytes = vec![0; MEMORY_SIZE];
+        bytes[..FONT_SET.len()].copy_from_slice(&FONT_SET);
+        bytes[BIG_FONT_START..BIG_FONT_START + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
+        Self {
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Memory image with the font set and `rom` loaded at the program start address, unless it
+    /// does not fit like `Vm::try_load`. Clone the result to share it between vms.
+    pub fn with_rom(rom: &[u8]) -> VmResult<Self> {
+        let space = MEMORY_SIZE - MEMORY_START;
+        if rom.len() > space {
+            return Err(VmError::RomTooLarge(rom.len(), space));
+        }
+        let mut memory = Self::new();
+        memory.as_mut_slice()[MEMORY_START..MEMORY_START + rom.len()].copy_from_slice(rom);
+        Ok(memory)
+    }
+
+    /// True if the bytes are still shared with another `Memory`
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.bytes) > 1
+    }
+
+    /// Mutable access to the bytes, copying them first if they are shared
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if Arc::get_mut(&mut self.bytes).is_none() {
+            self.bytes = Arc::from(&self.bytes[..]);
+        }
+        Arc::get_mut(&mut self.bytes).expect("memory is uniquely owned after copy")
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) {
+        // Avoid copying shared memory if the write does not change anything
+        if self.bytes[address] != value {
+            self.as_mut_slice()[address] = value;
+        }
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Memory {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_is_loaded() {
+        let memory = Memory::new();
+        assert_eq!(memory.len(), MEMORY_SIZE);
+        assert_eq!(memory[..FONT_SET.len()], FONT_SET);
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let image = Memory::with_rom(&[0x12, 0x00]).unwrap();
+        let mut first = image.clone();
+        let second = image.clone();
+        assert!(first.is_shared());
+
+        // Writing the same value keeps the memory shared
+        first.write(MEMORY_START, 0x12);
+        assert!(first.is_shared());
+
+        first.write(MEMORY_START, 0xFF);
+        assert!(!first.is_shared());
+        assert_eq!(first[MEMORY_START], 0xFF);
+        assert_eq!(second[MEMORY_START], 0x12);
+        assert_eq!(image[MEMORY_START], 0x12);
+    }
+
+    #[test]
+    fn rom_too_large() {
+        let space = MEMORY_SIZE - MEMORY_START;
+        assert!(Memory::with_rom(&vec![0; space]).is_ok());
+        assert_eq!(
+            Memory::with_rom(&vec![0; space + 1]).err(),
+            Some(VmError::RomTooLarge(space + 1, space))
+        );
+    }
+}
@@ -1,6 +1,106 @@
+//! The chip8 machine. `vm::Vm` runs programs, `step` is the same machine as a pure function over
+//! owned states.
+
+use crate::debug::events::EventKind;
+use input::Input;
+use state::VmState;
+use vm::{ProgramState, TimerClock, Vm};
+
+pub mod bus;
+pub mod compress;
+pub mod dump;
+pub mod engine;
+pub mod error;
 mod font;
+pub mod frame;
+pub mod framebuffer;
+pub mod gamepad;
 pub mod gpu;
+pub mod history;
 pub mod input;
 pub mod instruction;
 pub mod iter;
+pub(crate) mod json;
+pub mod memory;
+pub mod pacing;
+pub mod profile;
+pub mod quirks;
+pub mod rewind;
+pub mod speed;
+pub mod state;
+pub mod trace;
 pub mod vm;
+
+/// Execute one instruction of `state` with the keys of `input` held, returning the next state and
+/// the events fired by the instruction, `EventKind::Halt` when the program stopped. Nothing is
+/// shared between calls, which suits model checking and state containers. Running a program
+/// with `Vm` is much faster. The timers count down once per step, like with
+/// `TimerClock::Instructions`.
+pub fn step(state: VmState, input: &Input) -> (VmState, Vec<EventKind>) {
+    let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+    vm.restore(&state);
+    vm.input.keys = input.keys;
+    let before = vm.snapshot();
+    let program_state = vm.cycle();
+    let after = vm.snapshot();
+
+    let mut events = EventKind::of_step(&before, &after);
+    if let Ok(ProgramState::Halted(_)) = program_state {
+        events.push(EventKind::Halt);
+    }
+    (after, events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::Key;
+
+    #[test]
+    fn pure_step() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 5
+            0xE0, 0x9E, // skp v0
+            0x00, 0xFD, // exit
+            0xD0, 0x05, // drw v0, v0, 5
+        ]);
+        let start = vm.snapshot();
+        let mut input = Input::new();
+
+        let (state, events) = step(start.clone(), &input);
+        assert_eq!(state.registers[0], 5);
+        assert!(events.is_empty());
+        assert_eq!(step(start.clone(), &input).0, state);
+
+        let (released, events) = step(state.clone(), &input);
+        assert_eq!(events, vec![EventKind::KeyPoll]);
+        let (_, events) = step(released, &input);
+        assert_eq!(events, vec![EventKind::Halt]);
+
+        input.key_down(Key::Five);
+        let (pressed, _) = step(state, &input);
+        assert!(pressed.keys[5]);
+        let (drawn, events) = step(pressed, &input);
+        assert_eq!(events, vec![EventKind::Draw]);
+        assert!(drawn.display.iter().any(|pixel| *pixel));
+    }
+
+    #[test]
+    fn step_counts_timers() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x03, // 200: ld v0, 3
+            0xF0, 0x15, // 202: ld dt, v0
+            0x12, 0x04, // 204: jp 0x204
+        ]);
+        let input = Input::new();
+        let mut state = vm.snapshot();
+        let mut timers = Vec::new();
+        for _ in 0..5 {
+            state = step(state, &input).0;
+            timers.push(state.delay_timer);
+        }
+        assert_eq!(timers, vec![0, 2, 1, 0, 0]);
+    }
+}
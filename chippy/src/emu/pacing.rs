@@ -0,0 +1,152 @@
+//! Frame pacing that holds an exact frame rate without busy looping for the whole frame.
+
+use std::time::{Duration, Instant};
+
+/// Remaining time below which the pacer stops sleeping and yields until the deadline, as sleeps
+/// usually overshoot by about a millisecond
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+
+/// Source of time of a `Pacer`, so that tests can run the schedule without sleeping.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Block until `deadline`
+    fn sleep_until(&mut self, deadline: Instant);
+}
+
+/// The system clock, sleeping the thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Sleep for most of the remaining time, then yield until `deadline`
+    fn sleep_until(&mut self, deadline: Instant) {
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            match deadline - now {
+                remaining if remaining > SPIN_THRESHOLD => {
+                    std::thread::sleep(remaining - SPIN_THRESHOLD)
+                }
+                _ => std::thread::yield_now(),
+            }
+        }
+    }
+}
+
+/// Waits for the start of every frame on a fixed schedule.
+#[derive(Debug, Clone)]
+pub struct Pacer<C: Clock = SystemClock> {
+    clock: C,
+    period: Duration,
+    next: Instant,
+    dropped: u64,
+}
+
+impl Pacer {
+    pub fn new(period: Duration) -> Self {
+        Self::with_clock(period, SystemClock)
+    }
+
+    pub fn with_fps(fps: u32) -> Self {
+        Self::new(Duration::from_secs(1) / fps.max(1))
+    }
+}
+
+impl<C: Clock> Pacer<C> {
+    pub fn with_clock(period: Duration, clock: C) -> Self {
+        Self {
+            next: clock.now() + period,
+            clock,
+            period,
+            dropped: 0,
+        }
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Frames skipped so far by restarting the schedule after running late
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Wait for the start of the next frame. Frames are scheduled from the previous deadline
+    /// rather than the end of the wait, so the rate does not drift. A frame that ran more than a
+    /// whole period late restarts the schedule instead of running the missed frames back to back.
+    pub fn wait(&mut self) {
+        let now = self.clock.now();
+        if now < self.next {
+            self.clock.sleep_until(self.next);
+            self.next += self.period;
+        } else if now - self.next > self.period {
+            self.dropped += ((now - self.next).as_nanos() / self.period.as_nanos().max(1)) as u64;
+            self.next = now + self.period;
+        } else {
+            self.next += self.period;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Time that only moves when slept or advanced
+    struct ManualClock(Instant);
+
+    impl ManualClock {
+        fn advance(&mut self, duration: Duration) {
+            self.0 += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.0
+        }
+
+        fn sleep_until(&mut self, deadline: Instant) {
+            self.0 = self.0.max(deadline);
+        }
+    }
+
+    #[test]
+    fn fixed_schedule() {
+        let period = Duration::from_millis(5);
+        let start = Instant::now();
+        let mut pacer = Pacer::with_clock(period, ManualClock(start));
+        for frame in 0..10 {
+            if frame == 3 {
+                // A slow frame is made up for by the following ones
+                pacer.clock_mut().advance(Duration::from_millis(8));
+            }
+            pacer.wait();
+        }
+        assert_eq!(pacer.clock().now() - start, period * 10);
+        assert_eq!(pacer.dropped(), 0);
+
+        // Running more than a whole period late skips the missed frames
+        pacer.clock_mut().advance(period * 3);
+        pacer.wait();
+        assert_eq!(pacer.dropped(), 2);
+        assert_eq!(pacer.clock().now() - start, period * 13);
+        pacer.wait();
+        assert_eq!(pacer.clock().now() - start, period * 14);
+    }
+}
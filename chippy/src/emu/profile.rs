@@ -0,0 +1,132 @@
+//! Execution counters, to find the hot loops of a program and the instruction mix of a run.
+//! Instructions are classed by their first nibble, the way the opcode tables list them.
+
+/// Executions of every address and of every opcode class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// Indexed by address, so counting never allocates
+    addresses: Vec<u64>,
+    classes: [u64; 16],
+    total: u64,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self {
+            addresses: vec![0; 1 << 16],
+            classes: [0; 16],
+            total: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.addresses.iter_mut().for_each(|count| *count = 0);
+        self.classes = [0; 16];
+        self.total = 0;
+    }
+
+    /// Count the execution of `opcode` at `address`
+    pub fn count(&mut self, address: u16, opcode: u16) {
+        self.addresses[address as usize] += 1;
+        self.classes[(opcode >> 12) as usize] += 1;
+        self.total += 1;
+    }
+
+    /// Instructions executed since profiling started
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Executions of the instruction at `address`
+    pub fn hits(&self, address: u16) -> u64 {
+        self.addresses[address as usize]
+    }
+
+    /// Executions of the opcodes starting with the nibble `class`, `0x8` counting the arithmetic
+    /// of `8xyn` for instance
+    pub fn class(&self, class: u8) -> u64 {
+        self.classes[(class & 0xF) as usize]
+    }
+
+    /// The `count` most executed addresses with their executions, the most executed first
+    pub fn hottest(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut hot: Vec<(u16, u64)> = self
+            .addresses
+            .iter()
+            .enumerate()
+            .filter(|(_, hits)| **hits > 0)
+            .map(|(address, hits)| (address as u16, *hits))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(count);
+        hot
+    }
+
+    /// Share of each opcode class in the executed instructions, from 0 to 1, with the mnemonic of
+    /// an instruction of the class
+    pub fn mix(&self) -> Vec<(u8, &'static str, f64)> {
+        (0..16u8)
+            .filter(|class| self.classes[*class as usize] > 0)
+            .map(|class| {
+                let share = self.classes[class as usize] as f64 / self.total as f64;
+                (class, class_name(class), share)
+            })
+            .collect()
+    }
+}
+
+/// Mnemonic of the opcodes starting with the nibble `class`
+pub fn class_name(class: u8) -> &'static str {
+    match class & 0xF {
+        0x0 => "sys",
+        0x1 => "jp",
+        0x2 => "call",
+        0x3 | 0x5 => "se",
+        0x4 | 0x9 => "sne",
+        0x6 | 0xA | 0xF => "ld",
+        0x7 => "add",
+        0x8 => "alu",
+        0xB => "jp v0",
+        0xC => "rnd",
+        0xD => "drw",
+        _ => "skp",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn count_executions() {
+        let mut vm = Vm::new().with_profiling();
+        vm.load(vec![
+            0x60, 0x00, // ld v0, 0
+            0x70, 0x01, // add v0, 1
+            0x30, 0x03, // se v0, 3
+            0x12, 0x02, // jp 0x202
+            0x00, 0xFD, // exit
+        ]);
+        vm.run(100);
+
+        let profile = vm.profile().unwrap();
+        assert_eq!(profile.total(), 10);
+        assert_eq!(profile.hits(0x202), 3);
+        assert_eq!(profile.hottest(2), vec![(0x202, 3), (0x204, 3)]);
+        assert_eq!(profile.class(0x1), 2);
+        assert_eq!(profile.class(0x7), 3);
+        let mix = profile.mix();
+        assert_eq!(mix.len(), 5);
+        assert_eq!(mix[0].0, 0x0);
+        assert_eq!(mix[2].1, "se");
+
+        vm.set_profiling(false);
+        assert!(vm.profile().is_none());
+    }
+}
@@ -0,0 +1,72 @@
+//! Behaviours on which the chip8 interpreters disagree. They are all off by default, which runs
+//! programs like the modern interpreters do.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// `or`, `and` and `xor` reset vf to 0, like the COSMAC VIP
+    pub vf_reset: bool,
+}
+
+impl Quirks {
+    /// Names of the quirks, as listed by rom headers
+    pub const NAMES: &'static [&'static str] = &["vf-reset"];
+
+    /// The quirks of the COSMAC VIP
+    pub fn vip() -> Self {
+        Self { vf_reset: true }
+    }
+
+    /// Enable the quirk `name`, returns false if it is not one of `Quirks::NAMES`
+    pub fn enable(&mut self, name: &str) -> bool {
+        match name {
+            "vf-reset" => self.vf_reset = true,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The quirks named in `names`, and the names of the quirks not supported
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> (Self, Vec<&'a str>) {
+        let mut quirks = Self::default();
+        let unsupported = names
+            .into_iter()
+            .filter(|name| !quirks.enable(name))
+            .collect();
+        (quirks, unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{debug::Inspect, emu::vm::Vm};
+
+    #[test]
+    fn vf_reset() {
+        let program = vec![
+            0x6F, 0x01, // ld vf, 1
+            0x80, 0x11, // or v0, v1
+            0x6F, 0x01, // ld vf, 1
+            0x80, 0x12, // and v0, v1
+            0x6F, 0x01, // ld vf, 1
+            0x80, 0x13, // xor v0, v1
+        ];
+        let mut vm = Vm::new();
+        vm.load(program.clone());
+        for _ in 0..6 {
+            vm.cycle().unwrap();
+            assert_eq!(vm.register(0xF), 1);
+        }
+
+        let mut vm = Vm::new().with_quirks(Quirks::vip());
+        vm.load(program);
+        for cycle in 0..6 {
+            vm.cycle().unwrap();
+            assert_eq!(vm.register(0xF), (cycle + 1) % 2);
+        }
+
+        let (quirks, unsupported) = Quirks::from_names(vec!["vf-reset", "shift"]);
+        assert_eq!(quirks, Quirks::vip());
+        assert_eq!(unsupported, vec!["shift"]);
+    }
+}
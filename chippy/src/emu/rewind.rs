@@ -0,0 +1,115 @@
+//! Rewinding a running program, for the rewind hotkey of the frontends. The machine is
+//! snapshotted every few frames into a compressed `SnapshotHistory`.
+
+use super::{bus::Bus, compress::SnapshotHistory, state::VmState, vm::Vm};
+
+/// Snapshots between two keyframes of the history
+pub const KEYFRAME_INTERVAL: usize = 60;
+
+pub struct Rewinder {
+    history: SnapshotHistory,
+    /// Frames between two snapshots
+    interval: usize,
+    /// Frames recorded since the last snapshot
+    since_snapshot: usize,
+    /// Reused by every snapshot
+    scratch: VmState,
+}
+
+impl Rewinder {
+    /// Snapshot every `interval` frames, keeping the last `capacity` snapshots. The program can
+    /// be rewound up to `interval * capacity` frames.
+    pub fn new(interval: usize, capacity: usize) -> Self {
+        Self {
+            history: SnapshotHistory::new(capacity, KEYFRAME_INTERVAL),
+            interval: interval.max(1),
+            since_snapshot: 0,
+            scratch: VmState::default(),
+        }
+    }
+
+    /// Number of snapshots available
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Drop every snapshot, for when another program is loaded
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.since_snapshot = 0;
+    }
+
+    /// Call once per frame, snapshots `vm` on the first frame and then every `interval` frames
+    pub fn record<B: Bus>(&mut self, vm: &Vm<B>) {
+        self.since_snapshot += 1;
+        if self.history.is_empty() || self.since_snapshot >= self.interval {
+            vm.snapshot_into(&mut self.scratch);
+            self.history.push(&self.scratch);
+            self.since_snapshot = 0;
+        }
+    }
+
+    /// Restore `vm` to the newest snapshot at least `frames` frames old, or the oldest one.
+    /// The snapshots after it are dropped. Returns the number of frames rewound.
+    pub fn rewind<B: Bus>(&mut self, vm: &mut Vm<B>, frames: usize) -> usize {
+        if frames == 0 || self.history.is_empty() {
+            return 0;
+        }
+        let back = match frames.checked_sub(self.since_snapshot) {
+            Some(rest) => rest.div_ceil(self.interval),
+            None => 0,
+        }
+        .min(self.history.len() - 1);
+        for _ in 0..back {
+            self.history.pop();
+        }
+
+        let rewound = self.since_snapshot + back * self.interval;
+        self.since_snapshot = 0;
+        match self.history.get(0) {
+            Some(state) => {
+                vm.restore(&state);
+                rewound
+            }
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debug::Inspect;
+
+    #[test]
+    fn rewind_frames() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x70, 0x01, // add v0, 1
+            0x12, 0x00, // jp 0x200
+        ]);
+        let mut rewinder = Rewinder::new(4, 3);
+        assert_eq!(rewinder.rewind(&mut vm, 1), 0);
+
+        // One add per frame, v0 counts the frames
+        for _ in 0..14 {
+            vm.run(2);
+            rewinder.record(&vm);
+        }
+        assert_eq!(rewinder.len(), 3);
+        assert_eq!(vm.register(0), 14);
+
+        // Snapshots at v0 = 5, 9 and 13, the last one 1 frame ago
+        assert_eq!(rewinder.rewind(&mut vm, 1), 1);
+        assert_eq!(vm.register(0), 13);
+        assert_eq!(rewinder.rewind(&mut vm, 3), 4);
+        assert_eq!(vm.register(0), 9);
+        assert_eq!(rewinder.rewind(&mut vm, 100), 4);
+        assert_eq!(vm.register(0), 5);
+        assert_eq!(rewinder.len(), 1);
+    }
+}
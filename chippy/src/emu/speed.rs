@@ -0,0 +1,138 @@
+//! Pacing of the emulation with a speed that changes smoothly, for fast-forward and slow motion
+//! transitions without sudden jumps.
+
+use super::frame::DEFAULT_CYCLES_PER_FRAME;
+use std::time::Duration;
+
+/// Instructions per second of the frontends, `DEFAULT_CYCLES_PER_FRAME` at 60 frames per second
+pub const DEFAULT_SPEED: f64 = DEFAULT_CYCLES_PER_FRAME as f64 * 60.0;
+
+/// Instructions per second target that moves linearly towards a new target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedRamp {
+    speed: f64,
+    target: f64,
+    /// Change of speed per second while ramping
+    rate: f64,
+    /// Fraction of an instruction carried over to the next call to `advance`
+    carry: f64,
+}
+
+impl SpeedRamp {
+    pub fn new(speed: f64) -> Self {
+        let speed = speed.max(0.0);
+        Self {
+            speed,
+            target: speed,
+            rate: 0.0,
+            carry: 0.0,
+        }
+    }
+
+    /// Current speed in instructions per second
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+
+    pub fn is_ramping(&self) -> bool {
+        self.speed != self.target
+    }
+
+    /// Change the speed immediately
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(0.0);
+        self.target = self.speed;
+    }
+
+    /// Move from the current speed to `target` over `duration`
+    pub fn ramp_to(&mut self, target: f64, duration: Duration) {
+        self.target = target.max(0.0);
+        let seconds = duration.as_secs_f64();
+        self.rate = match seconds > 0.0 {
+            true => (self.target - self.speed).abs() / seconds,
+            false => f64::INFINITY,
+        };
+    }
+
+    /// Advance the ramp by `elapsed` and return the number of instructions to run in that time.
+    /// The instructions follow the speed during the ramp, so they add up to the same total as
+    /// running many short steps.
+    pub fn advance(&mut self, elapsed: Duration) -> usize {
+        let seconds = elapsed.as_secs_f64();
+        if self.rate.is_infinite() {
+            self.speed = self.target;
+        }
+        let start = self.speed;
+
+        // Time spent ramping, the rest of `elapsed` runs at the target speed
+        let distance = (self.target - start).abs();
+        let ramp_time = match distance > 0.0 {
+            true => (distance / self.rate).min(seconds),
+            false => 0.0,
+        };
+        self.speed = match ramp_time < seconds {
+            true => self.target,
+            false => start + (self.target - start).signum() * self.rate * ramp_time,
+        };
+
+        let instructions = (start + self.speed) / 2.0 * ramp_time
+            + self.speed * (seconds - ramp_time)
+            + self.carry;
+        // Tolerate rounding errors so many short steps add up to whole instructions
+        let whole = (instructions + 1e-9).floor();
+        self.carry = (instructions - whole).max(0.0);
+        whole as usize
+    }
+}
+
+impl Default for SpeedRamp {
+    fn default() -> Self {
+        Self::new(DEFAULT_SPEED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    fn constant_speed() {
+        let mut ramp = SpeedRamp::new(60.0);
+        let total: usize = (0..60).map(|_| ramp.advance(seconds(1.0 / 60.0))).sum();
+        assert_eq!(total, 60);
+
+        // Fractions of instructions are carried over
+        let mut ramp = SpeedRamp::new(10.0);
+        let total: usize = (0..100).map(|_| ramp.advance(seconds(0.01))).sum();
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn ramp_up_and_down() {
+        let mut ramp = SpeedRamp::new(100.0);
+        ramp.ramp_to(300.0, seconds(1.0));
+        assert!(ramp.is_ramping());
+
+        // Average speed of 150 over the first half second
+        assert_eq!(ramp.advance(seconds(0.5)), 75);
+        assert!((ramp.speed() - 200.0).abs() < 1e-9);
+        // 0.5s finishing the ramp at an average of 250, then 0.5s at 300
+        assert_eq!(ramp.advance(seconds(1.0)), 275);
+        assert!(!ramp.is_ramping());
+
+        ramp.ramp_to(0.0, seconds(0.5));
+        assert_eq!(ramp.advance(seconds(1.0)), 75);
+        assert_eq!(ramp.speed(), 0.0);
+
+        ramp.ramp_to(50.0, Duration::ZERO);
+        assert_eq!(ramp.advance(seconds(1.0)), 50);
+    }
+}
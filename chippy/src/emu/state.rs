@@ -0,0 +1,500 @@
+use super::{
+    error::{StateError, StateResult},
+    gpu::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH},
+    json::{self, Value},
+    vm::{FLAG_COUNT, MEMORY_SIZE, REGISTER_SIZE, STACK_SIZE},
+};
+use std::convert::TryFrom;
+
+const DISPLAY_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+const HIRES_DISPLAY_SIZE: usize = HIRES_WIDTH * HIRES_HEIGHT;
+/// Bytes of the encoded display, large enough for either resolution
+const DISPLAY_BYTES: usize = HIRES_DISPLAY_SIZE / 8;
+const NO_KEY: u8 = 0xFF;
+
+/// Fields of `VmState::to_json`
+const JSON_FIELDS: [&str; 6] = [
+    "registers",
+    "index",
+    "program_counter",
+    "stack",
+    "delay_timer",
+    "sound_timer",
+];
+
+/// Version of the `VmState::encode` format, the first byte of a snapshot. Snapshots of the
+/// formats before it start with the font at address 0 instead and are rejected by `decode`.
+pub const ENCODED_VERSION: u8 = 1;
+
+/// Size in bytes of a snapshot encoded with `VmState::encode`.
+pub const ENCODED_SIZE: usize = 1 // version
+    + MEMORY_SIZE // memory
+    + REGISTER_SIZE // registers
+    + STACK_SIZE * 2 // stack
+    + 1 // stack pointer
+    + 2 // index
+    + 2 // program counter
+    + 1 // delay timer
+    + 1 // sound timer
+    + 1 // wait for key
+    + FLAG_COUNT // flags
+    + 1 // high resolution
+    + DISPLAY_BYTES // display, one bit per pixel
+    + 2; // keys, one bit per key
+
+/// A full copy of the machine state at an instruction boundary. With the `serde` feature it
+/// can be stored in any serde format, check states read from outside with `VmState::validate`
+/// before restoring them.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmState {
+    pub memory: Vec<u8>,
+    pub registers: [u8; REGISTER_SIZE],
+    pub stack: [u16; STACK_SIZE],
+    pub stack_pointer: usize,
+    pub index: u16,
+    pub program_counter: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Register receiving the key while the program waits for one with `ld vx, k`
+    pub wait_for_key: Option<u8>,
+    /// SUPER-CHIP RPL user flags
+    pub flags: [u8; FLAG_COUNT],
+    /// `SCREEN_WIDTH * SCREEN_HEIGHT` pixels, or `HIRES_WIDTH * HIRES_HEIGHT` pixels in high
+    /// resolution mode
+    pub display: Vec<bool>,
+    pub keys: [bool; 16],
+}
+
+impl VmState {
+    /// Encode the state into a flat fixed size byte buffer. This is the "naive" snapshot format
+    /// that the compressed formats in `emu::compress` are built on.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ENCODED_SIZE);
+        bytes.push(ENCODED_VERSION);
+        bytes.extend_from_slice(&self.memory);
+        bytes.extend_from_slice(&self.registers);
+        for entry in self.stack.iter() {
+            bytes.extend_from_slice(&entry.to_be_bytes());
+        }
+        bytes.push(self.stack_pointer as u8);
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.extend_from_slice(&self.program_counter.to_be_bytes());
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.push(self.wait_for_key.unwrap_or(NO_KEY));
+        bytes.extend_from_slice(&self.flags);
+        bytes.push(self.is_hires() as u8);
+        let mut display = pack_bits(&self.display);
+        display.resize(DISPLAY_BYTES, 0);
+        bytes.extend(display);
+        bytes.extend(pack_bits(&self.keys));
+        bytes
+    }
+
+    /// Decode a buffer created by `VmState::encode`. Fails if the buffer is not a valid snapshot
+    /// of the current format version.
+    pub fn decode(bytes: &[u8]) -> StateResult<VmState> {
+        match bytes.first() {
+            Some(&version) if version != ENCODED_VERSION => {
+                return Err(StateError::Version(version, ENCODED_VERSION))
+            }
+            _ if bytes.len() != ENCODED_SIZE => {
+                return Err(StateError::Size(bytes.len(), ENCODED_SIZE))
+            }
+            _ => (),
+        }
+
+        let (memory, rest) = bytes[1..].split_at(MEMORY_SIZE);
+        let (registers, rest) = rest.split_at(REGISTER_SIZE);
+        let (stack, rest) = rest.split_at(STACK_SIZE * 2);
+        let (fixed, rest) = rest.split_at(8);
+        let (flags, rest) = rest.split_at(FLAG_COUNT);
+        let (hires, rest) = rest.split_at(1);
+        let (display, keys) = rest.split_at(DISPLAY_BYTES);
+        let invalid = |field: &str| Err(StateError::InvalidValue(field.to_string()));
+        if fixed[0] as usize > STACK_SIZE {
+            return invalid("stack_pointer");
+        }
+        if fixed[7] != NO_KEY && fixed[7] >= 16 {
+            return invalid("wait_for_key");
+        }
+        let display_size = match hires[0] {
+            0 => DISPLAY_SIZE,
+            _ => HIRES_DISPLAY_SIZE,
+        };
+
+        let mut state = VmState {
+            memory: memory.to_vec(),
+            registers: [0; REGISTER_SIZE],
+            stack: [0; STACK_SIZE],
+            stack_pointer: fixed[0] as usize,
+            index: u16::from_be_bytes([fixed[1], fixed[2]]),
+            program_counter: u16::from_be_bytes([fixed[3], fixed[4]]),
+            delay_timer: fixed[5],
+            sound_timer: fixed[6],
+            wait_for_key: match fixed[7] {
+                NO_KEY => None,
+                key => Some(key),
+            },
+            flags: [0; FLAG_COUNT],
+            display: unpack_bits(display, display_size),
+            keys: [false; 16],
+        };
+
+        state.registers.copy_from_slice(registers);
+        state.flags.copy_from_slice(flags);
+        for (entry, pair) in state.stack.iter_mut().zip(stack.chunks_exact(2)) {
+            *entry = u16::from_be_bytes([pair[0], pair[1]]);
+        }
+        state.keys.copy_from_slice(&unpack_bits(keys, 16));
+
+        Ok(state)
+    }
+
+    /// The registers, index, program counter, live stack entries and timers as a JSON object,
+    /// one field per line.
+    ///
+    /// ```text
+    /// {
+    ///   "registers": [7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+    ///   "index": 768,
+    ///   "program_counter": 522,
+    ///   "stack": [514],
+    ///   "delay_timer": 0,
+    ///   "sound_timer": 0
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let list = |values: &mut dyn Iterator<Item = u16>| {
+            values
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!(
+            "{{\n  \"registers\": [{}],\n  \"index\": {},\n  \"program_counter\": {},\n  \"stack\": [{}],\n  \"delay_timer\": {},\n  \"sound_timer\": {}\n}}\n",
+            list(&mut self.registers.iter().map(|value| *value as u16)),
+            self.index,
+            self.program_counter,
+            list(&mut self.stack[..self.stack_pointer.min(STACK_SIZE)].iter().copied()),
+            self.delay_timer,
+            self.sound_timer
+        )
+    }
+
+    /// Read a state written by `VmState::to_json`. Every field must be present, the rest of the
+    /// state (memory, display, keys) is cleared.
+    pub fn from_json(src: &str) -> StateResult<VmState> {
+        let mut state = VmState {
+            memory: vec![0; MEMORY_SIZE],
+            registers: [0; REGISTER_SIZE],
+            stack: [0; STACK_SIZE],
+            stack_pointer: 0,
+            index: 0,
+            program_counter: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            wait_for_key: None,
+            flags: [0; FLAG_COUNT],
+            display: vec![false; DISPLAY_SIZE],
+            keys: [false; 16],
+        };
+
+        let mut missing = JSON_FIELDS.to_vec();
+        for (name, value) in json::parse_object(src).map_err(StateError::Syntax)? {
+            let invalid = || StateError::InvalidValue(name.clone());
+            match (name.as_str(), value) {
+                ("registers", Value::Array(values)) if values.len() == REGISTER_SIZE => {
+                    for (register, value) in state.registers.iter_mut().zip(values) {
+                        *register = u8::try_from(value).map_err(|_| invalid())?;
+                    }
+                }
+                ("stack", Value::Array(values)) if values.len() <= STACK_SIZE => {
+                    state.stack_pointer = values.len();
+                    for (entry, value) in state.stack.iter_mut().zip(values) {
+                        *entry = u16::try_from(value).map_err(|_| invalid())?;
+                    }
+                }
+                ("index", Value::Number(value)) => {
+                    state.index = u16::try_from(value).map_err(|_| invalid())?
+                }
+                ("program_counter", Value::Number(value)) => {
+                    state.program_counter = u16::try_from(value).map_err(|_| invalid())?
+                }
+                ("delay_timer", Value::Number(value)) => {
+                    state.delay_timer = u8::try_from(value).map_err(|_| invalid())?
+                }
+                ("sound_timer", Value::Number(value)) => {
+                    state.sound_timer = u8::try_from(value).map_err(|_| invalid())?
+                }
+                (name, _) if JSON_FIELDS.contains(&name) => return Err(invalid()),
+                _ => return Err(StateError::UnknownField(name)),
+            }
+            missing.retain(|field| *field != name);
+        }
+
+        match missing.first() {
+            Some(field) => Err(StateError::MissingField(field)),
+            None => Ok(state),
+        }
+    }
+
+    /// Check that the state can be restored: buffers of the right size and values in range.
+    /// Snapshots and decoded states are always valid, deserialized ones may not be.
+    pub fn validate(&self) -> StateResult<()> {
+        let invalid = |field: &str| Err(StateError::InvalidValue(field.to_string()));
+        if self.memory.len() != MEMORY_SIZE {
+            return invalid("memory");
+        }
+        if self.stack_pointer > STACK_SIZE {
+            return invalid("stack_pointer");
+        }
+        if self.wait_for_key.is_some_and(|key| key >= 16) {
+            return invalid("wait_for_key");
+        }
+        if self.display.len() != DISPLAY_SIZE && !self.is_hires() {
+            return invalid("display");
+        }
+        Ok(())
+    }
+
+    /// True if the display is the SUPER-CHIP 128x64 display
+    pub fn is_hires(&self) -> bool {
+        self.display.len() == HIRES_DISPLAY_SIZE
+    }
+
+    /// Compare two states, returning what changed going from `self` to `other`.
+    pub fn diff(&self, other: &VmState) -> StateDiff {
+        StateDiff {
+            memory: changed(&self.memory, &other.memory)
+                .map(|i| i as u16)
+                .collect(),
+            registers: changed(&self.registers, &other.registers)
+                .map(|i| i as u8)
+                .collect(),
+            index: self.index != other.index,
+            stack: self.stack_pointer != other.stack_pointer || self.stack != other.stack,
+            display: self.display != other.display,
+        }
+    }
+}
+
+/// Changes between two states, created by `VmState::diff`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StateDiff {
+    /// Addresses of the changed memory bytes
+    pub memory: Vec<u16>,
+    /// Numbers of the changed registers
+    pub registers: Vec<u8>,
+    pub index: bool,
+    pub stack: bool,
+    pub display: bool,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+            && self.registers.is_empty()
+            && !self.index
+            && !self.stack
+            && !self.display
+    }
+}
+
+fn changed<'a, T: PartialEq>(a: &'a [T], b: &'a [T]) -> impl Iterator<Item = usize> + 'a {
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(i, _)| i)
+}
+
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, bit)| byte | ((*bit as u8) << (7 - i)))
+        })
+        .collect()
+}
+
+fn unpack_bits(bytes: &[u8], len: usize) -> Vec<bool> {
+    (0..len)
+        .map(|i| (bytes[i / 8] >> (7 - (i % 8))) & 0b1 != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> VmState {
+        let mut display = vec![false; DISPLAY_SIZE];
+        display[0] = true;
+        display[DISPLAY_SIZE - 1] = true;
+
+        let mut memory = vec![0; MEMORY_SIZE];
+        memory[0x200] = 0x12;
+        memory[0xFFF] = 0x34;
+
+        VmState {
+            memory,
+            registers: [7; REGISTER_SIZE],
+            stack: [0x202; STACK_SIZE],
+            stack_pointer: 3,
+            index: 0x300,
+            program_counter: 0x234,
+            delay_timer: 10,
+            sound_timer: 20,
+            wait_for_key: Some(0xA),
+            flags: [1, 2, 3, 4, 5, 6, 7, 8],
+            display,
+            keys: [true; 16],
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let state = state();
+        let bytes = state.encode();
+        assert_eq!(bytes.len(), ENCODED_SIZE);
+        assert_eq!(VmState::decode(&bytes), Ok(state));
+    }
+
+    #[test]
+    fn encode_decode_hires() {
+        let mut state = state();
+        state.display = vec![false; HIRES_DISPLAY_SIZE];
+        state.display[HIRES_DISPLAY_SIZE - 1] = true;
+        assert!(state.is_hires());
+        let bytes = state.encode();
+        assert_eq!(bytes.len(), ENCODED_SIZE);
+        assert_eq!(VmState::decode(&bytes), Ok(state));
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(state().validate(), Ok(()));
+        let invalid = |field: &str| Err(StateError::InvalidValue(field.to_string()));
+
+        let mut state = state();
+        state.memory.pop();
+        assert_eq!(state.validate(), invalid("memory"));
+        let mut state = self::state();
+        state.stack_pointer = STACK_SIZE + 1;
+        assert_eq!(state.validate(), invalid("stack_pointer"));
+        let mut state = self::state();
+        state.wait_for_key = Some(16);
+        assert_eq!(state.validate(), invalid("wait_for_key"));
+        let mut state = self::state();
+        state.display.push(true);
+        assert_eq!(state.validate(), invalid("display"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use crate::{bench::WORKLOAD, emu::vm::Vm};
+
+        let mut vm = Vm::new();
+        vm.load(WORKLOAD.to_vec());
+        for _ in 0..500 {
+            vm.cycle().unwrap();
+        }
+        vm.input.key_down(crate::emu::input::Key::A);
+        let state = vm.snapshot();
+
+        let json = serde_json::to_string(&state).unwrap();
+        let read: VmState = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, state);
+        assert_eq!(read.validate(), Ok(()));
+
+        let mut restored = Vm::new();
+        restored.restore(&read);
+        assert_eq!(restored.snapshot(), state);
+        for _ in 0..500 {
+            vm.cycle().unwrap();
+            restored.cycle().unwrap();
+        }
+        assert_eq!(restored.snapshot(), vm.snapshot());
+    }
+
+    #[test]
+    fn decode_rejects_invalid_snapshots() {
+        let mut bytes = state().encode();
+        assert_eq!(
+            VmState::decode(&bytes[..ENCODED_SIZE - 1]),
+            Err(StateError::Size(ENCODED_SIZE - 1, ENCODED_SIZE))
+        );
+        assert_eq!(VmState::decode(&[]), Err(StateError::Size(0, ENCODED_SIZE)));
+        // Older snapshots start with the memory, the font at address 0
+        let old = crate::emu::vm::Vm::new().snapshot().encode();
+        assert_eq!(
+            VmState::decode(&old[1..]),
+            Err(StateError::Version(0xF0, ENCODED_VERSION))
+        );
+
+        bytes[1 + MEMORY_SIZE + REGISTER_SIZE + STACK_SIZE * 2] = STACK_SIZE as u8 + 1;
+        assert_eq!(
+            VmState::decode(&bytes),
+            Err(StateError::InvalidValue("stack_pointer".to_string()))
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut state = state();
+        state.registers[0] = 0xFF;
+        let json = state.to_json();
+        assert!(json.contains("\"stack\": [514, 514, 514],\n"));
+
+        let read = VmState::from_json(&json).unwrap();
+        assert_eq!(read.registers, state.registers);
+        assert_eq!(read.stack_pointer, 3);
+        assert_eq!(read.stack[..3], state.stack[..3]);
+        assert_eq!(read.to_json(), json);
+    }
+
+    #[test]
+    fn json_errors() {
+        let json = state().to_json();
+        assert_eq!(
+            VmState::from_json(&json.replace("\"index\": 768", "\"index\": 65536")),
+            Err(StateError::InvalidValue("index".to_string()))
+        );
+        assert_eq!(
+            VmState::from_json(&json.replace("  \"sound_timer\": 20\n", "  \"keys\": 0\n")),
+            Err(StateError::UnknownField("keys".to_string()))
+        );
+        assert_eq!(
+            VmState::from_json(&json.replace(",\n  \"sound_timer\": 20", "")),
+            Err(StateError::MissingField("sound_timer"))
+        );
+        assert_eq!(
+            VmState::from_json("{\"index\" 1}"),
+            Err(StateError::Syntax(9))
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let before = state();
+        assert!(before.diff(&before).is_empty());
+
+        let mut after = before.clone();
+        after.memory[0x300] = 0xAB;
+        after.memory[0x301] = 0xCD;
+        after.registers[0xF] = 1;
+        after.index = 0x302;
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.memory, vec![0x300, 0x301]);
+        assert_eq!(diff.registers, vec![0xF]);
+        assert!(diff.index);
+        assert!(!diff.stack);
+        assert!(!diff.display);
+    }
+}
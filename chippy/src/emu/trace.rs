@@ -0,0 +1,383 @@
+//! Trace files of every executed instruction, for captures of long sessions. The instructions
+//! are stored in compressed blocks listed by an index at the end of the file, so that the
+//! instructions of a cycle or frame are found by reading a single block.
+//!
+//! ```text
+//! "CH8TRACE" version
+//! block...
+//! index: (offset u64, first cycle u64, first frame u64, entries u32) per block
+//! block count u32, index offset u64, "CH8I"
+//! ```
+//!
+//! Numbers are little endian. In a block every instruction is a varint of the change of address
+//! shifted left by 2, the next bit set when the opcode differs from the last one executed at the
+//! address in the block and the low bit set when the frame changed. The frame change and the
+//! opcode follow when their bit is set, so an instruction of a loop takes a single byte.
+
+use super::compress::{read_varint, write_varint};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+const MAGIC: &[u8; 8] = b"CH8TRACE";
+const VERSION: u8 = 1;
+const INDEX_MAGIC: &[u8; 4] = b"CH8I";
+/// Instructions per block
+pub const BLOCK_ENTRIES: usize = 4096;
+const INDEX_ENTRY_SIZE: usize = 28;
+const FOOTER_SIZE: usize = 16;
+
+/// An executed instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Instructions executed before this one
+    pub cycle: u64,
+    pub frame: u64,
+    pub address: u16,
+    pub opcode: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BlockIndex {
+    offset: u64,
+    first_cycle: u64,
+    first_frame: u64,
+    entries: u32,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Writes executed instructions to a trace file.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    offset: u64,
+    index: Vec<BlockIndex>,
+    cycles: u64,
+    block: Vec<u8>,
+    block_entries: usize,
+    last_address: u16,
+    last_frame: u64,
+    /// Last opcode executed at every address of the block
+    opcodes: HashMap<u16, u16>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        Ok(Self {
+            out,
+            offset: MAGIC.len() as u64 + 1,
+            index: Vec::new(),
+            cycles: 0,
+            block: Vec::new(),
+            block_entries: 0,
+            last_address: 0,
+            last_frame: 0,
+            opcodes: HashMap::new(),
+        })
+    }
+
+    /// Instructions written so far
+    pub fn len(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cycles == 0
+    }
+
+    /// Add the instruction `opcode` executed at `address` during `frame`. Frames can not go back.
+    pub fn push(&mut self, frame: u64, address: u16, opcode: u16) -> io::Result<()> {
+        if frame < self.last_frame {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Trace frames must not go back",
+            ));
+        }
+        if self.block_entries == 0 {
+            self.index.push(BlockIndex {
+                offset: self.offset,
+                first_cycle: self.cycles,
+                first_frame: frame,
+                entries: 0,
+            });
+            self.last_address = 0;
+            self.last_frame = frame;
+            self.opcodes.clear();
+        }
+
+        let delta = address.wrapping_sub(self.last_address) as i16;
+        let zigzag = ((delta << 1) ^ (delta >> 15)) as u16 as usize;
+        let new_opcode = self.opcodes.insert(address, opcode) != Some(opcode);
+        let new_frame = frame != self.last_frame;
+        write_varint(
+            &mut self.block,
+            zigzag << 2 | (new_opcode as usize) << 1 | new_frame as usize,
+        );
+        if new_frame {
+            write_varint(&mut self.block, (frame - self.last_frame) as usize);
+        }
+        if new_opcode {
+            self.block.extend(opcode.to_be_bytes());
+        }
+        self.last_address = address;
+        self.last_frame = frame;
+        self.cycles += 1;
+        self.block_entries += 1;
+        if self.block_entries == BLOCK_ENTRIES {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if let Some(index) = self.index.last_mut() {
+            index.entries = self.block_entries as u32;
+        }
+        self.out.write_all(&self.block)?;
+        self.offset += self.block.len() as u64;
+        self.block.clear();
+        self.block_entries = 0;
+        Ok(())
+    }
+
+    /// Write the last block and the index, returning the output
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.block_entries > 0 {
+            self.write_block()?;
+        }
+        let mut index = Vec::with_capacity(self.index.len() * INDEX_ENTRY_SIZE + FOOTER_SIZE);
+        for block in self.index.iter() {
+            index.extend(block.offset.to_le_bytes());
+            index.extend(block.first_cycle.to_le_bytes());
+            index.extend(block.first_frame.to_le_bytes());
+            index.extend(block.entries.to_le_bytes());
+        }
+        index.extend((self.index.len() as u32).to_le_bytes());
+        index.extend(self.offset.to_le_bytes());
+        index.extend(INDEX_MAGIC);
+        self.out.write_all(&index)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Reads the instructions of a trace file by cycle or frame.
+pub struct TraceReader<R: Read + Seek> {
+    input: R,
+    index: Vec<BlockIndex>,
+    /// Offset of the index, where the last block ends
+    end: u64,
+    /// Last decoded block, the instructions of a frame are usually read together
+    cached: Option<(usize, Vec<TraceEntry>)>,
+}
+
+impl<R: Read + Seek> TraceReader<R> {
+    /// Read the index of the trace file `input`
+    pub fn open(mut input: R) -> io::Result<Self> {
+        let mut header = [0; MAGIC.len() + 1];
+        input.seek(SeekFrom::Start(0))?;
+        input.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION {
+            return Err(invalid("Not a chippy trace file"));
+        }
+
+        let mut footer = [0; FOOTER_SIZE];
+        input.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+        input.read_exact(&mut footer)?;
+        if &footer[12..] != INDEX_MAGIC {
+            return Err(invalid("Trace file without an index"));
+        }
+        let count = u32::from_le_bytes(footer[..4].try_into().unwrap()) as usize;
+        let end = u64::from_le_bytes(footer[4..12].try_into().unwrap());
+
+        let mut bytes = vec![0; count * INDEX_ENTRY_SIZE];
+        input.seek(SeekFrom::Start(end))?;
+        input.read_exact(&mut bytes)?;
+        let field =
+            |entry: &[u8], at: usize| u64::from_le_bytes(entry[at..at + 8].try_into().unwrap());
+        let index = bytes
+            .chunks_exact(INDEX_ENTRY_SIZE)
+            .map(|entry| BlockIndex {
+                offset: field(entry, 0),
+                first_cycle: field(entry, 8),
+                first_frame: field(entry, 16),
+                entries: u32::from_le_bytes(entry[24..28].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Self {
+            input,
+            index,
+            end,
+            cached: None,
+        })
+    }
+
+    /// Instructions in the trace
+    pub fn len(&self) -> u64 {
+        self.index
+            .last()
+            .map_or(0, |block| block.first_cycle + block.entries as u64)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The instruction executed at `cycle`
+    pub fn get(&mut self, cycle: u64) -> io::Result<Option<TraceEntry>> {
+        let block = self
+            .index
+            .partition_point(|block| block.first_cycle <= cycle);
+        if block == 0 || cycle >= self.len() {
+            return Ok(None);
+        }
+        let first_cycle = self.index[block - 1].first_cycle;
+        let entries = self.block(block - 1)?;
+        Ok(entries.get((cycle - first_cycle) as usize).copied())
+    }
+
+    /// Up to `count` instructions from `cycle` on
+    pub fn entries(&mut self, cycle: u64, count: usize) -> io::Result<Vec<TraceEntry>> {
+        let mut entries = Vec::with_capacity(count.min(BLOCK_ENTRIES));
+        let mut cycle = cycle;
+        while entries.len() < count {
+            match self.get(cycle)? {
+                Some(entry) => entries.push(entry),
+                None => break,
+            }
+            cycle += 1;
+        }
+        Ok(entries)
+    }
+
+    /// First cycle of `frame` or of the first frame after it, `None` past the end of the trace
+    pub fn cycle_of_frame(&mut self, frame: u64) -> io::Result<Option<u64>> {
+        let after = self
+            .index
+            .partition_point(|block| block.first_frame < frame);
+        if after > 0 {
+            let entries = self.block(after - 1)?;
+            if let Some(entry) = entries.iter().find(|entry| entry.frame >= frame) {
+                return Ok(Some(entry.cycle));
+            }
+        }
+        Ok(self.index.get(after).map(|block| block.first_cycle))
+    }
+
+    fn block(&mut self, block: usize) -> io::Result<&[TraceEntry]> {
+        if !matches!(&self.cached, Some((cached, _)) if *cached == block) {
+            let index = self.index[block];
+            let end = self
+                .index
+                .get(block + 1)
+                .map_or(self.end, |next| next.offset);
+            let mut bytes = vec![0; end.saturating_sub(index.offset) as usize];
+            self.input.seek(SeekFrom::Start(index.offset))?;
+            self.input.read_exact(&mut bytes)?;
+            let entries =
+                decode_block(&index, &bytes).ok_or_else(|| invalid("Corrupt trace block"))?;
+            self.cached = Some((block, entries));
+        }
+        Ok(self
+            .cached
+            .as_ref()
+            .map(|(_, entries)| entries.as_slice())
+            .unwrap_or(&[]))
+    }
+}
+
+fn decode_block(index: &BlockIndex, bytes: &[u8]) -> Option<Vec<TraceEntry>> {
+    let mut cursor = bytes;
+    let mut entries = Vec::with_capacity(index.entries as usize);
+    let mut opcodes: HashMap<u16, u16> = HashMap::new();
+    let (mut address, mut frame) = (0u16, index.first_frame);
+    for cycle in index.first_cycle..index.first_cycle + index.entries as u64 {
+        let header = read_varint(&mut cursor)?;
+        let zigzag = (header >> 2) as u16;
+        let delta = (zigzag >> 1) as i16 ^ -((zigzag & 1) as i16);
+        address = address.wrapping_add(delta as u16);
+        if header & 1 != 0 {
+            frame += read_varint(&mut cursor)? as u64;
+        }
+        if header & 2 != 0 {
+            let (opcode, rest) = cursor.split_at_checked(2)?;
+            opcodes.insert(address, u16::from_be_bytes([opcode[0], opcode[1]]));
+            cursor = rest;
+        }
+        entries.push(TraceEntry {
+            cycle,
+            frame,
+            address,
+            opcode: *opcodes.get(&address)?,
+        });
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_seek() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x00, // ld v0, 0
+            0x70, 0x01, // add v0, 1
+            0x12, 0x02, // jp 0x202
+        ]);
+        let mut writer = TraceWriter::new(Cursor::new(Vec::new())).unwrap();
+        let mut expected = Vec::new();
+        for frame in 0..1000 {
+            for _ in 0..10 {
+                let step = vm.step();
+                writer.push(frame, step.address, step.opcode).unwrap();
+                expected.push((frame, step.address, step.opcode));
+            }
+        }
+        assert_eq!(writer.len(), 10_000);
+        assert!(writer.push(3, 0x200, 0).is_err());
+        let file = writer.finish().unwrap().into_inner();
+        // A byte per instruction and the opcodes of every block
+        assert!(
+            file.len() < 10_000 + 3 * 4 * 3 + 1000 + 200,
+            "{}",
+            file.len()
+        );
+
+        let mut reader = TraceReader::open(Cursor::new(file)).unwrap();
+        assert_eq!(reader.len(), 10_000);
+        let entry = reader.get(BLOCK_ENTRIES as u64 + 5).unwrap().unwrap();
+        let (frame, address, opcode) = expected[BLOCK_ENTRIES + 5];
+        assert_eq!(
+            (entry.frame, entry.address, entry.opcode),
+            (frame, address, opcode)
+        );
+        assert_eq!(reader.get(10_000).unwrap(), None);
+
+        assert_eq!(reader.cycle_of_frame(0).unwrap(), Some(0));
+        assert_eq!(reader.cycle_of_frame(409).unwrap(), Some(4090));
+        assert_eq!(reader.cycle_of_frame(1000).unwrap(), None);
+        let frame = reader.entries(4090, 20).unwrap();
+        assert_eq!(frame.len(), 20);
+        assert!(frame[..10].iter().all(|entry| entry.frame == 409));
+        assert_eq!(frame[10].frame, 410);
+        for entry in frame {
+            let (frame, address, opcode) = expected[entry.cycle as usize];
+            assert_eq!(
+                (entry.frame, entry.address, entry.opcode),
+                (frame, address, opcode)
+            );
+        }
+
+        assert!(TraceReader::open(Cursor::new(b"CH8TRACE\x01".to_vec())).is_err());
+    }
+}
@@ -1,31 +1,187 @@
+#[cfg(feature = "cached-engine")]
+use super::engine::BlockCache;
+use super::input::Input;
 use crate::{
-    emu::font::FONT_SET,
+    debug::{events::EventKind, Inspect},
+    emu::bus::Bus,
+    emu::engine::Engine,
+    emu::error::{VmError, VmResult},
+    emu::font::{BIG_FONT_SPRITE_SIZE, BIG_FONT_START},
+    emu::frame::Frames,
     emu::gpu::Gpu,
+    emu::history::History,
     emu::instruction::{Instruction, RegisterValuePair, TargetSourcePair},
+    emu::memory::Memory,
+    emu::profile::Profile,
+    emu::quirks::Quirks,
+    emu::state::VmState,
 };
-use byteorder::{BigEndian, ReadBytesExt};
-
-use super::input::Input;
+use std::{collections::BTreeSet, fmt, ops::Range, time::Duration};
 
 const INITIAL_PROGRAM_COUNTER: u16 = 0x200;
-const MEMORY_SIZE: usize = 4096;
-const MEMORY_START: usize = 512;
-const REGISTER_SIZE: usize = 16;
-const STACK_SIZE: usize = 16;
+pub(crate) const MEMORY_SIZE: usize = 4096;
+pub(crate) const MEMORY_START: usize = 512;
+pub(crate) const REGISTER_SIZE: usize = 16;
+pub(crate) const STACK_SIZE: usize = 16;
+/// Number of SUPER-CHIP RPL user flags, saved and loaded by `ld r, vx` and `ld vx, r`
+pub(crate) const FLAG_COUNT: usize = 8;
+
+/// Rate of the delay and sound timers
+pub const TIMER_FREQUENCY: u32 = 60;
+
+/// Time between two counts of the delay and sound timers
+pub const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / TIMER_FREQUENCY as u64);
 
 type Register = u8;
 type StackEntry = u16;
 
+/// What counts down the delay and sound timers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimerClock {
+    /// Once per executed instruction, deterministic but tied to the instruction rate
+    Instructions,
+    /// At 60 Hz of the time given to `Vm::tick` or once per `Vm::run_frame`, whatever the
+    /// instruction rate
+    #[default]
+    Realtime,
+}
+
+/// Result of executing an instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ProgramState {
     Continue,
-    Stop,
+    /// The instruction jumped to itself (`jp` to its own address), the usual way of ending a
+    /// chip8 program. The display will not change anymore but the timers keep running, the vm
+    /// can still be cycled.
+    Halt,
+    /// The program ended with `exit` (00FD), a trapped `sys` or `Vm::request_stop`. Every
+    /// following cycle returns `Halted` without executing anything until the vm is reset or
+    /// restored.
+    Halted(StopReason),
+    /// The instruction at the program counter can not execute, see `Vm::cycle`. Returned by the
+    /// run loops, `Vm::run`, `Vm::run_frame` and `Vm::step`.
+    Error(VmError),
+    /// The instruction at the program counter is on a breakpoint or accesses a watched range, it
+    /// was not executed. See `Vm::break_hit`, the next cycle executes it.
+    BreakpointHit,
+    /// `ld vx, k` (Fx0A) found no key pressed, every cycle executes it again until one is. The
+    /// timers keep running.
+    WaitingForKey,
+}
+
+/// Kind of memory access stopping the vm on a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Memory watched by `Vm::add_watchpoint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: Range<u16>,
+    pub access: Access,
+}
+
+/// What stopped the vm with `ProgramState::BreakpointHit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Break {
+    Breakpoint(u16),
+    /// The instruction at `address` accesses the watched memory
+    Watchpoint {
+        address: u16,
+        watchpoint: Watchpoint,
+    },
+}
+
+/// Why a program stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// The program executed `exit` (00FD)
+    Exit,
+    /// Stopped with `Vm::request_stop`
+    Requested,
+    /// `sys addr` (0nnn) with `SysPolicy::Trap`, the program counter stays on it
+    Sys(u16),
+}
+
+/// Host routine run by `sys addr` with `SysPolicy::Callback`, given the address. The returned
+/// program counter moves on like for any instruction.
+pub type SysHandler<B> = fn(&mut Vm<B>, u16) -> ProgramCounter;
+
+/// What `sys addr` (0nnn) does. The machine code routines of the COSMAC VIP can not run here.
+#[derive(Debug, Default)]
+pub enum SysPolicy<B: Bus = Memory> {
+    /// Skip the instruction, like most interpreters
+    #[default]
+    Ignore,
+    /// Stop the program with `StopReason::Sys`, to catch roms relying on VIP routines
+    Trap,
+    /// Run a host routine, for host call extensions
+    Callback(SysHandler<B>),
+}
+
+impl<B: Bus> Clone for SysPolicy<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B: Bus> Copy for SysPolicy<B> {}
+
+/// What an instruction executed by `Vm::step` did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    /// Address of the instruction
+    pub address: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    /// Program counter after the instruction
+    pub next: u16,
+    pub state: ProgramState,
+    /// Registers whose value changed, one bit per register
+    pub registers_written: u16,
+    pub memory_read: Option<Range<u16>>,
+    pub memory_written: Option<Range<u16>>,
+    /// The instruction changed the display mode or drew, cleared or scrolled the display
+    pub drew: bool,
+}
+
+impl StepResult {
+    /// True if register `register` changed
+    pub fn wrote_register(&self, register: u8) -> bool {
+        self.registers_written & (1 << (register & 0xF)) != 0
+    }
+}
+
+/// What the instructions executed by `Vm::run_frame` did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameResult {
+    /// Instructions executed
+    pub cycles: usize,
+    /// State of the last instruction, the frame ends early on `Halted`, `Error` and
+    /// `BreakpointHit`
+    pub state: ProgramState,
+    /// Events fired during the frame, each kind once in the order it first fired
+    pub events: Vec<EventKind>,
+}
+
+/// Change of the buzzer reported by `Vm::take_sound_event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    /// The sound timer became non-zero, the buzzer should start
+    Started,
+    /// The sound timer ran out, the buzzer should stop
+    Stopped,
 }
 
 pub enum ProgramCounter {
     Next,
     Skip,
     Jump(u16),
-    Stop,
+    /// Stay on the instruction until a key is pressed
+    Wait,
+    Stop(StopReason),
 }
 
 fn skip_if(condition: bool) -> ProgramCounter {
@@ -36,10 +192,11 @@ fn skip_if(condition: bool) -> ProgramCounter {
     }
 }
 
-pub struct Vm {
+/// The chip8 machine. The cpu reaches memory through the `Bus` `B`, by default the 4K `Memory`.
+pub struct Vm<B: Bus = Memory> {
     pub gpu: Gpu,
     pub input: Input,
-    memory: [u8; MEMORY_SIZE],
+    memory: B,
     registers: [Register; REGISTER_SIZE],
     stack: [StackEntry; STACK_SIZE],
     stack_pointer: usize,
@@ -47,16 +204,59 @@ pub struct Vm {
     program_counter: u16,
     deplay_timer: u8,
     sound_timer: u8,
+    timer_clock: TimerClock,
+    /// Time given to `Vm::tick` that did not make a whole timer period yet
+    timer_elapsed: Duration,
+    /// Buzzer state last reported by `Vm::take_sound_event`
+    sound_reported: bool,
+    /// Set while `Vm::run_frame` runs, the timers count down once at the end of the frame
+    /// instead of after every instruction
+    in_frame: bool,
     wait_for_key: Option<u8>,
+    /// RPL user flags of the HP48 calculators, they survive `Vm::reset`
+    flags: [u8; FLAG_COUNT],
+    history: History,
+    profile: Option<Profile>,
+    stop_reason: Option<StopReason>,
+    paused: bool,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    break_hit: Option<Break>,
+    /// Address of the last break, its instruction executes on the next cycle
+    resume_at: Option<u16>,
+    engine: Engine,
+    quirks: Quirks,
+    sys_policy: SysPolicy<B>,
+    #[cfg(feature = "cached-engine")]
+    pub(super) cache: BlockCache<B>,
 }
 
 impl Vm {
     pub fn new() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
-        for (index, character) in FONT_SET.iter().enumerate() {
-            memory[index] = *character;
-        }
+        Self::with_memory(Memory::new())
+    }
 
+    /// The `len` bytes of memory at `address`, borrowed instead of copied like
+    /// `Vm::read_memory`
+    pub fn read(&self, address: u16, len: usize) -> VmResult<&[u8]> {
+        let (start, end) = (address as usize, address as usize + len);
+        self.memory
+            .get(start..end)
+            .ok_or(VmError::MemoryOutOfRange(start, end))
+    }
+}
+
+impl<B: Bus> Vm<B> {
+    /// Create a vm using an existing memory image or another memory model. Vms created from
+    /// clones of the same `Memory` share the rom until one of them writes to it.
+    ///
+    /// ```
+    /// # use chippy::emu::{memory::Memory, vm::Vm};
+    /// let image = Memory::with_rom(&[0x12, 0x00])?;
+    /// let vms: Vec<Vm> = (0..1000).map(|_| Vm::with_memory(image.clone())).collect();
+    /// # Ok::<(), chippy::emu::error::VmError>(())
+    /// ```
+    pub fn with_memory(memory: B) -> Self {
         Self {
             gpu: Gpu::new(),
             input: Input::new(),
@@ -68,70 +268,732 @@ impl Vm {
             program_counter: INITIAL_PROGRAM_COUNTER,
             deplay_timer: 0,
             sound_timer: 0,
+            timer_clock: TimerClock::default(),
+            timer_elapsed: Duration::ZERO,
+            sound_reported: false,
+            in_frame: false,
             wait_for_key: None,
+            flags: [0; FLAG_COUNT],
+            history: History::default(),
+            profile: None,
+            stop_reason: None,
+            paused: false,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            break_hit: None,
+            resume_at: None,
+            engine: Engine::default(),
+            quirks: Quirks::default(),
+            sys_policy: SysPolicy::default(),
+            #[cfg(feature = "cached-engine")]
+            cache: BlockCache::new(),
         }
     }
 
+    /// Execute instructions with `engine`
+    ///
+    /// ```
+    /// # use chippy::emu::{engine::Engine, vm::Vm};
+    /// let vm = Vm::new().with_engine(Engine::Match);
+    /// ```
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Run programs with the behaviours of another interpreter
+    ///
+    /// ```
+    /// # use chippy::emu::{quirks::Quirks, vm::Vm};
+    /// let vm = Vm::new().with_quirks(Quirks::vip());
+    /// ```
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Run `sys addr` (0nnn) instructions with `policy`
+    ///
+    /// ```
+    /// # use chippy::emu::vm::{ProgramCounter, SysPolicy, Vm};
+    /// let vm = Vm::new().with_sys_policy(SysPolicy::Callback(|vm, addr| {
+    ///     vm.set_register(0xF, (addr & 0xFF) as u8);
+    ///     ProgramCounter::Next
+    /// }));
+    /// ```
+    pub fn with_sys_policy(mut self, policy: SysPolicy<B>) -> Self {
+        self.sys_policy = policy;
+        self
+    }
+
+    pub fn set_sys_policy(&mut self, policy: SysPolicy<B>) {
+        self.sys_policy = policy;
+    }
+
+    pub fn sys_policy(&self) -> SysPolicy<B> {
+        self.sys_policy
+    }
+
+    /// Count down the timers with `clock`, `TimerClock::Realtime` by default
+    ///
+    /// ```
+    /// # use chippy::emu::vm::{TimerClock, Vm};
+    /// let vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+    /// ```
+    pub fn with_timer_clock(mut self, clock: TimerClock) -> Self {
+        self.timer_clock = clock;
+        self
+    }
+
+    pub fn timer_clock(&self) -> TimerClock {
+        self.timer_clock
+    }
+
+    /// Load a program at 0x200. Panics if it does not fit in memory, see `Vm::try_load`.
     pub fn load(&mut self, buffer: Vec<u8>) {
-        for (index, value) in buffer.iter().enumerate() {
-            self.memory[index + MEMORY_START] = *value;
+        if let Err(err) = self.try_load(&buffer) {
+            panic!("{}", err);
         }
     }
 
+    /// Load a program at 0x200 unless it does not fit in memory, returns the number of bytes
+    /// loaded
+    pub fn try_load(&mut self, rom: &[u8]) -> VmResult<usize> {
+        let space = self.memory.size().saturating_sub(MEMORY_START);
+        if rom.len() > space {
+            return Err(VmError::RomTooLarge(rom.len(), space));
+        }
+        self.memory.load(MEMORY_START as u16, rom);
+        self.clear_cache();
+        Ok(rom.len())
+    }
+
+    /// Load a program like `Vm::try_load`, also rejecting a rom of odd length. Instructions are
+    /// two bytes long but many roms end with sprite data of any length, so only use this for roms
+    /// known to be code only.
+    pub fn try_load_even(&mut self, rom: &[u8]) -> VmResult<usize> {
+        if !rom.len().is_multiple_of(2) {
+            return Err(VmError::OddRomLength(rom.len()));
+        }
+        self.try_load(rom)
+    }
+
     pub fn reset(&mut self) {
-        for index in MEMORY_START..MEMORY_SIZE {
-            self.memory[index] = 0;
+        for address in MEMORY_START..self.memory.size() {
+            self.memory.write(address as u16, 0);
         }
+        self.clear_cache();
 
-        self.gpu.clear();
+        self.gpu = Gpu::new();
         self.registers = [0; REGISTER_SIZE];
         self.stack = [0; STACK_SIZE];
         self.stack_pointer = 0;
         self.index = 0;
         self.program_counter = INITIAL_PROGRAM_COUNTER;
+        self.timer_elapsed = Duration::ZERO;
+        self.history.clear();
+        if let Some(profile) = &mut self.profile {
+            profile.clear();
+        }
+        self.stop_reason = None;
+        self.break_hit = None;
+        self.resume_at = None;
     }
 
-    pub fn cycle(&mut self) -> ProgramState {
-        let position = self.program_counter as usize;
-        let mut parts = &self.memory[position..position + 2];
-        let opcode = parts.read_u16::<BigEndian>().unwrap();
+    /// Capture the current machine state so it can later be restored with `Vm::restore`.
+    pub fn snapshot(&self) -> VmState {
+        let mut state = VmState::default();
+        self.snapshot_into(&mut state);
+        state
+    }
 
-        match self.execute_instruction(opcode) {
-            ProgramCounter::Next => self.program_counter += 2,
-            ProgramCounter::Skip => self.program_counter += 4,
-            ProgramCounter::Jump(addr) => self.program_counter = addr,
-            ProgramCounter::Stop => return ProgramState::Stop,
+    /// Capture the current machine state into `state` like `Vm::snapshot`, reusing its buffers.
+    /// Does not allocate once `state` has held a snapshot of this vm.
+    pub fn snapshot_into(&self, state: &mut VmState) {
+        state.memory.resize(self.memory.size(), 0);
+        for (address, byte) in state.memory.iter_mut().enumerate() {
+            *byte = self.memory.read(address as u16);
+        }
+        state.registers = self.registers;
+        state.stack = self.stack;
+        state.stack_pointer = self.stack_pointer;
+        state.index = self.index;
+        state.program_counter = self.program_counter;
+        state.delay_timer = self.deplay_timer;
+        state.sound_timer = self.sound_timer;
+        state.wait_for_key = self.wait_for_key;
+        state.flags = self.flags;
+        state.display.clear();
+        state.display.extend_from_slice(self.gpu.display());
+        state.keys = self.input.keys;
+    }
+
+    pub fn restore(&mut self, state: &VmState) {
+        self.memory.load(0, &state.memory);
+        self.clear_cache();
+        self.registers = state.registers;
+        self.stack = state.stack;
+        self.stack_pointer = state.stack_pointer;
+        self.index = state.index;
+        self.program_counter = state.program_counter;
+        self.deplay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.wait_for_key = state.wait_for_key;
+        self.flags = state.flags;
+        self.gpu.load(&state.display);
+        self.input.keys = state.keys;
+        self.stop_reason = None;
+        self.break_hit = None;
+        self.resume_at = None;
+    }
+
+    /// Stop the program, the next cycle returns `ProgramState::Halted`
+    pub fn request_stop(&mut self) {
+        self.stop_reason = Some(StopReason::Requested);
+    }
+
+    /// True once the program stopped, see `ProgramState::Halted`
+    pub fn is_stopped(&self) -> bool {
+        self.stop_reason.is_some()
+    }
+
+    /// Why the program stopped, `None` while it is running
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Freeze the program, for example while the window is in the background. Cycles do nothing
+    /// so the timers do not run, and key presses are ignored until the vm is unpaused.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.input.set_locked(paused);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Copy of the memory in `range`
+    pub fn dump_memory(&self, range: Range<u16>) -> VmResult<Vec<u8>> {
+        if range.start > range.end {
+            return Err(VmError::MemoryOutOfRange(
+                range.start as usize,
+                range.end as usize,
+            ));
+        }
+        let mut bytes = vec![0; range.len()];
+        self.read_memory(range.start, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Copy the memory starting at `address` into `bytes`
+    pub fn read_memory(&self, address: u16, bytes: &mut [u8]) -> VmResult<()> {
+        let (start, end) = (address as usize, address as usize + bytes.len());
+        if end > self.memory.size() {
+            return Err(VmError::MemoryOutOfRange(start, end));
+        }
+        for (offset, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.memory.read(address + offset as u16);
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` to memory starting at `address`
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) -> VmResult<()> {
+        let (start, end) = (address as usize, address as usize + bytes.len());
+        if end > self.memory.size() {
+            return Err(VmError::MemoryOutOfRange(start, end));
+        }
+        self.memory.load(address, bytes);
+        self.clear_cache();
+        Ok(())
+    }
+
+    /// Set register v`register`, only its low nibble is used. The registers are read through
+    /// `Inspect`.
+    pub fn set_register(&mut self, register: Register, value: u8) {
+        self.registers[register as usize & 0xF] = value;
+    }
+
+    pub fn set_index(&mut self, index: u16) {
+        self.index = index;
+    }
+
+    /// Continue the program at `address`
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.program_counter = address;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.deplay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// RPL user flags, for frontends keeping them between runs like the HP48 did
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    /// Set the first flags to `flags`, the ones past the last flag are ignored
+    pub fn set_flags(&mut self, flags: &[u8]) {
+        let count = flags.len().min(FLAG_COUNT);
+        self.flags[..count].copy_from_slice(&flags[..count]);
+    }
+
+    /// Iterate over completed frames. Each frame runs the configured number of cycles and
+    /// captures the display and sound state.
+    pub fn frames(&mut self) -> Frames<'_, B> {
+        Frames::new(self)
+    }
+
+    /// Recently executed instructions, empty unless enabled with `Vm::set_history_capacity`.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub(crate) fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    /// Record the last `capacity` executed instructions, 0 disables recording.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history.set_capacity(capacity);
+    }
+
+    /// Record the last `capacity` executed instructions like `Vm::set_history_capacity`
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.set_history_capacity(capacity);
+        self
+    }
+
+    /// Address, opcode and instruction of the recorded history from the oldest to the most
+    /// recently executed instruction
+    pub fn trace(&self) -> impl DoubleEndedIterator<Item = (u16, u16, Instruction)> + '_ {
+        self.history
+            .iter()
+            .map(|entry| (entry.address, entry.opcode, entry.instruction()))
+    }
+
+    /// Executions counted per address and per opcode class, `None` unless enabled with
+    /// `Vm::set_profiling`.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Start counting executions from zero, or stop and drop the counters
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = match enabled {
+            true => Some(Profile::new()),
+            false => None,
+        };
+    }
+
+    /// Count executions like `Vm::set_profiling`
+    pub fn with_profiling(mut self) -> Self {
+        self.set_profiling(true);
+        self
+    }
+
+    /// True while the sound timer is active and the buzzer should be playing.
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Whether the buzzer started or stopped since the last call, for frontends that play a tone
+    /// while it runs. Call it once per frame, a sound shorter than the time between two calls is
+    /// not reported.
+    pub fn take_sound_event(&mut self) -> Option<SoundEvent> {
+        let active = self.sound_active();
+        if active == self.sound_reported {
+            return None;
+        }
+        self.sound_reported = active;
+        match active {
+            true => Some(SoundEvent::Started),
+            false => Some(SoundEvent::Stopped),
+        }
+    }
+
+    pub(crate) fn check_program_counter(&self) -> VmResult<()> {
+        match self.program_counter as usize + 1 < self.memory.size() {
+            true => Ok(()),
+            false => Err(VmError::PcOutOfRange(self.program_counter)),
+        }
+    }
+
+    /// Fail on an instruction accessing memory out of range, before it wrote part of it
+    pub(super) fn check_memory_access(&self, opcode: u16) -> VmResult<()> {
+        let (read, written) = self.memory_access(&Instruction::parse(opcode));
+        for range in read.iter().chain(written.iter()) {
+            // The end saturates at u16::MAX for accesses past the address space
+            if range.end as usize > self.memory.size() || range.end == u16::MAX {
+                return Err(VmError::MemoryOutOfRange(
+                    range.start as usize,
+                    range.end as usize,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute one instruction like `Vm::cycle`, describing what it did. While the vm is stopped,
+    /// paused, on a breakpoint or on an instruction that can not execute nothing is executed:
+    /// `next` is `address` and nothing is reported as accessed.
+    pub fn step(&mut self) -> StepResult {
+        let address = self.program_counter;
+        let opcode = match self.check_program_counter() {
+            Ok(()) => self.memory.read_u16(address),
+            Err(_) => 0,
+        };
+        let instruction = Instruction::parse(opcode);
+        let registers = self.registers;
+        let executes = self.stop_reason.is_none() && !self.paused;
+        let (memory_read, memory_written) = match executes {
+            true => self.memory_access(&instruction),
+            false => (None, None),
         };
 
-        if self.deplay_timer > 0 {
-            self.deplay_timer -= 1;
+        let state = self.cycle().unwrap_or_else(ProgramState::Error);
+        let executes =
+            executes && !matches!(state, ProgramState::BreakpointHit | ProgramState::Error(_));
+        let (memory_read, memory_written) = match executes {
+            true => (memory_read, memory_written),
+            false => (None, None),
+        };
+        let registers_written = registers
+            .iter()
+            .zip(self.registers.iter())
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .fold(0, |written, (register, _)| written | 1 << register);
+        let drew = executes
+            && matches!(
+                instruction,
+                Instruction::ClearDisplay
+                    | Instruction::Draw { .. }
+                    | Instruction::ScrollDown(_)
+                    | Instruction::ScrollRight
+                    | Instruction::ScrollLeft
+                    | Instruction::LowRes
+                    | Instruction::HighRes
+            );
+        StepResult {
+            address,
+            opcode,
+            instruction,
+            next: self.program_counter,
+            state,
+            registers_written,
+            memory_read,
+            memory_written,
+            drew,
+        }
+    }
+
+    /// Memory read and written by `instruction` with the current index
+    fn memory_access(&self, instruction: &Instruction) -> (Option<Range<u16>>, Option<Range<u16>>) {
+        let range = |len: u16| Some(self.index..self.index.saturating_add(len));
+        match *instruction {
+            Instruction::Draw { n: 0, .. } => (range(32), None),
+            Instruction::Draw { n, .. } => (range(n as u16), None),
+            Instruction::StoreBCD(_) => (None, range(3)),
+            Instruction::DumpRegisters(x) => (None, range(x as u16 + 1)),
+            Instruction::LoadRegisters(x) => (range(x as u16 + 1), None),
+            _ => (None, None),
+        }
+    }
+
+    /// Execute the instruction at the program counter. An instruction that can not execute
+    /// returns an error and leaves the vm unchanged, every following cycle fails the same way
+    /// until the vm state is changed.
+    pub fn cycle(&mut self) -> VmResult<ProgramState> {
+        if let Some(reason) = self.stop_reason {
+            return Ok(ProgramState::Halted(reason));
+        }
+        if self.paused {
+            return Ok(ProgramState::Continue);
+        }
+        self.check_program_counter()?;
+        if self.check_break() {
+            return Ok(ProgramState::BreakpointHit);
+        }
+
+        let address = self.program_counter;
+        let opcode = self.memory.read_u16(address);
+        self.check_memory_access(opcode)?;
+        let next = match self.engine {
+            Engine::Match => self.execute_instruction(opcode),
+            #[cfg(feature = "table-engine")]
+            Engine::Table => self.execute_table(opcode),
+            #[cfg(feature = "cached-engine")]
+            Engine::Cached => self.execute_table(opcode),
+        }?;
+        self.history.push(address, opcode);
+        if let Some(profile) = &mut self.profile {
+            profile.count(address, opcode);
         }
+        Ok(self.finish_instruction(next))
+    }
+
+    /// Stop the vm with `ProgramState::BreakpointHit` before executing the instruction at
+    /// `address`
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
 
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
+    /// Returns false if there was no breakpoint at `address`
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Stop the vm with `ProgramState::BreakpointHit` before executing an instruction that
+    /// accesses memory of `range` the way of `access`. Fetching instructions is not a read.
+    pub fn add_watchpoint(&mut self, range: Range<u16>, access: Access) {
+        let watchpoint = Watchpoint { range, access };
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
         }
+    }
+
+    /// Returns false if there was no such watchpoint
+    pub fn remove_watchpoint(&mut self, range: Range<u16>, access: Access) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.range != range || watchpoint.access != access);
+        self.watchpoints.len() != len
+    }
 
+    /// Remove every breakpoint and watchpoint
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    /// What stopped the vm the last time a cycle returned `ProgramState::BreakpointHit`
+    pub fn break_hit(&self) -> Option<&Break> {
+        self.break_hit.as_ref()
+    }
+
+    /// True if the instruction at the program counter must not execute because of a breakpoint
+    /// or a watchpoint, unless the vm broke on it on the previous cycle
+    fn check_break(&mut self) -> bool {
+        let address = self.program_counter;
+        if self.resume_at.take() == Some(address)
+            || (self.breakpoints.is_empty() && self.watchpoints.is_empty())
+        {
+            return false;
+        }
+
+        let hit = if self.breakpoints.contains(&address) {
+            Some(Break::Breakpoint(address))
+        } else {
+            let instruction = Instruction::parse(self.memory.read_u16(address));
+            let (read, written) = self.memory_access(&instruction);
+            self.watchpoints
+                .iter()
+                .find(|watchpoint| {
+                    let accessed = match watchpoint.access {
+                        Access::Read => &read,
+                        Access::Write => &written,
+                    };
+                    accessed.as_ref().is_some_and(|range| {
+                        range.start < watchpoint.range.end && watchpoint.range.start < range.end
+                    })
+                })
+                .map(|watchpoint| Break::Watchpoint {
+                    address,
+                    watchpoint: watchpoint.clone(),
+                })
+        };
+        match hit {
+            Some(hit) => {
+                self.break_hit = Some(hit);
+                self.resume_at = Some(address);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run up to `cycles` instructions, returning early with the state of the first instruction
+    /// that does not return `ProgramState::Continue`. `Engine::Cached` runs whole blocks of
+    /// instructions at once unless the history is recorded or the vm profiled.
+    pub fn run(&mut self, cycles: usize) -> ProgramState {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            #[cfg(feature = "cached-engine")]
+            if self.engine == Engine::Cached
+                && self.history.capacity() == 0
+                && self.profile.is_none()
+                && self.stop_reason.is_none()
+                && !self.paused
+                && self.breakpoints.is_empty()
+                && self.watchpoints.is_empty()
+            {
+                let (executed, state) = self.run_block(remaining);
+                remaining -= executed;
+                match state {
+                    ProgramState::Continue => continue,
+                    state => return state,
+                }
+            }
+
+            remaining -= 1;
+            match self.cycle() {
+                Ok(ProgramState::Continue) => (),
+                Ok(state) => return state,
+                Err(err) => return ProgramState::Error(err),
+            }
+        }
         ProgramState::Continue
     }
 
-    pub fn execute_instruction(&mut self, opcode: u16) -> ProgramCounter {
-        match Instruction::parse(opcode) {
-            Instruction::CallMachineCode(_) => {
-                ProgramCounter::Next // TODO
+    /// Run one frame of `ipf` instructions and count the timers down once, whatever the
+    /// `TimerClock`, which runs the program at `ipf * TIMER_FREQUENCY` instructions per second.
+    /// The frame ends early, without counting the timers, when the program stops or a breakpoint
+    /// is hit. Nothing runs while the vm is paused or stopped.
+    pub fn run_frame(&mut self, ipf: usize) -> FrameResult {
+        if self.paused || self.stop_reason.is_some() {
+            return FrameResult {
+                cycles: 0,
+                state: self.cycle().unwrap_or_else(ProgramState::Error),
+                events: Vec::new(),
+            };
+        }
+
+        self.in_frame = true;
+        let frame = self.run_frame_cycles(ipf);
+        self.in_frame = false;
+        if let ProgramState::Continue | ProgramState::Halt | ProgramState::WaitingForKey =
+            frame.state
+        {
+            match self.timer_clock {
+                TimerClock::Instructions => self.count_down(1),
+                TimerClock::Realtime => {
+                    self.tick(TIMER_PERIOD);
+                }
             }
-            Instruction::ClearDisplay => {
-                self.gpu.clear();
-                ProgramCounter::Next
+        }
+        frame
+    }
+
+    fn run_frame_cycles(&mut self, ipf: usize) -> FrameResult {
+        let mut frame = FrameResult {
+            cycles: 0,
+            state: ProgramState::Continue,
+            events: Vec::new(),
+        };
+        let fire = |events: &mut Vec<EventKind>, kind| {
+            if !events.contains(&kind) {
+                events.push(kind);
             }
-            Instruction::Return => match self.pop_stack() {
-                Some(addr) => ProgramCounter::Jump(addr),
-                None => ProgramCounter::Stop,
-            },
-            Instruction::Jump(addr) => ProgramCounter::Jump(addr),
-            Instruction::Call(addr) => {
-                self.push_stack();
-                ProgramCounter::Jump(addr)
+        };
+        for _ in 0..ipf {
+            let fired = EventKind::of_next(&*self);
+            frame.state = self.cycle().unwrap_or_else(ProgramState::Error);
+            match frame.state {
+                ProgramState::BreakpointHit => {
+                    fire(&mut frame.events, EventKind::Breakpoint);
+                    return frame;
+                }
+                ProgramState::Error(_) => return frame,
+                ProgramState::Halted(_) => {
+                    frame.cycles += 1;
+                    fire(&mut frame.events, EventKind::Halt);
+                    return frame;
+                }
+                _ => {
+                    frame.cycles += 1;
+                    fired
+                        .into_iter()
+                        .for_each(|kind| fire(&mut frame.events, kind));
+                }
+            }
+        }
+        frame
+    }
+
+    /// Move the program counter after an instruction and tick the timers
+    pub(crate) fn finish_instruction(&mut self, next: ProgramCounter) -> ProgramState {
+        let mut state = ProgramState::Continue;
+        match next {
+            ProgramCounter::Next => self.program_counter += 2,
+            ProgramCounter::Skip => self.program_counter += 4,
+            ProgramCounter::Jump(addr) => {
+                if addr == self.program_counter {
+                    state = ProgramState::Halt;
+                }
+                self.program_counter = addr;
+            }
+            ProgramCounter::Wait => state = ProgramState::WaitingForKey,
+            ProgramCounter::Stop(reason) => {
+                self.stop_reason = Some(reason);
+                return ProgramState::Halted(reason);
             }
+        };
+
+        self.tick_timers(1);
+        state
+    }
+
+    /// Let `elapsed` time pass for the timers of a `TimerClock::Realtime` vm, counting them down
+    /// once per `TIMER_PERIOD`. The time left over is carried to the next call. Returns the number
+    /// of counts, 0 while the vm is paused or stopped and with `TimerClock::Instructions`.
+    pub fn tick(&mut self, elapsed: Duration) -> usize {
+        if self.timer_clock != TimerClock::Realtime || self.paused || self.stop_reason.is_some() {
+            return 0;
+        }
+        self.timer_elapsed += elapsed;
+        let ticks = (self.timer_elapsed.as_nanos() / TIMER_PERIOD.as_nanos()) as u32;
+        self.timer_elapsed -= TIMER_PERIOD * ticks;
+        self.count_down(ticks as usize);
+        ticks as usize
+    }
+
+    /// Count down the timers for `cycles` instructions of a `TimerClock::Instructions` vm, unless
+    /// they run in a frame of `Vm::run_frame`
+    pub(super) fn tick_timers(&mut self, cycles: usize) {
+        if self.timer_clock == TimerClock::Instructions && !self.in_frame {
+            self.count_down(cycles);
+        }
+    }
+
+    fn count_down(&mut self, ticks: usize) {
+        let ticks = ticks.min(u8::MAX as usize) as u8;
+        self.deplay_timer = self.deplay_timer.saturating_sub(ticks);
+        self.sound_timer = self.sound_timer.saturating_sub(ticks);
+    }
+
+    /// Execute `opcode` as the instruction at the program counter, returning where the program
+    /// counter moves
+    pub fn execute_instruction(&mut self, opcode: u16) -> VmResult<ProgramCounter> {
+        Ok(match Instruction::parse(opcode) {
+            Instruction::CallMachineCode(addr) => self.op_sys(addr),
+            Instruction::ClearDisplay => self.op_cls(),
+            Instruction::Return => self.op_ret()?,
+            Instruction::Exit => ProgramCounter::Stop(StopReason::Exit),
+            Instruction::ScrollDown(lines) => self.op_scroll_down(lines),
+            Instruction::ScrollRight => self.op_scroll_right(),
+            Instruction::ScrollLeft => self.op_scroll_left(),
+            Instruction::LowRes => self.op_set_hires(false),
+            Instruction::HighRes => self.op_set_hires(true),
+            Instruction::Jump(addr) => ProgramCounter::Jump(addr),
+            Instruction::Call(addr) => self.op_call(addr)?,
             Instruction::SkipIfEq(RegisterValuePair { register, value }) => {
                 skip_if(self.get_register(register) == value)
             }
@@ -142,161 +1004,314 @@ impl Vm {
                 skip_if(self.get_register(target) == self.get_register(source))
             }
             Instruction::SetReg(RegisterValuePair { register, value }) => {
-                self.set_register(register, value);
-                ProgramCounter::Next
+                self.op_set_reg(register, value)
             }
             Instruction::AddValueToReg(RegisterValuePair { register, value }) => {
-                let (sum, _) = self.get_register(register).overflowing_add(value);
-                self.set_register(register, sum);
-                ProgramCounter::Next
+                self.op_add_value(register, value)
             }
             Instruction::SetRegXToRegY(TargetSourcePair { target, source }) => {
-                self.set_register(target, self.get_register(source));
-                ProgramCounter::Next
-            }
-            Instruction::BitXOrY(TargetSourcePair { target, source }) => {
-                let result = self.get_register(target) | self.get_register(source);
-                self.set_register(target, result);
-                ProgramCounter::Next
+                self.op_set_reg(target, self.get_register(source))
             }
+            Instruction::BitXOrY(TargetSourcePair { target, source }) => self.op_or(target, source),
             Instruction::BitXAndY(TargetSourcePair { target, source }) => {
-                let result = self.get_register(target) & self.get_register(source);
-                self.set_register(target, result);
-                ProgramCounter::Next
+                self.op_and(target, source)
             }
             Instruction::BitXXorY(TargetSourcePair { target, source }) => {
-                let result = self.get_register(target) ^ self.get_register(source);
-                self.set_register(target, result);
-                ProgramCounter::Next
+                self.op_xor(target, source)
             }
             Instruction::AddYToX(TargetSourcePair { target, source }) => {
-                let (result, did_overflow) = self
-                    .get_register(target)
-                    .overflowing_add(self.get_register(source));
-                self.set_vf_confitional(did_overflow);
-                self.set_register(target, result);
-                ProgramCounter::Next
+                self.op_add(target, source)
             }
             Instruction::SubYFromX(TargetSourcePair { target, source }) => {
-                let (result, did_overflow) = self
-                    .get_register(target)
-                    .overflowing_sub(self.get_register(source));
-                self.set_vf_confitional(!did_overflow);
-                self.set_register(target, result);
-                ProgramCounter::Next
+                self.op_sub(target, source)
             }
             Instruction::ShiftRight(TargetSourcePair { target, source }) => {
-                let value = self.get_register(target);
-                self.set_vf_register(value & 0xF);
-                self.set_register(target, value >> 1);
-                ProgramCounter::Next
+                self.op_shr(target, source)
             }
             Instruction::SubXFromYIntoX(TargetSourcePair { target, source }) => {
-                let (result, did_overflow) = self
-                    .get_register(source)
-                    .overflowing_sub(self.get_register(target));
-                self.set_vf_confitional(!did_overflow);
-                self.set_register(target, result);
-                ProgramCounter::Next
+                self.op_subn(target, source)
             }
             Instruction::ShiftLeft(TargetSourcePair { target, source }) => {
-                let value = self.get_register(target);
-                self.set_vf_register(value >> 7);
-                self.set_register(target, value << 1);
-                ProgramCounter::Next
+                self.op_shl(target, source)
             }
             Instruction::SkipIfDifferent(TargetSourcePair { target, source }) => {
                 skip_if(self.get_register(target) != self.get_register(source))
             }
-            Instruction::SetI(value) => {
-                self.index = value;
-                ProgramCounter::Next
-            }
-            Instruction::JumpNPlusPC(addr) => {
-                ProgramCounter::Jump(addr + self.get_register(0x0) as u16)
-            }
+            Instruction::SetI(value) => self.op_set_i(value),
+            Instruction::JumpNPlusPC(addr) => self.op_jump_v0(addr),
             Instruction::Random(RegisterValuePair { register, value }) => {
-                // TODO: get random number between 0, 255
-                let random = 0x5d;
-                self.set_register(register, random & value);
-                ProgramCounter::Next
-            }
-            Instruction::Draw { x, y, n } => {
-                let (i, nn) = (self.index as usize, n as usize);
-                let new_vf = self.gpu.draw(
-                    self.get_register(x) as usize,
-                    self.get_register(y) as usize,
-                    &self.memory[i..i + nn],
-                );
-                self.set_vf_register(new_vf);
-                ProgramCounter::Next
+                self.op_random(register, value)
             }
+            Instruction::Draw { x, y, n } => self.op_draw(x, y, n),
             Instruction::SkipIfKeyPressed(register) => {
-                let value = self.get_register(register);
-                skip_if(self.input.is_pressed(value))
+                skip_if(self.input.poll(self.get_register(register)))
             }
             Instruction::SkipIfNotKeyPressed(register) => {
-                let value = self.get_register(register);
-                skip_if(!self.input.is_pressed(value))
-            }
-            Instruction::SetXAsDT(register) => {
-                self.set_register(register, self.deplay_timer);
-                ProgramCounter::Next
+                skip_if(!self.input.poll(self.get_register(register)))
             }
-            Instruction::WaitInputStoreIn(register) => {
-                self.wait_for_key = Some(self.get_register(register));
-                ProgramCounter::Next
+            Instruction::SetXAsDT(register) => self.op_set_reg(register, self.deplay_timer),
+            Instruction::WaitInputStoreIn(register) => self.op_wait_key(register),
+            Instruction::SetDTAsX(register) => self.op_set_delay_timer(register),
+            Instruction::SetSTAsX(register) => self.op_set_sound_timer(register),
+            Instruction::AddXToI(register) => self.op_add_i(register),
+            Instruction::SetIToFontSprite(register) => self.op_font(register),
+            Instruction::StoreBCD(register) => self.op_bcd(register),
+            Instruction::DumpRegisters(limit) => self.op_dump_registers(limit),
+            Instruction::LoadRegisters(limit) => self.op_load_registers(limit),
+            Instruction::SetIToBigFontSprite(register) => self.op_big_font(register),
+            Instruction::StoreFlags(limit) => self.op_store_flags(limit),
+            Instruction::LoadFlags(limit) => self.op_load_flags(limit),
+            Instruction::Invalid(opcode) => {
+                return Err(VmError::InvalidOpcode(self.program_counter, opcode))
             }
-            Instruction::SetDTAsX(register) => {
-                self.deplay_timer = self.get_register(register);
-                ProgramCounter::Next
-            }
-            Instruction::SetSTAsX(register) => {
-                self.sound_timer = self.get_register(register);
-                ProgramCounter::Next
-            }
-            Instruction::AddXToI(register) => {
-                let (result, _) = self
-                    .index
-                    .overflowing_add(self.get_register(register) as u16);
-                self.index = result;
-                ProgramCounter::Next
-            }
-            Instruction::SetIToFontSprite(register) => {
-                self.index = self.get_register(register) as u16 * 5; // sprites are 5 bytes long
-                ProgramCounter::Next
-            }
-            Instruction::StoreBCD(register) => {
-                let value = self.get_register(register);
-                self.set_memory(self.index, value / 100); // hundreds
-                self.set_memory(self.index + 1, (value % 100) / 10); // tens
-                self.set_memory(self.index + 2, value % 10); // ones
-                ProgramCounter::Next
-            }
-            Instruction::DumpRegisters(limit) => {
-                for r in 0..=limit {
-                    self.set_memory(self.index, self.get_register(r));
-                    self.index += 1;
-                }
+        })
+    }
+
+    // Instructions with side effects, shared by the execution engines
+
+    pub(super) fn op_sys(&mut self, addr: u16) -> ProgramCounter {
+        match self.sys_policy {
+            SysPolicy::Ignore => ProgramCounter::Next,
+            SysPolicy::Trap => ProgramCounter::Stop(StopReason::Sys(addr)),
+            SysPolicy::Callback(handler) => handler(self, addr),
+        }
+    }
+
+    pub(super) fn op_cls(&mut self) -> ProgramCounter {
+        self.gpu.clear();
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_scroll_down(&mut self, lines: u8) -> ProgramCounter {
+        self.gpu.scroll_down(lines as usize);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_scroll_right(&mut self) -> ProgramCounter {
+        self.gpu.scroll_right();
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_scroll_left(&mut self) -> ProgramCounter {
+        self.gpu.scroll_left();
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_set_hires(&mut self, hires: bool) -> ProgramCounter {
+        self.gpu.set_hires(hires);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_ret(&mut self) -> VmResult<ProgramCounter> {
+        match self.pop_stack() {
+            Some(addr) => Ok(ProgramCounter::Jump(addr)),
+            None => Err(VmError::StackUnderflow(self.program_counter)),
+        }
+    }
+
+    pub(super) fn op_call(&mut self, addr: u16) -> VmResult<ProgramCounter> {
+        self.push_stack()?;
+        Ok(ProgramCounter::Jump(addr))
+    }
+
+    pub(super) fn op_set_reg(&mut self, register: Register, value: u8) -> ProgramCounter {
+        self.set_register(register, value);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_add_value(&mut self, register: Register, value: u8) -> ProgramCounter {
+        let (sum, _) = self.get_register(register).overflowing_add(value);
+        self.set_register(register, sum);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_or(&mut self, target: Register, source: Register) -> ProgramCounter {
+        let result = self.get_register(target) | self.get_register(source);
+        self.set_logic_result(target, result)
+    }
+
+    pub(super) fn op_and(&mut self, target: Register, source: Register) -> ProgramCounter {
+        let result = self.get_register(target) & self.get_register(source);
+        self.set_logic_result(target, result)
+    }
+
+    pub(super) fn op_xor(&mut self, target: Register, source: Register) -> ProgramCounter {
+        let result = self.get_register(target) ^ self.get_register(source);
+        self.set_logic_result(target, result)
+    }
+
+    /// Store the result of `or`, `and` or `xor`, vf is reset after it with `Quirks::vf_reset`
+    fn set_logic_result(&mut self, target: Register, result: u8) -> ProgramCounter {
+        self.set_register(target, result);
+        if self.quirks.vf_reset {
+            self.set_register(0xF, 0);
+        }
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_add(&mut self, target: Register, source: Register) -> ProgramCounter {
+        let (result, did_overflow) = self
+            .get_register(target)
+            .overflowing_add(self.get_register(source));
+        self.set_vf_confitional(did_overflow);
+        self.op_set_reg(target, result)
+    }
+
+    pub(super) fn op_sub(&mut self, target: Register, source: Register) -> ProgramCounter {
+        let (result, did_overflow) = self
+            .get_register(target)
+            .overflowing_sub(self.get_register(source));
+        self.set_vf_confitional(!did_overflow);
+        self.op_set_reg(target, result)
+    }
+
+    pub(super) fn op_shr(&mut self, target: Register, _source: Register) -> ProgramCounter {
+        let value = self.get_register(target);
+        self.set_vf_register(value & 0x1);
+        self.op_set_reg(target, value >> 1)
+    }
+
+    pub(super) fn op_subn(&mut self, target: Register, source: Register) -> ProgramCounter {
+        let (result, did_overflow) = self
+            .get_register(source)
+            .overflowing_sub(self.get_register(target));
+        self.set_vf_confitional(!did_overflow);
+        self.op_set_reg(target, result)
+    }
+
+    pub(super) fn op_shl(&mut self, target: Register, _source: Register) -> ProgramCounter {
+        let value = self.get_register(target);
+        self.set_vf_register(value >> 7);
+        self.op_set_reg(target, value << 1)
+    }
+
+    pub(super) fn op_set_i(&mut self, value: u16) -> ProgramCounter {
+        self.index = value;
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_jump_v0(&mut self, addr: u16) -> ProgramCounter {
+        ProgramCounter::Jump(addr + self.get_register(0x0) as u16)
+    }
+
+    pub(super) fn op_random(&mut self, register: Register, value: u8) -> ProgramCounter {
+        // TODO: get random number between 0, 255
+        let random = 0x5d;
+        self.op_set_reg(register, random & value)
+    }
+
+    pub(super) fn op_draw(&mut self, x: Register, y: Register, n: u8) -> ProgramCounter {
+        if n == 0 {
+            return self.op_draw_large(x, y);
+        }
+        let mut sprite = [0; 15];
+        for (offset, row) in sprite[..n as usize].iter_mut().enumerate() {
+            *row = self.get_memory(self.index + offset as u16);
+        }
+        let new_vf = self.gpu.draw(
+            self.get_register(x) as usize,
+            self.get_register(y) as usize,
+            &sprite[..n as usize],
+        );
+        self.set_vf_register(new_vf);
+        ProgramCounter::Next
+    }
+
+    /// `drw vx, vy, 0` draws a 16x16 sprite of 32 bytes
+    fn op_draw_large(&mut self, x: Register, y: Register) -> ProgramCounter {
+        let mut sprite = [0; 16];
+        for (offset, row) in sprite.iter_mut().enumerate() {
+            let address = self.index + offset as u16 * 2;
+            *row = u16::from_be_bytes([self.get_memory(address), self.get_memory(address + 1)]);
+        }
+        let new_vf = self.gpu.draw_large(
+            self.get_register(x) as usize,
+            self.get_register(y) as usize,
+            &sprite,
+        );
+        self.set_vf_register(new_vf);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_wait_key(&mut self, register: Register) -> ProgramCounter {
+        self.input.poll_any();
+        match self.input.keys.iter().position(|pressed| *pressed) {
+            Some(key) => {
+                self.wait_for_key = None;
+                self.set_register(register, key as u8);
                 ProgramCounter::Next
             }
-            Instruction::LoadRegisters(limit) => {
-                for r in 0..=limit {
-                    self.set_register(r, self.get_memory(self.index));
-                    self.index += 1;
-                }
-                ProgramCounter::Next
+            None => {
+                self.wait_for_key = Some(register);
+                ProgramCounter::Wait
             }
-            Instruction::Invalid(_) => ProgramCounter::Next, // Skip invalid instructions
         }
     }
 
-    fn get_register(&self, register: Register) -> u8 {
-        self.registers[register as usize]
+    pub(super) fn op_set_delay_timer(&mut self, register: Register) -> ProgramCounter {
+        self.deplay_timer = self.get_register(register);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_set_sound_timer(&mut self, register: Register) -> ProgramCounter {
+        self.sound_timer = self.get_register(register);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_add_i(&mut self, register: Register) -> ProgramCounter {
+        let (result, _) = self
+            .index
+            .overflowing_add(self.get_register(register) as u16);
+        self.op_set_i(result)
+    }
+
+    pub(super) fn op_font(&mut self, register: Register) -> ProgramCounter {
+        self.op_set_i(self.get_register(register) as u16 * 5) // sprites are 5 bytes long
+    }
+
+    pub(super) fn op_big_font(&mut self, register: Register) -> ProgramCounter {
+        let digit = self.get_register(register) as u16;
+        self.op_set_i(BIG_FONT_START as u16 + digit * BIG_FONT_SPRITE_SIZE as u16)
+    }
+
+    pub(super) fn op_bcd(&mut self, register: Register) -> ProgramCounter {
+        let value = self.get_register(register);
+        self.set_memory(self.index, value / 100); // hundreds
+        self.set_memory(self.index + 1, (value % 100) / 10); // tens
+        self.set_memory(self.index + 2, value % 10); // ones
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_dump_registers(&mut self, limit: Register) -> ProgramCounter {
+        for r in 0..=limit {
+            self.set_memory(self.index, self.get_register(r));
+            self.index += 1;
+        }
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_load_registers(&mut self, limit: Register) -> ProgramCounter {
+        for r in 0..=limit {
+            self.set_register(r, self.get_memory(self.index));
+            self.index += 1;
+        }
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_store_flags(&mut self, limit: Register) -> ProgramCounter {
+        let count = (limit as usize).min(FLAG_COUNT - 1) + 1;
+        self.flags[..count].copy_from_slice(&self.registers[..count]);
+        ProgramCounter::Next
+    }
+
+    pub(super) fn op_load_flags(&mut self, limit: Register) -> ProgramCounter {
+        let count = (limit as usize).min(FLAG_COUNT - 1) + 1;
+        self.registers[..count].copy_from_slice(&self.flags[..count]);
+        ProgramCounter::Next
     }
 
-    fn set_register(&mut self, register: Register, value: u8) {
-        self.registers[register as usize] = value;
+    pub(super) fn get_register(&self, register: Register) -> u8 {
+        self.registers[register as usize]
     }
 
     fn set_vf_register(&mut self, value: u8) {
@@ -308,32 +1323,147 @@ impl Vm {
         self.set_vf_register(value);
     }
 
-    fn push_stack(&mut self) {
-        self.stack[self.stack_pointer] = self.program_counter + 2;
+    fn push_stack(&mut self) -> VmResult<()> {
+        let entry = self
+            .stack
+            .get_mut(self.stack_pointer)
+            .ok_or(VmError::StackOverflow(self.program_counter))?;
+        *entry = self.program_counter + 2;
         self.stack_pointer += 1;
+        Ok(())
     }
 
     fn pop_stack(&mut self) -> Option<u16> {
-        self.stack_pointer -= 1;
-        self.stack.get(self.stack_pointer).map(|s| *s)
+        let stack_pointer = self.stack_pointer.checked_sub(1)?;
+        let entry = *self.stack.get(stack_pointer)?;
+        self.stack_pointer = stack_pointer;
+        Some(entry)
     }
 
     fn get_memory(&self, index: u16) -> u8 {
-        self.memory[index as usize]
+        self.memory.read(index)
+    }
+
+    /// Opcode at `address`
+    pub(super) fn fetch(&self, address: u16) -> u16 {
+        self.memory.read_u16(address)
+    }
+
+    pub(super) fn memory_size(&self) -> usize {
+        self.memory.size()
+    }
+
+    /// Move past `count` instructions that returned `ProgramCounter::Next` at once
+    pub(super) fn skip_instructions(&mut self, count: usize) {
+        self.program_counter += 2 * count as u16;
+        self.tick_timers(count);
+    }
+
+    /// Drop the compiled blocks after memory changed behind the cpu
+    fn clear_cache(&mut self) {
+        #[cfg(feature = "cached-engine")]
+        self.cache.clear();
     }
 
     fn set_memory(&mut self, index: u16, value: u8) {
-        self.memory[index as usize] = value;
+        self.memory.write(index, value);
+        #[cfg(feature = "cached-engine")]
+        self.cache.invalidate(index);
+    }
+}
+
+impl<B: Bus> Inspect for Vm<B> {
+    fn register(&self, register: u8) -> u8 {
+        self.registers[register as usize & 0xF]
+    }
+
+    fn index(&self) -> u16 {
+        self.index
+    }
+
+    fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    fn stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.deplay_timer
+    }
+
+    fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    fn memory(&self, address: u16) -> u8 {
+        self.memory
+            .read((address as usize % self.memory.size()) as u16)
+    }
+}
+
+impl<B: Bus> fmt::Display for Vm<B> {
+    /// The registers, timers, stack and the next instruction, for debugging sessions
+    ///
+    /// ```text
+    /// pc 0202  i 0300  sp 1  dt 00  st 3C
+    /// v0 05  v1 00  v2 00  v3 00  v4 00  v5 00  v6 00  v7 00
+    /// v8 00  v9 00  va 00  vb 00  vc 00  vd 00  ve 00  vf 01
+    /// stack 0206
+    /// next 7001  add v0, 0x01
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "pc {:04X}  i {:04X}  sp {}  dt {:02X}  st {:02X}",
+            self.program_counter,
+            self.index,
+            self.stack_pointer,
+            self.deplay_timer,
+            self.sound_timer
+        )?;
+        for (row, values) in self.registers.chunks(8).enumerate() {
+            let registers: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("v{:x} {:02X}", row * 8 + i, value))
+                .collect();
+            writeln!(f, "{}", registers.join("  "))?;
+        }
+        let stack: Vec<String> = self.stack[..self.stack_pointer]
+            .iter()
+            .map(|entry| format!("{:04X}", entry))
+            .collect();
+        writeln!(f, "stack {}", stack.join(" "))?;
+        // The pc is past the end of memory after a `VmError::PcOutOfRange`
+        if self.check_program_counter().is_err() {
+            return write!(f, "next ----  unavailable");
+        }
+        let opcode = self.memory.read_u16(self.program_counter);
+        write!(
+            f,
+            "next {:04X}  {}",
+            opcode,
+            Instruction::parse(opcode).to_asm()
+        )
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::emu::{gpu::SCREEN_WIDTH, input::Key};
 
     fn cycle(vm: &mut Vm, n: usize) {
         for _ in 0..n {
-            vm.cycle();
+            vm.cycle().unwrap();
         }
     }
 
@@ -359,16 +1489,16 @@ mod tests {
             0x00, 0xEE, // 00EE - Return
         ]);
 
-        vm.cycle(); // Call to addr 204
+        vm.cycle().unwrap(); // Call to addr 204
         assert_eq!(vm.stack[0], 0x202);
         assert_eq!(vm.stack_pointer, 1);
         assert_eq!(vm.program_counter, 0x204);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.stack_pointer, 0);
         assert_eq!(vm.program_counter, 0x202);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.program_counter, 0x200);
     }
 
@@ -389,40 +1519,57 @@ mod tests {
             0x61, 0xF0, // v1 = 0xf0
             0x81, 0x24, // v1 = v1 + v2 => 0x01; vf = 0x01
             0x81, 0x25, // v1 = v1 - v2 => 0xf0; vf = 0x00
+            0x61, 0x0E, // v1 = 0x0e
+            0x81, 0x26, // v1 = v1 >> 1 => 0x07; vf = 0x00
+            0x81, 0x26, // v1 = v1 >> 1 => 0x03; vf = 0x01
+            0x81, 0x2E, // v1 = v1 << 1 => 0x06; vf = 0x00
         ];
 
         vm.load(program);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0xF0);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0x01);
         assert_eq!(vm.get_register(0xf), 0x00);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), vm.get_register(2));
 
-        vm.cycle();
-        vm.cycle();
-        vm.cycle();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0xf1);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0x11);
 
-        vm.cycle();
-        vm.cycle();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0x30);
 
-        vm.cycle();
-        vm.cycle();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0x01);
         assert_eq!(vm.get_register(0xf), 0x01);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(1), 0xf0);
         assert_eq!(vm.get_register(0xf), 0x00);
+
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
+        assert_eq!(vm.get_register(1), 0x07);
+        assert_eq!(vm.get_register(0xf), 0x00);
+
+        vm.cycle().unwrap();
+        assert_eq!(vm.get_register(1), 0x03);
+        assert_eq!(vm.get_register(0xf), 0x01);
+
+        vm.cycle().unwrap();
+        assert_eq!(vm.get_register(1), 0x06);
+        assert_eq!(vm.get_register(0xf), 0x00);
     }
 
     #[test]
@@ -443,20 +1590,20 @@ mod tests {
         vm.load(program);
         assert_eq!(vm.index, 0x0);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.index, 0x500);
 
-        vm.cycle();
-        vm.cycle();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
         assert_eq!(vm.index, 0x505);
 
-        vm.cycle();
-        vm.cycle();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
         assert_eq!(vm.index, 0xF);
 
-        vm.cycle();
-        vm.cycle();
-        vm.cycle();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_memory(vm.index), 2);
         assert_eq!(vm.get_memory(vm.index + 1), 1);
         assert_eq!(vm.get_memory(vm.index + 2), 8);
@@ -489,7 +1636,7 @@ mod tests {
         vm.load(program);
 
         // Load the index with value 0x400
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.index, 0x400);
 
         // Load registers V0 to V5
@@ -499,7 +1646,7 @@ mod tests {
         }
 
         // Execute the dump instruction for registers v0 - v5
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.index, 0x406);
         for i in 0..=5 {
             assert_eq!(vm.get_register(i), vm.get_memory(0x400 + i as u16))
@@ -513,7 +1660,7 @@ mod tests {
         }
 
         // Execute the load instruction
-        vm.cycle();
+        vm.cycle().unwrap();
         for (i, value) in register_values.iter().enumerate() {
             assert_eq!(vm.get_register(i as u8), *value);
         }
@@ -527,19 +1674,736 @@ mod tests {
             0xF0, 0x18, // ld st, v0
         ];
 
-        let mut vm = Vm::new();
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
         vm.load(program);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.get_register(0x0), 0x05);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.deplay_timer, 0x04);
 
-        vm.cycle();
+        vm.cycle().unwrap();
         assert_eq!(vm.sound_timer, 0x04);
         assert_eq!(vm.deplay_timer, 0x03);
     }
 
+    #[test]
+    fn snapshot_and_restore() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0xF0, 0x15, // ld dt, v0
+            0x22, 0x08, // call 0x208
+            0x00, 0x00, // sys 0x000
+            0x00, 0xE0, // cls
+        ]);
+        cycle(&mut vm, 2);
+        let state = vm.snapshot();
+
+        vm.cycle().unwrap();
+        assert_ne!(vm.snapshot(), state);
+
+        vm.restore(&state);
+        assert_eq!(vm.snapshot(), state);
+        assert_eq!(vm.program_counter, 0x204);
+        assert_eq!(vm.stack_pointer, 0);
+    }
+
+    #[test]
+    fn shared_memory_copy_on_write() {
+        let image = Memory::with_rom(&[
+            0xA3, 0x00, // ld i, 0x300
+            0xF0, 0x55, // ld [i], v0
+        ])
+        .unwrap();
+        let mut first = Vm::with_memory(image.clone());
+        let mut second = Vm::with_memory(image.clone());
+
+        first.registers[0] = 0xAB;
+        cycle(&mut first, 2);
+        cycle(&mut second, 2);
+
+        assert_eq!(first.get_memory(0x300), 0xAB);
+        assert_eq!(second.get_memory(0x300), 0x00);
+        assert!(!first.memory.is_shared());
+        assert!(second.memory.is_shared());
+    }
+
+    #[test]
+    fn key_polling() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0xE0, 0x9E, // skp v0
+            0x00, 0xE0, // cls
+            0xE0, 0xA1, // sknp v0
+        ]);
+        vm.input.key_down(Key::Five);
+        cycle(&mut vm, 2);
+        assert_eq!(vm.program_counter, 0x206);
+
+        cycle(&mut vm, 1);
+        assert_eq!(vm.program_counter, 0x208);
+
+        let polled = vm.input.take_polled();
+        assert!(polled[0x5]);
+        assert_eq!(polled.iter().filter(|p| **p).count(), 1);
+    }
+
+    #[test]
+    fn instruction_history() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0x12, 0x00, // jp 0x200
+        ]);
+        cycle(&mut vm, 2);
+        assert!(vm.history().is_empty());
+
+        vm.set_history_capacity(3);
+        cycle(&mut vm, 4);
+        let addresses: Vec<u16> = vm.history().iter().map(|e| e.address).collect();
+        assert_eq!(addresses, vec![0x202, 0x200, 0x202]);
+        assert_eq!(
+            vm.history().get(0).map(|e| e.instruction()),
+            Some(Instruction::Jump(0x200))
+        );
+
+        let mut vm = Vm::new().with_history(2);
+        vm.load(vec![0x60, 0x05, 0x12, 0x00]);
+        cycle(&mut vm, 3);
+        let trace: Vec<_> = vm.trace().collect();
+        assert_eq!(
+            trace,
+            vec![
+                (0x202, 0x1200, Instruction::Jump(0x200)),
+                (
+                    0x200,
+                    0x6005,
+                    Instruction::SetReg(RegisterValuePair {
+                        register: 0,
+                        value: 5
+                    })
+                ),
+            ]
+        );
+    }
+
+    /// Memory with a write only output port mapped at 0xF00
+    struct MappedPort {
+        memory: Memory,
+        port: Vec<u8>,
+    }
+
+    impl Bus for MappedPort {
+        fn size(&self) -> usize {
+            self.memory.size()
+        }
+
+        fn read(&self, address: u16) -> u8 {
+            self.memory.read(address)
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            match address {
+                0xF00 => self.port.push(value),
+                _ => self.memory.write(address as usize, value),
+            }
+        }
+    }
+
+    #[test]
+    fn custom_bus() {
+        let memory = Memory::with_rom(&[
+            0x60, 0x42, // ld v0, 0x42
+            0x61, 0x07, // ld v1, 0x07
+            0xAE, 0xFF, // ld i, 0xEFF
+            0xF1, 0x55, // ld [i], v1
+        ])
+        .unwrap();
+        let mut vm = Vm::with_memory(MappedPort {
+            memory,
+            port: Vec::new(),
+        });
+        for _ in 0..4 {
+            vm.cycle().unwrap();
+        }
+        assert_eq!(vm.memory.port, vec![0x07]);
+        assert_eq!(vm.memory(0xEFF), 0x42);
+        assert_eq!(vm.memory(0xF00), 0x00);
+    }
+
+    #[test]
+    fn stop_and_halt() {
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+        vm.load(vec![
+            0x00, 0xFD, // exit
+        ]);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Exit)));
+        assert_eq!(vm.stop_reason(), Some(StopReason::Exit));
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Exit)));
+        assert_eq!(vm.program_counter, 0x200);
+
+        vm.reset();
+        vm.load(vec![
+            0x60, 0x02, // ld v0, 0x02
+            0xF0, 0x15, // ld dt, v0
+            0x12, 0x04, // jp 0x204
+        ]);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Continue));
+        assert_eq!(vm.cycle(), Ok(ProgramState::Continue));
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halt));
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halt));
+        assert_eq!(vm.deplay_timer, 0);
+
+        vm.request_stop();
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Requested)));
+        let state = vm.snapshot();
+        vm.restore(&state);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halt));
+    }
+
+    #[test]
+    fn display_state() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // 200: ld v0, 0x05
+            0x22, 0x06, // 202: call 0x206
+            0x00, 0x00, // 204
+            0x71, 0x01, // 206: add v1, 0x01
+        ]);
+        vm.run(2);
+        assert_eq!(
+            vm.to_string(),
+            "pc 0206  i 0000  sp 1  dt 00  st 00\n\
+             v0 05  v1 00  v2 00  v3 00  v4 00  v5 00  v6 00  v7 00\n\
+             v8 00  v9 00  va 00  vb 00  vc 00  vd 00  ve 00  vf 00\n\
+             stack 0204\n\
+             next 7101  add v1, 0x01"
+        );
+
+        vm.set_program_counter(0xFFF);
+        assert!(vm.to_string().ends_with("next ----  unavailable"));
+    }
+
+    #[test]
+    fn sys_policies() {
+        let program = vec![
+            0x60, 0x01, // ld v0, 1
+            0x01, 0x23, // sys 0x123
+            0x70, 0x01, // add v0, 1
+            0x12, 0x06, // jp 0x206
+        ];
+        for engine in Engine::VARIANTS {
+            let mut vm = Vm::new().with_engine(engine.parse().unwrap());
+            vm.load(program.clone());
+            assert_eq!(vm.run(100), ProgramState::Halt, "{}", engine);
+            assert_eq!(vm.register(0), 2, "{}", engine);
+
+            vm.reset();
+            vm.load(program.clone());
+            vm.set_sys_policy(SysPolicy::Trap);
+            assert_eq!(
+                vm.run(100),
+                ProgramState::Halted(StopReason::Sys(0x123)),
+                "{}",
+                engine
+            );
+            assert_eq!(vm.program_counter, 0x202, "{}", engine);
+
+            // The host routine skips the add
+            vm.reset();
+            vm.load(program.clone());
+            vm.set_sys_policy(SysPolicy::Callback(|vm, addr| {
+                vm.set_register(0xF, addr as u8);
+                ProgramCounter::Skip
+            }));
+            assert_eq!(vm.run(100), ProgramState::Halt, "{}", engine);
+            assert_eq!(vm.register(0), 1, "{}", engine);
+            assert_eq!(vm.register(0xF), 0x23, "{}", engine);
+        }
+    }
+
+    #[test]
+    fn load_checks_size() {
+        let mut vm = Vm::new();
+        assert_eq!(vm.try_load(&[0x12, 0x00, 0xFF]), Ok(3));
+        assert_eq!(vm.memory(0x202), 0xFF);
+        assert_eq!(
+            vm.try_load_even(&[0x12, 0x00, 0xFF]),
+            Err(VmError::OddRomLength(3))
+        );
+
+        let space = MEMORY_SIZE - MEMORY_START;
+        assert_eq!(vm.try_load(&vec![1; space]), Ok(space));
+        assert_eq!(vm.memory(0xFFF), 1);
+        assert_eq!(
+            vm.try_load(&vec![2; space + 1]),
+            Err(VmError::RomTooLarge(space + 1, space))
+        );
+        assert_eq!(vm.memory(0x200), 1);
+    }
+
+    #[test]
+    fn dump_and_write_memory() {
+        let mut vm = Vm::new();
+        vm.write_memory(0x300, &[1, 2, 3]).unwrap();
+        assert_eq!(vm.dump_memory(0x2FF..0x303), Ok(vec![0, 1, 2, 3]));
+        assert_eq!(vm.dump_memory(0x300..0x300), Ok(vec![]));
+        assert_eq!(
+            vm.dump_memory(0xFFF..0x1001),
+            Err(VmError::MemoryOutOfRange(0xFFF, 0x1001))
+        );
+        assert_eq!(
+            vm.write_memory(0xFFF, &[1, 2]),
+            Err(VmError::MemoryOutOfRange(0xFFF, 0x1001))
+        );
+        assert_eq!(vm.get_memory(0xFFF), 0);
+        assert_eq!(vm.read(0x2FF, 4), Ok(&[0, 1, 2, 3][..]));
+        assert_eq!(
+            vm.read(0xFFF, 2),
+            Err(VmError::MemoryOutOfRange(0xFFF, 0x1001))
+        );
+    }
+
+    #[test]
+    fn set_registers() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x80, 0x14, // 200: add v0, v1
+            0x00, 0xFD, // 202: exit
+            0xF1, 0x1E, // 204: add i, v1
+        ]);
+        vm.set_register(0, 2);
+        vm.set_register(0x11, 3);
+        vm.set_index(0x300);
+        vm.set_program_counter(0x204);
+        vm.set_delay_timer(5);
+        vm.set_sound_timer(6);
+        assert_eq!(vm.register(1), 3);
+        assert_eq!((vm.delay_timer(), vm.sound_timer()), (5, 6));
+
+        vm.cycle().unwrap();
+        assert_eq!(vm.index(), 0x303);
+        vm.set_program_counter(0x200);
+        vm.cycle().unwrap();
+        assert_eq!(vm.register(0), 5);
+    }
+
+    #[test]
+    fn paused() {
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0xF0, 0x15, // ld dt, v0
+            0x12, 0x04, // jp 0x204
+        ]);
+        cycle(&mut vm, 2);
+        assert_eq!(vm.deplay_timer, 4);
+
+        vm.input.key_down(Key::A);
+        vm.set_paused(true);
+        cycle(&mut vm, 3);
+        assert_eq!(vm.deplay_timer, 4);
+        assert_eq!(vm.program_counter, 0x204);
+        assert!(!vm.input.is_pressed(Key::A as u8));
+
+        vm.set_paused(false);
+        cycle(&mut vm, 1);
+        assert_eq!(vm.deplay_timer, 3);
+    }
+
+    #[test]
+    fn realtime_timers() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x05, // ld v0, 0x05
+            0xF0, 0x15, // ld dt, v0
+            0xF0, 0x18, // ld st, v0
+            0x12, 0x06, // jp 0x206
+        ]);
+        vm.run(100);
+        assert_eq!((vm.deplay_timer, vm.sound_timer), (5, 5));
+
+        assert_eq!(vm.tick(TIMER_PERIOD / 2), 0);
+        assert_eq!(vm.tick(TIMER_PERIOD / 2), 1);
+        assert_eq!(vm.tick(TIMER_PERIOD * 3), 3);
+        assert_eq!(vm.deplay_timer, 1);
+        assert!(vm.sound_active());
+
+        vm.set_paused(true);
+        assert_eq!(vm.tick(TIMER_PERIOD), 0);
+        vm.set_paused(false);
+        vm.tick(TIMER_PERIOD * 10);
+        assert!(!vm.sound_active());
+
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+        assert_eq!(vm.tick(TIMER_PERIOD), 0);
+    }
+
+    #[test]
+    fn sound_events() {
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+        vm.load(vec![
+            0x60, 0x02, // ld v0, 0x02
+            0xF0, 0x18, // ld st, v0
+            0x12, 0x04, // jp 0x204
+        ]);
+        vm.cycle().unwrap();
+        assert_eq!(vm.take_sound_event(), None);
+        vm.cycle().unwrap();
+        assert_eq!(vm.take_sound_event(), Some(SoundEvent::Started));
+        assert_eq!(vm.take_sound_event(), None);
+        vm.run(2);
+        assert_eq!(vm.take_sound_event(), Some(SoundEvent::Stopped));
+        assert_eq!(vm.take_sound_event(), None);
+    }
+
+    #[test]
+    fn wait_for_key() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0xF3, 0x0A, // 200: ld v3, k
+            0x00, 0xFD, // 202: exit
+        ]);
+        assert_eq!(vm.cycle(), Ok(ProgramState::WaitingForKey));
+        assert_eq!(vm.cycle(), Ok(ProgramState::WaitingForKey));
+        assert_eq!(vm.program_counter(), 0x200);
+        assert_eq!(vm.snapshot().wait_for_key, Some(3));
+
+        vm.input.key_down(Key::B);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Continue));
+        assert_eq!(vm.register(3), 0xB);
+        assert_eq!(vm.snapshot().wait_for_key, None);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Exit)));
+    }
+
+    #[test]
+    fn run_frames() {
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Realtime);
+        vm.load(vec![
+            0x60, 0x05, // 200: ld v0, 5
+            0xF0, 0x18, // 202: ld st, v0
+            0xD0, 0x01, // 204: drw v0, v0, 1
+            0xE0, 0x9E, // 206: skp v0
+            0x12, 0x04, // 208: jp 0x204
+            0x00, 0xFD, // 20A: exit
+        ]);
+
+        let frame = vm.run_frame(5);
+        assert_eq!(frame.cycles, 5);
+        assert_eq!(frame.state, ProgramState::Continue);
+        assert_eq!(
+            frame.events,
+            vec![EventKind::Beep, EventKind::Draw, EventKind::KeyPoll]
+        );
+        assert_eq!(vm.sound_timer(), 4);
+        assert_eq!(vm.program_counter(), 0x204);
+
+        vm.add_breakpoint(0x206);
+        let frame = vm.run_frame(5);
+        assert_eq!(frame.cycles, 1);
+        assert_eq!(frame.state, ProgramState::BreakpointHit);
+        assert_eq!(frame.events, vec![EventKind::Draw, EventKind::Breakpoint]);
+        assert_eq!(vm.sound_timer(), 4);
+
+        vm.clear_breakpoints();
+        vm.input.key_down(Key::Five);
+        let frame = vm.run_frame(5);
+        assert_eq!(frame.cycles, 2);
+        assert_eq!(frame.state, ProgramState::Halted(StopReason::Exit));
+        assert_eq!(frame.events, vec![EventKind::KeyPoll, EventKind::Halt]);
+
+        let frame = vm.run_frame(5);
+        assert_eq!(
+            (frame.cycles, frame.state),
+            (0, ProgramState::Halted(StopReason::Exit))
+        );
+        assert!(frame.events.is_empty());
+    }
+
+    #[test]
+    fn timers_ignore_instruction_rate() {
+        let program = vec![
+            0x60, 0x3C, // 200: ld v0, 60
+            0xF0, 0x15, // 202: ld dt, v0
+            0x70, 0x01, // 204: add v0, 1
+            0x12, 0x04, // 206: jp 0x204
+        ];
+        let timers = |ipf: usize| {
+            let mut vm = Vm::new();
+            vm.load(program.clone());
+            vm.run(2);
+            (0..30).for_each(|_| {
+                vm.run_frame(ipf);
+            });
+            let by_frames = vm.delay_timer();
+            vm.run(ipf * 10);
+            vm.tick(TIMER_PERIOD * 10);
+            (by_frames, vm.delay_timer())
+        };
+        assert_eq!(timers(8), (30, 20));
+        assert_eq!(timers(200), (30, 20));
+    }
+
+    #[test]
+    fn frames_wait_on_delay_timer() {
+        for clock in [TimerClock::Instructions, TimerClock::Realtime] {
+            let mut vm = Vm::new().with_timer_clock(clock);
+            vm.load(vec![
+                0x60, 0x0A, // 200: ld v0, 10
+                0xF0, 0x15, // 202: ld dt, v0
+                0xF1, 0x07, // 204: ld v1, dt
+                0x31, 0x00, // 206: se v1, 0
+                0x12, 0x04, // 208: jp 0x204
+                0x62, 0x07, // 20A: ld v2, 7
+                0x12, 0x0C, // 20C: jp 0x20C
+            ]);
+            for _ in 0..20 {
+                vm.run_frame(11);
+            }
+            assert_eq!(vm.register(2), 7, "{:?}", clock);
+        }
+    }
+
+    #[test]
+    fn run_frame_counts_timers_once() {
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Instructions);
+        vm.load(vec![
+            0x60, 0x20, // 200: ld v0, 0x20
+            0xF0, 0x15, // 202: ld dt, v0
+            0x70, 0x01, // 204: add v0, 1
+            0x12, 0x04, // 206: jp 0x204
+        ]);
+        vm.run(2);
+        assert_eq!(vm.delay_timer(), 0x1F);
+        vm.run_frame(10);
+        assert_eq!(vm.delay_timer(), 0x1E);
+        vm.run_frame(10);
+        assert_eq!(vm.delay_timer(), 0x1D);
+
+        // Instructions run on their own still count the timers
+        vm.run(4);
+        assert_eq!(vm.delay_timer(), 0x19);
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0xA3, 0x00, // 200: ld i, 0x300
+            0x60, 0x01, // 202: ld v0, 1
+            0xF0, 0x55, // 204: ld [i], v0
+            0xF0, 0x65, // 206: ld v0, [i]
+            0x12, 0x02, // 208: jp 0x202
+        ]);
+        vm.add_breakpoint(0x202);
+        vm.add_watchpoint(0x301..0x302, Access::Write);
+        vm.add_watchpoint(0x2FF..0x301, Access::Write);
+        vm.add_watchpoint(0x301..0x303, Access::Read);
+
+        assert_eq!(vm.cycle(), Ok(ProgramState::Continue));
+        assert_eq!(vm.cycle(), Ok(ProgramState::BreakpointHit));
+        assert_eq!(vm.break_hit(), Some(&Break::Breakpoint(0x202)));
+        assert_eq!(vm.program_counter(), 0x202);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Continue));
+
+        // ld [i], v0 only writes 0x300, then increments i
+        assert_eq!(vm.cycle(), Ok(ProgramState::BreakpointHit));
+        assert_eq!(
+            vm.break_hit(),
+            Some(&Break::Watchpoint {
+                address: 0x204,
+                watchpoint: Watchpoint {
+                    range: 0x2FF..0x301,
+                    access: Access::Write
+                }
+            })
+        );
+        assert_eq!(vm.memory(0x300), 0);
+        assert_eq!(vm.cycle(), Ok(ProgramState::Continue));
+        assert_eq!(vm.memory(0x300), 1);
+
+        assert_eq!(vm.run(10), ProgramState::BreakpointHit);
+        assert_eq!(vm.program_counter(), 0x206);
+        assert!(vm.remove_breakpoint(0x202));
+        assert!(!vm.remove_breakpoint(0x202));
+        assert!(vm.remove_watchpoint(0x301..0x303, Access::Read));
+        vm.clear_breakpoints();
+        assert_eq!(vm.run(10), ProgramState::Continue);
+    }
+
+    #[test]
+    fn step() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0x07, // ld v0, 0x07
+            0xA3, 0x00, // ld i, 0x300
+            0xF1, 0x55, // ld [i], v1
+            0xD0, 0x05, // drw v0, v0, 5
+        ]);
+
+        let step = vm.step();
+        assert_eq!(
+            (step.address, step.opcode, step.next),
+            (0x200, 0x6007, 0x202)
+        );
+        assert_eq!(step.instruction, Instruction::parse(0x6007));
+        assert_eq!(step.state, ProgramState::Continue);
+        assert!(step.wrote_register(0) && !step.wrote_register(1));
+        assert!(!step.drew);
+
+        vm.step();
+        let step = vm.step();
+        assert_eq!(step.registers_written, 0);
+        assert_eq!(step.memory_written, Some(0x300..0x302));
+        assert_eq!(step.memory_read, None);
+
+        let step = vm.step();
+        assert!(step.drew);
+        assert_eq!(step.memory_read, Some(vm.index..vm.index + 5));
+
+        vm.request_stop();
+        let step = vm.step();
+        assert_eq!(
+            (step.state, step.next),
+            (ProgramState::Halted(StopReason::Requested), step.address)
+        );
+        assert!(!step.drew);
+    }
+
+    #[test]
+    fn execution_errors() {
+        let error = |program: Vec<u8>, cycles: usize| {
+            let mut vm = Vm::new();
+            vm.load(program);
+            for _ in 1..cycles {
+                vm.cycle().unwrap();
+            }
+            let pc = vm.program_counter;
+            let error = vm.cycle().unwrap_err();
+            assert_eq!(vm.program_counter, pc);
+            error
+        };
+
+        // call 0x200 until the stack is full
+        assert_eq!(
+            error(vec![0x22, 0x00], STACK_SIZE + 1),
+            VmError::StackOverflow(0x200)
+        );
+        // ret with an empty stack
+        assert_eq!(error(vec![0x00, 0xEE], 1), VmError::StackUnderflow(0x200));
+        assert_eq!(
+            error(vec![0xFF, 0xFF], 1),
+            VmError::InvalidOpcode(0x200, 0xFFFF)
+        );
+        // ld i, 0xFFE; ld b, v0
+        assert_eq!(
+            error(vec![0xAF, 0xFE, 0xF0, 0x33], 2),
+            VmError::MemoryOutOfRange(0xFFE, 0x1001)
+        );
+        // jp 0xFFF
+        assert_eq!(error(vec![0x1F, 0xFF], 2), VmError::PcOutOfRange(0xFFF));
+
+        let mut vm = Vm::new();
+        vm.load(vec![0x00, 0xFD]); // exit
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Exit)));
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Exit)));
+
+        // The run loops of every engine stop on the failing instruction
+        let programs = [
+            (0xF0, 0x33, VmError::MemoryOutOfRange(0xFFE, 0x1001)), // ld b, v0
+            (0x00, 0xEE, VmError::StackUnderflow(0x202)),           // ret
+            (0xFF, 0xFF, VmError::InvalidOpcode(0x202, 0xFFFF)),
+        ];
+        for engine in Engine::VARIANTS {
+            for (high, low, error) in programs.iter() {
+                let mut vm = Vm::new().with_engine(engine.parse().unwrap());
+                vm.load(vec![0xAF, 0xFE, *high, *low]); // ld i, 0xFFE
+                let error = ProgramState::Error(*error);
+                assert_eq!(vm.run(100), error, "{}", engine);
+                assert_eq!(vm.program_counter, 0x202, "{}", engine);
+                assert_eq!(vm.run(100), error, "{}", engine);
+            }
+        }
+    }
+
+    #[test]
+    fn super_chip() {
+        let mut vm = Vm::new();
+        let mut rom = vec![
+            0x00, 0xFF, // high
+            0xA2, 0x10, // ld i, 0x210
+            0xD0, 0x00, // drw v0, v0, 0
+            0x00, 0xC4, // scd 0x4
+            0x00, 0xFB, // scr
+            0xF1, 0x30, // ld hf, v1
+            0xF1, 0x75, // ld r, v1
+            0x12, 0x0E, // jp 0x20E
+        ];
+        rom.extend_from_slice(&[0xFF; 32]);
+        vm.load(rom);
+
+        cycle(&mut vm, 3);
+        assert!(vm.gpu.is_hires());
+        assert_eq!(vm.gpu.display().len(), 128 * 64);
+        assert!(vm.gpu.get(15, 15));
+        assert!(!vm.gpu.get(16, 15));
+        assert_eq!(vm.registers[0xF], 0);
+
+        cycle(&mut vm, 2);
+        assert!(!vm.gpu.get(4, 3));
+        assert!(vm.gpu.get(4, 4));
+        assert!(!vm.gpu.get(3, 4));
+        assert!(vm.gpu.get(19, 19));
+        // The 64x32 view shows the 2x2 blocks of the high resolution display
+        assert!(vm.gpu.memory[2 * SCREEN_WIDTH + 2]);
+        assert!(!vm.gpu.memory[SCREEN_WIDTH + 1]);
+
+        vm.registers[0] = 7;
+        vm.registers[1] = 0;
+        cycle(&mut vm, 2);
+        assert_eq!(vm.index, BIG_FONT_START as u16);
+        assert_eq!(vm.flags[..2], [7, 0]);
+
+        vm.registers[0] = 0;
+        vm.op_load_flags(0xF);
+        assert_eq!(vm.registers[0], 7);
+
+        let state = vm.snapshot();
+        assert!(state.is_hires());
+        vm.reset();
+        assert!(!vm.gpu.is_hires());
+        assert_eq!(vm.flags[0], 7);
+        vm.restore(&state);
+        assert!(vm.gpu.get(4, 4));
+        assert_eq!(vm.snapshot(), state);
+
+        vm.set_flags(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(vm.flags(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn no_allocation() {
+        use crate::{bench::WORKLOAD, testing::allocations};
+
+        let mut vm = Vm::new();
+        vm.load(WORKLOAD.to_vec());
+        vm.set_history_capacity(16);
+        let mut state = vm.snapshot();
+        let mut bytes = [0; 16];
+        let count = allocations::count(|| {
+            for _ in 0..1000 {
+                vm.cycle().unwrap();
+            }
+            vm.snapshot_into(&mut state);
+            vm.read_memory(0x200, &mut bytes).unwrap();
+        });
+        assert_eq!(count, 0);
+        assert_eq!(state, vm.snapshot());
+        assert_eq!(bytes[..], WORKLOAD[..16]);
+    }
+
     // TODO: input and control flow
 }
@@ -0,0 +1,86 @@
+//! Process exit code of a frontend when the program executes `exit` (00FD), so test roms can
+//! report whether they passed.
+//!
+//! An exit code is a number, or a register `vX` to exit with the value the program left in it.
+
+use crate::debug::Inspect;
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Fixed(i32),
+    Register(u8),
+}
+
+impl ExitCode {
+    pub fn code(&self, target: &impl Inspect) -> i32 {
+        match self {
+            Self::Fixed(code) => *code,
+            Self::Register(register) => target.register(*register) as i32,
+        }
+    }
+}
+
+impl Default for ExitCode {
+    fn default() -> Self {
+        Self::Fixed(0)
+    }
+}
+
+impl FromStr for ExitCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let register = s
+            .strip_prefix('v')
+            .or_else(|| s.strip_prefix('V'))
+            .filter(|digit| digit.len() == 1);
+        match register {
+            Some(digit) => u8::from_str_radix(digit, 16)
+                .map(Self::Register)
+                .map_err(|_| format!("Invalid register '{}'", s)),
+            None => s
+                .parse()
+                .map(Self::Fixed)
+                .map_err(|_| format!("Invalid exit code '{}', expected a number or vX", s)),
+        }
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(code) => write!(f, "{}", code),
+            Self::Register(register) => write!(f, "v{:X}", register),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::{ProgramState, StopReason, Vm};
+
+    #[test]
+    fn parse() {
+        assert_eq!("3".parse(), Ok(ExitCode::Fixed(3)));
+        assert_eq!("vF".parse(), Ok(ExitCode::Register(0xF)));
+        assert_eq!("V0".parse::<ExitCode>().unwrap().to_string(), "v0");
+        assert!("vG".parse::<ExitCode>().is_err());
+        assert!("pass".parse::<ExitCode>().is_err());
+    }
+
+    #[test]
+    fn register_code() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x61, 0x02, // ld v1, 0x02
+            0x00, 0xFD, // exit
+        ]);
+        vm.cycle().unwrap();
+        assert_eq!(vm.cycle(), Ok(ProgramState::Halted(StopReason::Exit)));
+        assert_eq!(vm.stop_reason(), Some(StopReason::Exit));
+        assert_eq!(ExitCode::Register(1).code(&vm), 2);
+        assert_eq!(ExitCode::default().code(&vm), 0);
+    }
+}
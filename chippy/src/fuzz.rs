@@ -0,0 +1,307 @@
+//! Coverage guided fuzzer for chip8 roms. Key sequences are mutated from the ones that executed
+//! new addresses, looking for input that makes the rom break a machine invariant or reach an
+//! invalid opcode. Every finding comes with its input as a recording that `Autoplay` replays.
+
+use crate::{
+    autoplay::RANDOM_HOLD_FRAMES,
+    debug::Inspect,
+    emu::{
+        error::VmError,
+        frame::DEFAULT_CYCLES_PER_FRAME,
+        instruction::Instruction,
+        vm::{ProgramState, Vm, MEMORY_SIZE, TIMER_PERIOD},
+    },
+    netplay::InputFrame,
+    soak::{self, Rng, Violation},
+};
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Frames of input played per run unless configured with `Fuzzer::frames`
+pub const DEFAULT_FRAMES: usize = 600;
+
+/// Way a rom went wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Crash {
+    Violation(Violation),
+    /// The program reached an opcode that is no instruction
+    InvalidOpcode {
+        address: u16,
+        opcode: u16,
+    },
+    /// Any other instruction that can not execute
+    Error(VmError),
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crash::Violation(violation) => write!(f, "{}", violation),
+            Crash::InvalidOpcode { address, opcode } => {
+                write!(f, "invalid opcode {:04X} at {:#05X}", opcode, address)
+            }
+            Crash::Error(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Input that crashed the rom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Keypad changes by frame, see `autoplay::write_recording`
+    pub input: Vec<InputFrame>,
+    /// Number of instructions executed before the crash
+    pub cycle: usize,
+    pub crash: Crash,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} cycles", self.crash, self.cycle)
+    }
+}
+
+/// Result of playing one key sequence.
+struct Run {
+    covered: Vec<bool>,
+    crash: Option<(usize, Crash)>,
+}
+
+pub struct Fuzzer {
+    rom: Vec<u8>,
+    rng: Rng,
+    frames: usize,
+    cycles_per_frame: usize,
+    /// Addresses executed by any run
+    coverage: Vec<bool>,
+    /// Key sequences that executed new addresses, held keys by frame
+    corpus: Vec<Vec<u16>>,
+    findings: Vec<Finding>,
+    runs: usize,
+}
+
+impl Fuzzer {
+    pub fn new(rom: Vec<u8>, seed: u64) -> Self {
+        Self {
+            rom,
+            rng: Rng::new(seed),
+            frames: DEFAULT_FRAMES,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            coverage: vec![false; MEMORY_SIZE],
+            corpus: Vec::new(),
+            findings: Vec::new(),
+            runs: 0,
+        }
+    }
+
+    pub fn frames(mut self, frames: usize) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    pub fn cycles_per_frame(mut self, cycles: usize) -> Self {
+        self.cycles_per_frame = cycles.max(1);
+        self
+    }
+
+    /// Play one mutated key sequence, returning the finding if it crashed the rom in a way not
+    /// seen before
+    pub fn step(&mut self) -> Option<Finding> {
+        let input = self.next_input();
+        let run = self.run(&input);
+        self.runs += 1;
+
+        let mut new_coverage = false;
+        for (covered, hit) in self.coverage.iter_mut().zip(run.covered) {
+            new_coverage |= hit && !*covered;
+            *covered |= hit;
+        }
+        if new_coverage {
+            self.corpus.push(input.clone());
+        }
+
+        let (cycle, crash) = run.crash?;
+        if self.findings.iter().any(|finding| finding.crash == crash) {
+            return None;
+        }
+        let finding = Finding {
+            input: changes(&input),
+            cycle,
+            crash,
+        };
+        self.findings.push(finding.clone());
+        Some(finding)
+    }
+
+    /// Number of addresses executed so far
+    pub fn covered(&self) -> usize {
+        self.coverage.iter().filter(|covered| **covered).count()
+    }
+
+    /// Number of key sequences kept for finding new addresses
+    pub fn corpus_len(&self) -> usize {
+        self.corpus.len()
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// A random key sequence, or one of the corpus with a span of frames replaced
+    fn next_input(&mut self) -> Vec<u16> {
+        if self.corpus.is_empty() || self.rng.next_u64().is_multiple_of(4) {
+            let mut input = vec![0; self.frames];
+            self.randomize(&mut input, 0, self.frames);
+            return input;
+        }
+        let mut input = self.corpus[self.rng.next_u64() as usize % self.corpus.len()].clone();
+        let start = self.rng.next_u64() as usize % self.frames;
+        let len = 1 + self.rng.next_u64() as usize % (2 * RANDOM_HOLD_FRAMES as usize);
+        self.randomize(&mut input, start, (start + len).min(self.frames));
+        input
+    }
+
+    /// Hold random keys, or none, for `RANDOM_HOLD_FRAMES` at a time over `start..end`
+    fn randomize(&mut self, input: &mut [u16], start: usize, end: usize) {
+        for chunk in input[start..end].chunks_mut(RANDOM_HOLD_FRAMES as usize) {
+            let keys = match self.rng.next_u64() % 17 {
+                16 => 0,
+                key => 1 << key,
+            };
+            chunk.iter_mut().for_each(|frame| *frame = keys);
+        }
+    }
+
+    fn run(&self, input: &[u16]) -> Run {
+        let mut vm = Vm::new();
+        vm.load(self.rom.clone());
+        let mut covered = vec![false; MEMORY_SIZE];
+        let mut cycle = 0;
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            for (frame, keys) in input.iter().enumerate() {
+                InputFrame {
+                    frame: frame as u64,
+                    keys: *keys,
+                }
+                .apply(&mut vm.input);
+                for _ in 0..self.cycles_per_frame {
+                    let address = vm.program_counter();
+                    covered[address as usize % MEMORY_SIZE] = true;
+                    let opcode = (vm.memory(address) as u16) << 8 | vm.memory(address + 1) as u16;
+                    if let Instruction::Invalid(opcode) = Instruction::parse(opcode) {
+                        return Err(Crash::InvalidOpcode { address, opcode });
+                    }
+                    match vm.cycle() {
+                        Ok(ProgramState::Halted(_)) => return Ok(()),
+                        Ok(_) => (),
+                        Err(err) => return Err(Crash::Error(err)),
+                    }
+                    cycle += 1;
+                    soak::check(&vm).map_err(Crash::Violation)?;
+                }
+                vm.tick(TIMER_PERIOD);
+            }
+            Ok(())
+        }));
+
+        let crash = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(crash)) => Some(crash),
+            Err(payload) => Some(Crash::Violation(Violation::Panic(soak::panic_message(
+                payload.as_ref(),
+            )))),
+        };
+        Run {
+            covered,
+            crash: crash.map(|crash| (cycle, crash)),
+        }
+    }
+}
+
+/// Keypad changes of a key sequence held by frame
+fn changes(input: &[u16]) -> Vec<InputFrame> {
+    let mut changes: Vec<InputFrame> = Vec::new();
+    for (frame, keys) in input.iter().enumerate() {
+        if changes.last().map_or(0, |change| change.keys) != *keys {
+            changes.push(InputFrame {
+                frame: frame as u64,
+                keys: *keys,
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autoplay::Autoplay;
+
+    // Jumps to an invalid opcode while key 5 is held
+    const ROM: [u8; 10] = [
+        0x60, 0x05, // 200: ld v0, 5
+        0xE0, 0xA1, // 202: sknp v0
+        0x12, 0x08, // 204: jp 0x208
+        0x12, 0x02, // 206: jp 0x202
+        0xFF, 0xFF, // 208: invalid
+    ];
+
+    #[test]
+    fn finds_crashing_input() {
+        let mut fuzzer = Fuzzer::new(ROM.to_vec(), 1).frames(60);
+        while fuzzer.findings().is_empty() && fuzzer.runs() < 1000 {
+            fuzzer.step();
+        }
+
+        let finding = &fuzzer.findings()[0];
+        assert_eq!(
+            finding.crash,
+            Crash::InvalidOpcode {
+                address: 0x208,
+                opcode: 0xFFFF
+            }
+        );
+        assert_eq!(fuzzer.covered(), 5);
+        assert!(fuzzer.corpus_len() > 0);
+
+        let mut autoplay = Autoplay::Recorded(finding.input.clone());
+        let pressed_five = (0..60).any(|frame| autoplay.keys(frame) & 1 << 5 != 0);
+        assert!(pressed_five);
+    }
+
+    #[test]
+    fn reaches_code_after_delay() {
+        let rom = vec![
+            0x60, 0x0A, // 200: ld v0, 10
+            0xF0, 0x15, // 202: ld dt, v0
+            0xF1, 0x07, // 204: ld v1, dt
+            0x31, 0x00, // 206: se v1, 0
+            0x12, 0x04, // 208: jp 0x204
+            0xFF, 0xFF, // 20A: invalid
+        ];
+        let mut fuzzer = Fuzzer::new(rom, 1).frames(30);
+        let finding = fuzzer.step().unwrap();
+        assert_eq!(
+            finding.crash,
+            Crash::InvalidOpcode {
+                address: 0x20A,
+                opcode: 0xFFFF
+            }
+        );
+    }
+
+    #[test]
+    fn changes_of_input() {
+        let frames = changes(&[0, 0, 4, 4, 0]);
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].frame, frames[0].keys), (2, 4));
+        assert_eq!((frames[1].frame, frames[1].keys), (4, 0));
+    }
+}
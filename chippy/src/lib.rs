@@ -1,5 +1,30 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
+pub mod audio;
+pub mod autoplay;
+pub mod bench;
+pub mod debug;
+pub mod embed;
 pub mod emu;
+pub mod exit;
+pub mod fuzz;
+pub mod lint;
+pub mod locale;
+pub mod netplay;
 pub mod parser;
+pub mod render;
+pub mod rom;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub mod runner;
+pub mod score;
+pub mod session;
+pub mod soak;
+pub mod sprite;
+pub mod status;
+pub mod storage;
+#[cfg(feature = "jpeg-encoder")]
+pub mod stream;
+pub mod testing;
+pub mod video;
+pub mod wav;
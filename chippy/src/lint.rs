@@ -0,0 +1,325 @@
+//! Portability linter: flags the instructions of a program that a target interpreter does not
+//! support and the patterns whose result depends on its quirks.
+//!
+//! The code of a rom is found by following its jumps, calls and skips from 0x200, sprites and
+//! other data are not linted. Code only reached through `jp v0` tables is missed.
+
+use crate::emu::{instruction::Instruction, vm::MEMORY_START};
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+/// Interpreter a program should run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// COSMAC VIP chip8
+    Vip,
+    /// SUPER-CHIP 1.1 on the HP48 calculators
+    Schip,
+    /// XO-CHIP, which also runs the SUPER-CHIP instructions
+    Xochip,
+}
+
+impl Target {
+    pub const VARIANTS: &'static [&'static str] = &["vip", "schip", "xochip"];
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vip" => Ok(Target::Vip),
+            "schip" => Ok(Target::Schip),
+            "xochip" => Ok(Target::Xochip),
+            _ => Err(format!(
+                "Unknown target '{}', expected one of {}",
+                s,
+                Target::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Target::VARIANTS[*self as usize])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The target does not run the instruction
+    Unsupported,
+    /// The instruction runs but behaves differently on other targets
+    Quirk,
+}
+
+/// Portability problem of one instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub address: u16,
+    pub opcode: u16,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let asm = Instruction::parse(self.opcode).to_asm();
+        write!(f, "{:03X} {}: {}", self.address, asm, self.message)
+    }
+}
+
+/// Lint the code of `rom`, see `code_addresses`
+pub fn lint_rom(rom: &[u8], target: Target) -> Vec<Finding> {
+    lint(rom, &code_addresses(rom), target)
+}
+
+/// Lint the instructions of `program` at `code`, the program is loaded at 0x200
+pub fn lint(program: &[u8], code: &BTreeSet<u16>, target: Target) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for address in code.iter() {
+        let opcode = match opcode_at(program, *address) {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+        let next = opcode_at(program, address.wrapping_add(2)).map(Instruction::parse);
+        let mut report = |severity, message| {
+            findings.push(Finding {
+                address: *address,
+                opcode,
+                severity,
+                message,
+            })
+        };
+        if let Some(message) = unsupported(opcode, target) {
+            report(Severity::Unsupported, message);
+        }
+        if let Some(message) = quirk(Instruction::parse(opcode), next, target) {
+            report(Severity::Quirk, message);
+        }
+    }
+    findings
+}
+
+/// Addresses of the instructions reached from 0x200 by following jumps, calls and both sides of
+/// skips. A `jp v0` ends the walk since its target depends on v0.
+pub fn code_addresses(rom: &[u8]) -> BTreeSet<u16> {
+    let mut code = BTreeSet::new();
+    let mut pending = vec![MEMORY_START as u16];
+    while let Some(address) = pending.pop() {
+        let opcode = match opcode_at(rom, address) {
+            Some(opcode) if code.insert(address) => opcode,
+            _ => continue,
+        };
+        let next = address.wrapping_add(2);
+        match Instruction::parse(opcode) {
+            Instruction::Jump(target) => pending.push(target),
+            Instruction::Call(target) => pending.extend([target, next]),
+            Instruction::SkipIfEq(_)
+            | Instruction::SkipIfNeq(_)
+            | Instruction::SkipIfRegEq(_)
+            | Instruction::SkipIfDifferent(_)
+            | Instruction::SkipIfKeyPressed(_)
+            | Instruction::SkipIfNotKeyPressed(_) => pending.extend([next, next.wrapping_add(2)]),
+            Instruction::Return | Instruction::Exit | Instruction::JumpNPlusPC(_) => (),
+            // XO-CHIP `i := long nnnn` is followed by its 16 bit address
+            Instruction::Invalid(0xF000) => pending.push(next.wrapping_add(2)),
+            _ => pending.push(next),
+        }
+    }
+    code
+}
+
+fn opcode_at(program: &[u8], address: u16) -> Option<u16> {
+    let offset = (address as usize).checked_sub(MEMORY_START)?;
+    match program.get(offset..offset + 2)? {
+        [high, low] => Some(u16::from_be_bytes([*high, *low])),
+        _ => None,
+    }
+}
+
+/// Mnemonic of an XO-CHIP instruction, which chippy does not run
+fn xochip_instruction(opcode: u16) -> Option<&'static str> {
+    match opcode & 0xF00F {
+        0x5002 => return Some("save vx - vy"),
+        0x5003 => return Some("load vx - vy"),
+        _ => (),
+    }
+    match opcode {
+        0xF000 => Some("i := long"),
+        0xF002 => Some("audio"),
+        _ if opcode & 0xFFF0 == 0x00D0 => Some("scroll-up"),
+        _ if opcode & 0xF0FF == 0xF001 => Some("plane"),
+        _ if opcode & 0xF0FF == 0xF03A => Some("pitch"),
+        _ => None,
+    }
+}
+
+fn unsupported(opcode: u16, target: Target) -> Option<String> {
+    let instruction = Instruction::parse(opcode);
+    match instruction {
+        Instruction::Invalid(_) => match xochip_instruction(opcode) {
+            Some(_) if target == Target::Xochip => None,
+            Some(name) => Some(format!("{} is an XO-CHIP instruction", name)),
+            None => Some("Unknown opcode".to_string()),
+        },
+        Instruction::CallMachineCode(_) if target != Target::Vip => {
+            Some("Machine code routines only run on the COSMAC VIP".to_string())
+        }
+        Instruction::Draw { n: 0, .. } if target == Target::Vip => {
+            Some("Draws nothing on the VIP, a 16x16 sprite on SUPER-CHIP".to_string())
+        }
+        Instruction::ScrollDown(_)
+        | Instruction::ScrollRight
+        | Instruction::ScrollLeft
+        | Instruction::Exit
+        | Instruction::LowRes
+        | Instruction::HighRes
+        | Instruction::SetIToBigFontSprite(_)
+        | Instruction::StoreFlags(_)
+        | Instruction::LoadFlags(_)
+            if target == Target::Vip =>
+        {
+            Some("SUPER-CHIP instruction".to_string())
+        }
+        Instruction::StoreFlags(x) | Instruction::LoadFlags(x)
+            if x > 7 && target == Target::Schip =>
+        {
+            Some("The HP48 only has 8 flags, v0 to v7".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Whether `instruction` uses i
+fn reads_index(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Draw { .. }
+            | Instruction::AddXToI(_)
+            | Instruction::StoreBCD(_)
+            | Instruction::DumpRegisters(_)
+            | Instruction::LoadRegisters(_)
+    )
+}
+
+fn quirk(instruction: Instruction, next: Option<Instruction>, target: Target) -> Option<String> {
+    match instruction {
+        Instruction::ShiftRight(pair) | Instruction::ShiftLeft(pair)
+            if pair.target != pair.source =>
+        {
+            let (x, y) = (pair.target, pair.source);
+            Some(match target {
+                Target::Schip => format!(
+                    "Shifts v{:X} on schip, the VIP and XO-CHIP shift v{:X} into v{:X}",
+                    x, y, x
+                ),
+                _ => format!(
+                    "Shifts v{:X} into v{:X} on {}, SUPER-CHIP shifts v{:X}",
+                    y, x, target, x
+                ),
+            })
+        }
+        Instruction::JumpNPlusPC(address) if address >> 8 != 0 => {
+            let x = address >> 8;
+            Some(match target {
+                Target::Schip => format!("Adds v{:X} on schip, the VIP and XO-CHIP add v0", x),
+                _ => format!("Adds v0 on {}, SUPER-CHIP adds v{:X}", target, x),
+            })
+        }
+        Instruction::DumpRegisters(_) | Instruction::LoadRegisters(_)
+            if next.as_ref().is_some_and(reads_index) =>
+        {
+            Some(match target {
+                Target::Schip => {
+                    "The next instruction uses i, left unchanged on schip but incremented by the \
+                     VIP and XO-CHIP"
+                        .to_string()
+                }
+                _ => format!(
+                    "The next instruction uses i, incremented on {} but left unchanged by \
+                     SUPER-CHIP",
+                    target
+                ),
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 18] = [
+        0x00, 0xFF, // 200: high
+        0x34, 0x00, // 202: se v4, 0
+        0x12, 0x0C, // 204: jp 0x20C
+        0x81, 0x26, // 206: shr v1, v2
+        0xF1, 0x55, // 208: ld [i], v1
+        0xD0, 0x10, // 20A: drw v0, v1, 0
+        0x22, 0x10, // 20C: call 0x210
+        0xB3, 0x00, // 20E: jp v0, 0x300
+        0xF1, 0x01, // 210: plane 1
+    ];
+
+    fn lint_summary(target: Target) -> Vec<(u16, Severity)> {
+        lint_rom(&ROM, target)
+            .into_iter()
+            .map(|finding| (finding.address, finding.severity))
+            .collect()
+    }
+
+    #[test]
+    fn code_of_rom() {
+        let code: Vec<u16> = code_addresses(&ROM).into_iter().collect();
+        assert_eq!(code, (0x200..=0x210).step_by(2).collect::<Vec<u16>>());
+
+        // Sprite data after an endless loop is not code
+        let code = code_addresses(&[0x12, 0x00, 0xF0, 0x90]);
+        assert_eq!(code.into_iter().collect::<Vec<u16>>(), vec![0x200]);
+    }
+
+    #[test]
+    fn lint_targets() {
+        use Severity::*;
+        assert_eq!(
+            lint_summary(Target::Vip),
+            vec![
+                (0x200, Unsupported),
+                (0x206, Quirk),
+                (0x208, Quirk),
+                (0x20A, Unsupported),
+                (0x20E, Quirk),
+                (0x210, Unsupported),
+            ]
+        );
+        assert_eq!(
+            lint_summary(Target::Schip),
+            vec![
+                (0x206, Quirk),
+                (0x208, Quirk),
+                (0x20E, Quirk),
+                (0x210, Unsupported),
+            ]
+        );
+        assert_eq!(
+            lint_summary(Target::Xochip),
+            vec![(0x206, Quirk), (0x208, Quirk), (0x20E, Quirk)]
+        );
+
+        let finding = &lint_rom(&ROM, Target::Schip)[0];
+        assert_eq!(
+            finding.to_string(),
+            "206 shr v1, v2: Shifts v1 on schip, the VIP and XO-CHIP shift v2 into v1"
+        );
+    }
+
+    #[test]
+    fn target_names() {
+        for name in Target::VARIANTS {
+            assert_eq!(name.parse::<Target>().unwrap().to_string(), *name);
+        }
+        assert!("chip8".parse::<Target>().is_err());
+    }
+}
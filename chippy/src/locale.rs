@@ -0,0 +1,436 @@
+//! Translations of the text frontends show to players, so kiosk and web builds can be
+//! localized.
+//!
+//! Every `Message` has a text in every `Language`, `{}` marks where its arguments go. The
+//! language is picked once at startup with `set_language`, usually from a command line flag or
+//! `Language::from_env`.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// Language of the messages, `Language::En` until `set_language` is called
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::En as u8);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    En,
+    Fr,
+    De,
+}
+
+impl Language {
+    pub const VARIANTS: &'static [&'static str] = &["en", "fr", "de"];
+
+    /// Language of the user from `LC_ALL`, `LC_MESSAGES` or `LANG`, such as `fr_FR.UTF-8`.
+    /// English when none is set or the language has no translation.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_locale(&value))
+            .unwrap_or_default()
+    }
+
+    /// Language of a locale name such as `de_DE.UTF-8`
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let code = locale.split(['_', '.', '-']).next()?;
+        code.to_lowercase().parse().ok()
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Language::En),
+            "fr" => Ok(Language::Fr),
+            "de" => Ok(Language::De),
+            _ => Err(format!(
+                "Unknown language '{}', expected one of {}",
+                s,
+                Language::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Language::VARIANTS[*self as usize])
+    }
+}
+
+/// Select the language of every message
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::Fr,
+        2 => Language::De,
+        _ => Language::En,
+    }
+}
+
+/// Text shown to players by the frontends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    // Status bar and window title
+    StatusSlot,
+    StatusRewind,
+    StatusScore,
+    SavedSlot,
+    LoadedSlot,
+    BestScore,
+    NoKeyLabels,
+    // Errors
+    NoRomFile,
+    OpenRomFailed,
+    ReadHeaderFailed,
+    ReadDatabaseFailed,
+    InvalidAddress,
+    // Debugger panes
+    Paused,
+    Running,
+    Registers,
+    Keypad,
+    Watches,
+    History,
+    Disassembly,
+    Memory,
+    GoTo,
+    // Debugger messages
+    TriggerHit,
+    BreakpointHit,
+    NoEarlierState,
+    BreakpointSet,
+    BreakpointDeleted,
+    NoBreakpoint,
+    BreakpointsDeleted,
+    TriggerEnabled,
+    TriggerDisabled,
+    NoTriggers,
+    Triggers,
+    Watching,
+    NoWatches,
+    WatchCount,
+    WatchRemoved,
+    NoWatch,
+    WatchesRemoved,
+    WroteBytes,
+    LoadedBytes,
+    Patching,
+    Patched,
+    LastChange,
+    NoChange,
+    NotAJump,
+    NoBookmarks,
+    NotFound,
+}
+
+impl Message {
+    pub const ALL: &'static [Message] = &[
+        Message::StatusSlot,
+        Message::StatusRewind,
+        Message::StatusScore,
+        Message::SavedSlot,
+        Message::LoadedSlot,
+        Message::BestScore,
+        Message::NoKeyLabels,
+        Message::NoRomFile,
+        Message::OpenRomFailed,
+        Message::ReadHeaderFailed,
+        Message::ReadDatabaseFailed,
+        Message::InvalidAddress,
+        Message::Paused,
+        Message::Running,
+        Message::Registers,
+        Message::Keypad,
+        Message::Watches,
+        Message::History,
+        Message::Disassembly,
+        Message::Memory,
+        Message::GoTo,
+        Message::TriggerHit,
+        Message::BreakpointHit,
+        Message::NoEarlierState,
+        Message::BreakpointSet,
+        Message::BreakpointDeleted,
+        Message::NoBreakpoint,
+        Message::BreakpointsDeleted,
+        Message::TriggerEnabled,
+        Message::TriggerDisabled,
+        Message::NoTriggers,
+        Message::Triggers,
+        Message::Watching,
+        Message::NoWatches,
+        Message::WatchCount,
+        Message::WatchRemoved,
+        Message::NoWatch,
+        Message::WatchesRemoved,
+        Message::WroteBytes,
+        Message::LoadedBytes,
+        Message::Patching,
+        Message::Patched,
+        Message::LastChange,
+        Message::NoChange,
+        Message::NotAJump,
+        Message::NoBookmarks,
+        Message::NotFound,
+    ];
+
+    /// Text of the message in the selected language
+    pub fn text(self) -> &'static str {
+        self.text_in(language())
+    }
+
+    pub fn text_in(self, language: Language) -> &'static str {
+        self.texts()[language as usize]
+    }
+
+    /// Text of the message with every `{}` replaced by the next argument
+    pub fn format(self, args: &[&dyn fmt::Display]) -> String {
+        self.format_in(language(), args)
+    }
+
+    pub fn format_in(self, language: Language, args: &[&dyn fmt::Display]) -> String {
+        let mut parts = self.text_in(language).split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        let mut args = args.iter();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                text.push_str(&arg.to_string());
+            }
+            text.push_str(part);
+        }
+        text
+    }
+
+    /// Texts in the order of `Language`
+    fn texts(self) -> [&'static str; 3] {
+        match self {
+            Message::StatusSlot => ["Slot {}", "Emplacement {}", "Slot {}"],
+            Message::StatusRewind => ["Rewind {}", "Retour {}", "Zurück {}"],
+            Message::StatusScore => [
+                "Score {} (best {})",
+                "Score {} (record {})",
+                "Punkte {} (Rekord {})",
+            ],
+            Message::SavedSlot => [
+                "Saved slot {}",
+                "Emplacement {} sauvegardé",
+                "Slot {} gespeichert",
+            ],
+            Message::LoadedSlot => ["Loaded slot {}", "Emplacement {} chargé", "Slot {} geladen"],
+            Message::BestScore => ["best score {}", "meilleur score {}", "Rekord {}"],
+            Message::NoKeyLabels => [
+                "No key labels for this rom",
+                "Aucune touche décrite pour cette rom",
+                "Keine Tastenbelegung für dieses ROM",
+            ],
+            Message::NoRomFile => [
+                "No rom file given",
+                "Aucun fichier rom donné",
+                "Keine ROM-Datei angegeben",
+            ],
+            Message::OpenRomFailed => [
+                "Failed to open c8 file",
+                "Impossible d'ouvrir le fichier c8",
+                "Die c8-Datei konnte nicht geöffnet werden",
+            ],
+            Message::ReadHeaderFailed => [
+                "Failed to read the rom header",
+                "Impossible de lire l'en-tête de la rom",
+                "Der ROM-Header konnte nicht gelesen werden",
+            ],
+            Message::ReadDatabaseFailed => [
+                "Failed to read the rom database",
+                "Impossible de lire la base de roms",
+                "Die ROM-Datenbank konnte nicht gelesen werden",
+            ],
+            Message::InvalidAddress => [
+                "Invalid address: {}",
+                "Adresse invalide : {}",
+                "Ungültige Adresse: {}",
+            ],
+            Message::Paused => ["Chippy - Paused", "Chippy - En pause", "Chippy - Pausiert"],
+            Message::Running => ["Chippy - Running", "Chippy - En cours", "Chippy - Läuft"],
+            Message::Registers => ["Registers", "Registres", "Register"],
+            Message::Keypad => ["Keypad", "Clavier", "Tastenfeld"],
+            Message::Watches => ["Watches", "Surveillances", "Beobachtungen"],
+            Message::History => ["History - {}/{}", "Historique - {}/{}", "Verlauf - {}/{}"],
+            Message::Disassembly => ["Disassembly", "Désassemblage", "Disassemblierung"],
+            Message::Memory => ["Memory", "Mémoire", "Speicher"],
+            Message::GoTo => ["Go to: {}_", "Aller à : {}_", "Gehe zu: {}_"],
+            Message::TriggerHit => [
+                "Trigger {} at {}",
+                "Déclencheur {} à {}",
+                "Auslöser {} bei {}",
+            ],
+            Message::BreakpointHit => [
+                "Breakpoint at {}",
+                "Point d'arrêt à {}",
+                "Haltepunkt bei {}",
+            ],
+            Message::NoEarlierState => [
+                "No earlier state to step back to",
+                "Aucun état précédent où revenir",
+                "Kein früherer Zustand vorhanden",
+            ],
+            Message::BreakpointSet => [
+                "Breakpoint set at {}",
+                "Point d'arrêt posé à {}",
+                "Haltepunkt bei {} gesetzt",
+            ],
+            Message::BreakpointDeleted => [
+                "Breakpoint deleted at {}",
+                "Point d'arrêt supprimé à {}",
+                "Haltepunkt bei {} gelöscht",
+            ],
+            Message::NoBreakpoint => [
+                "No breakpoint at {}",
+                "Aucun point d'arrêt à {}",
+                "Kein Haltepunkt bei {}",
+            ],
+            Message::BreakpointsDeleted => [
+                "Breakpoints deleted",
+                "Points d'arrêt supprimés",
+                "Haltepunkte gelöscht",
+            ],
+            Message::TriggerEnabled => [
+                "Trigger {} enabled",
+                "Déclencheur {} activé",
+                "Auslöser {} aktiviert",
+            ],
+            Message::TriggerDisabled => [
+                "Trigger {} disabled",
+                "Déclencheur {} désactivé",
+                "Auslöser {} deaktiviert",
+            ],
+            Message::NoTriggers => [
+                "No triggers enabled",
+                "Aucun déclencheur activé",
+                "Keine Auslöser aktiviert",
+            ],
+            Message::Triggers => ["Triggers: {}", "Déclencheurs : {}", "Auslöser: {}"],
+            Message::Watching => ["Watching {}", "Surveille {}", "Beobachte {}"],
+            Message::NoWatches => ["No watches", "Aucune surveillance", "Keine Beobachtungen"],
+            Message::WatchCount => ["{} watches", "{} surveillances", "{} Beobachtungen"],
+            Message::WatchRemoved => [
+                "Removed watch {}",
+                "Surveillance {} retirée",
+                "Beobachtung {} entfernt",
+            ],
+            Message::NoWatch => [
+                "No watch {}",
+                "Pas de surveillance {}",
+                "Keine Beobachtung {}",
+            ],
+            Message::WatchesRemoved => [
+                "Watches removed",
+                "Surveillances retirées",
+                "Beobachtungen entfernt",
+            ],
+            Message::WroteBytes => [
+                "Wrote {} bytes to {}",
+                "{} octets écrits dans {}",
+                "{} Bytes nach {} geschrieben",
+            ],
+            Message::LoadedBytes => [
+                "Loaded {} bytes at {}",
+                "{} octets chargés à {}",
+                "{} Bytes bei {} geladen",
+            ],
+            Message::Patching => [
+                "Patching {}, type or paste lines and finish with an empty line",
+                "Correctif à {}, tapez ou collez des lignes et terminez par une ligne vide",
+                "Patch bei {}, Zeilen tippen oder einfügen und mit einer leeren Zeile beenden",
+            ],
+            Message::Patched => [
+                "Patched {} bytes at {}",
+                "{} octets corrigés à {}",
+                "{} Bytes bei {} gepatcht",
+            ],
+            Message::LastChange => [
+                "{} last changed by the instruction at {}, {} steps back",
+                "{} modifié en dernier par l'instruction à {}, {} pas en arrière",
+                "{} zuletzt von der Anweisung bei {} geändert, {} Schritte zurück",
+            ],
+            Message::NoChange => [
+                "{} did not change in the last {} steps",
+                "{} n'a pas changé pendant les {} derniers pas",
+                "{} hat sich in den letzten {} Schritten nicht geändert",
+            ],
+            Message::NotAJump => [
+                "Not a jump or call",
+                "Ni un saut ni un appel",
+                "Kein Sprung oder Aufruf",
+            ],
+            Message::NoBookmarks => ["No bookmarks", "Aucun signet", "Keine Lesezeichen"],
+            Message::NotFound => ["Not found: {}", "Introuvable : {}", "Nicht gefunden: {}"],
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_language_has_every_message() {
+        for message in Message::ALL {
+            let arguments = message.text_in(Language::En).matches("{}").count();
+            for language in Language::VARIANTS.iter().map(|name| name.parse().unwrap()) {
+                let text = message.text_in(language);
+                assert!(!text.is_empty(), "{:?} {}", message, language);
+                assert_eq!(
+                    text.matches("{}").count(),
+                    arguments,
+                    "{:?} {}",
+                    message,
+                    language
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            Message::WroteBytes.format_in(Language::Fr, &[&12, &"dump.bin"]),
+            "12 octets écrits dans dump.bin"
+        );
+        assert_eq!(
+            Message::BreakpointSet.format_in(Language::De, &[&"2A4"]),
+            "Haltepunkt bei 2A4 gesetzt"
+        );
+        assert_eq!(
+            Message::NotFound.format_in(Language::En, &[]),
+            "Not found: "
+        );
+        assert_eq!(Message::Keypad.format_in(Language::En, &[&1]), "Keypad");
+    }
+
+    #[test]
+    fn parse_language() {
+        assert_eq!(Language::from_locale("fr_FR.UTF-8"), Some(Language::Fr));
+        assert_eq!(Language::from_locale("DE"), Some(Language::De));
+        assert_eq!(Language::from_locale("C"), None);
+        assert_eq!(Language::De.to_string(), "de");
+        assert!("es".parse::<Language>().is_err());
+        assert_eq!(Language::default(), Language::En);
+    }
+}
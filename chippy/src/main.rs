@@ -1,12 +1,20 @@
-use chippy::emu::vm::Vm;
+use chippy::emu::{
+    frame::DEFAULT_CYCLES_PER_FRAME,
+    vm::{ProgramState, Vm},
+};
 
 fn main() {
     let bytes = std::fs::read("roms/pong.ch8").unwrap();
     let mut vm = Vm::new();
     vm.load(bytes);
 
-    for _ in 0..10000 {
-        vm.cycle();
+    // Run in frames so that the timers count down
+    for _ in 0..1000 {
+        if let ProgramState::Halted(_) | ProgramState::Error(_) =
+            vm.run_frame(DEFAULT_CYCLES_PER_FRAME).state
+        {
+            break;
+        }
     }
 
     println!("{}", vm.gpu);
@@ -0,0 +1,340 @@
+//! Experimental input mirroring between two instances over TCP.
+//!
+//! The host sends the keypad state of every frame, tagged with the frame number, to a follower
+//! that applies it before running the same frame. The vm is deterministic so both instances show
+//! the same session as long as they start from the same rom and run the same frames.
+//!
+//! `VoteServer` lets any number of clients share a single keypad instead. Clients send one key
+//! per line as a hex digit, and at the end of every window of frames the key with the most votes
+//! is held down for the next window.
+
+use crate::emu::input::{Input, Key, KEY_LIST};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+/// Encoded size of an `InputFrame`
+pub const MESSAGE_SIZE: usize = 10;
+
+/// Keypad state of a frame, one bit per key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputFrame {
+    pub frame: u64,
+    pub keys: u16,
+}
+
+impl InputFrame {
+    pub fn capture(frame: u64, input: &Input) -> Self {
+        let keys = input
+            .keys
+            .iter()
+            .enumerate()
+            .filter(|(_, pressed)| **pressed)
+            .fold(0, |keys, (key, _)| keys | 1 << key);
+        Self { frame, keys }
+    }
+
+    /// Replace the keypad state of `input`
+    pub fn apply(&self, input: &mut Input) {
+        for (key, pressed) in input.keys.iter_mut().enumerate() {
+            *pressed = self.keys & (1 << key) != 0;
+        }
+    }
+
+    pub fn encode(&self) -> [u8; MESSAGE_SIZE] {
+        let mut bytes = [0; MESSAGE_SIZE];
+        bytes[..8].copy_from_slice(&self.frame.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.keys.to_be_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; MESSAGE_SIZE]) -> Self {
+        let mut frame = [0; 8];
+        frame.copy_from_slice(&bytes[..8]);
+        Self {
+            frame: u64::from_be_bytes(frame),
+            keys: u16::from_be_bytes([bytes[8], bytes[9]]),
+        }
+    }
+}
+
+/// Sending side of a session.
+pub struct Host {
+    stream: TcpStream,
+}
+
+impl Host {
+    /// Wait for a follower to connect on `addr`. The session should start right after so the
+    /// follower sees every frame.
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self { stream })
+    }
+
+    pub fn send(&mut self, frame: InputFrame) -> io::Result<()> {
+        self.stream.write_all(&frame.encode())
+    }
+}
+
+/// Receiving side of a session.
+pub struct Follower {
+    stream: TcpStream,
+}
+
+impl Follower {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream })
+    }
+
+    /// Wait for the input of the next frame, `None` once the host ended the session
+    pub fn recv(&mut self) -> io::Result<Option<InputFrame>> {
+        let mut bytes = [0; MESSAGE_SIZE];
+        match self.stream.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(InputFrame::decode(&bytes))),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Votes for every key of the keypad.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Votes {
+    counts: [usize; KEY_LIST.len()],
+}
+
+impl Votes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vote(&mut self, key: Key) {
+        self.counts[key as usize] += 1;
+    }
+
+    /// Key with the most votes, the lowest key wins ties. `None` without votes
+    pub fn winner(&self) -> Option<Key> {
+        let (key, count) = self
+            .counts
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, count)| **count)?;
+        match *count {
+            0 => None,
+            _ => Some(KEY_LIST[key]),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts = Default::default();
+    }
+}
+
+/// Client of a `VoteServer` with its partially received line
+struct Voter {
+    reader: BufReader<TcpStream>,
+    line: String,
+}
+
+/// Keypad driven by the majority vote of the connected clients.
+pub struct VoteServer {
+    listener: TcpListener,
+    voters: Vec<Voter>,
+    votes: Votes,
+    /// Frames in a voting window
+    window: usize,
+    frame: usize,
+    held: Option<Key>,
+}
+
+impl VoteServer {
+    pub fn bind(addr: impl ToSocketAddrs, window: usize) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            voters: Vec::new(),
+            votes: Votes::new(),
+            window: window.max(1),
+            frame: 0,
+            held: None,
+        })
+    }
+
+    pub fn voters(&self) -> usize {
+        self.voters.len()
+    }
+
+    /// Key held down for the current window
+    pub fn held(&self) -> Option<Key> {
+        self.held
+    }
+
+    /// Collect the pending votes and apply the held key to `input`, called once per frame.
+    /// Clients that disconnect or fail are dropped.
+    pub fn update(&mut self, input: &mut Input) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    self.voters.push(Voter {
+                        reader: BufReader::new(stream),
+                        line: String::new(),
+                    });
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let votes = &mut self.votes;
+        self.voters.retain_mut(|voter| loop {
+            match voter.reader.read_line(&mut voter.line) {
+                Ok(0) => return false,
+                Ok(_) if voter.line.ends_with('\n') => {
+                    let key = u8::from_str_radix(voter.line.trim(), 16).ok();
+                    if let Some(key) = key.and_then(|key| KEY_LIST.get(key as usize)) {
+                        votes.vote(*key);
+                    }
+                    voter.line.clear();
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(_) => return false,
+            }
+        });
+
+        self.frame += 1;
+        if self.frame == self.window {
+            self.frame = 0;
+            self.held = self.votes.winner();
+            self.votes.clear();
+        }
+
+        input.clear();
+        if let Some(key) = self.held {
+            input.key_down(key);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::{input::Key, vm::Vm};
+
+    #[test]
+    fn encode_frame() {
+        let mut input = Input::new();
+        input.key_down(Key::A);
+        input.key_down(Key::One);
+        let frame = InputFrame::capture(300, &input);
+        assert_eq!(frame.keys, 0b0000_0100_0000_0010);
+        assert_eq!(InputFrame::decode(&frame.encode()), frame);
+
+        let mut applied = Input::new();
+        applied.key_down(Key::F);
+        frame.apply(&mut applied);
+        assert_eq!(applied.keys, input.keys);
+    }
+
+    #[test]
+    fn majority_vote() {
+        let mut votes = Votes::new();
+        assert_eq!(votes.winner(), None);
+        votes.vote(Key::Five);
+        votes.vote(Key::Two);
+        assert_eq!(votes.winner(), Some(Key::Two));
+        votes.vote(Key::Five);
+        assert_eq!(votes.winner(), Some(Key::Five));
+        votes.clear();
+        assert_eq!(votes, Votes::new());
+    }
+
+    #[test]
+    fn vote_server() {
+        let mut server = VoteServer::bind("127.0.0.1:0", 1000).unwrap();
+        let addr = server.listener.local_addr().unwrap();
+        let mut clients: Vec<TcpStream> =
+            (0..3).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        clients[0].write_all(b"5\n").unwrap();
+        clients[1].write_all(b"5\nA").unwrap();
+        clients[2].write_all(b"a\n").unwrap();
+        clients.pop();
+
+        let mut input = Input::new();
+        let mut collect = |server: &mut VoteServer, votes: usize| {
+            while server.votes.counts.iter().sum::<usize>() < votes {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                server.update(&mut input).unwrap();
+            }
+            // End the window
+            server.frame = server.window - 1;
+            server.update(&mut input).unwrap();
+            input.is_pressed(0x5)
+        };
+
+        assert!(collect(&mut server, 3));
+        assert_eq!(server.held(), Some(Key::Five));
+        assert_eq!(server.voters(), 2);
+
+        // The end of a line split across writes still counts as a single vote
+        clients[1].write_all(b"\n").unwrap();
+        assert!(!collect(&mut server, 1));
+        assert_eq!(server.held(), Some(Key::A));
+    }
+
+    #[test]
+    fn lockstep() {
+        // Count the frames key 5 is held in v1
+        let rom = vec![
+            0x60, 0x05, // ld v0, 5
+            0xE0, 0xA1, // sknp v0
+            0x71, 0x01, // add v1, 1
+            0x12, 0x02, // jp 0x202
+        ];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let host_rom = rom.clone();
+        let host = std::thread::spawn(move || {
+            let mut host = Host::new(listener.accept().unwrap().0).unwrap();
+            let mut vm = Vm::new();
+            vm.load(host_rom);
+            for frame in 0..60 {
+                vm.input.clear();
+                if frame % 3 == 0 {
+                    vm.input.key_down(Key::Five);
+                }
+                host.send(InputFrame::capture(frame, &vm.input)).unwrap();
+                vm.cycle().unwrap();
+            }
+            vm.snapshot()
+        });
+
+        let mut follower = Follower::connect(addr).unwrap();
+        let mut vm = Vm::new();
+        vm.load(rom);
+        let mut frames = 0;
+        while let Some(frame) = follower.recv().unwrap() {
+            assert_eq!(frame.frame, frames);
+            frame.apply(&mut vm.input);
+            vm.cycle().unwrap();
+            frames += 1;
+        }
+
+        assert_eq!(frames, 60);
+        assert_eq!(vm.snapshot(), host.join().unwrap());
+    }
+}
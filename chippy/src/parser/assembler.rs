@@ -0,0 +1,600 @@
+//! Assembler for whole programs, with labels and data directives on top of the instructions
+//! understood by `from_asm`.
+//!
+//! ```text
+//! ; computed jump into a table of labels
+//!         ld v0, 2
+//!         jp v0, actions
+//! table actions: idle, walk, jump
+//! idle:   jp idle
+//! walk:   jp walk
+//! jump:   jp jump
+//! sprite: db 0b11110000, 0x90, 0x90, 0x90, 0xF0
+//! ```
+//!
+//! - `name:` defines a label at the current address, labels can be used wherever an address is
+//!   expected.
+//! - `db BYTE, ...` emits raw bytes.
+//! - `org ADDRESS` emits zero bytes up to ADDRESS, which can not be behind the current address.
+//! - `table NAME: LABEL, ...` emits an aligned jump table of `jp LABEL` entries used with
+//!   `jp v0, NAME` where v0 is twice the index of the entry.
+//! - `calltable NAME: LABEL, ...` emits a jump table preceded by a `jp v0` dispatcher so that
+//!   `call NAME` calls the entry selected by v0.
+//!
+//! `Syntax::Structured` adds the control flow constructs described in `flow`.
+
+use super::{
+    error::{LineError, ParseError, ParseResult},
+    flow::{Flow, Lowered, GENERATED_PREFIX},
+    imp::{parse_instr, parse_number},
+};
+use crate::emu::{instruction::Instruction, vm::MEMORY_START};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+/// Directives that emit data or tables instead of an instruction
+pub const DIRECTIVES: [&str; 4] = ["db", "org", "table", "calltable"];
+
+/// Registers and keywords that can not be used as label names
+pub(super) const RESERVED: [&str; 9] = ["i", "k", "dt", "st", "f", "b", "hf", "r", "[i]"];
+
+/// Language accepted by the assembler
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax {
+    /// Instructions, labels and directives
+    Raw,
+    /// Raw syntax plus structured control flow
+    Structured,
+}
+
+/// Assembled program and the information gathered while assembling it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assembly {
+    /// Program bytes, loaded at 0x200 unless assembled with `assemble_at`
+    pub bytes: Vec<u8>,
+    /// Address of every label
+    pub labels: BTreeMap<String, u16>,
+    /// Runs of bytes emitted by consecutive `db` directives
+    pub data: Vec<DataBlock>,
+    /// Zero bytes emitted by `org` and to align tables
+    pub padding: Vec<DataBlock>,
+    /// Source line of every instruction by address, starting from 0
+    pub lines: BTreeMap<u16, usize>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Run of data bytes in an assembled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataBlock {
+    pub address: u16,
+    pub len: usize,
+}
+
+/// Possible mistake found while assembling that does not stop the program from being built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// Line number, starting from 0
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TableKind {
+    Jump,
+    Call,
+}
+
+#[derive(Debug)]
+enum Item<'a> {
+    Instruction(Cow<'a, str>),
+    Data(Vec<u8>),
+    /// Number of zero bytes emitted by `org`
+    Padding(u16),
+    Table {
+        kind: TableKind,
+        name: String,
+        entries: Vec<String>,
+    },
+}
+
+impl Item<'_> {
+    /// Number of bytes emitted for the item at `address`
+    fn size(&self, address: u16) -> u16 {
+        match self {
+            Item::Instruction(_) => 2,
+            Item::Data(bytes) => bytes.len() as u16,
+            Item::Padding(len) => *len,
+            Item::Table { kind, entries, .. } => {
+                let stub = match kind {
+                    TableKind::Jump => 0,
+                    TableKind::Call => 2,
+                };
+                address % 2 + stub + 2 * entries.len() as u16
+            }
+        }
+    }
+}
+
+/// Bounds of a table, keyed by the address it is entered through
+struct TableInfo {
+    kind: TableKind,
+    name: String,
+    len: usize,
+}
+
+/// Assemble `program` into bytes, resolving labels and expanding directives
+pub fn assemble(program: &str) -> ParseResult<Assembly> {
+    assemble_with(program, Syntax::Raw)
+}
+
+/// Assemble `program` written with `syntax`
+pub fn assemble_with(program: &str, syntax: Syntax) -> ParseResult<Assembly> {
+    assemble_at(program, syntax, MEMORY_START as u16)
+}
+
+/// Assemble `program` to be loaded at `origin` instead of 0x200, labels resolve to addresses
+/// from `origin`
+pub fn assemble_at(program: &str, syntax: Syntax, origin: u16) -> ParseResult<Assembly> {
+    let mut flow = Flow::default();
+    let mut items = Vec::new();
+    let mut labels = BTreeMap::new();
+    let mut tables = HashMap::new();
+    let mut address = origin;
+
+    for (ln, line) in program.split('\n').enumerate() {
+        let err = |err| ParseError::Line(ln, err);
+        let mut line = line.split(';').next().unwrap_or("").trim();
+
+        if let Some(item) = parse_table(line).map_err(err)? {
+            if let Item::Table {
+                kind,
+                name,
+                entries,
+            } = &item
+            {
+                let start = address.wrapping_add(address % 2);
+                define(&mut labels, name, start).map_err(err)?;
+                let info = TableInfo {
+                    kind: *kind,
+                    name: name.clone(),
+                    len: entries.len(),
+                };
+                tables.insert(start, info);
+            }
+            address = address.wrapping_add(item.size(address));
+            items.push((ln, item));
+            continue;
+        }
+
+        if let Some((label, rest)) = split_label(line) {
+            define(&mut labels, label, address).map_err(err)?;
+            line = rest;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        if syntax == Syntax::Structured {
+            if let Some(lowered) = flow.lower(ln, line).map_err(err)? {
+                for part in lowered {
+                    match part {
+                        Lowered::Label(label) => {
+                            labels.insert(label, address);
+                        }
+                        Lowered::Instruction(instruction) => {
+                            items.push((ln, Item::Instruction(Cow::Owned(instruction))));
+                            address = address.wrapping_add(2);
+                        }
+                    }
+                }
+                continue;
+            }
+        }
+
+        let item = match line.split_once(char::is_whitespace) {
+            Some(("db", bytes)) => Item::Data(parse_bytes(bytes).map_err(err)?),
+            Some(("org", target)) => Item::Padding(parse_org(target, address).map_err(err)?),
+            _ => Item::Instruction(Cow::Borrowed(line)),
+        };
+        address = address.wrapping_add(item.size(address));
+        items.push((ln, item));
+    }
+    if let Some(ln) = flow.unclosed() {
+        return Err(ParseError::Line(ln, LineError::UnclosedBlock));
+    }
+
+    let mut bytes = Vec::new();
+    let mut data_blocks: Vec<DataBlock> = Vec::new();
+    let mut padding = Vec::new();
+    let mut lines = BTreeMap::new();
+    let mut warnings = Vec::new();
+    let mut previous = None;
+    for (ln, item) in items {
+        let err = |err| ParseError::Line(ln, err);
+        match item {
+            Item::Instruction(line) => {
+                let instruction = parse_instr(&resolve(&line, &labels)).map_err(err)?;
+                if let Some(message) = check_table_index(&instruction, previous.as_ref(), &tables) {
+                    warnings.push(Warning { line: ln, message });
+                }
+                lines.insert((origin as usize + bytes.len()) as u16, ln);
+                bytes.extend_from_slice(&instruction.to_u16().to_be_bytes());
+                previous = Some(instruction);
+            }
+            Item::Data(data) => {
+                let address = (origin as usize + bytes.len()) as u16;
+                match data_blocks.last_mut() {
+                    Some(block) if block.address as usize + block.len == address as usize => {
+                        block.len += data.len()
+                    }
+                    _ => data_blocks.push(DataBlock {
+                        address,
+                        len: data.len(),
+                    }),
+                }
+                bytes.extend(data);
+                previous = None;
+            }
+            Item::Padding(len) => {
+                if len > 0 {
+                    padding.push(DataBlock {
+                        address: (origin as usize + bytes.len()) as u16,
+                        len: len as usize,
+                    });
+                }
+                bytes.resize(bytes.len() + len as usize, 0);
+                previous = None;
+            }
+            Item::Table { kind, entries, .. } => {
+                if (origin as usize + bytes.len()) % 2 == 1 {
+                    padding.push(DataBlock {
+                        address: (origin as usize + bytes.len()) as u16,
+                        len: 1,
+                    });
+                    bytes.push(0);
+                }
+                let start = (origin as usize + bytes.len()) as u16;
+                if kind == TableKind::Call {
+                    let dispatch = Instruction::JumpNPlusPC(start + 2);
+                    bytes.extend_from_slice(&dispatch.to_u16().to_be_bytes());
+                }
+                for entry in entries.iter() {
+                    let target = labels
+                        .get(entry)
+                        .ok_or_else(|| err(LineError::UnknownLabel(entry.clone())))?;
+                    bytes.extend_from_slice(&Instruction::Jump(*target).to_u16().to_be_bytes());
+                }
+                if entries.len() > 128 {
+                    warnings.push(Warning {
+                        line: ln,
+                        message: format!(
+                            "Table has {} entries but v0 can only reach the first 128",
+                            entries.len()
+                        ),
+                    });
+                }
+                previous = None;
+            }
+        }
+    }
+
+    labels.retain(|label, _| !label.starts_with(GENERATED_PREFIX));
+    Ok(Assembly {
+        bytes,
+        labels,
+        data: data_blocks,
+        padding,
+        lines,
+        warnings,
+    })
+}
+
+/// Parse a `table` or `calltable` directive
+fn parse_table(line: &str) -> Result<Option<Item<'_>>, LineError> {
+    let (kind, rest) = match line.split_once(char::is_whitespace) {
+        Some(("table", rest)) => (TableKind::Jump, rest),
+        Some(("calltable", rest)) => (TableKind::Call, rest),
+        _ => return Ok(None),
+    };
+    let (name, entries) = rest
+        .split_once(':')
+        .ok_or_else(|| LineError::InvalidInstruction(line.to_string()))?;
+    let entries = entries
+        .split(',')
+        .map(|entry| entry.trim().to_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+
+    Ok(Some(Item::Table {
+        kind,
+        name: name.trim().to_string(),
+        entries,
+    }))
+}
+
+/// Number of bytes emitted by `org target` at `address`
+fn parse_org(target: &str, address: u16) -> Result<u16, LineError> {
+    let target: u16 = parse_number(target.trim())?;
+    target
+        .checked_sub(address)
+        .ok_or(LineError::OrgBehind(target, address))
+}
+
+/// Split a leading `label:` from the rest of the line
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+    match label.contains(char::is_whitespace) {
+        true => None,
+        false => Some((label, rest.trim())),
+    }
+}
+
+fn define(labels: &mut BTreeMap<String, u16>, label: &str, address: u16) -> Result<(), LineError> {
+    let label = label.to_lowercase();
+    let valid = matches!(label.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && !RESERVED.contains(&label.as_str())
+        && !(label.len() == 2 && label.starts_with('v'));
+    if !valid {
+        return Err(LineError::InvalidLabel(label));
+    }
+    match labels.insert(label.clone(), address) {
+        Some(_) => Err(LineError::DuplicateLabel(label)),
+        None => Ok(()),
+    }
+}
+
+fn parse_bytes(src: &str) -> Result<Vec<u8>, LineError> {
+    src.split(',')
+        .map(|byte| {
+            let byte = byte.trim().to_lowercase();
+            match byte.strip_prefix("0b") {
+                Some(bits) => u8::from_str_radix(bits, 2).map_err(LineError::from),
+                None => parse_number(&byte),
+            }
+        })
+        .collect()
+}
+
+/// Replace operands that name a label with the address of the label
+fn resolve(line: &str, labels: &BTreeMap<String, u16>) -> String {
+    let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+        Some(parts) => parts,
+        None => return line.to_string(),
+    };
+    let operands: Vec<String> = operands
+        .split(',')
+        .map(|operand| {
+            let operand = operand.trim();
+            match labels.get(&operand.to_lowercase()) {
+                Some(address) => format!("0x{:03X}", address),
+                None => operand.to_string(),
+            }
+        })
+        .collect();
+    format!("{} {}", mnemonic, operands.join(", "))
+}
+
+/// Check a constant table index loaded into v0 right before entering a table
+fn check_table_index(
+    instruction: &Instruction,
+    previous: Option<&Instruction>,
+    tables: &HashMap<u16, TableInfo>,
+) -> Option<String> {
+    let table = match instruction {
+        Instruction::JumpNPlusPC(address) => tables
+            .get(address)
+            .filter(|table| table.kind == TableKind::Jump),
+        Instruction::Call(address) => tables
+            .get(address)
+            .filter(|table| table.kind == TableKind::Call),
+        _ => None,
+    }?;
+
+    let index = match previous {
+        Some(Instruction::SetReg(pair)) if pair.register == 0 => pair.value as usize,
+        _ => return None,
+    };
+    if index % 2 == 1 {
+        Some(format!(
+            "Index {} into table {} is odd, entries are 2 bytes long",
+            index, table.name
+        ))
+    } else if index / 2 >= table.len {
+        Some(format!(
+            "Index {} is past the end of table {} with {} entries",
+            index, table.name, table.len
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn labels_and_data() {
+        let assembly = assemble(
+            "start:  ld i, sprite ; point at the sprite
+                    call draw
+                    jp start
+            draw:   drw v0, v1, 5
+                    ret
+            sprite: db 0b11110000, 0x90, 144, 0x90, 0xF0",
+        )
+        .unwrap();
+
+        assert_eq!(assembly.labels["start"], 0x200);
+        assert_eq!(assembly.labels["draw"], 0x206);
+        assert_eq!(assembly.labels["sprite"], 0x20A);
+        assert_eq!(
+            assembly.bytes,
+            vec![
+                0xA2, 0x0A, 0x22, 0x06, 0x12, 0x00, 0xD0, 0x15, 0x00, 0xEE, 0xF0, 0x90, 0x90, 0x90,
+                0xF0
+            ]
+        );
+        assert!(assembly.warnings.is_empty());
+        assert_eq!(
+            assembly.data,
+            vec![DataBlock {
+                address: 0x20A,
+                len: 5
+            }]
+        );
+        let lines: Vec<(u16, usize)> = assembly.lines.into_iter().collect();
+        assert_eq!(
+            lines,
+            vec![(0x200, 0), (0x202, 1), (0x204, 2), (0x206, 3), (0x208, 4)]
+        );
+    }
+
+    #[test]
+    fn jump_and_call_tables() {
+        let assembly = assemble(
+            "one:   db 0x01
+             table jt: left, right
+             calltable ct: right, left
+             left:  ret
+             right: ret",
+        )
+        .unwrap();
+
+        // The tables are aligned after the single data byte
+        assert_eq!(assembly.labels["jt"], 0x202);
+        assert_eq!(assembly.labels["ct"], 0x206);
+        assert_eq!(
+            assembly.bytes,
+            vec![
+                0x01, 0x00, // db, padding
+                0x12, 0x0C, 0x12, 0x0E, // jt
+                0xB2, 0x08, 0x12, 0x0E, 0x12, 0x0C, // ct
+                0x00, 0xEE, 0x00, 0xEE,
+            ]
+        );
+        assert_eq!(
+            assembly.padding,
+            vec![DataBlock {
+                address: 0x201,
+                len: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn org_padding() {
+        let assembly = assemble(
+            "       jp main
+                    org 0x206
+            main:   jp main
+                    org 0x208",
+        )
+        .unwrap();
+
+        assert_eq!(assembly.labels["main"], 0x206);
+        assert_eq!(assembly.bytes, vec![0x12, 0x06, 0, 0, 0, 0, 0x12, 0x06]);
+        assert_eq!(
+            assembly.padding,
+            vec![DataBlock {
+                address: 0x202,
+                len: 4
+            }]
+        );
+
+        assert!(matches!(
+            assemble("cls\ncls\norg 0x202"),
+            Err(ParseError::Line(2, LineError::OrgBehind(0x202, 0x204)))
+        ));
+    }
+
+    #[test]
+    fn table_index_lint() {
+        let assembly = assemble(
+            "ld v0, 2
+             jp v0, jt
+             ld v0, 4
+             jp v0, jt
+             ld v0, 1
+             call ct
+             table jt: left, right
+             calltable ct: left
+             left: ret
+             right: ret",
+        )
+        .unwrap();
+
+        let lines: Vec<usize> = assembly.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, vec![3, 5]);
+    }
+
+    #[test]
+    fn structured_control_flow() {
+        let program = "ld v0, 0
+            loop
+                add v0, 1
+                while v0 != 5
+            again
+            if v0 == 5 then ld v1, 1
+            if v0 == 4 begin
+                ld v2, 1
+            else
+                ld v2, 2
+            end
+            done: jp done";
+        assert!(assemble(program).is_err());
+        let assembly = assemble_with(program, Syntax::Structured).unwrap();
+        assert_eq!(assembly.labels.keys().collect::<Vec<_>>(), vec!["done"]);
+
+        let mut vm = Vm::new();
+        vm.load(assembly.bytes);
+        for _ in 0..100 {
+            vm.cycle().unwrap();
+        }
+        let state = vm.snapshot();
+        assert_eq!(state.program_counter, assembly.labels["done"]);
+        assert_eq!(&state.registers[0..3], &[5, 1, 2]);
+    }
+
+    #[test]
+    fn unbalanced_blocks() {
+        assert!(matches!(
+            assemble_with("loop\nif v0 == 1 begin\nagain", Syntax::Structured),
+            Err(ParseError::Line(2, LineError::UnexpectedKeyword(_)))
+        ));
+        assert!(matches!(
+            assemble_with("cls\nloop", Syntax::Structured),
+            Err(ParseError::Line(1, LineError::UnclosedBlock))
+        ));
+        assert!(matches!(
+            assemble_with("while v0 == 1", Syntax::Structured),
+            Err(ParseError::Line(0, LineError::UnexpectedKeyword(_)))
+        ));
+        assert!(matches!(
+            assemble_with("if v0 < 1 then cls", Syntax::Structured),
+            Err(ParseError::Line(0, LineError::InvalidCondition(_)))
+        ));
+    }
+
+    #[test]
+    fn label_errors() {
+        assert!(matches!(
+            assemble("a: cls\na: cls"),
+            Err(ParseError::Line(1, LineError::DuplicateLabel(_)))
+        ));
+        assert!(matches!(
+            assemble("v1: cls"),
+            Err(ParseError::Line(0, LineError::InvalidLabel(_)))
+        ));
+        assert!(matches!(
+            assemble("table jt: missing"),
+            Err(ParseError::Line(0, LineError::UnknownLabel(_)))
+        ));
+    }
+}
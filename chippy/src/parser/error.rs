@@ -21,6 +21,27 @@ pub enum LineError {
     #[error("Wrong number of arguments: expected {0}, got {1}")]
     WrongNumberOfArguments(usize, usize),
 
+    #[error("Invalid label: {0}")]
+    InvalidLabel(String),
+
+    #[error("Duplicate label: {0}")]
+    DuplicateLabel(String),
+
+    #[error("Unknown label: {0}")]
+    UnknownLabel(String),
+
+    #[error("Invalid condition: {0}")]
+    InvalidCondition(String),
+
+    #[error("Unexpected {0} outside of a matching block")]
+    UnexpectedKeyword(String),
+
+    #[error("org 0x{0:03X} is behind the current address 0x{1:03X}")]
+    OrgBehind(u16, u16),
+
+    #[error("Block is never closed")]
+    UnclosedBlock,
+
     #[error("Unknown error")]
     Unknown,
 }
@@ -0,0 +1,182 @@
+//! Structured control flow lowered to skips and jumps, enabled with `Syntax::Structured`.
+//!
+//! ```text
+//! loop
+//!     if v0 == 10 then ld v0, 0
+//!     while v1 != 0
+//!     if v2 key begin
+//!         add v3, 1
+//!     else
+//!         ld v3, 0
+//!     end
+//! again
+//! ```
+//!
+//! Conditions compare a register with a number or register using `==` and `!=`, or check a key
+//! with `vx key` (pressed) and `vx -key` (not pressed). `while` leaves the innermost loop when
+//! its condition is false.
+
+use super::error::LineError;
+
+/// Prefix of generated labels, user labels can not start with it
+pub(super) const GENERATED_PREFIX: char = '@';
+
+/// Keywords of the structured control flow constructs
+pub(super) const KEYWORDS: [&str; 8] = [
+    "loop", "again", "while", "if", "then", "begin", "else", "end",
+];
+
+/// Output of lowering a line
+#[derive(Debug, PartialEq)]
+pub(super) enum Lowered {
+    Instruction(String),
+    Label(String),
+}
+
+#[derive(Debug)]
+enum Block {
+    Loop {
+        start: String,
+        end: String,
+    },
+    If {
+        otherwise: String,
+        end: String,
+        has_else: bool,
+    },
+}
+
+/// Open blocks while lowering a program
+#[derive(Debug, Default)]
+pub(super) struct Flow {
+    /// Line the block was opened on and the block
+    blocks: Vec<(usize, Block)>,
+    next_label: usize,
+}
+
+impl Flow {
+    /// Lower a structured line, returns `None` if it is not a control flow construct
+    pub fn lower(&mut self, ln: usize, line: &str) -> Result<Option<Vec<Lowered>>, LineError> {
+        let (keyword, rest) = line
+            .split_once(char::is_whitespace)
+            .map(|(keyword, rest)| (keyword, rest.trim()))
+            .unwrap_or((line, ""));
+
+        let lowered = match keyword {
+            "loop" => {
+                let start = self.label("loop");
+                let end = self.label("loop_end");
+                self.blocks.push((
+                    ln,
+                    Block::Loop {
+                        start: start.clone(),
+                        end,
+                    },
+                ));
+                vec![Lowered::Label(start)]
+            }
+            "again" => match self.blocks.pop() {
+                Some((_, Block::Loop { start, end })) => vec![
+                    Lowered::Instruction(format!("jp {}", start)),
+                    Lowered::Label(end),
+                ],
+                _ => return Err(LineError::UnexpectedKeyword(keyword.to_string())),
+            },
+            "while" => {
+                let end = self
+                    .blocks
+                    .iter()
+                    .rev()
+                    .find_map(|(_, block)| match block {
+                        Block::Loop { end, .. } => Some(end.clone()),
+                        _ => None,
+                    })
+                    .ok_or_else(|| LineError::UnexpectedKeyword(keyword.to_string()))?;
+                vec![
+                    Lowered::Instruction(skip(rest, true)?),
+                    Lowered::Instruction(format!("jp {}", end)),
+                ]
+            }
+            "if" => {
+                if let Some((condition, statement)) = rest.split_once(" then ") {
+                    vec![
+                        Lowered::Instruction(skip(condition, false)?),
+                        Lowered::Instruction(statement.trim().to_string()),
+                    ]
+                } else if let Some(condition) = rest.strip_suffix(" begin") {
+                    let otherwise = self.label("else");
+                    let end = self.label("end");
+                    let lowered = vec![
+                        Lowered::Instruction(skip(condition, true)?),
+                        Lowered::Instruction(format!("jp {}", otherwise)),
+                    ];
+                    let block = Block::If {
+                        otherwise,
+                        end,
+                        has_else: false,
+                    };
+                    self.blocks.push((ln, block));
+                    lowered
+                } else {
+                    return Err(LineError::InvalidInstruction(line.to_string()));
+                }
+            }
+            "else" => match self.blocks.last_mut() {
+                Some((
+                    _,
+                    Block::If {
+                        otherwise,
+                        end,
+                        has_else: has_else @ false,
+                    },
+                )) => {
+                    *has_else = true;
+                    vec![
+                        Lowered::Instruction(format!("jp {}", end)),
+                        Lowered::Label(otherwise.clone()),
+                    ]
+                }
+                _ => return Err(LineError::UnexpectedKeyword(keyword.to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some((
+                    _,
+                    Block::If {
+                        otherwise,
+                        end,
+                        has_else,
+                    },
+                )) => match has_else {
+                    true => vec![Lowered::Label(end)],
+                    false => vec![Lowered::Label(otherwise), Lowered::Label(end)],
+                },
+                _ => return Err(LineError::UnexpectedKeyword(keyword.to_string())),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(lowered))
+    }
+
+    /// Line of the innermost block that was never closed
+    pub fn unclosed(&self) -> Option<usize> {
+        self.blocks.last().map(|(ln, _)| *ln)
+    }
+
+    fn label(&mut self, kind: &str) -> String {
+        self.next_label += 1;
+        format!("{}{}{}", GENERATED_PREFIX, kind, self.next_label)
+    }
+}
+
+/// Instruction that skips the next one if `condition` evaluates to `when`
+fn skip(condition: &str, when: bool) -> Result<String, LineError> {
+    let tokens: Vec<&str> = condition.split_whitespace().collect();
+    let (mnemonic, operands) = match tokens.as_slice() {
+        [x, "==", y] => (if when { "se" } else { "sne" }, format!("{}, {}", x, y)),
+        [x, "!=", y] => (if when { "sne" } else { "se" }, format!("{}, {}", x, y)),
+        [x, "key"] => (if when { "skp" } else { "sknp" }, x.to_string()),
+        [x, "-key"] => (if when { "sknp" } else { "skp" }, x.to_string()),
+        _ => return Err(LineError::InvalidCondition(condition.to_string())),
+    };
+    Ok(format!("{} {}", mnemonic, operands))
+}
@@ -2,7 +2,7 @@ use super::error::{LineError, ParseError, ParseResult};
 use crate::emu::instruction::{Instruction, RegisterValuePair, TargetSourcePair};
 use std::str::FromStr;
 
-trait FromStrRadix: Sized {
+pub(super) trait FromStrRadix: Sized {
     fn from_str_radix(src: &str, radix: u32) -> Result<Self, LineError>;
 }
 
@@ -18,6 +18,16 @@ macro_rules! impl_str_radix {
 impl_str_radix!(u8);
 impl_str_radix!(u16);
 
+/// Instruction mnemonics understood by `parse_instr`
+pub const MNEMONICS: [&str; 27] = [
+    "add", "and", "call", "cls", "drw", "exit", "high", "jp", "ld", "low", "or", "raw", "ret",
+    "rnd", "scd", "scl", "scr", "se", "shl", "shr", "skp", "sknp", "sne", "sub", "subn", "sys",
+    "xor",
+];
+
+/// Registers with a name other than `vX`
+pub const SPECIAL_REGISTERS: [&str; 8] = ["i", "k", "dt", "st", "f", "b", "hf", "r"];
+
 fn ts(target: u8, source: u8) -> TargetSourcePair {
     TargetSourcePair { target, source }
 }
@@ -43,7 +53,7 @@ pub fn parse(program: &str) -> ParseResult<Vec<Instruction>> {
         .collect::<ParseResult<Vec<Instruction>>>()
 }
 
-fn parse_instr(line: &str) -> Result<Instruction, LineError> {
+pub(super) fn parse_instr(line: &str) -> Result<Instruction, LineError> {
     use Instruction::*;
     let lo = line.to_lowercase();
 
@@ -60,6 +70,12 @@ fn parse_instr(line: &str) -> Result<Instruction, LineError> {
         "sys" => Ok(CallMachineCode(parse_addr(tokens[0])?)),
         "cls" => Ok(ClearDisplay),
         "ret" => Ok(Return),
+        "exit" => Ok(Exit),
+        "scd" => Ok(ScrollDown(parse_number(tokens[0])?)),
+        "scr" => Ok(ScrollRight),
+        "scl" => Ok(ScrollLeft),
+        "low" => Ok(LowRes),
+        "high" => Ok(HighRes),
         "call" => Ok(Call(parse_addr(tokens[0])?)),
         "raw" => Ok(Invalid(parse_addr(tokens[0])?)),
         "skp" => Ok(SkipIfKeyPressed(parse_register(tokens[0])?)),
@@ -152,9 +168,12 @@ fn parse_instr(line: &str) -> Result<Instruction, LineError> {
             "dt" => Ok(SetDTAsX(parse_register(tokens[1])?)),
             "st" => Ok(SetSTAsX(parse_register(tokens[1])?)),
             "f" => Ok(SetIToFontSprite(parse_register(tokens[1])?)),
+            "hf" => Ok(SetIToBigFontSprite(parse_register(tokens[1])?)),
+            "r" => Ok(StoreFlags(parse_register(tokens[1])?)),
             "i" => Ok(SetI(parse_addr(tokens[1])?)),
             _ => match tokens[1] {
                 "k" => Ok(WaitInputStoreIn(parse_register(tokens[0])?)),
+                "r" => Ok(LoadFlags(parse_register(tokens[0])?)),
                 "dt" => Ok(SetXAsDT(parse_register(tokens[0])?)),
                 "[i]" => Ok(LoadRegisters(parse_register(tokens[0])?)),
                 _ => match tokens[1].chars().next() {
@@ -184,7 +203,7 @@ fn parse_instr(line: &str) -> Result<Instruction, LineError> {
     }
 }
 
-fn parse_number<T>(number: &str) -> Result<T, LineError>
+pub(super) fn parse_number<T>(number: &str) -> Result<T, LineError>
 where
     T: FromStrRadix + FromStr<Err = std::num::ParseIntError>,
 {
@@ -0,0 +1,176 @@
+//! Where an assembled program lays out its code, data and padding in memory and the free space
+//! left after it, rendered by `chippy map`.
+
+use super::{assembler::Assembly, report::PROGRAM_SPACE};
+use crate::emu::vm::MEMORY_START;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Instructions and jump tables
+    Code,
+    /// Bytes of `db` directives
+    Data,
+    /// Zero bytes of `org` and table alignment
+    Padding,
+    /// Memory after the end of the program
+    Free,
+}
+
+impl RegionKind {
+    pub const VARIANTS: &'static [&'static str] = &["code", "data", "padding", "free"];
+
+    /// Character drawing the kind in `MemoryMap::bar`
+    pub fn symbol(&self) -> char {
+        match self {
+            RegionKind::Code => '#',
+            RegionKind::Data => '=',
+            RegionKind::Padding => '.',
+            RegionKind::Free => ' ',
+        }
+    }
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(RegionKind::VARIANTS[*self as usize])
+    }
+}
+
+/// Run of bytes of the same kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub address: u16,
+    pub len: usize,
+}
+
+/// Kind of every byte of the program space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryMap {
+    /// Regions ordered by address, from 0x200 to the end of memory or of the program
+    pub regions: Vec<Region>,
+    kinds: Vec<RegionKind>,
+}
+
+impl MemoryMap {
+    pub fn new(assembly: &Assembly) -> Self {
+        let mut kinds = vec![RegionKind::Code; assembly.bytes.len()];
+        let blocks = assembly
+            .data
+            .iter()
+            .map(|block| (block, RegionKind::Data))
+            .chain(
+                assembly
+                    .padding
+                    .iter()
+                    .map(|block| (block, RegionKind::Padding)),
+            );
+        for (block, kind) in blocks {
+            let start = block.address as usize - MEMORY_START;
+            kinds[start..start + block.len].fill(kind);
+        }
+        if kinds.len() < PROGRAM_SPACE {
+            kinds.resize(PROGRAM_SPACE, RegionKind::Free);
+        }
+
+        let mut regions: Vec<Region> = Vec::new();
+        for (offset, kind) in kinds.iter().enumerate() {
+            match regions.last_mut() {
+                Some(region) if region.kind == *kind => region.len += 1,
+                _ => regions.push(Region {
+                    kind: *kind,
+                    address: (MEMORY_START + offset) as u16,
+                    len: 1,
+                }),
+            }
+        }
+        Self { regions, kinds }
+    }
+
+    /// Bytes of the regions of `kind`
+    pub fn total(&self, kind: RegionKind) -> usize {
+        self.kinds.iter().filter(|byte| **byte == kind).count()
+    }
+
+    /// Draw `len` bytes from `address` as `width` cells, each showing the kind of most of its
+    /// bytes. Bytes outside of the map count as free.
+    pub fn bar(&self, address: u16, len: usize, width: usize) -> String {
+        let start = (address as usize).saturating_sub(MEMORY_START);
+        (0..width)
+            .map(|cell| {
+                let mut counts = [0; 4];
+                for offset in start + cell * len / width..start + (cell + 1) * len / width {
+                    let kind = self.kinds.get(offset).unwrap_or(&RegionKind::Free);
+                    counts[*kind as usize] += 1;
+                }
+                let (kind, _) = [
+                    RegionKind::Code,
+                    RegionKind::Data,
+                    RegionKind::Padding,
+                    RegionKind::Free,
+                ]
+                .iter()
+                .zip(counts.iter())
+                .rev()
+                .max_by_key(|(_, count)| **count)
+                .unwrap();
+                kind.symbol()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in self.regions.iter() {
+            writeln!(
+                f,
+                "{:03X}-{:03X}  {:>5}  {}",
+                region.address,
+                region.address as usize + region.len - 1,
+                region.len,
+                region.kind
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    #[test]
+    fn regions() {
+        let assembly = parser::assemble(
+            "main:   jp main
+                    db 1, 2
+                    org 0x208
+                    cls",
+        )
+        .unwrap();
+        let map = MemoryMap::new(&assembly);
+
+        let regions: Vec<(RegionKind, u16, usize)> = map
+            .regions
+            .iter()
+            .map(|region| (region.kind, region.address, region.len))
+            .collect();
+        assert_eq!(
+            regions,
+            vec![
+                (RegionKind::Code, 0x200, 2),
+                (RegionKind::Data, 0x202, 2),
+                (RegionKind::Padding, 0x204, 4),
+                (RegionKind::Code, 0x208, 2),
+                (RegionKind::Free, 0x20A, PROGRAM_SPACE - 10),
+            ]
+        );
+        assert_eq!(map.total(RegionKind::Padding), 4);
+        assert_eq!(map.bar(0x200, 12, 6), "#=..# ");
+        assert_eq!(map.bar(0x200, 16, 4), "#.# ");
+        assert!(map.to_string().starts_with("200-201      2  code\n"));
+    }
+}
@@ -1,13 +1,24 @@
 use crate::emu::{instruction::Instruction, iter::ByteCodeIter};
 use crate::parser::error::ParseResult;
 
+pub mod assembler;
 pub mod error;
+mod flow;
 pub mod imp;
+pub mod map;
+pub mod report;
+pub mod symbols;
+pub mod syntax;
 
 pub fn from_asm(program: &str) -> ParseResult<Vec<Instruction>> {
     imp::parse(program)
 }
 
+/// Assemble a program with labels and directives, see `assembler`
+pub fn assemble(program: &str) -> ParseResult<assembler::Assembly> {
+    assembler::assemble(program)
+}
+
 pub fn from_bytecode(bytecode: &[u8]) -> ParseResult<Vec<Instruction>> {
     Ok(ByteCodeIter::new(bytecode)
         .map(|code| Instruction::parse(code))
@@ -21,6 +32,27 @@ pub fn to_bytecode(instructions: &[Instruction]) -> ParseResult<Vec<u8>> {
         .collect())
 }
 
+/// Disassemble a rom so that `assemble` gives back the exact same bytes. Opcodes whose mnemonic
+/// does not assemble to the same opcode are written as `raw` and a trailing odd byte as `db`.
+pub fn disassemble_exact(bytecode: &[u8]) -> String {
+    let words = bytecode.chunks_exact(2);
+    let trailing = words.remainder().first();
+    let mut lines: Vec<String> = words
+        .map(|word| {
+            let opcode = u16::from_be_bytes([word[0], word[1]]);
+            let asm = Instruction::parse(opcode).to_asm();
+            match imp::parse_instr(&asm) {
+                Ok(instruction) if instruction.to_u16() == opcode => asm,
+                _ => Instruction::Invalid(opcode).to_asm(),
+            }
+        })
+        .collect();
+    if let Some(byte) = trailing {
+        lines.push(format!("db 0x{:02X}", byte));
+    }
+    lines.join("\n")
+}
+
 pub fn to_asm(instructions: &[Instruction]) -> ParseResult<String> {
     let lines: Vec<String> = instructions
         .iter()
@@ -30,6 +62,35 @@ pub fn to_asm(instructions: &[Instruction]) -> ParseResult<String> {
     Ok(format!("{}", lines.join("\n")))
 }
 
+/// Bytes of a live patch written at `origin`: hex bytes such as `A20A 12 00`, or assembly
+/// assembled at `origin`
+pub fn patch_bytes(text: &str, origin: u16) -> ParseResult<Vec<u8>> {
+    match parse_hex(text) {
+        Some(bytes) => Ok(bytes),
+        None => assembler::assemble_at(text, assembler::Syntax::Raw, origin)
+            .map(|assembly| assembly.bytes),
+    }
+}
+
+/// Bytes of whitespace separated words of hex digit pairs, `None` if the text is not hex or
+/// starts with a directive such as `db`
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let first = text.split_whitespace().next()?;
+    if assembler::DIRECTIVES.contains(&first.to_lowercase().as_str()) {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    for word in text.split_whitespace() {
+        if word.len() % 2 == 1 || !word.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        for pair in (0..word.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&word[pair..pair + 2], 16).ok()?);
+        }
+    }
+    Some(bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::emu::instruction::{RegisterValuePair, TargetSourcePair};
@@ -138,6 +199,31 @@ raw 0xF169"#,
         )
     }
 
+    #[test]
+    fn disassemble_exact_round_trip() {
+        // xorshift so the test does not need a random number crate
+        let mut seed = 0x2545F491u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        for _ in 0..500 {
+            let len = next() as usize % 256;
+            let rom: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            let asm = disassemble_exact(&rom);
+            assert_eq!(assemble(&asm).unwrap().bytes, rom, "{}", asm);
+        }
+
+        let every_opcode: Vec<u8> = (0..=u16::MAX).flat_map(|op| op.to_be_bytes()).collect();
+        for rom in every_opcode.chunks(0xE00) {
+            let asm = disassemble_exact(rom);
+            assert_eq!(assemble(&asm).unwrap().bytes, rom);
+        }
+    }
+
     #[test]
     fn from_bytecode_to_instructions() {
         let result = from_bytecode(&get_program()).unwrap();
@@ -169,4 +255,18 @@ raw 0xF169"#,
         let iter = result.split('\n').zip(actual.split('\n'));
         iter.for_each(|(r, a)| assert_eq!(*r, *a));
     }
+
+    #[test]
+    fn patch() {
+        assert_eq!(
+            patch_bytes("A20A 12\n00", 0x300).unwrap(),
+            vec![0xA2, 0x0A, 0x12, 0x00]
+        );
+        assert_eq!(patch_bytes("db 12, 0x34", 0x300).unwrap(), vec![0x0C, 0x34]);
+        assert_eq!(
+            patch_bytes("loop: add v0, 1\njp loop", 0x300).unwrap(),
+            vec![0x70, 0x01, 0x13, 0x00]
+        );
+        assert!(patch_bytes("A2 0", 0x300).is_err());
+    }
 }
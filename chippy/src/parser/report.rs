@@ -0,0 +1,162 @@
+//! Summary of where the space in an assembled program goes.
+
+use super::assembler::{Assembly, DataBlock};
+use crate::emu::vm::{MEMORY_SIZE, MEMORY_START};
+use std::fmt;
+
+/// Number of data blocks listed in the report
+const LARGEST_DATA_BLOCKS: usize = 5;
+
+/// Bytes available to a program loaded at 0x200
+pub const PROGRAM_SPACE: usize = MEMORY_SIZE - MEMORY_START;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelSize {
+    pub name: String,
+    pub address: u16,
+    /// Bytes from the label to the next label or the end of the program
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildReport {
+    /// Size of the program in bytes
+    pub size: usize,
+    /// Bytes left before the end of memory, negative if the program does not fit
+    pub remaining: isize,
+    /// Largest data blocks first
+    pub largest_data: Vec<DataBlock>,
+    /// Labels ordered by address
+    pub labels: Vec<LabelSize>,
+}
+
+impl BuildReport {
+    pub fn new(assembly: &Assembly) -> Self {
+        let size = assembly.bytes.len();
+        let end = (MEMORY_START + size) as u16;
+
+        let mut largest_data = assembly.data.clone();
+        largest_data.sort_by(|a, b| b.len.cmp(&a.len).then(a.address.cmp(&b.address)));
+        largest_data.truncate(LARGEST_DATA_BLOCKS);
+
+        let mut labels: Vec<(&String, u16)> = assembly
+            .labels
+            .iter()
+            .map(|(name, address)| (name, *address))
+            .collect();
+        labels.sort_by_key(|(_, address)| *address);
+        let labels = labels
+            .iter()
+            .enumerate()
+            .map(|(i, (name, address))| {
+                let next = labels[i + 1..]
+                    .iter()
+                    .map(|(_, next)| *next)
+                    .find(|next| next > address)
+                    .unwrap_or(end);
+                LabelSize {
+                    name: name.to_string(),
+                    address: *address,
+                    size: next.saturating_sub(*address) as usize,
+                }
+            })
+            .collect();
+
+        Self {
+            size,
+            remaining: PROGRAM_SPACE as isize - size as isize,
+            largest_data,
+            labels,
+        }
+    }
+
+    /// True if the program is larger than `max_size` bytes
+    pub fn exceeds(&self, max_size: usize) -> bool {
+        self.size > max_size
+    }
+
+    fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|label| label.address == address)
+            .map(|label| label.name.as_str())
+    }
+}
+
+impl fmt::Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Size: {} bytes, {} bytes remaining before 0xFFF",
+            self.size, self.remaining
+        )?;
+
+        if !self.largest_data.is_empty() {
+            writeln!(f, "\nLargest data blocks:")?;
+            for block in self.largest_data.iter() {
+                writeln!(
+                    f,
+                    "    {:03X}  {:>5}  {}",
+                    block.address,
+                    block.len,
+                    self.label_at(block.address).unwrap_or("")
+                )?;
+            }
+        }
+
+        if !self.labels.is_empty() {
+            writeln!(f, "\nLabels:")?;
+            for label in self.labels.iter() {
+                writeln!(
+                    f,
+                    "    {:03X}  {:>5}  {}",
+                    label.address, label.size, label.name
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    #[test]
+    fn report() {
+        let assembly = parser::assemble(
+            "main:   call draw
+                    jp main
+            draw:   ret
+            small:  db 1, 2
+                    cls
+            big:    db 1, 2, 3
+                    db 4",
+        )
+        .unwrap();
+        let report = BuildReport::new(&assembly);
+
+        assert_eq!(report.size, 14);
+        assert_eq!(report.remaining, 3570);
+        let blocks: Vec<(u16, usize)> = report
+            .largest_data
+            .iter()
+            .map(|block| (block.address, block.len))
+            .collect();
+        assert_eq!(blocks, vec![(0x20A, 4), (0x206, 2)]);
+        let sizes: Vec<(&str, usize)> = report
+            .labels
+            .iter()
+            .map(|label| (label.name.as_str(), label.size))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![("main", 4), ("draw", 2), ("small", 4), ("big", 4)]
+        );
+
+        assert!(report.exceeds(13));
+        assert!(!report.exceeds(14));
+        assert!(report.to_string().contains("20A      4  big"));
+    }
+}
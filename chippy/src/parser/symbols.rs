@@ -0,0 +1,151 @@
+//! Debug symbols of an assembled program, for debuggers that show label names and source lines.
+//!
+//! The chippy format is plain text with one symbol per line, a kind, a hex address and a value.
+//! Lines starting with `#` are comments.
+//!
+//! ```text
+//! # chippy symbols 1
+//! label 0200 main
+//! data 0210 12
+//! line 0200 3
+//! ```
+//!
+//! `label` names an address, `data` marks a run of data bytes with its length in decimal and
+//! `line` gives the source line of an instruction, counting from 1. `Symbols::to_nocash` writes
+//! the `.sym` format of the no$ debuggers instead.
+
+use super::assembler::{Assembly, DataBlock};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// First line of a symbol file
+pub const HEADER: &str = "# chippy symbols 1";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    pub labels: BTreeMap<String, u16>,
+    pub data: Vec<DataBlock>,
+    /// Source line of every instruction, starting from 0 like `Assembly::lines`
+    pub lines: BTreeMap<u16, usize>,
+}
+
+impl Symbols {
+    pub fn new(assembly: &Assembly) -> Self {
+        Self {
+            labels: assembly.labels.clone(),
+            data: assembly.data.clone(),
+            lines: assembly.lines.clone(),
+        }
+    }
+
+    /// Name of the label at `address`, the first by name if several share it
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(_, label)| **label == address)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The labels and data blocks in the `.sym` format of the no$ debuggers, ordered by address
+    pub fn to_nocash(&self) -> String {
+        let mut symbols: Vec<(u16, String)> = self
+            .labels
+            .iter()
+            .map(|(name, address)| (*address, name.clone()))
+            .chain(
+                self.data
+                    .iter()
+                    .map(|block| (block.address, format!(".byt:{:04X}", block.len))),
+            )
+            .collect();
+        symbols.sort();
+        symbols
+            .iter()
+            .map(|(address, name)| format!("{:08X} {}\n", address, name))
+            .collect()
+    }
+}
+
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for (name, address) in self.labels.iter() {
+            writeln!(f, "label {:04X} {}", address, name)?;
+        }
+        for block in self.data.iter() {
+            writeln!(f, "data {:04X} {}", block.address, block.len)?;
+        }
+        for (address, line) in self.lines.iter() {
+            writeln!(f, "line {:04X} {}", address, line + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut symbols = Symbols::default();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid symbol on line {}: {}", number + 1, line);
+            let mut fields = line.split_whitespace();
+            let (kind, address, value) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(kind), Some(address), Some(value)) => (kind, address, value),
+                _ => return Err(invalid()),
+            };
+            let address = u16::from_str_radix(address, 16).map_err(|_| invalid())?;
+            match kind {
+                "label" => {
+                    symbols.labels.insert(value.to_string(), address);
+                }
+                "data" => symbols.data.push(DataBlock {
+                    address,
+                    len: value.parse().map_err(|_| invalid())?,
+                }),
+                "line" => {
+                    let line: usize = value.parse().map_err(|_| invalid())?;
+                    let line = line.checked_sub(1).ok_or_else(invalid)?;
+                    symbols.lines.insert(address, line);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::assembler;
+
+    #[test]
+    fn export_symbols() {
+        let assembly = assembler::assemble(
+            "main:\n  ld i, sprite\n  drw v0, v0, 2\nloop: jp loop\nsprite: db 0xFF, 0x81",
+        )
+        .unwrap();
+        let symbols = Symbols::new(&assembly);
+        assert_eq!(symbols.label_at(0x204), Some("loop"));
+
+        let text = symbols.to_string();
+        assert_eq!(
+            text,
+            "# chippy symbols 1\nlabel 0204 loop\nlabel 0200 main\nlabel 0206 sprite\n\
+             data 0206 2\nline 0200 2\nline 0202 3\nline 0204 4\n"
+        );
+        assert_eq!(text.parse::<Symbols>(), Ok(symbols.clone()));
+        assert_eq!(
+            symbols.to_nocash(),
+            "00000200 main\n00000204 loop\n00000206 .byt:0002\n00000206 sprite\n"
+        );
+
+        assert!("label 0200".parse::<Symbols>().is_err());
+        assert!("line 0200 0".parse::<Symbols>().is_err());
+        assert!("frame 0200 1".parse::<Symbols>().is_err());
+    }
+}
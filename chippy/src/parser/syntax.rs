@@ -0,0 +1,190 @@
+//! Syntax highlighting definitions for editors, generated from the tables of mnemonics,
+//! directives and keywords used by the assembler so they never fall out of date.
+//!
+//! - `textmate` gives a TextMate grammar, used by VS Code, Sublime Text and most editors.
+//! - `tree_sitter_grammar` and `tree_sitter_highlights` give a Tree-sitter grammar and its
+//!   highlight queries, used by Neovim, Helix and Zed.
+
+use super::{
+    assembler::{DIRECTIVES, RESERVED},
+    flow::KEYWORDS,
+    imp::{MNEMONICS, SPECIAL_REGISTERS},
+};
+
+/// Scope name of the TextMate grammar
+pub const SCOPE: &str = "source.chippy";
+
+/// Extension of assembly source files
+pub const FILE_EXTENSION: &str = "c8s";
+
+/// Generated definition file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxFile {
+    /// Path of the file, relative to the output directory
+    pub path: &'static str,
+    pub contents: String,
+}
+
+/// Every definition file
+pub fn files() -> Vec<SyntaxFile> {
+    vec![
+        SyntaxFile {
+            path: "chippy.tmLanguage.json",
+            contents: textmate(),
+        },
+        SyntaxFile {
+            path: "tree-sitter-chippy/grammar.js",
+            contents: tree_sitter_grammar(),
+        },
+        SyntaxFile {
+            path: "tree-sitter-chippy/queries/highlights.scm",
+            contents: tree_sitter_highlights(),
+        },
+    ]
+}
+
+/// Quote a string for JSON and javascript
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Regex matching any of the words
+fn words(words: &[&str]) -> String {
+    format!("\\b({})\\b", words.join("|"))
+}
+
+/// TextMate grammar as JSON
+pub fn textmate() -> String {
+    let registers = format!("\\b(v[0-9a-f]|{})\\b|\\[i\\]", SPECIAL_REGISTERS.join("|"));
+    let rules = [
+        ("comment.line.semicolon.chippy", ";.*$".to_string()),
+        (
+            "entity.name.label.chippy",
+            "^\\s*[a-z_][a-z0-9_.]*(?=\\s*:)".to_string(),
+        ),
+        ("keyword.other.directive.chippy", words(&DIRECTIVES)),
+        ("keyword.control.chippy", words(&KEYWORDS)),
+        ("keyword.other.mnemonic.chippy", words(&MNEMONICS)),
+        ("variable.language.register.chippy", registers),
+        (
+            "constant.numeric.chippy",
+            "\\b(0x[0-9a-f]+|0b[01]+|[0-9]+)\\b".to_string(),
+        ),
+        ("keyword.operator.chippy", "==|!=|-?\\bkey\\b".to_string()),
+    ];
+
+    let patterns: Vec<String> = rules
+        .iter()
+        .map(|(name, regex)| {
+            format!(
+                "    {{ \"name\": {}, \"match\": {} }}",
+                quote(name),
+                quote(&format!("(?i){}", regex))
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"name\": \"chippy\",\n  \"scopeName\": {},\n  \"fileTypes\": [{}],\n  \"patterns\": [\n{}\n  ]\n}}\n",
+        quote(SCOPE),
+        quote(FILE_EXTENSION),
+        patterns.join(",\n")
+    )
+}
+
+/// Tree-sitter `grammar.js`. The grammar is a flat list of tokens, which is all highlighting needs.
+pub fn tree_sitter_grammar() -> String {
+    let choice = |words: &[&str]| {
+        let words: Vec<String> = words.iter().map(|word| quote(word)).collect();
+        format!("choice({})", words.join(", "))
+    };
+    let registers: Vec<&str> = SPECIAL_REGISTERS
+        .iter()
+        .chain(RESERVED.iter())
+        .copied()
+        .fold(Vec::new(), |mut registers, register| {
+            if !registers.contains(&register) {
+                registers.push(register);
+            }
+            registers
+        });
+
+    format!(
+        r#"module.exports = grammar({{
+  name: 'chippy',
+  word: $ => $.identifier,
+  extras: $ => [/\s/, $.comment],
+  rules: {{
+    source_file: $ => repeat(choice(
+      $.label, $.mnemonic, $.directive, $.keyword, $.register, $.number, $.operator,
+      $.identifier, ','
+    )),
+    comment: $ => /;.*/,
+    label: $ => /[A-Za-z_][A-Za-z0-9_.]*:/,
+    mnemonic: $ => {},
+    directive: $ => {},
+    keyword: $ => {},
+    register: $ => choice(token(prec(1, /[vV][0-9a-fA-F]/)), {}),
+    number: $ => /0x[0-9a-fA-F]+|0b[01]+|[0-9]+/,
+    operator: $ => choice('==', '!=', '-key', 'key'),
+    identifier: $ => /[A-Za-z_][A-Za-z0-9_.]*/,
+  }}
+}});
+"#,
+        choice(&MNEMONICS),
+        choice(&DIRECTIVES),
+        choice(&KEYWORDS),
+        registers
+            .iter()
+            .map(|register| quote(register))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Tree-sitter `queries/highlights.scm` for `tree_sitter_grammar`
+pub fn tree_sitter_highlights() -> String {
+    [
+        "(comment) @comment",
+        "(label) @label",
+        "(identifier) @label",
+        "(mnemonic) @function.builtin",
+        "(directive) @keyword.directive",
+        "(keyword) @keyword",
+        "(register) @variable.builtin",
+        "(number) @number",
+        "(operator) @operator",
+    ]
+    .iter()
+    .map(|line| format!("{}\n", line))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::instruction::Instruction;
+
+    #[test]
+    fn mnemonics_cover_every_instruction() {
+        for opcode in 0..=u16::MAX {
+            let asm = Instruction::parse(opcode).to_asm();
+            let mnemonic = asm.split_whitespace().next().unwrap();
+            assert!(MNEMONICS.contains(&mnemonic), "{}", asm);
+        }
+    }
+
+    #[test]
+    fn generated_files() {
+        let textmate = textmate();
+        assert!(textmate.contains(r#""match": "(?i)\\b(db|org|table|calltable)\\b""#));
+        assert!(textmate.contains(r#""scopeName": "source.chippy""#));
+
+        let grammar = tree_sitter_grammar();
+        for mnemonic in MNEMONICS.iter() {
+            assert!(grammar.contains(&quote(mnemonic)), "{}", mnemonic);
+        }
+        assert!(grammar.contains(r#""[i]""#));
+        assert_eq!(files().len(), 3);
+        assert!(tree_sitter_highlights().contains("(mnemonic) @function.builtin\n"));
+    }
+}
@@ -0,0 +1,400 @@
+//! Conversion of the display to RGBA pixels for frontends that draw images.
+
+use crate::emu::gpu::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::{fmt, str::FromStr};
+
+/// Colors of set and unset pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub on: [u8; 4],
+    pub off: [u8; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            on: [0xCD, 0xCE, 0xCF, 0xFF],
+            off: [0x19, 0x23, 0x30, 0xFF],
+        }
+    }
+}
+
+impl Palette {
+    /// Built in palettes, cycled through by the frontends
+    pub const PRESETS: &'static [Palette] = &[
+        Palette {
+            on: [0xCD, 0xCE, 0xCF, 0xFF],
+            off: [0x19, 0x23, 0x30, 0xFF],
+        },
+        // Black and white
+        Palette {
+            on: [0xFF, 0xFF, 0xFF, 0xFF],
+            off: [0x00, 0x00, 0x00, 0xFF],
+        },
+        // Amber monitor
+        Palette {
+            on: [0xFF, 0xB0, 0x00, 0xFF],
+            off: [0x28, 0x18, 0x00, 0xFF],
+        },
+        // Green phosphor
+        Palette {
+            on: [0x33, 0xFF, 0x66, 0xFF],
+            off: [0x00, 0x22, 0x0A, 0xFF],
+        },
+        // COSMAC VIP television
+        Palette {
+            on: [0xF0, 0xF0, 0xE0, 0xFF],
+            off: [0x20, 0x20, 0x28, 0xFF],
+        },
+        // High contrast yellow
+        Palette {
+            on: [0xFF, 0xFF, 0x00, 0xFF],
+            off: [0x00, 0x00, 0x00, 0xFF],
+        },
+        // Inverted, dark pixels on a light background
+        Palette {
+            on: [0x00, 0x00, 0x00, 0xFF],
+            off: [0xFF, 0xFF, 0xFF, 0xFF],
+        },
+        // Sky blue from the Okabe-Ito colorblind safe colors
+        Palette {
+            on: [0x56, 0xB4, 0xE9, 0xFF],
+            off: [0x00, 0x00, 0x00, 0xFF],
+        },
+    ];
+
+    /// Names of the presets accepted by `FromStr`, in the order of `PRESETS`
+    pub const PRESET_NAMES: &'static [&'static str] = &[
+        "default", "white", "amber", "green", "vip", "yellow", "inverted", "sky",
+    ];
+
+    /// Presets for low vision and colorblind players, their contrast ratio is at least
+    /// `ACCESSIBLE_CONTRAST`
+    pub const ACCESSIBLE: &'static [&'static str] = &["white", "yellow", "inverted", "sky"];
+
+    /// WCAG contrast ratio of enhanced (AAA) contrast
+    pub const ACCESSIBLE_CONTRAST: f64 = 7.0;
+
+    /// Preset called `name` in `PRESET_NAMES`
+    pub fn preset(name: &str) -> Option<Palette> {
+        let index = Palette::PRESET_NAMES
+            .iter()
+            .position(|preset| *preset == name)?;
+        Some(Palette::PRESETS[index])
+    }
+
+    /// WCAG contrast ratio between the two colors, from 1 (same luminance) to 21 (black and
+    /// white)
+    pub fn contrast_ratio(&self) -> f64 {
+        let (on, off) = (luminance(self.on), luminance(self.off));
+        (on.max(off) + 0.05) / (on.min(off) + 0.05)
+    }
+}
+
+/// WCAG relative luminance of an sRGB color
+fn luminance([r, g, b, _]: [u8; 4]) -> f64 {
+    let linear = |channel: u8| {
+        let channel = channel as f64 / 255.0;
+        match channel <= 0.03928 {
+            true => channel / 12.92,
+            false => ((channel + 0.055) / 1.055).powf(2.4),
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Hex colors of set and unset pixels, `ON:OFF` as in `ffb000:281800`, or the name of a
+    /// preset
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(preset) = Palette::preset(s) {
+            return Ok(preset);
+        }
+        let color = |src: &str| {
+            let value = match src.trim_start_matches('#') {
+                hex if hex.len() == 6 => u32::from_str_radix(hex, 16).ok(),
+                _ => None,
+            };
+            value
+                .map(|value| {
+                    let [_, r, g, b] = value.to_be_bytes();
+                    [r, g, b, 0xFF]
+                })
+                .ok_or_else(|| format!("Invalid color: {}", src))
+        };
+        match s.split_once(':') {
+            Some((on, off)) => Ok(Palette {
+                on: color(on)?,
+                off: color(off)?,
+            }),
+            None => Err(format!(
+                "Invalid palette, expected ON:OFF colors or one of {}: {}",
+                Palette::PRESET_NAMES.join(", "),
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, _] = self.on;
+        write!(f, "{:02x}{:02x}{:02x}:", r, g, b)?;
+        let [r, g, b, _] = self.off;
+        write!(f, "{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// Size in bytes of the RGBA image of the 64x32 display drawn with `scale` pixels per chip8 pixel
+pub fn rgba_size(scale: usize) -> usize {
+    SCREEN_WIDTH * scale * SCREEN_HEIGHT * scale * 4
+}
+
+/// Width and height of a display of `pixels` pixels, the high resolution display when it has
+/// 128x64 pixels and the 64x32 display otherwise, like `Gpu::load`
+pub fn display_size(pixels: usize) -> (usize, usize) {
+    match pixels == HIRES_WIDTH * HIRES_HEIGHT {
+        true => (HIRES_WIDTH, HIRES_HEIGHT),
+        false => (SCREEN_WIDTH, SCREEN_HEIGHT),
+    }
+}
+
+/// Pixels per chip8 pixel drawing a display of `pixels` pixels as large as the 64x32 display
+/// drawn with `scale`, so both resolutions fill images of the same size
+pub fn fit_scale(pixels: usize, scale: usize) -> usize {
+    (scale * SCREEN_WIDTH / display_size(pixels).0).max(1)
+}
+
+/// Draw the display into `buffer`, an RGBA image of `width * scale` pixels per row where the
+/// width is given by `display_size`. Rows past the end of the buffer are not drawn.
+pub fn draw_rgba(display: &[bool], scale: usize, palette: Palette, buffer: &mut [u8]) {
+    let columns = display_size(display.len()).0;
+    let width = columns * scale;
+    for (index, pixel) in buffer.chunks_exact_mut(4).enumerate() {
+        let x = (index % width) / scale;
+        let y = (index / width) / scale;
+        let color = match display.get(y * columns + x) {
+            Some(true) => palette.on,
+            _ => palette.off,
+        };
+        pixel.copy_from_slice(&color);
+    }
+}
+
+pub fn to_rgba(display: &[bool], scale: usize, palette: Palette) -> Vec<u8> {
+    let mut buffer = vec![0; display.len() * scale * scale * 4];
+    draw_rgba(display, scale, palette, &mut buffer);
+    buffer
+}
+
+/// Draw pixel intensities from 0 (off) to 255 (on) like `draw_rgba`, mixing the palette colors
+pub fn draw_intensity_rgba(intensity: &[u8], scale: usize, palette: Palette, buffer: &mut [u8]) {
+    let columns = display_size(intensity.len()).0;
+    let width = columns * scale;
+    for (index, pixel) in buffer.chunks_exact_mut(4).enumerate() {
+        let x = (index % width) / scale;
+        let y = (index / width) / scale;
+        let level = intensity.get(y * columns + x).copied().unwrap_or(0) as u16;
+        for (channel, (on, off)) in pixel.iter_mut().zip(palette.on.iter().zip(palette.off)) {
+            *channel = ((*on as u16 * level + off as u16 * (255 - level)) / 255) as u8;
+        }
+    }
+}
+
+/// How consecutive frames are combined to hide sprites flickering at 30Hz
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Blend {
+    /// Only the current frame
+    #[default]
+    None,
+    /// Pixels set in one of the two frames are drawn at half intensity
+    Average,
+    /// Pixels set in either frame are drawn
+    Max,
+}
+
+impl Blend {
+    pub const VARIANTS: &'static [&'static str] = &["none", "average", "max"];
+}
+
+impl FromStr for Blend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Blend::None),
+            "average" => Ok(Blend::Average),
+            "max" => Ok(Blend::Max),
+            _ => Err(format!("Unknown blend mode: {}", s)),
+        }
+    }
+}
+
+/// Combines every frame with the previous one. The first frame after switching between the
+/// 64x32 and high resolution displays is blended with a blank frame.
+#[derive(Debug, Clone)]
+pub struct FrameBlender {
+    blend: Blend,
+    previous: Vec<bool>,
+}
+
+impl FrameBlender {
+    pub fn new(blend: Blend) -> Self {
+        Self {
+            blend,
+            previous: vec![false; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    /// Intensity of every pixel of `display` blended with the previous frame, for
+    /// `draw_intensity_rgba`
+    pub fn blend(&mut self, display: &[bool]) -> Vec<u8> {
+        let mut intensity = vec![0; display.len()];
+        self.blend_into(display, &mut intensity);
+        intensity
+    }
+
+    /// Write the blended intensities to `intensity` like `FrameBlender::blend`, without
+    /// allocating unless the size of the display changed
+    pub fn blend_into(&mut self, display: &[bool], intensity: &mut [u8]) {
+        if self.previous.len() != display.len() {
+            self.previous = vec![false; display.len()];
+        }
+        let level = |on: bool| if on { 255u16 } else { 0 };
+        let pixels = display.iter().zip(self.previous.iter());
+        for (output, (current, previous)) in intensity.iter_mut().zip(pixels) {
+            *output = match self.blend {
+                Blend::None => level(*current) as u8,
+                Blend::Average => ((level(*current) + level(*previous)) / 2) as u8,
+                Blend::Max => level(*current || *previous) as u8,
+            };
+        }
+        self.previous.copy_from_slice(display);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_palette() {
+        let palette: Palette = "#FFB000:281800".parse().unwrap();
+        assert_eq!(palette.on, [0xFF, 0xB0, 0x00, 0xFF]);
+        assert_eq!(palette.off, [0x28, 0x18, 0x00, 0xFF]);
+        assert_eq!(palette.to_string(), "ffb000:281800");
+        assert_eq!(Palette::PRESETS[0], Palette::default());
+        assert!("ffb000".parse::<Palette>().is_err());
+        assert!("ffb00:281800".parse::<Palette>().is_err());
+        assert_eq!("amber".parse::<Palette>().unwrap(), Palette::PRESETS[2]);
+        assert_eq!(Palette::PRESET_NAMES.len(), Palette::PRESETS.len());
+    }
+
+    #[test]
+    fn contrast() {
+        let white = Palette::preset("white").unwrap();
+        assert!((white.contrast_ratio() - 21.0).abs() < 1e-9);
+        assert_eq!(Palette::preset("inverted").unwrap().contrast_ratio(), 21.0);
+        let same = Palette {
+            on: [0x80, 0x40, 0x20, 0xFF],
+            off: [0x80, 0x40, 0x20, 0xFF],
+        };
+        assert_eq!(same.contrast_ratio(), 1.0);
+        for name in Palette::ACCESSIBLE {
+            let palette = Palette::preset(name).unwrap();
+            assert!(
+                palette.contrast_ratio() >= Palette::ACCESSIBLE_CONTRAST,
+                "{} {}",
+                name,
+                palette.contrast_ratio()
+            );
+        }
+    }
+
+    #[test]
+    fn scaled_pixels() {
+        let mut display = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        display[1] = true;
+        let palette = Palette::default();
+        let image = to_rgba(&display, 2, palette);
+        assert_eq!(image.len(), rgba_size(2));
+
+        let pixel = |x: usize, y: usize| &image[(y * SCREEN_WIDTH * 2 + x) * 4..][..4];
+        assert_eq!(pixel(1, 0), palette.off);
+        assert_eq!(pixel(2, 0), palette.on);
+        assert_eq!(pixel(3, 1), palette.on);
+        assert_eq!(pixel(4, 1), palette.off);
+    }
+
+    #[test]
+    fn hires_pixels() {
+        let mut display = [false; HIRES_WIDTH * HIRES_HEIGHT];
+        display[HIRES_WIDTH + 127] = true;
+        let palette = Palette::default();
+        let scale = fit_scale(display.len(), 2);
+        assert_eq!(scale, 1);
+        let image = to_rgba(&display, scale, palette);
+        assert_eq!(image.len(), rgba_size(2));
+
+        let pixel = |x: usize, y: usize| &image[(y * HIRES_WIDTH + x) * 4..][..4];
+        assert_eq!(pixel(127, 1), palette.on);
+        assert_eq!(pixel(126, 1), palette.off);
+        assert_eq!(pixel(63, 0), palette.off);
+        assert_eq!(display_size(SCREEN_WIDTH * SCREEN_HEIGHT), (64, 32));
+
+        let mut blender = FrameBlender::new(Blend::Max);
+        blender.blend(&[true; SCREEN_WIDTH * SCREEN_HEIGHT]);
+        let intensity = blender.blend(&display);
+        assert_eq!(intensity.len(), display.len());
+        assert_eq!(intensity.iter().filter(|level| **level == 255).count(), 1);
+    }
+
+    #[test]
+    fn blend_frames() {
+        let frame = |pixel: usize| {
+            let mut display = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            display[pixel] = true;
+            display
+        };
+        let mut average = FrameBlender::new(Blend::Average);
+        let mut max = FrameBlender::new(Blend::Max);
+        let mut none = FrameBlender::new(Blend::None);
+        for blender in [&mut average, &mut max, &mut none] {
+            blender.blend(&frame(0));
+        }
+
+        assert_eq!(average.blend(&frame(1))[..3], [127, 127, 0]);
+        assert_eq!(max.blend(&frame(1))[..3], [255, 255, 0]);
+        assert_eq!(none.blend(&frame(1))[..3], [0, 255, 0]);
+
+        let palette = Palette {
+            on: [200, 200, 200, 255],
+            off: [0, 100, 0, 255],
+        };
+        let mut buffer = vec![0; rgba_size(1)];
+        draw_intensity_rgba(&average.blend(&frame(0)), 1, palette, &mut buffer);
+        assert_eq!(buffer[..8], [99, 149, 99, 255, 99, 149, 99, 255]);
+        assert_eq!(buffer[8..12], palette.off);
+    }
+
+    #[test]
+    fn no_allocation() {
+        use crate::{emu::framebuffer::Framebuffer, testing::allocations};
+
+        let display = [true; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let frame = Framebuffer::from_display(&display, SCREEN_WIDTH, SCREEN_HEIGHT);
+        let mut blender = FrameBlender::new(Blend::Average);
+        let mut intensity = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let mut buffer = vec![0; rgba_size(2)];
+        let count = allocations::count(|| {
+            draw_rgba(&display, 2, Palette::default(), &mut buffer);
+            blender.blend_into(&display, &mut intensity);
+            draw_intensity_rgba(&intensity, 2, Palette::default(), &mut buffer);
+            frame.draw_rgba(Palette::default(), &mut buffer);
+        });
+        assert_eq!(count, 0);
+        assert_eq!(intensity[0], 127);
+    }
+}
@@ -0,0 +1,91 @@
+//! Roms packed in zip archives, as found in most rom collections.
+
+use super::{
+    error::{RomError, RomResult},
+    is_rom,
+};
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// Read a rom from a zip archive. Without an `entry` name the archive must contain a single rom,
+/// or a single file if none of them has a rom extension.
+pub fn read_zip(reader: impl Read + Seek, entry: Option<&str>) -> RomResult<Vec<u8>> {
+    let mut archive = ZipArchive::new(reader).map_err(zip_error)?;
+    let name = match entry {
+        Some(entry) => entry.to_string(),
+        None => {
+            let mut files: Vec<&str> = archive
+                .file_names()
+                .filter(|name| !name.ends_with('/'))
+                .collect();
+            files.sort_unstable();
+            let roms: Vec<&str> = files.iter().copied().filter(|name| is_rom(name)).collect();
+            match (roms.as_slice(), files.as_slice()) {
+                ([rom], _) => rom.to_string(),
+                ([], [file]) => file.to_string(),
+                ([], _) => return Err(RomError::NoRomInArchive),
+                (roms, _) => {
+                    let names = roms.iter().map(|name| name.to_string()).collect();
+                    return Err(RomError::AmbiguousArchive(names));
+                }
+            }
+        }
+    };
+
+    let mut file = archive.by_name(&name).map_err(zip_error)?;
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn zip_error(err: zip::result::ZipError) -> RomError {
+    match err {
+        zip::result::ZipError::FileNotFound => RomError::NoRomInArchive,
+        err => RomError::InvalidArchive(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::{write::FileOptions, ZipWriter};
+
+    fn archive(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    #[test]
+    fn select_entry() {
+        let zip = archive(&[("readme.txt", b"hello"), ("games/pong.ch8", &[0x12, 0x00])]);
+        assert_eq!(read_zip(zip.clone(), None).unwrap(), vec![0x12, 0x00]);
+        assert_eq!(
+            read_zip(zip.clone(), Some("readme.txt")).unwrap(),
+            b"hello".to_vec()
+        );
+        assert_eq!(
+            read_zip(zip, Some("missing.ch8")),
+            Err(RomError::NoRomInArchive)
+        );
+
+        let single = archive(&[("PONG", &[0x00, 0xE0])]);
+        assert_eq!(read_zip(single, None).unwrap(), vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn ambiguous_archive() {
+        let zip = archive(&[("a.ch8", &[0x00]), ("b.CH8", &[0x01])]);
+        assert_eq!(
+            read_zip(zip, None),
+            Err(RomError::AmbiguousArchive(vec![
+                "a.ch8".to_string(),
+                "b.CH8".to_string()
+            ]))
+        );
+    }
+}
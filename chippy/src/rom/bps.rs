@@ -0,0 +1,222 @@
+//! BPS patches, a sequence of copy actions from the source rom, the patch and the target rom
+//! protected by crc32 checksums.
+
+use super::error::{RomError, RomResult};
+
+const HEADER: &[u8] = b"BPS1";
+/// Source, target and patch checksums
+const FOOTER_SIZE: usize = 12;
+
+/// True if `patch` starts with the BPS header
+pub fn is_bps(patch: &[u8]) -> bool {
+    patch.starts_with(HEADER)
+}
+
+fn invalid(reason: &str) -> RomError {
+    RomError::InvalidPatch(format!("BPS: {}", reason))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> RomResult<u8> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| invalid("unexpected end of patch"))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> RomResult<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or_else(|| invalid("unexpected end of patch"))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn number(&mut self) -> RomResult<usize> {
+        let (mut number, mut shift) = (0usize, 1usize);
+        loop {
+            let byte = self.byte()?;
+            number = number
+                .checked_add((byte & 0x7F) as usize * shift)
+                .ok_or_else(|| invalid("number too large"))?;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+            shift = shift
+                .checked_shl(7)
+                .ok_or_else(|| invalid("number too large"))?;
+            number += shift;
+        }
+    }
+
+    /// Relative offset, the lowest bit is the sign
+    fn offset(&mut self) -> RomResult<isize> {
+        let number = self.number()?;
+        let magnitude = (number >> 1) as isize;
+        Ok(if number & 1 == 1 {
+            -magnitude
+        } else {
+            magnitude
+        })
+    }
+}
+
+/// Apply a BPS patch, checking that it is applied to the rom it was made for
+pub fn apply(rom: &[u8], patch: &[u8]) -> RomResult<Vec<u8>> {
+    if !is_bps(patch) || patch.len() < HEADER.len() + FOOTER_SIZE {
+        return Err(invalid("missing header"));
+    }
+    let actions_end = patch.len() - FOOTER_SIZE;
+    let checksum =
+        |at: usize| u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]]);
+    if crc32(&patch[..patch.len() - 4]) != checksum(actions_end + 8) {
+        return Err(RomError::ChecksumMismatch("patch"));
+    }
+    if crc32(rom) != checksum(actions_end) {
+        return Err(RomError::ChecksumMismatch("source rom"));
+    }
+
+    let mut reader = Reader {
+        bytes: &patch[..actions_end],
+        position: HEADER.len(),
+    };
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid("source size does not match the rom"));
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0isize, 0isize);
+    while reader.position < actions_end {
+        let action = reader.number()?;
+        let len = (action >> 2) + 1;
+        match action & 3 {
+            // Source read
+            0 => {
+                let start = target.len();
+                let bytes = rom
+                    .get(start..start + len)
+                    .ok_or_else(|| invalid("source read out of range"))?;
+                target.extend_from_slice(bytes);
+            }
+            // Target read
+            1 => target.extend_from_slice(reader.bytes(len)?),
+            // Source copy
+            2 => {
+                source_offset += reader.offset()?;
+                let start = source_offset as usize;
+                let bytes = rom
+                    .get(start..start + len)
+                    .filter(|_| source_offset >= 0)
+                    .ok_or_else(|| invalid("source copy out of range"))?;
+                target.extend_from_slice(bytes);
+                source_offset += len as isize;
+            }
+            // Target copy, byte by byte as the copied range can overlap the output
+            _ => {
+                target_offset += reader.offset()?;
+                for _ in 0..len {
+                    let byte = *target
+                        .get(target_offset as usize)
+                        .filter(|_| target_offset >= 0)
+                        .ok_or_else(|| invalid("target copy out of range"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(invalid("target size does not match"));
+    }
+    if crc32(&target) != checksum(actions_end + 4) {
+        return Err(RomError::ChecksumMismatch("target rom"));
+    }
+    Ok(target)
+}
+
+/// CRC-32 as used by zip and png
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let x = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | x);
+                return bytes;
+            }
+            bytes.push(x);
+            value -= 1;
+        }
+    }
+
+    fn build(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(0));
+        patch.extend_from_slice(actions);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn actions() {
+        let source = [0x60, 0x05, 0x00, 0xE0];
+        let target = [0x60, 0x05, 0x12, 0x00, 0x12, 0x00, 0x60, 0x05];
+
+        let mut actions = Vec::new();
+        actions.extend(number((2 - 1) << 2)); // source read 2
+        actions.extend(number(((2 - 1) << 2) | 1)); // target read 2
+        actions.extend_from_slice(&[0x12, 0x00]);
+        actions.extend(number(((2 - 1) << 2) | 3)); // target copy 2 from 2
+        actions.extend(number(2 << 1));
+        actions.extend(number(((2 - 1) << 2) | 2)); // source copy 2 from 0
+        actions.extend(number(0));
+
+        let patch = build(&source, &target, &actions);
+        assert_eq!(apply(&source, &patch).unwrap(), target.to_vec());
+        assert_eq!(
+            apply(&[0x00, 0x00, 0x00, 0xE0], &patch),
+            Err(RomError::ChecksumMismatch("source rom"))
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Roms written in assembly and embedded in a Rust application at compile time. The build script
+//! assembles the sources into `OUT_DIR` and `include_rom!` embeds the bytes.
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     chippy::rom::bundle::assemble("roms/pong.asm").unwrap();
+//! }
+//!
+//! // main.rs
+//! const PONG: &[u8] = chippy::include_rom!("roms/pong.asm");
+//! ```
+//!
+//! The paths are relative to the package of the build script and can not leave it with `..`.
+
+use super::error::{RomError, RomResult};
+use crate::parser::assembler;
+use std::path::{Component, Path, PathBuf};
+
+/// Directory of `OUT_DIR` holding the assembled roms
+pub const BUNDLE_DIR: &str = "chippy-roms";
+
+/// Embed the rom assembled from `source` by `rom::bundle::assemble` in the build script, as a
+/// `&'static [u8; N]`
+#[macro_export]
+macro_rules! include_rom {
+    ($source:literal) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/chippy-roms/", $source, ".ch8"))
+    };
+}
+
+/// Assemble `source` for `include_rom!`, to be called from a build script. Cargo is told to run
+/// the build script again when the source changes. Returns the path of the rom.
+pub fn assemble(source: impl AsRef<Path>) -> RomResult<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(RomError::NoOutDir)?;
+    let source = source.as_ref();
+    println!("cargo:rerun-if-changed={}", source.display());
+    assemble_into(source, Path::new(&out_dir))
+}
+
+fn assemble_into(source: &Path, out_dir: &Path) -> RomResult<PathBuf> {
+    let text = std::fs::read_to_string(source)?;
+    let assembly = assembler::assemble(&text).map_err(|err| RomError::Assembly(err.to_string()))?;
+
+    // Keep the rom inside the bundle directory whatever the source path is
+    let mut rom = out_dir.join(BUNDLE_DIR);
+    for component in source.components() {
+        if let Component::Normal(name) = component {
+            rom.push(name);
+        }
+    }
+    let mut name = rom.into_os_string();
+    name.push(".ch8");
+    let rom = PathBuf::from(name);
+    if let Some(dir) = rom.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&rom, assembly.bytes)?;
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assemble_source() {
+        let dir = std::env::temp_dir().join(format!("chippy-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("loop.asm");
+        std::fs::write(&source, "main: jp main").unwrap();
+
+        let out_dir = dir.join("out");
+        let rom = assemble_into(&source, &out_dir).unwrap();
+        assert!(rom.starts_with(out_dir.join(BUNDLE_DIR)));
+        assert!(rom.to_string_lossy().ends_with("loop.asm.ch8"));
+        assert_eq!(std::fs::read(&rom).unwrap(), vec![0x12, 0x00]);
+
+        std::fs::write(&source, "jp nowhere").unwrap();
+        assert!(matches!(
+            assemble_into(&source, &out_dir),
+            Err(RomError::Assembly(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
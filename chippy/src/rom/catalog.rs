@@ -0,0 +1,126 @@
+//! Listing of the roms in a directory, with a preview of the screen of every rom.
+
+use super::{checksum, error::RomResult, is_rom};
+use crate::emu::{
+    frame::DEFAULT_CYCLES_PER_FRAME,
+    gpu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    vm::{ProgramState, Vm},
+};
+use std::path::{Path, PathBuf};
+
+/// Cycles run headless to draw the thumbnail of a rom, past the title screen of most games
+pub const THUMBNAIL_CYCLES: usize = 2000;
+
+pub type Thumbnail = [bool; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+#[derive(Clone)]
+pub struct CatalogEntry {
+    /// File name without the extension
+    pub name: String,
+    pub path: PathBuf,
+    /// Size of the rom in bytes
+    pub size: usize,
+    /// Checksum identifying the rom regardless of its file name
+    pub crc32: u32,
+    /// Display after running the rom for `THUMBNAIL_CYCLES` cycles
+    pub thumbnail: Thumbnail,
+}
+
+#[derive(Clone, Default)]
+pub struct Catalog {
+    /// Entries ordered by name
+    pub entries: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    /// List the roms in `dir`. Zip archives are included when the `zip` feature is enabled, files
+    /// that can not be read as a rom are skipped.
+    pub fn scan(dir: impl AsRef<Path>) -> RomResult<Self> {
+        let mut entries = Vec::new();
+        for file in std::fs::read_dir(dir)? {
+            let path = file?.path();
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let is_zip = cfg!(feature = "zip") && file_name.to_lowercase().ends_with(".zip");
+            if !path.is_file() || !(is_rom(file_name) || is_zip) {
+                continue;
+            }
+
+            let rom = match super::read(&path, None) {
+                Ok(rom) => rom,
+                Err(_) => continue,
+            };
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            entries.push(CatalogEntry {
+                name,
+                size: rom.len(),
+                crc32: checksum(&rom),
+                thumbnail: thumbnail(&rom, THUMBNAIL_CYCLES),
+                path,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name).then(a.path.cmp(&b.path)));
+        Ok(Self { entries })
+    }
+}
+
+/// Display of `rom` after running it headless for `cycles` cycles, or until it stops or halts.
+/// The cycles run in frames of `DEFAULT_CYCLES_PER_FRAME` so that title screens waiting on the
+/// delay timer get drawn.
+pub fn thumbnail(rom: &[u8], cycles: usize) -> Thumbnail {
+    let mut vm = Vm::new();
+    vm.load(rom.to_vec());
+    let mut remaining = cycles;
+    while remaining > 0 {
+        let frame = vm.run_frame(remaining.min(DEFAULT_CYCLES_PER_FRAME));
+        match frame.state {
+            ProgramState::Halted(_) | ProgramState::Halt | ProgramState::Error(_) => break,
+            _ => remaining -= frame.cycles,
+        }
+    }
+    vm.gpu.memory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_directory() {
+        let dir = std::env::temp_dir().join(format!("chippy-catalog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Draw the font sprite of 0 in the top left corner
+        std::fs::write(dir.join("zero.ch8"), [0xD0, 0x15, 0x12, 0x02]).unwrap();
+        std::fs::write(dir.join("blank.c8"), [0x12, 0x00]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a rom").unwrap();
+
+        let catalog = Catalog::scan(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = catalog.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["blank", "zero"]);
+        assert_eq!(catalog.entries[1].size, 4);
+        assert!(catalog.entries[0].thumbnail.iter().all(|pixel| !pixel));
+        assert!(catalog.entries[1].thumbnail[0]);
+        assert!(!catalog.entries[1].thumbnail[4]);
+    }
+
+    #[test]
+    fn thumbnail_after_delay() {
+        let rom = [
+            0x60, 0x3C, // 200: ld v0, 60
+            0xF0, 0x15, // 202: ld dt, v0
+            0xF1, 0x07, // 204: ld v1, dt
+            0x31, 0x00, // 206: se v1, 0
+            0x12, 0x04, // 208: jp 0x204
+            0xD2, 0x25, // 20A: drw v2, v2, 5
+            0x12, 0x0C, // 20C: jp 0x20C
+        ];
+        assert!(thumbnail(&rom, THUMBNAIL_CYCLES)[0]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Per rom settings, keyed by the crc32 of the rom like the high scores. A line gives a rom
+//! and the palettes it was designed for, the first one is used when the rom starts. Lines with
+//! `key` name what a key of the keypad does in the game.
+//!
+//! ```text
+//! # crc32   palettes
+//! 8f2b5a61  ffb000:281800 ffffff:000000
+//! 8f2b5a61  key 5 Accelerate
+//! ```
+
+use super::header::RomHeader;
+use crate::render::Palette;
+use std::{collections::BTreeMap, fmt, io, path::Path};
+
+/// Settings of a rom
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RomInfo {
+    /// Preferred palettes, in order
+    pub palettes: Vec<Palette>,
+    /// What the keys of the keypad do, by key
+    pub keys: BTreeMap<u8, String>,
+}
+
+/// Settings of every known rom.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RomDatabase {
+    roms: BTreeMap<u32, RomInfo>,
+}
+
+impl RomDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the database from `path`, a missing file is an empty database. Invalid lines and lines
+    /// starting with `#` are ignored.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut roms = BTreeMap::<u32, RomInfo>::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let (crc32, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let crc32 = match u32::from_str_radix(crc32, 16) {
+                Ok(crc32) => crc32,
+                Err(_) => continue,
+            };
+            match rest.trim_start().strip_prefix("key ") {
+                Some(key) => {
+                    let (key, label) = key.trim_start().split_once(' ').unwrap_or((key, ""));
+                    let (key, label) = match (u8::from_str_radix(key, 16), label.trim()) {
+                        (Ok(key), label) if key < 16 && !label.is_empty() => (key, label),
+                        _ => continue,
+                    };
+                    let info = roms.entry(crc32).or_default();
+                    info.keys.insert(key, label.to_string());
+                }
+                None => {
+                    let palettes = rest.split_whitespace().map(str::parse).collect();
+                    if let Ok(palettes) = palettes {
+                        roms.entry(crc32).or_default().palettes = palettes;
+                    }
+                }
+            }
+        }
+        Self { roms }
+    }
+
+    pub fn get(&self, crc32: u32) -> Option<&RomInfo> {
+        self.roms.get(&crc32)
+    }
+
+    pub fn insert(&mut self, crc32: u32, info: RomInfo) {
+        self.roms.insert(crc32, info);
+    }
+
+    /// Palettes to cycle through for a rom: its preferred palettes followed by the presets
+    pub fn palettes(&self, crc32: u32) -> Vec<Palette> {
+        let mut palettes = self
+            .get(crc32)
+            .map(|info| info.palettes.clone())
+            .unwrap_or_default();
+        for preset in Palette::PRESETS {
+            if !palettes.contains(preset) {
+                palettes.push(*preset);
+            }
+        }
+        palettes
+    }
+
+    /// What the keys do in a rom: the labels of the database, replaced by the labels of the rom
+    /// header
+    pub fn key_labels(&self, crc32: u32, header: &RomHeader) -> BTreeMap<u8, String> {
+        let mut labels = self
+            .get(crc32)
+            .map(|info| info.keys.clone())
+            .unwrap_or_default();
+        labels.extend(header.keys.clone());
+        labels
+    }
+}
+
+/// One line describing the controls of a rom, such as `W Accelerate  A Left`. `key_name` gives
+/// the name of the key the player presses for a key of the keypad.
+pub fn key_hints(labels: &BTreeMap<u8, String>, key_name: impl Fn(u8) -> String) -> String {
+    let hints: Vec<String> = labels
+        .iter()
+        .map(|(key, label)| format!("{} {}", key_name(*key), label))
+        .collect();
+    hints.join("  ")
+}
+
+impl fmt::Display for RomDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (crc32, info) in self.roms.iter() {
+            if !info.palettes.is_empty() || info.keys.is_empty() {
+                write!(f, "{:08x}", crc32)?;
+                for palette in info.palettes.iter() {
+                    write!(f, " {}", palette)?;
+                }
+                writeln!(f)?;
+            }
+            for (key, label) in info.keys.iter() {
+                writeln!(f, "{:08x} key {:X} {}", crc32, key, label)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let database = RomDatabase::parse(
+            "# crc32 palettes\n8f2b5a61  ffb000:281800 ffffff:000000\nzz 000000:ffffff\n1 bad\n",
+        );
+        let palettes = &database.get(0x8f2b5a61).unwrap().palettes;
+        assert_eq!(palettes.len(), 2);
+        assert_eq!(palettes[1], Palette::PRESETS[1]);
+        assert_eq!(database.get(1), None);
+        assert_eq!(RomDatabase::parse(&database.to_string()), database);
+    }
+
+    #[test]
+    fn key_labels() {
+        let database = RomDatabase::parse(
+            "7 ffb000:281800
+7 key 5 Turn left
+7  key a  Fire 
+7 key 10 Jump
+8 key 2 Up
+",
+        );
+        let info = database.get(7).unwrap();
+        assert_eq!(info.palettes.len(), 1);
+        assert_eq!(info.keys.len(), 2);
+        assert_eq!(info.keys[&0xA], "Fire");
+        assert_eq!(database.get(8).unwrap().palettes, vec![]);
+        assert_eq!(RomDatabase::parse(&database.to_string()), database);
+
+        let header = RomHeader::parse("[keys]\n5 = \"Left\"\n6 = \"Right\"").unwrap();
+        let labels = database.key_labels(7, &header);
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels[&5], "Left");
+        assert_eq!(
+            key_hints(&labels, |key| format!("{:X}", key)),
+            "5 Left  6 Right  A Fire"
+        );
+        assert_eq!(database.key_labels(9, &header), header.keys);
+    }
+
+    #[test]
+    fn palettes_to_cycle() {
+        let mut database = RomDatabase::new();
+        assert_eq!(database.palettes(7), Palette::PRESETS.to_vec());
+
+        let amber = Palette::PRESETS[2];
+        database.insert(
+            7,
+            RomInfo {
+                palettes: vec![amber],
+                ..RomInfo::default()
+            },
+        );
+        let palettes = database.palettes(7);
+        assert_eq!(palettes[0], amber);
+        assert_eq!(palettes.len(), Palette::PRESETS.len());
+    }
+}
@@ -0,0 +1,215 @@
+//! Instruction level differences between two roms.
+
+use crate::emu::{instruction::Instruction, vm::MEMORY_START};
+use std::fmt;
+
+/// Difference in a single instruction, or a single byte at the end of an odd sized rom.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// An instruction replaced by another one
+    Changed {
+        old_address: u16,
+        new_address: u16,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+    /// An instruction only in the old rom
+    Removed { address: u16, bytes: Vec<u8> },
+    /// An instruction only in the new rom
+    Inserted { address: u16, bytes: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal,
+    Remove,
+    Insert,
+}
+
+/// Compare two roms instruction by instruction. Instructions inserted or removed in the middle
+/// of the rom are found by aligning the longest common sequence of instructions, so the rest of
+/// the rom does not show up as changed.
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<Change> {
+    let old_words: Vec<&[u8]> = old.chunks(2).collect();
+    let new_words: Vec<&[u8]> = new.chunks(2).collect();
+
+    let prefix = old_words
+        .iter()
+        .zip(new_words.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_words[prefix..]
+        .iter()
+        .rev()
+        .zip(new_words[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old_words[prefix..old_words.len() - suffix];
+    let b = &new_words[prefix..new_words.len() - suffix];
+
+    // lcs[i][j] is the length of the longest common sequence of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lcs = vec![0u16; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = match a[i] == b[j] {
+                true => lcs[(i + 1) * width + j + 1] + 1,
+                false => lcs[(i + 1) * width + j].max(lcs[i * width + j + 1]),
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if j == b.len()
+            || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            ops.push(Op::Remove);
+            i += 1;
+        } else {
+            ops.push(Op::Insert);
+            j += 1;
+        }
+    }
+
+    let address = |index: usize| (MEMORY_START + (prefix + index) * 2) as u16;
+    let mut changes = Vec::new();
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while k < ops.len() {
+        if ops[k] == Op::Equal {
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+
+        // Pair up a run of removals with the insertions that follow it
+        let removed = ops[k..].iter().take_while(|op| **op == Op::Remove).count();
+        let inserted = ops[k + removed..]
+            .iter()
+            .take_while(|op| **op == Op::Insert)
+            .count();
+        let changed = removed.min(inserted);
+        for n in 0..changed {
+            changes.push(Change::Changed {
+                old_address: address(i + n),
+                new_address: address(j + n),
+                old: a[i + n].to_vec(),
+                new: b[j + n].to_vec(),
+            });
+        }
+        for n in changed..removed {
+            changes.push(Change::Removed {
+                address: address(i + n),
+                bytes: a[i + n].to_vec(),
+            });
+        }
+        for n in changed..inserted {
+            changes.push(Change::Inserted {
+                address: address(j + n),
+                bytes: b[j + n].to_vec(),
+            });
+        }
+        i += removed;
+        j += inserted;
+        k += removed + inserted;
+    }
+    changes
+}
+
+/// Opcode and mnemonic of an instruction, or the byte of a trailing odd byte
+fn describe(bytes: &[u8]) -> String {
+    match bytes {
+        [high, low] => {
+            let opcode = u16::from_be_bytes([*high, *low]);
+            format!("{:04X}  {}", opcode, Instruction::parse(opcode).to_asm())
+        }
+        _ => format!("{:02X}    db 0x{:02X}", bytes[0], bytes[0]),
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Changed {
+                old_address,
+                new_address,
+                old,
+                new,
+            } => {
+                writeln!(f, "- {:03X}  {}", old_address, describe(old))?;
+                write!(f, "+ {:03X}  {}", new_address, describe(new))
+            }
+            Change::Removed { address, bytes } => {
+                write!(f, "- {:03X}  {}", address, describe(bytes))
+            }
+            Change::Inserted { address, bytes } => {
+                write!(f, "+ {:03X}  {}", address, describe(bytes))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_instruction() {
+        let old = [0x60, 0x05, 0x00, 0xE0, 0x12, 0x00];
+        let new = [0x60, 0x06, 0x00, 0xE0, 0x12, 0x00];
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![Change::Changed {
+                old_address: 0x200,
+                new_address: 0x200,
+                old: vec![0x60, 0x05],
+                new: vec![0x60, 0x06]
+            }]
+        );
+        assert_eq!(
+            changes[0].to_string(),
+            "- 200  6005  ld v0, 0x05\n+ 200  6006  ld v0, 0x06"
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn inserted_and_removed() {
+        let old = [0x00, 0xE0, 0x60, 0x05, 0x12, 0x00];
+        let new = [0x00, 0xE0, 0x00, 0xEE, 0x60, 0x05, 0x12, 0x00, 0xAB];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::Inserted {
+                    address: 0x202,
+                    bytes: vec![0x00, 0xEE]
+                },
+                Change::Inserted {
+                    address: 0x208,
+                    bytes: vec![0xAB]
+                },
+            ]
+        );
+
+        assert_eq!(
+            diff(&new, &old),
+            vec![
+                Change::Removed {
+                    address: 0x202,
+                    bytes: vec![0x00, 0xEE]
+                },
+                Change::Removed {
+                    address: 0x208,
+                    bytes: vec![0xAB]
+                },
+            ]
+        );
+    }
+}
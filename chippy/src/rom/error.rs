@@ -0,0 +1,54 @@
+use thiserror::Error;
+
+pub type RomResult<T> = std::result::Result<T, RomError>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RomError {
+    #[error("Invalid patch line {0}: {1}")]
+    InvalidPatchLine(usize, String),
+
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+
+    #[error("Checksum of the {0} does not match the patch")]
+    ChecksumMismatch(&'static str),
+
+    #[error("IO Error: {0}")]
+    Io(String),
+
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+
+    #[error("No rom found in the archive")]
+    NoRomInArchive,
+
+    #[error("Archive contains several roms, pick one of: {}", .0.join(", "))]
+    AmbiguousArchive(Vec<String>),
+
+    #[error("Zip archives are not supported, enable the zip feature")]
+    ZipDisabled,
+
+    #[error("Patch writes past the end of memory at 0x{0:03X}")]
+    PatchOutOfRange(usize),
+
+    #[error("Invalid rom header line {0}: {1}")]
+    InvalidHeader(usize, String),
+
+    #[error("Invalid test suite line {0}: {1}")]
+    InvalidSuite(usize, String),
+
+    #[error("Test suite names no rom or source")]
+    NoSuiteProgram,
+
+    #[error("Failed to assemble the source: {0}")]
+    Assembly(String),
+
+    #[error("OUT_DIR is not set, roms can only be bundled from a build script")]
+    NoOutDir,
+}
+
+impl From<std::io::Error> for RomError {
+    fn from(err: std::io::Error) -> Self {
+        RomError::Io(err.to_string())
+    }
+}
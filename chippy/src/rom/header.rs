@@ -0,0 +1,295 @@
+//! Settings shipped by the author of a rom in a sidecar file next to it, `game.ch8` is described
+//! by `game.toml`. The file is a small subset of TOML: `key = value` lines with strings, numbers
+//! and arrays of strings, and a `[keys]` table naming what the keys of the keypad do.
+//!
+//! ```text
+//! title = "Space Racer"
+//! variant = "schip"    # chip8 or schip, sets the default speed
+//! speed = 20           # instructions per frame
+//! palette = "ffb000:281800"
+//! quirks = ["shift"]
+//!
+//! [keys]
+//! 5 = "Accelerate"
+//! 4 = "Left"
+//! 6 = "Right"
+//! ```
+
+use super::error::{RomError, RomResult};
+use crate::{
+    emu::frame::{SCHIP_CYCLES_PER_FRAME, VIP_CYCLES_PER_FRAME},
+    render::Palette,
+};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Extension of the header file of a rom
+pub const EXTENSION: &str = "toml";
+
+/// Machine a rom was written for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// COSMAC VIP chip8
+    #[default]
+    Chip8,
+    /// SUPER-CHIP on the HP48 calculators
+    Schip,
+}
+
+impl Variant {
+    pub const VARIANTS: &'static [&'static str] = &["chip8", "schip"];
+
+    /// Instructions per frame the games of the variant are usually tuned for
+    pub fn cycles_per_frame(&self) -> usize {
+        match self {
+            Variant::Chip8 => VIP_CYCLES_PER_FRAME,
+            Variant::Schip => SCHIP_CYCLES_PER_FRAME,
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" => Ok(Variant::Chip8),
+            "schip" => Ok(Variant::Schip),
+            _ => Err(format!(
+                "Unknown variant '{}', expected one of {}",
+                s,
+                Variant::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Variant::VARIANTS[*self as usize])
+    }
+}
+
+/// Settings of a rom given by its header file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RomHeader {
+    pub title: Option<String>,
+    pub variant: Option<Variant>,
+    /// Instructions per frame
+    pub speed: Option<usize>,
+    pub palette: Option<Palette>,
+    /// Names of the interpreter behaviours the rom relies on, see `Quirks::NAMES`
+    pub quirks: Vec<String>,
+    /// What the keys of the keypad do, by key
+    pub keys: BTreeMap<u8, String>,
+}
+
+impl RomHeader {
+    /// Path of the header file of the rom at `rom`
+    pub fn path_for(rom: impl AsRef<Path>) -> PathBuf {
+        rom.as_ref().with_extension(EXTENSION)
+    }
+
+    /// Read the header file of the rom at `rom`, `None` if the rom has none
+    pub fn load_for(rom: impl AsRef<Path>) -> RomResult<Option<Self>> {
+        match std::fs::read_to_string(Self::path_for(rom)) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn parse(text: &str) -> RomResult<Self> {
+        let mut header = Self::default();
+        let mut in_keys = false;
+        for (number, line) in text.lines().enumerate() {
+            let invalid = |message: String| RomError::InvalidHeader(number + 1, message);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[') {
+                match strip_comment(table).strip_suffix(']') {
+                    Some("keys") => in_keys = true,
+                    _ => return Err(invalid(format!("Unknown table [{}", table))),
+                }
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected key = value".to_string()))?;
+            let (key, value) = (key.trim(), value.trim());
+            if in_keys {
+                let key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|key| *key < 16)
+                    .ok_or_else(|| invalid(format!("Unknown key {}", key)))?;
+                header
+                    .keys
+                    .insert(key, parse_string(value).map_err(invalid)?);
+                continue;
+            }
+            match key {
+                "title" => header.title = Some(parse_string(value).map_err(invalid)?),
+                "variant" => {
+                    let variant = parse_string(value).map_err(invalid)?;
+                    header.variant = Some(variant.parse().map_err(invalid)?);
+                }
+                "speed" => {
+                    let speed = strip_comment(value)
+                        .parse()
+                        .ok()
+                        .filter(|speed| *speed > 0)
+                        .ok_or_else(|| invalid(format!("Invalid speed {}", value)))?;
+                    header.speed = Some(speed);
+                }
+                "palette" => {
+                    let palette = parse_string(value).map_err(invalid)?;
+                    header.palette = Some(palette.parse().map_err(invalid)?);
+                }
+                "quirks" => header.quirks = parse_strings(value).map_err(invalid)?,
+                _ => return Err(invalid(format!("Unknown setting {}", key))),
+            }
+        }
+        Ok(header)
+    }
+
+    /// Instructions per frame, the speed of the header or the usual speed of its variant
+    pub fn cycles_per_frame(&self) -> Option<usize> {
+        self.speed
+            .or_else(|| self.variant.map(|variant| variant.cycles_per_frame()))
+    }
+
+    /// What `key` does in the game
+    pub fn key_label(&self, key: u8) -> Option<&str> {
+        self.keys.get(&key).map(String::as_str)
+    }
+}
+
+/// Drop a trailing `# comment` from a value that is not a string
+pub(super) fn strip_comment(value: &str) -> &str {
+    value.split('#').next().unwrap_or_default().trim()
+}
+
+/// Parse a quoted string at the start of `value`, returning it and the text after it
+fn parse_quoted(value: &str) -> Result<(String, &str), String> {
+    let mut chars = value
+        .strip_prefix('"')
+        .ok_or_else(|| format!("Expected a quoted string: {}", value))?
+        .char_indices();
+    let mut string = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &value[index + 2..])),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => string.push(escaped),
+                Some((_, 'n')) => string.push('\n'),
+                _ => return Err(format!("Invalid escape in {}", value)),
+            },
+            c => string.push(c),
+        }
+    }
+    Err(format!("Unterminated string: {}", value))
+}
+
+pub(super) fn parse_string(value: &str) -> Result<String, String> {
+    let (string, rest) = parse_quoted(value)?;
+    match strip_comment(rest) {
+        "" => Ok(string),
+        rest => Err(format!("Unexpected text after the string: {}", rest)),
+    }
+}
+
+/// Parse an array of strings such as `["shift", "jump"]`
+pub(super) fn parse_strings(value: &str) -> Result<Vec<String>, String> {
+    let mut rest = value
+        .strip_prefix('[')
+        .ok_or_else(|| format!("Expected an array: {}", value))?
+        .trim_start();
+    let mut strings = Vec::new();
+    loop {
+        if let Some(after) = rest.strip_prefix(']') {
+            return match strip_comment(after) {
+                "" => Ok(strings),
+                after => Err(format!("Unexpected text after the array: {}", after)),
+            };
+        }
+        let (string, after) = parse_quoted(rest)?;
+        strings.push(string);
+        let after = after.trim_start();
+        rest = match after.strip_prefix(',') {
+            Some(after) => after.trim_start(),
+            None if after.starts_with(']') => after,
+            None => return Err(format!("Expected , or ] in {}", value)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let header = RomHeader::parse(
+            r##"
+            # Sidecar of space-racer.ch8
+            title = "Space \"Racer\""
+            variant = "schip"   # tuned for the HP48
+            palette = "#ffb000:281800"
+            quirks = ["shift", "jump" ] # trailing comment
+
+            [keys]
+            5 = "Accelerate"
+            a = "Brake"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(header.title.as_deref(), Some("Space \"Racer\""));
+        assert_eq!(header.variant, Some(Variant::Schip));
+        assert_eq!(header.cycles_per_frame(), Some(SCHIP_CYCLES_PER_FRAME));
+        assert_eq!(header.palette, Some("ffb000:281800".parse().unwrap()));
+        assert_eq!(header.quirks, vec!["shift", "jump"]);
+        assert_eq!(header.key_label(0x5), Some("Accelerate"));
+        assert_eq!(header.key_label(0xA), Some("Brake"));
+        assert_eq!(header.key_label(0x1), None);
+
+        let header = RomHeader::parse("speed = 15\nquirks = []").unwrap();
+        assert_eq!(header.cycles_per_frame(), Some(15));
+        assert!(header.quirks.is_empty());
+        assert_eq!(RomHeader::parse("").unwrap().cycles_per_frame(), None);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text| match RomHeader::parse(text) {
+            Err(RomError::InvalidHeader(line, _)) => line,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        assert_eq!(error("title = \"a\"\nauthor = \"b\""), 2);
+        assert_eq!(error("title = a"), 1);
+        assert_eq!(error("title = \"a"), 1);
+        assert_eq!(error("variant = \"xo\""), 1);
+        assert_eq!(error("speed = 0"), 1);
+        assert_eq!(error("quirks = [\"a\" \"b\"]"), 1);
+        assert_eq!(error("[sound]"), 1);
+        assert_eq!(error("[keys]\n10 = \"a\""), 2);
+        assert_eq!(error("title"), 1);
+    }
+
+    #[test]
+    fn load() {
+        let rom = std::env::temp_dir().join(format!("chippy-header-{}.ch8", std::process::id()));
+        assert_eq!(RomHeader::load_for(&rom), Ok(None));
+        let path = RomHeader::path_for(&rom);
+        std::fs::write(&path, "speed = 7").unwrap();
+        let header = RomHeader::load_for(&rom);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(header.unwrap().unwrap().speed, Some(7));
+    }
+}
@@ -0,0 +1,86 @@
+//! IPS patches, a list of byte runs written at offsets in the rom file.
+
+use super::error::{RomError, RomResult};
+
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+
+/// True if `patch` starts with the IPS header
+pub fn is_ips(patch: &[u8]) -> bool {
+    patch.starts_with(HEADER)
+}
+
+/// Apply an IPS patch, including run length encoded records and the truncation extension
+pub fn apply(rom: &[u8], patch: &[u8]) -> RomResult<Vec<u8>> {
+    let invalid = |reason: &str| RomError::InvalidPatch(format!("IPS: {}", reason));
+    if !is_ips(patch) {
+        return Err(invalid("missing header"));
+    }
+
+    let mut output = rom.to_vec();
+    let mut position = HEADER.len();
+    let mut read = |len: usize| -> RomResult<&[u8]> {
+        let bytes = patch
+            .get(position..position + len)
+            .ok_or_else(|| invalid("unexpected end of patch"))?;
+        position += len;
+        Ok(bytes)
+    };
+
+    loop {
+        let offset = read(3)?;
+        if offset == FOOTER {
+            break;
+        }
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let size = read(2)?;
+        let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+
+        let data = match size {
+            0 => {
+                let run = read(3)?;
+                let len = u16::from_be_bytes([run[0], run[1]]) as usize;
+                vec![run[2]; len]
+            }
+            _ => read(size)?.to_vec(),
+        };
+        if output.len() < offset + data.len() {
+            output.resize(offset + data.len(), 0);
+        }
+        output[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    if let Ok(truncate) = read(3) {
+        output.truncate(u32::from_be_bytes([0, truncate[0], truncate[1], truncate[2]]) as usize);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records() {
+        let rom = vec![0x60, 0x05, 0x00, 0xE0];
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x01, 0x09]); // 1: 09
+        patch.extend_from_slice(&[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xAA]); // 4: AA x3
+        patch.extend_from_slice(b"EOF");
+
+        let output = apply(&rom, &patch).unwrap();
+        assert_eq!(output, vec![0x60, 0x09, 0x00, 0xE0, 0xAA, 0xAA, 0xAA]);
+
+        patch.extend_from_slice(&[0x00, 0x00, 0x02]);
+        assert_eq!(apply(&rom, &patch).unwrap(), vec![0x60, 0x09]);
+    }
+
+    #[test]
+    fn truncated_patch() {
+        let patch = b"PATCH\x00\x00\x01\x00\x04\x09".to_vec();
+        assert!(matches!(
+            apply(&[0; 4], &patch),
+            Err(RomError::InvalidPatch(_))
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+//! Tools that work on rom files rather than a running machine.
+
+use error::{RomError, RomResult};
+use patch::Patch;
+use std::path::Path;
+
+#[cfg(feature = "zip")]
+pub mod archive;
+pub mod bps;
+pub mod bundle;
+pub mod catalog;
+pub mod database;
+pub mod diff;
+pub mod error;
+pub mod header;
+pub mod ips;
+pub mod patch;
+pub mod playlist;
+pub mod suite;
+
+/// Extensions of rom files, used to pick roms from directories and archives
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "c8", "sc8"];
+
+/// True if the file name has one of the rom extensions
+pub(crate) fn is_rom(name: &str) -> bool {
+    name.rsplit_once('.').is_some_and(|(_, extension)| {
+        ROM_EXTENSIONS
+            .iter()
+            .any(|rom| extension.eq_ignore_ascii_case(rom))
+    })
+}
+
+/// Crc32 of a rom, identifies a rom regardless of its file name
+pub fn checksum(rom: &[u8]) -> u32 {
+    bps::crc32(rom)
+}
+
+/// Read a rom file. Zip archives are opened with `archive::read_zip`, picking the archive
+/// member named `entry` or the only rom in the archive.
+pub fn read(path: impl AsRef<Path>, entry: Option<&str>) -> RomResult<Vec<u8>> {
+    let path = path.as_ref();
+    let is_zip = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        return Ok(std::fs::read(path)?);
+    }
+
+    #[cfg(feature = "zip")]
+    return archive::read_zip(std::fs::File::open(path)?, entry);
+    #[cfg(not(feature = "zip"))]
+    return Err(RomError::ZipDisabled);
+}
+
+/// Apply an IPS, BPS or text patch to a rom, the format is detected from the patch contents
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> RomResult<Vec<u8>> {
+    if ips::is_ips(patch) {
+        return ips::apply(rom, patch);
+    }
+    if bps::is_bps(patch) {
+        return bps::apply(rom, patch);
+    }
+
+    let text = std::str::from_utf8(patch)
+        .map_err(|_| RomError::InvalidPatch("unknown patch format".to_string()))?;
+    let mut output = rom.to_vec();
+    text.parse::<Patch>()?.apply(&mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_format() {
+        let rom = [0x60, 0x05];
+        let ips = b"PATCH\x00\x00\x01\x00\x01\x09EOF";
+        assert_eq!(apply_patch(&rom, ips).unwrap(), vec![0x60, 0x09]);
+        assert_eq!(apply_patch(&rom, b"201: 07").unwrap(), vec![0x60, 0x07]);
+        assert!(apply_patch(&rom, &[0xFF, 0xFE]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Plain text patch format for documenting rom hacks.
+//!
+//! ```text
+//! # Give the player more lives
+//! size 0x1F6
+//! 204: 60 09
+//! 2F0: 00 E0 00 EE
+//! ```
+//!
+//! Every write line starts with the address the bytes are written to, as seen by the program.
+//! The optional `size` line sets the size in bytes of the patched rom, truncating it or padding it
+//! with zeros. Lines starting with `#` are comments.
+
+use super::error::{RomError, RomResult};
+use crate::emu::vm::{MEMORY_SIZE, MEMORY_START};
+use std::{fmt, str::FromStr};
+
+/// Bytes written at an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Write {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    /// Size of the patched rom, unchanged if `None`
+    pub size: Option<usize>,
+    pub writes: Vec<Write>,
+}
+
+impl Patch {
+    /// Patch that turns `old` into `new`
+    pub fn between(old: &[u8], new: &[u8]) -> Self {
+        let mut writes: Vec<Write> = Vec::new();
+        for (offset, byte) in new.iter().enumerate() {
+            if old.get(offset) == Some(byte) {
+                continue;
+            }
+            let address = (MEMORY_START + offset) as u16;
+            match writes.last_mut() {
+                Some(write) if write.address as usize + write.bytes.len() == address as usize => {
+                    write.bytes.push(*byte)
+                }
+                _ => writes.push(Write {
+                    address,
+                    bytes: vec![*byte],
+                }),
+            }
+        }
+
+        Self {
+            size: Some(new.len()).filter(|size| *size != old.len()),
+            writes,
+        }
+    }
+
+    /// Apply the patch to a rom
+    pub fn apply(&self, rom: &mut Vec<u8>) -> RomResult<()> {
+        if let Some(size) = self.size {
+            rom.resize(size, 0);
+        }
+        for write in self.writes.iter() {
+            let end = write.address as usize + write.bytes.len();
+            if (write.address as usize) < MEMORY_START || end > MEMORY_SIZE {
+                return Err(RomError::PatchOutOfRange(write.address as usize));
+            }
+            let (start, end) = (write.address as usize - MEMORY_START, end - MEMORY_START);
+            if rom.len() < end {
+                rom.resize(end, 0);
+            }
+            rom[start..end].copy_from_slice(&write.bytes);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Patch {
+    type Err = RomError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patch = Patch::default();
+        for (ln, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let invalid = || RomError::InvalidPatchLine(ln, line.to_string());
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(size) = line.strip_prefix("size ") {
+                let size = size.trim();
+                let size = match size.strip_prefix("0x") {
+                    Some(hex) => usize::from_str_radix(hex, 16),
+                    None => size.parse(),
+                };
+                patch.size = Some(size.map_err(|_| invalid())?);
+                continue;
+            }
+
+            let (address, bytes) = line.split_once(':').ok_or_else(invalid)?;
+            let address = u16::from_str_radix(address.trim(), 16).map_err(|_| invalid())?;
+            let bytes = bytes
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| invalid())?;
+            patch.writes.push(Write { address, bytes });
+        }
+        Ok(patch)
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(size) = self.size {
+            writeln!(f, "size 0x{:03X}", size)?;
+        }
+        for write in self.writes.iter() {
+            write!(f, "{:03X}:", write.address)?;
+            for byte in write.bytes.iter() {
+                write!(f, " {:02X}", byte)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let old = vec![0x60, 0x05, 0x00, 0xE0, 0x12, 0x00];
+        let new = vec![0x60, 0x09, 0x00, 0xE0, 0x12, 0x02, 0x00, 0xEE];
+
+        let patch = Patch::between(&old, &new);
+        assert_eq!(patch.to_string(), "size 0x008\n201: 09\n205: 02 00 EE\n");
+        assert_eq!(patch.to_string().parse::<Patch>(), Ok(patch.clone()));
+
+        let mut patched = old.clone();
+        patch.apply(&mut patched).unwrap();
+        assert_eq!(patched, new);
+
+        let mut truncated = new;
+        Patch::between(&truncated.clone(), &old)
+            .apply(&mut truncated)
+            .unwrap();
+        assert_eq!(truncated, old);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let patch: Patch = "# comment\n\n200: 00 e0".parse().unwrap();
+        assert_eq!(patch.writes[0].bytes, vec![0x00, 0xE0]);
+        assert_eq!(
+            "200 00 E0".parse::<Patch>(),
+            Err(RomError::InvalidPatchLine(0, "200 00 E0".to_string()))
+        );
+        assert!("200: 0G".parse::<Patch>().is_err());
+
+        let mut rom = Vec::new();
+        let patch: Patch = "100: 00".parse().unwrap();
+        assert_eq!(patch.apply(&mut rom), Err(RomError::PatchOutOfRange(0x100)));
+    }
+}
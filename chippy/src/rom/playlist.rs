@@ -0,0 +1,114 @@
+//! Ordered list of roms played one after the other, for kiosks and attract modes.
+//!
+//! A playlist is either a directory, every rom in it sorted by file name, or a text file with one
+//! rom path per line. Relative paths are relative to the playlist file and lines starting with
+//! `#` are comments.
+
+use super::{error::RomResult, is_rom};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playlist {
+    roms: Vec<PathBuf>,
+    current: usize,
+}
+
+impl Playlist {
+    pub fn new(roms: Vec<PathBuf>) -> Self {
+        Self { roms, current: 0 }
+    }
+
+    /// Read the playlist at `path`, a directory or a playlist file
+    pub fn load(path: impl AsRef<Path>) -> RomResult<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut roms = Vec::new();
+            for file in std::fs::read_dir(path)? {
+                let rom = file?.path();
+                let name = rom.file_name().and_then(|name| name.to_str());
+                if rom.is_file() && name.is_some_and(is_rom) {
+                    roms.push(rom);
+                }
+            }
+            roms.sort();
+            return Ok(Self::new(roms));
+        }
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(Self::parse(&std::fs::read_to_string(path)?, dir))
+    }
+
+    /// Parse a playlist file, relative paths are joined to `dir`
+    pub fn parse(text: &str, dir: &Path) -> Self {
+        let roms = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| dir.join(line))
+            .collect();
+        Self::new(roms)
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn roms(&self) -> &[PathBuf] {
+        &self.roms
+    }
+
+    /// Rom being played
+    pub fn current(&self) -> Option<&Path> {
+        self.roms.get(self.current).map(PathBuf::as_path)
+    }
+
+    /// Move to the next rom, back to the first one after the last
+    pub fn advance(&mut self) -> Option<&Path> {
+        if !self.roms.is_empty() {
+            self.current = (self.current + 1) % self.roms.len();
+        }
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_advance() {
+        let mut playlist =
+            Playlist::parse("# demo\npong.ch8\n\n/roms/tetris.ch8\n", Path::new("demo"));
+        assert_eq!(
+            playlist.roms(),
+            [
+                PathBuf::from("demo/pong.ch8"),
+                PathBuf::from("/roms/tetris.ch8")
+            ]
+        );
+        assert_eq!(playlist.current(), Some(Path::new("demo/pong.ch8")));
+        assert_eq!(playlist.advance(), Some(Path::new("/roms/tetris.ch8")));
+        assert_eq!(playlist.advance(), Some(Path::new("demo/pong.ch8")));
+        assert_eq!(Playlist::default().advance(), None);
+    }
+
+    #[test]
+    fn load_directory() {
+        let dir = std::env::temp_dir().join(format!("chippy-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.ch8"), [0x12, 0x00]).unwrap();
+        std::fs::write(dir.join("a.c8"), [0x12, 0x00]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "a.c8").unwrap();
+
+        let playlist = Playlist::load(&dir).unwrap();
+        let from_file = Playlist::load(dir.join("notes.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(playlist.roms(), [dir.join("a.c8"), dir.join("b.ch8")]);
+        assert_eq!(from_file.roms(), [dir.join("a.c8")]);
+    }
+}
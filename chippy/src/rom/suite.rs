@@ -0,0 +1,352 @@
+//! Tests of a rom written as a suite file, run by `chippy test` so rom authors can check their
+//! games in CI. The file uses the TOML subset of the rom headers, every `[test.name]` table is a
+//! test that runs the program on a fresh machine and checks the machine state afterwards. The
+//! program runs in frames of `DEFAULT_CYCLES_PER_FRAME` instructions that count the timers down
+//! once each, like in the frontends.
+//!
+//! ```text
+//! rom = "pong.ch8"      # or source = "pong.asm", relative to the suite file
+//!
+//! [test.score_starts_at_zero]
+//! cycles = 500
+//! keys = ["5"]          # keys held during the whole run
+//! expect = ["v3 == 7", "i > 0x300", "[0x400] == 2"]
+//! display = "3f2a10bc"  # crc32 of the display, see `display_hash`
+//! memory = ["0x400: 00 01 02 03"]
+//! ```
+//!
+//! The assertions of `expect` are debugger expressions, see `debug::expr`.
+
+use super::{
+    bps,
+    error::{RomError, RomResult},
+    header::{parse_string, parse_strings, strip_comment},
+};
+use crate::{
+    debug::expr::Expr,
+    emu::{
+        frame::DEFAULT_CYCLES_PER_FRAME,
+        gpu::Gpu,
+        input::KEY_LIST,
+        vm::{ProgramState, Vm},
+    },
+    parser::assembler,
+};
+use std::path::{Path, PathBuf};
+
+/// Program tested by a suite
+#[derive(Debug, Clone, PartialEq)]
+pub enum Program {
+    Rom(PathBuf),
+    /// Assembly source, assembled before the tests run
+    Source(PathBuf),
+}
+
+/// One test of a suite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    /// Instructions run before checking the state, fewer if the program stops
+    pub cycles: usize,
+    /// Keys held down during the whole run
+    pub keys: Vec<u8>,
+    /// Expressions that must be true at the end of the run
+    pub expect: Vec<Expr>,
+    /// Expected `display_hash`
+    pub display: Option<u32>,
+    /// Expected bytes by start address
+    pub memory: Vec<(u16, Vec<u8>)>,
+}
+
+/// Test suite of a rom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suite {
+    pub program: Program,
+    pub tests: Vec<Case>,
+}
+
+impl Suite {
+    pub fn load(path: impl AsRef<Path>) -> RomResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    /// Parse a suite, the program paths are relative to `dir`
+    pub fn parse(text: &str, dir: &Path) -> RomResult<Self> {
+        let mut program = None;
+        let mut tests: Vec<(usize, Case)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let invalid = |message: String| RomError::InvalidSuite(number + 1, message);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[') {
+                let name = strip_comment(table)
+                    .strip_suffix(']')
+                    .and_then(|table| table.strip_prefix("test."))
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| invalid(format!("Unknown table [{}", table)))?;
+                if tests.iter().any(|(_, test)| test.name == name) {
+                    return Err(invalid(format!("Duplicate test {}", name)));
+                }
+                tests.push((number + 1, Case::new(name)));
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected key = value".to_string()))?;
+            let (key, value) = (key.trim(), value.trim());
+            let test = match tests.last_mut() {
+                Some((_, test)) => test,
+                None => {
+                    let path = dir.join(parse_string(value).map_err(invalid)?);
+                    program = Some(match key {
+                        "rom" => Program::Rom(path),
+                        "source" => Program::Source(path),
+                        _ => return Err(invalid(format!("Unknown setting {}", key))),
+                    });
+                    continue;
+                }
+            };
+            match key {
+                "cycles" => {
+                    test.cycles = strip_comment(value)
+                        .parse()
+                        .ok()
+                        .filter(|cycles| *cycles > 0)
+                        .ok_or_else(|| invalid(format!("Invalid cycles {}", value)))?;
+                }
+                "keys" => {
+                    for key in parse_strings(value).map_err(invalid)? {
+                        test.keys.push(
+                            u8::from_str_radix(&key, 16)
+                                .ok()
+                                .filter(|key| *key < 16)
+                                .ok_or_else(|| invalid(format!("Unknown key {}", key)))?,
+                        );
+                    }
+                }
+                "expect" => {
+                    for expr in parse_strings(value).map_err(invalid)? {
+                        let parsed = Expr::parse(&expr)
+                            .map_err(|err| invalid(format!("{} in {}", err, expr)))?;
+                        test.expect.push(parsed);
+                    }
+                }
+                "display" => {
+                    let hash = parse_string(value).map_err(invalid)?;
+                    test.display = Some(
+                        u32::from_str_radix(&hash, 16)
+                            .map_err(|_| invalid(format!("Invalid display hash {}", hash)))?,
+                    );
+                }
+                "memory" => {
+                    for bytes in parse_strings(value).map_err(invalid)? {
+                        test.memory.push(parse_memory(&bytes).map_err(invalid)?);
+                    }
+                }
+                _ => return Err(invalid(format!("Unknown setting {}", key))),
+            }
+        }
+
+        if let Some((line, test)) = tests.iter().find(|(_, test)| test.cycles == 0) {
+            return Err(RomError::InvalidSuite(
+                *line,
+                format!("Test {} has no cycles", test.name),
+            ));
+        }
+        Ok(Self {
+            program: program.ok_or(RomError::NoSuiteProgram)?,
+            tests: tests.into_iter().map(|(_, test)| test).collect(),
+        })
+    }
+
+    /// Bytes of the tested program, assembling it if the suite names a source
+    pub fn program_bytes(&self) -> RomResult<Vec<u8>> {
+        match &self.program {
+            Program::Rom(path) => super::read(path, None),
+            Program::Source(path) => {
+                let source = std::fs::read_to_string(path)?;
+                assembler::assemble(&source)
+                    .map(|assembly| assembly.bytes)
+                    .map_err(|err| RomError::Assembly(err.to_string()))
+            }
+        }
+    }
+}
+
+impl Case {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            cycles: 0,
+            keys: Vec::new(),
+            expect: Vec::new(),
+            display: None,
+            memory: Vec::new(),
+        }
+    }
+
+    /// Run `program` on a fresh machine, returning the failed assertions
+    pub fn run(&self, program: &[u8]) -> Vec<String> {
+        let mut vm = Vm::new();
+        vm.load(program.to_vec());
+        for key in self.keys.iter() {
+            vm.input.key_down(KEY_LIST[*key as usize]);
+        }
+        let mut remaining = self.cycles;
+        while remaining > 0 {
+            let frame = vm.run_frame(remaining.min(DEFAULT_CYCLES_PER_FRAME));
+            match frame.state {
+                ProgramState::Halted(_) => break,
+                ProgramState::Error(err) => return vec![err.to_string()],
+                _ => remaining -= frame.cycles,
+            }
+        }
+
+        let mut failures = Vec::new();
+        for expr in self.expect.iter() {
+            if expr.eval(&vm) == 0 {
+                failures.push(format!("Expected {}", expr));
+            }
+        }
+        if let Some(expected) = self.display {
+            let hash = display_hash(&vm.gpu);
+            if hash != expected {
+                failures.push(format!(
+                    "Display hash is {:08x}, expected {:08x}",
+                    hash, expected
+                ));
+            }
+        }
+        for (address, expected) in self.memory.iter() {
+            let end = *address as usize + expected.len();
+            match vm.dump_memory(*address..end.min(u16::MAX as usize) as u16) {
+                Ok(bytes) if &bytes == expected => (),
+                Ok(bytes) => failures.push(format!(
+                    "Memory at 0x{:03X} is {}, expected {}",
+                    address,
+                    hex(&bytes),
+                    hex(expected)
+                )),
+                Err(err) => failures.push(err.to_string()),
+            }
+        }
+        failures
+    }
+}
+
+/// Crc32 of the pixels of the current display, one byte per pixel
+pub fn display_hash(gpu: &Gpu) -> u32 {
+    let pixels: Vec<u8> = gpu.display().iter().map(|pixel| *pixel as u8).collect();
+    bps::crc32(&pixels)
+}
+
+/// Parse expected bytes such as `0x400: 00 01 02`
+fn parse_memory(text: &str) -> Result<(u16, Vec<u8>), String> {
+    let invalid = || format!("Invalid memory {}, expected address: bytes", text);
+    let (address, bytes) = text.split_once(':').ok_or_else(invalid)?;
+    let address = address.trim();
+    let address = u16::from_str_radix(address.strip_prefix("0x").unwrap_or(address), 16)
+        .map_err(|_| invalid())?;
+    let bytes = bytes
+        .split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match bytes.is_empty() {
+        true => Err(invalid()),
+        false => Ok((address, bytes)),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    bytes.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITE: &str = r#"
+        # Tests of the counter rom
+        source = "counter.asm"
+
+        [test.counts]
+        cycles = 20
+        expect = ["v0 == 3", "i >= 0x300"]
+        memory = ["0x300: 03"]
+
+        [test.key]
+        cycles = 20
+        keys = ["a"]
+        expect = ["v0 == 5"]
+        display = "00000000"
+    "#;
+
+    // ld i, 0x300; ld v0, 3; sknp va; add v0, 1; ld [i], v0; jp 0x20A
+    const PROGRAM: [u8; 12] = [
+        0xA3, 0x00, 0x60, 0x03, 0xEA, 0xA1, 0x70, 0x01, 0xF0, 0x55, 0x12, 0x0A,
+    ];
+
+    #[test]
+    fn parse() {
+        let suite = Suite::parse(SUITE, Path::new("roms")).unwrap();
+        assert_eq!(
+            suite.program,
+            Program::Source(Path::new("roms").join("counter.asm"))
+        );
+        assert_eq!(suite.tests.len(), 2);
+        assert_eq!(suite.tests[0].name, "counts");
+        assert_eq!(suite.tests[0].memory, vec![(0x300, vec![3])]);
+        assert_eq!(suite.tests[1].keys, vec![0xA]);
+        assert_eq!(suite.tests[1].display, Some(0));
+    }
+
+    #[test]
+    fn run() {
+        let suite = Suite::parse(SUITE, Path::new("")).unwrap();
+        assert_eq!(suite.tests[0].run(&PROGRAM), Vec::<String>::new());
+
+        let failures = suite.tests[1].run(&PROGRAM);
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].contains("v0"));
+        let blank = display_hash(&Gpu::new());
+        assert!(failures[1].contains(&format!("{:08x}", blank)));
+
+        // Waits for the delay timer set to 10 frames before setting v2
+        let program = [
+            0x60, 0x0A, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x62, 0x07, 0x12, 0x0C,
+        ];
+        let suite = Suite::parse(
+            "rom = \"delay.ch8\"\n[test.a]\ncycles = 220\nexpect = [\"v2 == 7\"]",
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(suite.tests[0].run(&program), Vec::<String>::new());
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text| match Suite::parse(text, Path::new("")) {
+            Err(RomError::InvalidSuite(line, _)) => line,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        assert_eq!(
+            error("rom = \"a.ch8\"\n[test.a]\ncycles = 1\nexpect = [\"v0 ==\"]"),
+            4
+        );
+        assert_eq!(error("rom = \"a.ch8\"\n[test.a]\nexpect = []"), 2);
+        assert_eq!(error("rom = \"a.ch8\"\n[test.a]\ncycles = 1\n[test.a]"), 4);
+        assert_eq!(error("rom = \"a.ch8\"\n[keys]"), 2);
+        assert_eq!(error("rom = \"a.ch8\"\n[test.a]\nmemory = [\"0x300\"]"), 3);
+        assert_eq!(error("title = \"a\""), 1);
+        assert_eq!(
+            Suite::parse("[test.a]\ncycles = 1", Path::new("")),
+            Err(RomError::NoSuiteProgram)
+        );
+    }
+}
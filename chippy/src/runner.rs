@@ -0,0 +1,143 @@
+//! Async frame runner for embedding the emulator in async applications. Enabled with either the
+//! `tokio` or `async-std` feature, if both are enabled the tokio timers are used.
+
+use crate::emu::{
+    error::VmResult,
+    frame::{Frame, DEFAULT_CYCLES_PER_FRAME},
+    input::Input,
+    vm::Vm,
+};
+use std::time::{Duration, Instant};
+
+/// Consumer of the frames produced by `run_async`.
+pub trait Sink: Send {
+    /// Called with every completed frame.
+    fn frame(&mut self, frame: &Frame);
+
+    /// Called before every frame so the sink can update the keypad state.
+    fn input(&mut self, input: &mut Input) {
+        let _ = input;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RunnerOptions {
+    pub fps: u32,
+    pub cycles_per_frame: usize,
+    /// Stop after this many frames, runs until the program stops if `None`
+    pub frame_limit: Option<usize>,
+}
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        Self {
+            fps: 60,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            frame_limit: None,
+        }
+    }
+}
+
+/// Run the vm at 60 frames per second, passing every frame to all `sinks`. Resolves when the
+/// program stops or fails.
+pub async fn run_async(vm: &mut Vm, sinks: &mut [Box<dyn Sink>]) -> VmResult<()> {
+    run_async_with(vm, sinks, RunnerOptions::default()).await
+}
+
+pub async fn run_async_with(
+    vm: &mut Vm,
+    sinks: &mut [Box<dyn Sink>],
+    options: RunnerOptions,
+) -> VmResult<()> {
+    let period = Duration::from_secs(1) / options.fps.max(1);
+    let mut number = 0;
+
+    loop {
+        if options.frame_limit == Some(number) {
+            break;
+        }
+
+        let now = Instant::now();
+
+        for sink in sinks.iter_mut() {
+            sink.input(&mut vm.input);
+        }
+
+        let mut frame = match vm
+            .frames()
+            .cycles_per_frame(options.cycles_per_frame)
+            .next()
+        {
+            Some(frame) => frame?,
+            None => break,
+        };
+        frame.number = number;
+        number += 1;
+
+        for sink in sinks.iter_mut() {
+            sink.frame(&frame);
+        }
+
+        if let Some(remaining) = period.checked_sub(now.elapsed()) {
+            sleep(remaining).await;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::emu::input::Key;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<usize>>>);
+
+    impl Sink for Recorder {
+        fn frame(&mut self, frame: &Frame) {
+            self.0.lock().unwrap().push(frame.number);
+        }
+
+        fn input(&mut self, input: &mut Input) {
+            input.key_down(Key::Five);
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_until_frame_limit() {
+        let mut vm = Vm::new();
+        vm.load(vec![0x12, 0x00]); // jp 0x200
+
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let mut sinks: Vec<Box<dyn Sink>> = vec![Box::new(Recorder(frames.clone()))];
+        let options = RunnerOptions {
+            fps: 1000,
+            frame_limit: Some(5),
+            ..RunnerOptions::default()
+        };
+
+        run_async_with(&mut vm, &mut sinks, options).await.unwrap();
+        assert_eq!(*frames.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+        assert!(vm.input.is_pressed(Key::Five as u8));
+    }
+
+    #[tokio::test]
+    async fn stops_on_error() {
+        let mut vm = Vm::new();
+        vm.load(vec![0x1F, 0xFF]); // jp 0xFFF
+
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        assert!(run_async(&mut vm, &mut sinks).await.is_err());
+    }
+}
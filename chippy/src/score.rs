@@ -0,0 +1,380 @@
+//! Best score of every rom, read from the memory where the game keeps its score.
+//!
+//! A score location is written `ADDRESS:LEN[:FORMAT]`, for example `0x2F0:3:bcd` for a score
+//! stored as three BCD digits (the output of `ld b, vx`) or `0x2F0:2:binary` for a big endian
+//! 16 bit counter. Best scores are stored per rom checksum in a text file with one
+//! `<crc32> <score>` line per rom.
+//!
+//! Games that show their score with the font usually store it with `ld b, vx` and read the digits
+//! back with `ld vx, [i]` before `ld f, vx`. `ScoreDetector` watches for that pattern to find the
+//! score without a configured location, `detect` runs a rom with random input to do so.
+
+use crate::{
+    autoplay::Autoplay,
+    debug::Inspect,
+    emu::{
+        frame::DEFAULT_CYCLES_PER_FRAME,
+        instruction::Instruction,
+        vm::{ProgramState, Vm, TIMER_PERIOD},
+    },
+    netplay::InputFrame,
+};
+use std::{collections::BTreeMap, fmt, io, path::Path, str::FromStr};
+
+/// Frames `detect` plays a rom for
+pub const DETECT_FRAMES: u64 = 60 * 60;
+
+/// Instructions after reading the digits of a `ld b, vx` target within which a `ld f, vx` counts
+/// as drawing them
+const DRAW_WINDOW: u64 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreFormat {
+    /// One decimal digit per byte, most significant digit first
+    Bcd,
+    /// Big endian unsigned integer
+    Binary,
+}
+
+impl ScoreFormat {
+    pub const VARIANTS: &'static [&'static str] = &["bcd", "binary"];
+}
+
+impl FromStr for ScoreFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bcd" => Ok(Self::Bcd),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!(
+                "Unknown score format '{}', expected one of {}",
+                s,
+                Self::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ScoreFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bcd => write!(f, "bcd"),
+            Self::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Memory range holding the score of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreLocation {
+    pub address: u16,
+    /// Number of bytes, at most 4
+    pub len: u8,
+    pub format: ScoreFormat,
+}
+
+impl ScoreLocation {
+    /// Current score, BCD bytes above 9 are read as 9
+    pub fn read(&self, target: &impl Inspect) -> u32 {
+        (0..self.len as u16)
+            .map(|offset| target.memory(self.address.wrapping_add(offset)) as u32)
+            .fold(0, |score, byte| match self.format {
+                ScoreFormat::Bcd => score * 10 + byte.min(9),
+                ScoreFormat::Binary => (score << 8) | byte,
+            })
+    }
+}
+
+impl FromStr for ScoreLocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid score location '{}', expected ADDRESS:LEN[:FORMAT]",
+                s
+            )
+        };
+        let mut parts = s.split(':');
+        let address = parts.next().ok_or_else(invalid)?;
+        let address = address.strip_prefix("0x").unwrap_or(address);
+        let address = u16::from_str_radix(address, 16).map_err(|_| invalid())?;
+        let len = parts
+            .next()
+            .and_then(|len| len.parse::<u8>().ok())
+            .filter(|len| (1..=4).contains(len))
+            .ok_or_else(invalid)?;
+        let format = match parts.next() {
+            Some(format) => format.parse()?,
+            None => ScoreFormat::Bcd,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            address,
+            len,
+            format,
+        })
+    }
+}
+
+impl fmt::Display for ScoreLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:03X}:{}:{}", self.address, self.len, self.format)
+    }
+}
+
+/// What a `ld b, vx` target was used for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Candidate {
+    writes: u32,
+    /// Number of writes that changed the stored value
+    changes: u32,
+    /// Number of times its digits were drawn with the font
+    draws: u32,
+    value: u8,
+}
+
+/// Finds the score of a game by watching the instructions it executes.
+#[derive(Debug, Clone, Default)]
+pub struct ScoreDetector {
+    candidates: BTreeMap<u16, Candidate>,
+    /// Candidate whose digits were read last, and the cycle they were read at
+    read: Option<(u16, u64)>,
+    cycle: u64,
+}
+
+impl ScoreDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look at the instruction `target` is about to execute, call it before every cycle
+    pub fn observe(&mut self, target: &impl Inspect) {
+        self.cycle += 1;
+        let pc = target.program_counter();
+        let opcode = (target.memory(pc) as u16) << 8 | target.memory(pc.wrapping_add(1)) as u16;
+        let index = target.index();
+        match Instruction::parse(opcode) {
+            Instruction::StoreBCD(register) => {
+                let value = target.register(register);
+                let candidate = self.candidates.entry(index).or_default();
+                candidate.changes += (candidate.writes > 0 && candidate.value != value) as u32;
+                candidate.writes += 1;
+                candidate.value = value;
+            }
+            Instruction::LoadRegisters(_) => {
+                self.read = self
+                    .candidates
+                    .range(index.saturating_sub(2)..=index)
+                    .next_back()
+                    .map(|(address, _)| (*address, self.cycle));
+            }
+            Instruction::SetIToFontSprite(_) | Instruction::SetIToBigFontSprite(_) => {
+                if let Some((address, cycle)) = self.read {
+                    if self.cycle - cycle <= DRAW_WINDOW {
+                        if let Some(candidate) = self.candidates.get_mut(&address) {
+                            candidate.draws += 1;
+                        }
+                    }
+                    self.read = None;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Most likely location of the score: a drawn `ld b, vx` target, preferring the ones whose
+    /// value changed most often
+    pub fn location(&self) -> Option<ScoreLocation> {
+        self.candidates
+            .iter()
+            .filter(|(_, candidate)| candidate.draws > 0)
+            .max_by_key(|(address, candidate)| {
+                (
+                    candidate.changes,
+                    candidate.writes,
+                    std::cmp::Reverse(**address),
+                )
+            })
+            .map(|(address, _)| ScoreLocation {
+                address: *address,
+                len: 3,
+                format: ScoreFormat::Bcd,
+            })
+    }
+}
+
+/// Play `rom` with random keys for `DETECT_FRAMES` frames to find its score
+pub fn detect(rom: &[u8], seed: u64) -> Option<ScoreLocation> {
+    let mut vm = Vm::new();
+    vm.load(rom.to_vec());
+    let mut autoplay = Autoplay::random(seed);
+    let mut detector = ScoreDetector::new();
+    for frame in 0..DETECT_FRAMES {
+        InputFrame {
+            frame,
+            keys: autoplay.keys(frame),
+        }
+        .apply(&mut vm.input);
+        for _ in 0..DEFAULT_CYCLES_PER_FRAME {
+            if vm.check_program_counter().is_err() {
+                return detector.location();
+            }
+            detector.observe(&vm);
+            if let Ok(ProgramState::Halted(_)) | Err(_) = vm.cycle() {
+                return detector.location();
+            }
+        }
+        vm.tick(TIMER_PERIOD);
+    }
+    detector.location()
+}
+
+/// Best score of every rom, keyed by the crc32 of the rom.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HighScores {
+    scores: BTreeMap<u32, u32>,
+}
+
+impl HighScores {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the scores from `path`, a missing file has no scores. Invalid lines are ignored.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(err) => return Err(err),
+        };
+        let scores = text
+            .lines()
+            .filter_map(|line| {
+                let (crc32, score) = line.trim().split_once(' ')?;
+                Some((
+                    u32::from_str_radix(crc32, 16).ok()?,
+                    score.trim().parse().ok()?,
+                ))
+            })
+            .collect();
+        Ok(Self { scores })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    pub fn best(&self, crc32: u32) -> Option<u32> {
+        self.scores.get(&crc32).copied()
+    }
+
+    /// Record a score, returns true if it is a new best score for the rom
+    pub fn submit(&mut self, crc32: u32, score: u32) -> bool {
+        match self.best(crc32) {
+            Some(best) if best >= score => false,
+            _ => {
+                self.scores.insert(crc32, score);
+                true
+            }
+        }
+    }
+}
+
+impl fmt::Display for HighScores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (crc32, score) in self.scores.iter() {
+            writeln!(f, "{:08x} {}", crc32, score)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn read_score() {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0x60, 0xFE, // ld v0, 254
+            0xA3, 0x00, // ld i, 0x300
+            0xF0, 0x33, // ld b, v0
+        ]);
+        for _ in 0..3 {
+            vm.cycle().unwrap();
+        }
+
+        let bcd: ScoreLocation = "300:3".parse().unwrap();
+        assert_eq!(bcd.read(&vm), 254);
+        let binary: ScoreLocation = "0x300:2:binary".parse().unwrap();
+        assert_eq!(binary.read(&vm), 0x0205);
+        assert_eq!(binary.to_string(), "0x300:2:binary");
+
+        assert!("300".parse::<ScoreLocation>().is_err());
+        assert!("300:5".parse::<ScoreLocation>().is_err());
+        assert!("300:2:hex".parse::<ScoreLocation>().is_err());
+    }
+
+    #[test]
+    fn detect_score() {
+        let rom = vec![
+            0x61, 0x00, // 200: ld v1, 0
+            0x71, 0x01, // 202: add v1, 1
+            0xA3, 0x10, // 204: ld i, 0x310      level, stored but never drawn
+            0xF1, 0x33, // 206: ld b, v1
+            0xA3, 0x00, // 208: ld i, 0x300      score
+            0xF1, 0x33, // 20A: ld b, v1
+            0xF2, 0x65, // 20C: ld v2, [i]
+            0xF0, 0x29, // 20E: ld f, v0
+            0xD0, 0x05, // 210: drw v0, v0, 5
+            0x12, 0x02, // 212: jp 0x202
+        ];
+        let location = detect(&rom, 0).unwrap();
+        assert_eq!(location, "0x300:3:bcd".parse().unwrap());
+
+        // Nothing is drawn with the font
+        assert_eq!(detect(&rom[..12], 0), None);
+
+        let paced = vec![
+            0x61, 0x00, // 200: ld v1, 0
+            0x60, 0x02, // 202: ld v0, 2
+            0xF0, 0x15, // 204: ld dt, v0
+            0xF0, 0x07, // 206: ld v0, dt
+            0x30, 0x00, // 208: se v0, 0
+            0x12, 0x06, // 20A: jp 0x206
+            0x71, 0x01, // 20C: add v1, 1
+            0xA3, 0x00, // 20E: ld i, 0x300
+            0xF1, 0x33, // 210: ld b, v1
+            0xF2, 0x65, // 212: ld v2, [i]
+            0xF0, 0x29, // 214: ld f, v0
+            0xD0, 0x05, // 216: drw v0, v0, 5
+            0x12, 0x02, // 218: jp 0x202
+        ];
+        assert_eq!(detect(&paced, 0), Some("0x300:3:bcd".parse().unwrap()));
+    }
+
+    #[test]
+    fn high_scores() {
+        let mut scores = HighScores::new();
+        assert!(scores.submit(0xABCD, 10));
+        assert!(!scores.submit(0xABCD, 5));
+        assert!(scores.submit(0xABCD, 20));
+        assert!(scores.submit(0x1, 3));
+        assert_eq!(scores.best(0xABCD), Some(20));
+        assert_eq!(scores.to_string(), "00000001 3\n0000abcd 20\n");
+
+        let path = std::env::temp_dir().join(format!("chippy-scores-{}", std::process::id()));
+        scores.save(&path).unwrap();
+        let loaded = HighScores::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, scores);
+        assert_eq!(HighScores::load(&path).unwrap(), HighScores::new());
+    }
+}
@@ -0,0 +1,143 @@
+//! What a frontend remembers of a rom between sessions, stored under a key derived from the rom
+//! checksum so a session follows the rom whatever its file name or URL. The record is plain text,
+//! one setting per line, which fits browser storage as well as a file.
+//!
+//! ```text
+//! palette ffb000:281800
+//! keymap colemak
+//! state 0a1b2c...
+//! ```
+
+use crate::{emu::state::VmState, render::Palette};
+use std::{fmt, str::FromStr};
+
+/// Key of the session of the rom with `checksum`, see `rom::checksum`
+pub fn storage_key(checksum: u32) -> String {
+    format!("chippy-session-{:08x}", checksum)
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Session {
+    pub palette: Option<Palette>,
+    /// Name of the keyboard layout of the frontend
+    pub keymap: Option<String>,
+    /// Save state to resume from
+    pub state: Option<VmState>,
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(palette) = &self.palette {
+            writeln!(f, "palette {}", palette)?;
+        }
+        if let Some(keymap) = &self.keymap {
+            writeln!(f, "keymap {}", keymap)?;
+        }
+        if let Some(state) = &self.state {
+            writeln!(f, "state {}", to_hex(&state.encode()))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Session {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut session = Session::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+            match name {
+                "palette" => session.palette = Some(value.parse()?),
+                "keymap" => session.keymap = Some(value.to_string()),
+                "state" => {
+                    let state = from_hex(value)
+                        .as_deref()
+                        .and_then(|bytes| VmState::decode(bytes).ok())
+                        .ok_or_else(|| "Invalid save state".to_string())?;
+                    session.state = Some(state);
+                }
+                _ => return Err(format!("Unknown session setting: {}", name)),
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// Value of the parameter `name` of a URL query such as `?rom=roms%2Fpong.ch8&palette=white`,
+/// so shared links can name the rom to load
+pub fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| percent_decode(value))
+}
+
+/// Decode `%XX` escapes and `+` as a space
+fn percent_decode(src: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(src.len());
+    let mut iter = src.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b'+' => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(src: &str) -> Option<Vec<u8>> {
+    if !src.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..src.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(src.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    #[test]
+    fn session_round_trip() {
+        let mut vm = Vm::new();
+        vm.load(vec![0x60, 0x05, 0x12, 0x02]);
+        vm.cycle().unwrap();
+        let session = Session {
+            palette: Some("white".parse().unwrap()),
+            keymap: Some("colemak".to_string()),
+            state: Some(vm.snapshot()),
+        };
+        let text = session.to_string();
+        assert!(text.starts_with("palette "));
+        assert_eq!(text.parse::<Session>(), Ok(session));
+
+        assert_eq!("".parse::<Session>(), Ok(Session::default()));
+        assert!("state 0g".parse::<Session>().is_err());
+        assert!("volume 3".parse::<Session>().is_err());
+        assert_eq!(storage_key(0xA1B2), "chippy-session-0000a1b2");
+    }
+
+    #[test]
+    fn query_params() {
+        let query = "?rom=roms%2Fpong.ch8&palette=white&title=Pong+2";
+        assert_eq!(query_param(query, "rom").as_deref(), Some("roms/pong.ch8"));
+        assert_eq!(query_param(query, "title").as_deref(), Some("Pong 2"));
+        assert_eq!(query_param(query, "speed"), None);
+        assert_eq!(query_param("rom=%2", "rom"), None);
+    }
+}
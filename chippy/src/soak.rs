@@ -0,0 +1,186 @@
+//! Stability harness running random roms with random input while checking the machine
+//! invariants after every instruction. Every rom and its input is generated from a seed, so a
+//! failure is reproduced by running the same seed again.
+
+use crate::{
+    debug::Inspect,
+    emu::{
+        frame::DEFAULT_CYCLES_PER_FRAME,
+        input::KEY_LIST,
+        vm::{ProgramState, Vm, MEMORY_SIZE, STACK_SIZE, TIMER_PERIOD},
+    },
+};
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Size of the generated roms in bytes
+pub const ROM_SIZE: usize = 512;
+
+/// Invariant broken while running a rom.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    Panic(String),
+    ProgramCounter(u16),
+    StackPointer(usize),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Panic(message) => write!(f, "panic: {}", message),
+            Violation::ProgramCounter(pc) => write!(f, "program counter out of range: {:#06X}", pc),
+            Violation::StackPointer(sp) => write!(f, "stack pointer out of range: {}", sp),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub seed: u64,
+    /// Number of instructions executed before the violation
+    pub cycle: usize,
+    pub violation: Violation,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {} failed after {} cycles, {}",
+            self.seed, self.cycle, self.violation
+        )
+    }
+}
+
+/// xorshift64 generator, good enough for test data and reproducible from its seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // A zero state would only ever produce zeros
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Rom of random bytes generated from `seed`
+pub fn random_rom(seed: u64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    (0..ROM_SIZE).map(|_| rng.next_u64() as u8).collect()
+}
+
+/// Run the random rom of `seed` for `cycles` instructions
+pub fn run_seed(seed: u64, cycles: usize) -> Result<usize, Failure> {
+    run_rom(&random_rom(seed), seed, cycles)
+}
+
+/// Run `rom` for `cycles` instructions, pressing random keys generated from `seed`. The timers
+/// count down once every `DEFAULT_CYCLES_PER_FRAME` instructions. Returns the number of
+/// instructions executed, fewer than `cycles` if the program stopped or reached an instruction
+/// that can not execute.
+pub fn run_rom(rom: &[u8], seed: u64, cycles: usize) -> Result<usize, Failure> {
+    let mut vm = Vm::new();
+    vm.load(rom.to_vec());
+    let mut rng = Rng::new(seed.rotate_left(32));
+    let mut cycle = 0;
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        while cycle < cycles {
+            // Change the pressed key every 64 instructions on average
+            let random = rng.next_u64();
+            if random & 63 == 0 {
+                vm.input.clear();
+                if let Some(key) = KEY_LIST.get((random >> 8) as usize % (KEY_LIST.len() + 1)) {
+                    vm.input.key_down(*key);
+                }
+            }
+
+            if let Ok(ProgramState::Halted(_)) | Err(_) = vm.cycle() {
+                return Ok(());
+            }
+            cycle += 1;
+            check(&vm)?;
+            if cycle % DEFAULT_CYCLES_PER_FRAME == 0 {
+                vm.tick(TIMER_PERIOD);
+            }
+        }
+        Ok(())
+    }));
+
+    let violation = match result {
+        Ok(Ok(())) => return Ok(cycle),
+        Ok(Err(violation)) => violation,
+        Err(payload) => Violation::Panic(panic_message(payload.as_ref())),
+    };
+    Err(Failure {
+        seed,
+        cycle,
+        violation,
+    })
+}
+
+/// Message of a caught panic
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+pub(crate) fn check(vm: &impl Inspect) -> Result<(), Violation> {
+    if vm.program_counter() as usize + 1 >= MEMORY_SIZE {
+        return Err(Violation::ProgramCounter(vm.program_counter()));
+    }
+    if vm.stack_pointer() > STACK_SIZE {
+        return Err(Violation::StackPointer(vm.stack_pointer()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        assert_eq!(random_rom(7), random_rom(7));
+        assert_ne!(random_rom(7), random_rom(8));
+        assert_eq!(run_seed(7, 1000), run_seed(7, 1000));
+    }
+
+    #[test]
+    fn violations() {
+        // jp 0x200
+        assert_eq!(run_rom(&[0x12, 0x00], 0, 1000), Ok(1000));
+
+        // jp 0xFFF
+        let failure = run_rom(&[0x1F, 0xFF], 3, 1000).unwrap_err();
+        assert_eq!(failure.violation, Violation::ProgramCounter(0xFFF));
+        assert_eq!((failure.seed, failure.cycle), (3, 1));
+
+        // call 0x200 until the stack is full, the overflow is an error rather than a panic
+        assert_eq!(run_rom(&[0x22, 0x00], 0, 1000), Ok(STACK_SIZE));
+
+        // Overflow the stack once the delay timer ran out
+        let rom = [
+            0x60, 0x0A, // 200: ld v0, 10
+            0xF0, 0x15, // 202: ld dt, v0
+            0xF1, 0x07, // 204: ld v1, dt
+            0x31, 0x00, // 206: se v1, 0
+            0x12, 0x04, // 208: jp 0x204
+            0x22, 0x0A, // 20A: call 0x20A
+        ];
+        assert!(run_rom(&rom, 0, 10_000).unwrap() < 10_000);
+    }
+}
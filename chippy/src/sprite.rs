@@ -0,0 +1,114 @@
+//! Convert monochrome bitmaps into sprites that can be drawn with `drw`.
+
+use std::fmt::Write;
+
+/// Sprites are one byte wide
+pub const SPRITE_WIDTH: usize = 8;
+/// Most rows a single `drw` instruction can draw
+pub const MAX_SPRITE_HEIGHT: usize = 15;
+
+/// Part of a bitmap that fits in a single sprite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    /// Offset in pixels of the sprite from the left of the bitmap
+    pub x: usize,
+    /// Offset in pixels of the sprite from the top of the bitmap
+    pub y: usize,
+    /// One byte per row, the most significant bit is the leftmost pixel
+    pub rows: Vec<u8>,
+}
+
+/// Split a bitmap of `width` by `height` pixels, stored row by row, into sprites. The bitmap is
+/// cut into 8 pixel wide columns and each column into chunks of at most 15 rows. Pixels past
+/// the right edge of the bitmap are left unset.
+pub fn from_bitmap(width: usize, height: usize, pixels: &[bool]) -> Vec<Sprite> {
+    assert_eq!(pixels.len(), width * height, "bitmap size does not match");
+
+    let mut sprites = Vec::new();
+    for x in (0..width).step_by(SPRITE_WIDTH) {
+        for y in (0..height).step_by(MAX_SPRITE_HEIGHT) {
+            let rows = (y..height.min(y + MAX_SPRITE_HEIGHT))
+                .map(|row| {
+                    (0..SPRITE_WIDTH)
+                        .filter(|col| x + col < width && pixels[row * width + x + col])
+                        .fold(0u8, |byte, col| byte | 0x80 >> col)
+                })
+                .collect();
+            sprites.push(Sprite { x, y, rows });
+        }
+    }
+    sprites
+}
+
+/// Concatenate the rows of every sprite
+pub fn to_bytes(sprites: &[Sprite]) -> Vec<u8> {
+    sprites
+        .iter()
+        .flat_map(|sprite| sprite.rows.iter().copied())
+        .collect()
+}
+
+/// Format sprites as `db` blocks for the assembler, one row per line. Each block is labeled
+/// `{name}_{x}_{y}` or just `name` if there is a single sprite.
+pub fn to_asm(sprites: &[Sprite], name: &str) -> String {
+    let mut asm = String::new();
+    for sprite in sprites {
+        match sprites.len() {
+            1 => writeln!(asm, "{}:", name),
+            _ => writeln!(asm, "{}_{}_{}:", name, sprite.x, sprite.y),
+        }
+        .unwrap();
+        for row in sprite.rows.iter() {
+            writeln!(asm, "    db 0b{:08b}", row).unwrap();
+        }
+    }
+    asm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    fn bitmap(art: &[&str]) -> (usize, usize, Vec<bool>) {
+        let pixels = art
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect();
+        (art[0].len(), art.len(), pixels)
+    }
+
+    #[test]
+    fn single_sprite() {
+        let (width, height, pixels) = bitmap(&["#..#", ".##.", "#..#"]);
+        let sprites = from_bitmap(width, height, &pixels);
+        assert_eq!(
+            sprites,
+            vec![Sprite {
+                x: 0,
+                y: 0,
+                rows: vec![0b10010000, 0b01100000, 0b10010000]
+            }]
+        );
+
+        let asm = to_asm(&sprites, "cross");
+        let assembly = parser::assemble(&asm).unwrap();
+        assert_eq!(assembly.labels["cross"], 0x200);
+        assert_eq!(assembly.bytes, to_bytes(&sprites));
+    }
+
+    #[test]
+    fn split_into_columns_and_chunks() {
+        let pixels = vec![true; 10 * 20];
+        let sprites = from_bitmap(10, 20, &pixels);
+
+        let layout: Vec<(usize, usize, usize)> = sprites
+            .iter()
+            .map(|sprite| (sprite.x, sprite.y, sprite.rows.len()))
+            .collect();
+        assert_eq!(layout, vec![(0, 0, 15), (0, 15, 5), (8, 0, 15), (8, 15, 5)]);
+        assert!(sprites[0].rows.iter().all(|row| *row == 0xFF));
+        assert!(sprites[2].rows.iter().all(|row| *row == 0b11000000));
+        assert!(to_asm(&sprites, "block").contains("block_8_15:"));
+    }
+}
@@ -0,0 +1,246 @@
+//! HTTP status endpoint of the server modes, so that a long running session can be monitored.
+//! `GET /metrics` answers in the Prometheus text format, any other request with a JSON object.
+//!
+//! ```text
+//! {"uptime":3600.0,"clients":2,"rom":"a1b2c3d4","ips":660.0,"frames":216000,"dropped_frames":3}
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+/// Time over which the instructions per second are measured
+const IPS_WINDOW: Duration = Duration::from_secs(1);
+/// Clients that do not send their request or take the answer in this time are dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Figures of a running session, updated by the frontend.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    started: Instant,
+    /// Checksum of the loaded rom, see `rom::checksum`
+    pub rom_checksum: u32,
+    /// Clients connected to the servers of the session
+    pub clients: usize,
+    /// Frames skipped because the emulator ran late
+    pub dropped_frames: u64,
+    frames: u64,
+    instructions: u64,
+    /// Start of the current ips window and the instructions executed before it
+    window: (Instant, u64),
+    ips: f64,
+}
+
+impl Metrics {
+    pub fn new(rom_checksum: u32) -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            rom_checksum,
+            clients: 0,
+            dropped_frames: 0,
+            frames: 0,
+            instructions: 0,
+            window: (now, 0),
+            ips: 0.0,
+        }
+    }
+
+    /// Count a frame that executed `instructions`
+    pub fn frame(&mut self, instructions: usize) {
+        self.frame_at(Instant::now(), instructions);
+    }
+
+    fn frame_at(&mut self, now: Instant, instructions: usize) {
+        self.frames += 1;
+        self.instructions += instructions as u64;
+        let (start, before) = self.window;
+        let elapsed = now.saturating_duration_since(start);
+        if elapsed >= IPS_WINDOW {
+            self.ips = (self.instructions - before) as f64 / elapsed.as_secs_f64();
+            self.window = (now, self.instructions);
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Instructions per second over the last second
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The metrics as a single line JSON object
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"uptime":{:.1},"clients":{},"rom":"{:08x}","ips":{:.1},"frames":{},"dropped_frames":{}}}"#,
+            self.uptime().as_secs_f64(),
+            self.clients,
+            self.rom_checksum,
+            self.ips,
+            self.frames,
+            self.dropped_frames
+        )
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let metrics: [(&str, &str, &str, String); 5] = [
+            (
+                "chippy_uptime_seconds",
+                "gauge",
+                "Time since the session started",
+                format!("{:.1}", self.uptime().as_secs_f64()),
+            ),
+            (
+                "chippy_clients",
+                "gauge",
+                "Connected clients",
+                self.clients.to_string(),
+            ),
+            (
+                "chippy_instructions_per_second",
+                "gauge",
+                "Instructions executed per second",
+                format!("{:.1}", self.ips),
+            ),
+            (
+                "chippy_frames_total",
+                "counter",
+                "Frames emulated",
+                self.frames.to_string(),
+            ),
+            (
+                "chippy_dropped_frames_total",
+                "counter",
+                "Frames skipped because the emulator ran late",
+                self.dropped_frames.to_string(),
+            ),
+        ];
+        let mut text = format!(
+            "# HELP chippy_rom_info Checksum of the loaded rom\n\
+             # TYPE chippy_rom_info gauge\n\
+             chippy_rom_info{{checksum=\"{:08x}\"}} 1\n",
+            self.rom_checksum
+        );
+        for (name, kind, help, value) in metrics.iter() {
+            text += &format!(
+                "# HELP {0} {1}\n# TYPE {0} {2}\n{0} {3}\n",
+                name, help, kind, value
+            );
+        }
+        text
+    }
+}
+
+/// HTTP server answering status requests between frames.
+pub struct StatusServer {
+    listener: TcpListener,
+}
+
+impl StatusServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answer the pending requests with `metrics`, call once per frame
+    pub fn serve(&mut self, metrics: &Metrics) -> io::Result<()> {
+        loop {
+            let mut client = match self.listener.accept() {
+                Ok((client, _)) => client,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            client.set_nonblocking(false)?;
+            client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+            client.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+            let mut request = [0; 1024];
+            let len = client.read(&mut request).unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..len]);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (content_type, body) = match path {
+                "/metrics" => ("text/plain; version=0.0.4", metrics.to_prometheus()),
+                _ => ("application/json", metrics.to_json()),
+            };
+            let response = format!(
+                "HTTP/1.0 200 OK\r\nCache-Control: no-cache\r\nContent-Type: {}\r\n\
+                 Content-Length: {}\r\n\r\n{}",
+                content_type,
+                body.len(),
+                body
+            );
+            // A client gone before the answer does not stop the session
+            let _ = client.write_all(response.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    #[test]
+    fn instructions_per_second() {
+        let mut metrics = Metrics::new(0xA1B2C3D4);
+        let start = metrics.started;
+        for frame in 1..=60 {
+            metrics.frame_at(start + IPS_WINDOW * frame / 60, 10);
+        }
+        assert_eq!(metrics.frames(), 60);
+        assert!((metrics.ips() - 600.0).abs() < 0.1);
+
+        metrics.frame_at(start + IPS_WINDOW * 3, 10);
+        assert!((metrics.ips() - 5.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn formats() {
+        let mut metrics = Metrics::new(0xA1B2C3D4);
+        metrics.clients = 2;
+        metrics.dropped_frames = 3;
+        let json = metrics.to_json();
+        assert!(json.starts_with(r#"{"uptime":"#));
+        assert!(json
+            .ends_with(r#""clients":2,"rom":"a1b2c3d4","ips":0.0,"frames":0,"dropped_frames":3}"#));
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains("chippy_rom_info{checksum=\"a1b2c3d4\"} 1\n"));
+        assert!(text.contains("# TYPE chippy_clients gauge\nchippy_clients 2\n"));
+        assert!(text.contains("chippy_dropped_frames_total 3\n"));
+    }
+
+    #[test]
+    fn serve_requests() {
+        let mut server = StatusServer::bind("127.0.0.1:0").unwrap();
+        let metrics = Metrics::new(0);
+        let mut client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        while response.is_empty() {
+            server.serve(&metrics).unwrap();
+            client
+                .set_read_timeout(Some(Duration::from_millis(10)))
+                .unwrap();
+            let _ = client.read_to_string(&mut response);
+        }
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(response.contains("Content-Type: text/plain"));
+        assert!(response.ends_with("chippy_dropped_frames_total 0\n"));
+    }
+}
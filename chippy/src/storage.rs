@@ -0,0 +1,270 @@
+//! Where frontends keep what outlives a run: save states, the RPL flags of the HP48 roms, best
+//! scores and the settings of the last session. Every blob is keyed by the checksum of its rom
+//! and its kind, so a frontend can keep them in files, browser storage or anything else by
+//! implementing `Storage`.
+
+use crate::{emu::state::VmState, session::Session};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    /// Save state of a numbered slot
+    State(u8),
+    /// RPL user flags written by `ld r, vx`
+    Flags,
+    /// Best score, in decimal
+    HighScore,
+    /// Text of a `Session`
+    Session,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::State(slot) => write!(f, "state{}", slot),
+            Kind::Flags => f.write_str("flags"),
+            Kind::HighScore => f.write_str("score"),
+            Kind::Session => f.write_str("session"),
+        }
+    }
+}
+
+/// Key of a blob, written `a1b2c3d4.state0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key {
+    /// Checksum of the rom, see `rom::checksum`
+    pub checksum: u32,
+    pub kind: Kind,
+}
+
+impl Key {
+    pub fn new(checksum: u32, kind: Kind) -> Self {
+        Self { checksum, kind }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}.{}", self.checksum, self.kind)
+    }
+}
+
+fn invalid(key: &Key) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}", key))
+}
+
+/// Blobs keyed by rom and kind. The typed methods are built on `get` and `put`.
+pub trait Storage {
+    /// The blob of `key`, `None` if there is none
+    fn get(&self, key: &Key) -> io::Result<Option<Vec<u8>>>;
+
+    fn put(&mut self, key: &Key, value: &[u8]) -> io::Result<()>;
+
+    /// Drop the blob of `key`, if any
+    fn remove(&mut self, key: &Key) -> io::Result<()>;
+
+    fn load_state(&self, checksum: u32, slot: u8) -> io::Result<Option<VmState>> {
+        let key = Key::new(checksum, Kind::State(slot));
+        match self.get(&key)? {
+            Some(bytes) => VmState::decode(&bytes).map(Some).map_err(|_| invalid(&key)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_state(&mut self, checksum: u32, slot: u8, state: &VmState) -> io::Result<()> {
+        self.put(&Key::new(checksum, Kind::State(slot)), &state.encode())
+    }
+
+    fn load_flags(&self, checksum: u32) -> io::Result<Option<Vec<u8>>> {
+        self.get(&Key::new(checksum, Kind::Flags))
+    }
+
+    fn save_flags(&mut self, checksum: u32, flags: &[u8]) -> io::Result<()> {
+        self.put(&Key::new(checksum, Kind::Flags), flags)
+    }
+
+    fn best_score(&self, checksum: u32) -> io::Result<Option<u32>> {
+        let key = Key::new(checksum, Kind::HighScore);
+        match self.get(&key)? {
+            Some(bytes) => std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|text| text.trim().parse().ok())
+                .map(Some)
+                .ok_or_else(|| invalid(&key)),
+            None => Ok(None),
+        }
+    }
+
+    /// Record a score, returns true if it is a new best score for the rom
+    fn submit_score(&mut self, checksum: u32, score: u32) -> io::Result<bool> {
+        match self.best_score(checksum)? {
+            Some(best) if best >= score => Ok(false),
+            _ => {
+                let key = Key::new(checksum, Kind::HighScore);
+                self.put(&key, score.to_string().as_bytes())?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn load_session(&self, checksum: u32) -> io::Result<Option<Session>> {
+        let key = Key::new(checksum, Kind::Session);
+        match self.get(&key)? {
+            Some(bytes) => std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|text| text.parse().ok())
+                .map(Some)
+                .ok_or_else(|| invalid(&key)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_session(&mut self, checksum: u32, session: &Session) -> io::Result<()> {
+        self.put(
+            &Key::new(checksum, Kind::Session),
+            session.to_string().as_bytes(),
+        )
+    }
+}
+
+/// Blobs kept in memory, for tests and frontends without persistence.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryStorage {
+    blobs: BTreeMap<Key, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &Key) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.blobs.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &Key, value: &[u8]) -> io::Result<()> {
+        self.blobs.insert(*key, value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &Key) -> io::Result<()> {
+        self.blobs.remove(key);
+        Ok(())
+    }
+}
+
+/// A file per blob in a directory, named after the key. The directory is created on the first
+/// write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File of the blob of `key`
+    pub fn path(&self, key: &Key) -> PathBuf {
+        self.dir.join(key.to_string())
+    }
+}
+
+impl Storage for FileStorage {
+    fn get(&self, key: &Key) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn put(&mut self, key: &Key, value: &[u8]) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(key), value)
+    }
+
+    fn remove(&mut self, key: &Key) -> io::Result<()> {
+        match std::fs::remove_file(self.path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emu::vm::Vm;
+
+    fn round_trip(storage: &mut impl Storage) {
+        let mut vm = Vm::new();
+        vm.load(vec![0x60, 0x05, 0x12, 0x02]);
+        vm.cycle().unwrap();
+        assert_eq!(storage.load_state(0xA1B2, 0).unwrap(), None);
+        storage.save_state(0xA1B2, 0, &vm.snapshot()).unwrap();
+        assert_eq!(storage.load_state(0xA1B2, 0).unwrap(), Some(vm.snapshot()));
+        assert_eq!(storage.load_state(0xA1B2, 1).unwrap(), None);
+        assert_eq!(storage.load_state(0xC3D4, 0).unwrap(), None);
+
+        storage.save_flags(0xA1B2, &[1, 2, 3]).unwrap();
+        assert_eq!(storage.load_flags(0xA1B2).unwrap(), Some(vec![1, 2, 3]));
+
+        assert!(storage.submit_score(0xA1B2, 120).unwrap());
+        assert!(!storage.submit_score(0xA1B2, 90).unwrap());
+        assert_eq!(storage.best_score(0xA1B2).unwrap(), Some(120));
+
+        let session = Session {
+            keymap: Some("colemak".to_string()),
+            ..Session::default()
+        };
+        storage.save_session(0xA1B2, &session).unwrap();
+        assert_eq!(storage.load_session(0xA1B2).unwrap(), Some(session));
+
+        let key = Key::new(0xA1B2, Kind::HighScore);
+        storage.put(&key, b"many").unwrap();
+        assert!(storage.best_score(0xA1B2).is_err());
+        storage.remove(&key).unwrap();
+        storage.remove(&key).unwrap();
+        assert_eq!(storage.best_score(0xA1B2).unwrap(), None);
+    }
+
+    #[test]
+    fn memory_storage() {
+        let mut storage = MemoryStorage::new();
+        round_trip(&mut storage);
+        assert_eq!(storage.len(), 3);
+    }
+
+    #[test]
+    fn file_storage() {
+        let dir = std::env::temp_dir().join(format!("chippy-storage-{}", std::process::id()));
+        let mut storage = FileStorage::new(dir.join("saves"));
+        round_trip(&mut storage);
+        assert_eq!(
+            storage.path(&Key::new(0xA1B2, Kind::State(3))),
+            dir.join("saves").join("0000a1b2.state3")
+        );
+        assert!(dir.join("saves").join("0000a1b2.flags").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
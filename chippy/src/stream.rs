@@ -0,0 +1,135 @@
+//! Frames served as an MJPEG stream over HTTP, so streaming software can capture the display
+//! as a media source. Enabled with the `jpeg-encoder` feature.
+
+use jpeg_encoder::{ColorType, Encoder};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+const BOUNDARY: &str = "chippyframe";
+/// Quality of the encoded frames, the display has few colors and compresses well
+const QUALITY: u8 = 90;
+/// Clients too slow to take a frame in this time are dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// HTTP server sending every frame to all connected clients.
+pub struct MjpegServer {
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+}
+
+impl MjpegServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Accept new clients and send them an RGBA frame of `width` by `height` pixels. Nothing is
+    /// encoded while no client is connected.
+    pub fn send(&mut self, rgba: &[u8], width: u16, height: u16) -> io::Result<()> {
+        self.accept()?;
+        if self.clients.is_empty() {
+            return Ok(());
+        }
+
+        let mut jpeg = Vec::new();
+        Encoder::new(&mut jpeg, QUALITY)
+            .encode(rgba, width, height, ColorType::Rgba)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        let mut part = format!(
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            BOUNDARY,
+            jpeg.len()
+        )
+        .into_bytes();
+        part.extend_from_slice(&jpeg);
+        part.extend_from_slice(b"\r\n");
+
+        self.clients
+            .retain_mut(|client| client.write_all(&part).is_ok());
+        Ok(())
+    }
+
+    fn accept(&mut self) -> io::Result<()> {
+        loop {
+            let mut client = match self.listener.accept() {
+                Ok((client, _)) => client,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            // Any request gets the stream, the request itself is not needed
+            client.set_nonblocking(true)?;
+            let _ = client.read(&mut [0; 1024]);
+            client.set_nonblocking(false)?;
+            client.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            let header = format!(
+                "HTTP/1.0 200 OK\r\nCache-Control: no-cache\r\n\
+                 Content-Type: multipart/x-mixed-replace; boundary={}\r\n\r\n",
+                BOUNDARY
+            );
+            if client.write_all(header.as_bytes()).is_ok() {
+                self.clients.push(client);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{self, Palette};
+    use std::io::BufRead;
+
+    #[test]
+    fn stream_frames() {
+        let mut server = MjpegServer::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+        let display = [true; 64 * 32];
+        let frame = render::to_rgba(&display, 1, Palette::default());
+        while server.clients() == 0 {
+            server.send(&frame, 64, 32).unwrap();
+        }
+        server.send(&frame, 64, 32).unwrap();
+
+        let mut reader = io::BufReader::new(client);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "HTTP/1.0 200 OK\r\n");
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, format!("--{}\r\n", BOUNDARY));
+
+        let mut length = 0;
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut jpeg = vec![0; length];
+        reader.read_exact(&mut jpeg).unwrap();
+        assert_eq!(&jpeg[..2], &[0xFF, 0xD8]);
+        assert_eq!(&jpeg[length - 2..], &[0xFF, 0xD9]);
+    }
+}
@@ -0,0 +1,162 @@
+//! Assertions for tests of chip8 programs. The expected cpu state is written as JSON in the
+//! format of `VmState::to_json`, so it can be copied from a run or attached to a bug report.
+//!
+//! ```
+//! # use chippy::{emu::vm::Vm, testing::assert_cpu_state};
+//! let mut vm = Vm::new();
+//! vm.load(vec![0x60, 0x07]); // ld v0, 0x07
+//! vm.cycle().unwrap();
+//! assert_cpu_state(
+//!     &vm.snapshot(),
+//!     r#"{
+//!   "registers": [7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+//!   "index": 0,
+//!   "program_counter": 514,
+//!   "stack": [],
+//!   "delay_timer": 0,
+//!   "sound_timer": 0
+//! }"#,
+//! );
+//! ```
+
+use crate::emu::{bus::Bus, state::VmState, vm::Vm};
+
+/// Heap allocations made by the current thread, counted for the tests of the code that must not
+/// allocate such as `Vm::cycle` and the renderer helpers.
+#[cfg(test)]
+pub(crate) mod allocations {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    struct Counter;
+
+    thread_local! {
+        static COUNT: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for Counter {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            COUNT.with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            COUNT.with(|count| count.set(count.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static COUNTER: Counter = Counter;
+
+    /// Number of allocations made while running `f`
+    pub(crate) fn count(f: impl FnOnce()) -> usize {
+        let before = COUNT.with(Cell::get);
+        f();
+        COUNT.with(Cell::get) - before
+    }
+}
+
+/// Fields of the cpu state that differ between `actual` and `expected`
+pub fn cpu_differences(actual: &VmState, expected: &VmState) -> Vec<&'static str> {
+    let live = |state: &VmState| state.stack[..state.stack_pointer.min(state.stack.len())].to_vec();
+    [
+        ("registers", actual.registers == expected.registers),
+        ("index", actual.index == expected.index),
+        (
+            "program_counter",
+            actual.program_counter == expected.program_counter,
+        ),
+        ("stack", live(actual) == live(expected)),
+        ("delay_timer", actual.delay_timer == expected.delay_timer),
+        ("sound_timer", actual.sound_timer == expected.sound_timer),
+    ]
+    .iter()
+    .filter(|(_, same)| !same)
+    .map(|(field, _)| *field)
+    .collect()
+}
+
+/// Panic unless the registers, index, program counter, stack and timers of `actual` match the
+/// JSON state `expected`
+pub fn assert_cpu_state(actual: &VmState, expected: &str) {
+    let expected = match VmState::from_json(expected) {
+        Ok(expected) => expected,
+        Err(e) => panic!("Invalid expected state: {}", e),
+    };
+    let differences = cpu_differences(actual, &expected);
+    if !differences.is_empty() {
+        panic!(
+            "Cpu state differs in {}\nexpected: {}actual: {}",
+            differences.join(", "),
+            expected.to_json(),
+            actual.to_json()
+        );
+    }
+}
+
+/// `assert_cpu_state` on the current state of `vm`
+pub fn assert_vm_state<B: Bus>(vm: &Vm<B>, expected: &str) {
+    assert_cpu_state(&vm.snapshot(), expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm() -> Vm {
+        let mut vm = Vm::new();
+        vm.load(vec![
+            0xA3, 0x00, // ld i, 0x300
+            0x22, 0x06, // call 0x206
+            0x00, 0x00, // sys
+            0x6F, 0x01, // ld vf, 0x01
+        ]);
+        for _ in 0..3 {
+            vm.cycle().unwrap();
+        }
+        vm
+    }
+
+    #[test]
+    fn matching_state() {
+        let vm = vm();
+        assert_vm_state(&vm, &vm.snapshot().to_json());
+        assert!(cpu_differences(&vm.snapshot(), &vm.snapshot()).is_empty());
+    }
+
+    #[test]
+    fn differences() {
+        let vm = vm();
+        let mut expected = vm.snapshot();
+        expected.registers[0xF] = 0;
+        expected.stack_pointer = 0;
+        // Entries above the stack pointer are not compared
+        expected.stack[5] = 0x123;
+        assert_eq!(
+            cpu_differences(&vm.snapshot(), &expected),
+            vec!["registers", "stack"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cpu state differs in program_counter")]
+    fn mismatch_panics() {
+        let vm = vm();
+        let mut expected = vm.snapshot();
+        expected.program_counter = 0x200;
+        assert_vm_state(&vm, &expected.to_json());
+    }
+
+    #[test]
+    fn count_allocations() {
+        assert_eq!(allocations::count(|| drop(vec![1u8; 4])), 1);
+        assert_eq!(allocations::count(|| drop(Vec::<u8>::new())), 0);
+    }
+}
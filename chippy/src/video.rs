@@ -0,0 +1,154 @@
+//! Video recording by piping RGBA frames to an external `ffmpeg` process. The container and
+//! codec are picked by ffmpeg from the extension of the output file.
+
+use crate::wav::WavRecorder;
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+/// Program started to encode the video, looked up in `PATH`
+pub const FFMPEG: &str = "ffmpeg";
+
+pub struct VideoRecorder {
+    output: PathBuf,
+    /// Video without sound, muxed with the audio into `output` by `finish`
+    video: PathBuf,
+    ffmpeg: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl VideoRecorder {
+    /// Start encoding frames of `width` by `height` RGBA pixels at `fps` frames per second
+    pub fn start(
+        output: impl AsRef<Path>,
+        fps: u32,
+        width: usize,
+        height: usize,
+    ) -> io::Result<Self> {
+        let output = output.as_ref().to_path_buf();
+        let video = silent_path(&output);
+        let mut ffmpeg = Command::new(FFMPEG)
+            .args(encode_args(&video, fps, width, height))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = ffmpeg.stdin.take();
+        Ok(Self {
+            output,
+            video,
+            ffmpeg,
+            stdin,
+        })
+    }
+
+    /// Add a frame, must be `width * height * 4` bytes
+    pub fn push(&mut self, rgba: &[u8]) -> io::Result<()> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.write_all(rgba),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "ffmpeg stopped")),
+        }
+    }
+
+    /// Wait for the video to be encoded and add the recorded `audio` to it
+    pub fn finish(mut self, audio: Option<&WavRecorder>) -> io::Result<()> {
+        drop(self.stdin.take());
+        check(self.ffmpeg.wait()?)?;
+
+        let audio = match audio {
+            Some(audio) => audio,
+            None => return std::fs::rename(&self.video, &self.output),
+        };
+        let wav = self.output.with_extension("wav.tmp");
+        let mut bytes = Vec::new();
+        audio.write(&mut bytes)?;
+        std::fs::write(&wav, bytes)?;
+
+        let status = Command::new(FFMPEG)
+            .args(mux_args(&self.video, &wav, &self.output))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        let _ = std::fs::remove_file(&wav);
+        let _ = std::fs::remove_file(&self.video);
+        check(status?)
+    }
+}
+
+fn check(status: std::process::ExitStatus) -> io::Result<()> {
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("ffmpeg failed with {}", status))),
+    }
+}
+
+/// `out.mp4` becomes `out.silent.mp4`, keeping the extension ffmpeg picks the format from
+fn silent_path(output: &Path) -> PathBuf {
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_extension(format!("silent.{}", extension))
+}
+
+fn encode_args(video: &Path, fps: u32, width: usize, height: usize) -> Vec<String> {
+    let size = format!("{}x{}", width, height);
+    let fps = fps.to_string();
+    let args = [
+        "-y",
+        "-loglevel",
+        "error",
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        "rgba",
+        "-s",
+        &size,
+        "-r",
+        &fps,
+        "-i",
+        "-",
+        "-pix_fmt",
+        "yuv420p",
+    ];
+    let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    args.push(video.to_string_lossy().into_owned());
+    args
+}
+
+fn mux_args(video: &Path, wav: &Path, output: &Path) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-loglevel", "error", "-i"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    args.push(video.to_string_lossy().into_owned());
+    args.extend(["-f", "wav", "-i"].iter().map(|arg| arg.to_string()));
+    args.push(wav.to_string_lossy().into_owned());
+    args.extend(
+        ["-c:v", "copy", "-shortest"]
+            .iter()
+            .map(|arg| arg.to_string()),
+    );
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffmpeg_arguments() {
+        let video = silent_path(Path::new("out/play.mp4"));
+        assert_eq!(video, Path::new("out/play.silent.mp4"));
+        assert_eq!(
+            encode_args(&video, 60, 512, 256).join(" "),
+            "-y -loglevel error -f rawvideo -pix_fmt rgba -s 512x256 -r 60 -i - \
+             -pix_fmt yuv420p out/play.silent.mp4"
+        );
+        assert_eq!(
+            mux_args(&video, Path::new("play.wav"), Path::new("play.mp4")).join(" "),
+            "-y -loglevel error -i out/play.silent.mp4 -f wav -i play.wav -c:v copy -shortest \
+             play.mp4"
+        );
+    }
+}
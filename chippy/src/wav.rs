@@ -0,0 +1,113 @@
+//! Recording of the buzzer into a WAV file, one chunk of samples per frame so the audio stays in
+//! sync with recorded video frames.
+
+use crate::emu::frame::Frame;
+use std::io::{self, Write};
+
+pub const SAMPLE_RATE: u32 = 44100;
+/// Pitch of the buzzer in Hz
+pub const TONE: u32 = 440;
+const AMPLITUDE: i16 = i16::MAX / 4;
+
+/// 16 bit mono square wave of the buzzer.
+#[derive(Debug, Clone)]
+pub struct WavRecorder {
+    fps: u32,
+    samples: Vec<i16>,
+    /// Frames pushed, used to keep the frames aligned when the frame rate does not divide the
+    /// sample rate
+    frames: u64,
+    /// Position in the square wave so the tone continues across frames
+    phase: u32,
+}
+
+impl WavRecorder {
+    pub fn new(fps: u32) -> Self {
+        Self {
+            fps: fps.max(1),
+            samples: Vec::new(),
+            frames: 0,
+            phase: 0,
+        }
+    }
+
+    /// Add a frame of audio, a tone while `sound` is true and silence otherwise
+    pub fn push(&mut self, sound: bool) {
+        self.frames += 1;
+        let end = (self.frames * SAMPLE_RATE as u64 / self.fps as u64) as usize;
+        let period = SAMPLE_RATE / TONE;
+        while self.samples.len() < end {
+            let sample = match (sound, self.phase < period / 2) {
+                (false, _) => 0,
+                (true, true) => AMPLITUDE,
+                (true, false) => -AMPLITUDE,
+            };
+            self.samples.push(sample);
+            self.phase = (self.phase + 1) % period;
+        }
+    }
+
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.push(frame.sound);
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Write the recording as a WAV file
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        let data_size = self.samples.len() as u32 * 2;
+        out.write_all(b"RIFF")?;
+        out.write_all(&(36 + data_size).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&1u16.to_le_bytes())?; // mono
+        out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?; // byte rate
+        out.write_all(&2u16.to_le_bytes())?; // block align
+        out.write_all(&16u16.to_le_bytes())?; // bits per sample
+        out.write_all(b"data")?;
+        out.write_all(&data_size.to_le_bytes())?;
+        for sample in self.samples.iter() {
+            out.write_all(&sample.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_of_samples() {
+        let mut recorder = WavRecorder::new(60);
+        recorder.push(false);
+        recorder.push(true);
+        assert_eq!(recorder.samples().len(), 1470);
+        assert!(recorder.samples()[..735].iter().all(|s| *s == 0));
+        assert_eq!(recorder.samples()[735], AMPLITUDE);
+        assert_eq!(recorder.samples()[735 + 50], -AMPLITUDE);
+
+        // 44100 is not a multiple of 7 frames per second, the total stays exact
+        let mut recorder = WavRecorder::new(7);
+        (0..7).for_each(|_| recorder.push(true));
+        assert_eq!(recorder.samples().len(), SAMPLE_RATE as usize);
+    }
+
+    #[test]
+    fn wav_header() {
+        let mut recorder = WavRecorder::new(60);
+        recorder.push(true);
+        let mut wav = Vec::new();
+        recorder.write(&mut wav).unwrap();
+
+        assert_eq!(wav.len(), 44 + 735 * 2);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[4..8], &(36u32 + 1470).to_le_bytes());
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[40..44], &1470u32.to_le_bytes());
+    }
+}
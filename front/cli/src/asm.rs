@@ -0,0 +1,83 @@
+use chippy::parser::{
+    assembler::{self, Syntax},
+    report::BuildReport,
+    symbols::Symbols,
+};
+use eyre::{eyre, Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct AsmOpt {
+    /// Enable structured control flow (loop/again, if/then, while)
+    #[structopt(long)]
+    structured: bool,
+
+    /// Print the size of the program, its largest data blocks and the size of every label
+    #[structopt(long)]
+    report: bool,
+
+    /// Fail if the program is larger than this many bytes
+    #[structopt(long)]
+    max_size: Option<usize>,
+
+    /// Write the labels, data blocks and source lines to this file for debuggers, in the .sym
+    /// format of the no$ debuggers if the file has a sym extension
+    #[structopt(long, parse(from_os_str))]
+    symbols: Option<PathBuf>,
+
+    /// Output rom file, defaults to the source file with a ch8 extension
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(name = "SOURCE", parse(from_os_str))]
+    source: PathBuf,
+}
+
+/// Assemble a source file into a rom
+pub fn run(opts: &AsmOpt) -> Result<()> {
+    let source = std::fs::read_to_string(&opts.source).wrap_err("Failed to open source file")?;
+    let syntax = match opts.structured {
+        true => Syntax::Structured,
+        false => Syntax::Raw,
+    };
+    let assembly = assembler::assemble_with(&source, syntax)?;
+    for warning in assembly.warnings.iter() {
+        eprintln!(
+            "warning: {}:{}: {}",
+            opts.source.display(),
+            warning.line + 1,
+            warning.message
+        );
+    }
+
+    let report = BuildReport::new(&assembly);
+    if opts.report {
+        print!("{}", report);
+    }
+    if let Some(max_size) = opts.max_size {
+        if report.exceeds(max_size) {
+            return Err(eyre!(
+                "Program is {} bytes, over the budget of {} bytes",
+                report.size,
+                max_size
+            ));
+        }
+    }
+
+    let output = opts
+        .output
+        .clone()
+        .unwrap_or_else(|| opts.source.with_extension("ch8"));
+    std::fs::write(&output, &assembly.bytes).wrap_err("Failed to write rom")?;
+
+    if let Some(path) = &opts.symbols {
+        let symbols = Symbols::new(&assembly);
+        let text = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("sym") => symbols.to_nocash(),
+            _ => symbols.to_string(),
+        };
+        std::fs::write(path, text).wrap_err("Failed to write symbols")?;
+    }
+    Ok(())
+}
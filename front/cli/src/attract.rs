@@ -0,0 +1,157 @@
+use chippy::{
+    autoplay::Autoplay,
+    emu::vm::{ProgramState, Vm},
+    render::{self, Palette},
+    rom::{self, playlist::Playlist},
+    status::{Metrics, StatusServer},
+    stream::MjpegServer,
+};
+use eyre::{eyre, Result, WrapErr};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// Size of a chip8 pixel in the streamed frames, high resolution pixels are half as large
+const STREAM_SCALE: usize = 8;
+const FRAME_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Extension of input recordings
+const RECORDING_EXTENSION: &str = "keys";
+/// Extension of input scripts, see `chippy::autoplay::compile_script`
+const SCRIPT_EXTENSION: &str = "input";
+
+#[derive(Debug, StructOpt)]
+pub struct AttractOpt {
+    /// Serve the frames as an MJPEG stream over HTTP on ADDR
+    #[structopt(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    stream: String,
+
+    /// Serve the uptime, viewers, rom checksum, speed and dropped frames over HTTP on ADDR, as
+    /// JSON or in the Prometheus text format at /metrics
+    #[structopt(long, value_name = "ADDR")]
+    status: Option<String>,
+
+    /// Seconds every rom is shown for, unless it stops before
+    #[structopt(long, default_value = "30")]
+    seconds: u64,
+
+    /// Directory of input recordings, NAME.keys or the input script NAME.input is played for
+    /// the rom NAME.ch8. Roms without a recording get random input.
+    #[structopt(long, parse(from_os_str))]
+    recordings: Option<PathBuf>,
+
+    /// Seed of the random input
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Instructions run per frame
+    #[structopt(long, default_value = "11")]
+    ipf: usize,
+
+    /// Stop after the last rom instead of starting over
+    #[structopt(long)]
+    once: bool,
+
+    /// Directory of roms or playlist file with one rom per line
+    #[structopt(name = "PLAYLIST", parse(from_os_str))]
+    playlist: PathBuf,
+}
+
+/// Play the roms of a playlist in turn with automated input, streaming the display
+pub fn run(opts: &AttractOpt) -> Result<()> {
+    let mut playlist = Playlist::load(&opts.playlist).wrap_err("Failed to read the playlist")?;
+    if playlist.is_empty() {
+        return Err(eyre!("The playlist has no rom"));
+    }
+    let mut stream =
+        MjpegServer::bind(&opts.stream).wrap_err("Failed to start the stream server")?;
+    println!("Streaming on http://{}", stream.local_addr()?);
+    let mut status_server = match &opts.status {
+        Some(addr) => Some(StatusServer::bind(addr).wrap_err("Failed to start the status server")?),
+        None => None,
+    };
+    let mut metrics = Metrics::new(0);
+
+    let frames = opts.seconds * 60;
+    let mut next_frame = Instant::now();
+    for played in 0.. {
+        if opts.once && played == playlist.len() {
+            break;
+        }
+        let path = playlist.current().unwrap().to_path_buf();
+        playlist.advance();
+        let rom = match chippy::rom::read(&path, None) {
+            Ok(rom) => rom,
+            Err(e) => {
+                eprintln!("Failed to open {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let mut autoplay = autoplay(opts, &path, played)?;
+        metrics.rom_checksum = rom::checksum(&rom);
+
+        let mut vm = Vm::new();
+        if let Err(e) = vm.try_load(&rom) {
+            eprintln!("Failed to load {}: {}", path.display(), e);
+            continue;
+        }
+        for frame in 0..frames {
+            autoplay.apply(frame, &mut vm.input);
+            let frame = vm.run_frame(opts.ipf);
+            metrics.frame(frame.cycles);
+            if let ProgramState::Halted(_) | ProgramState::Error(_) = frame.state {
+                break;
+            }
+
+            let display = vm.gpu.display();
+            let scale = render::fit_scale(display.len(), STREAM_SCALE);
+            let rgba = render::to_rgba(display, scale, Palette::default());
+            let (width, height) = (vm.gpu.width() * scale, vm.gpu.height() * scale);
+            stream
+                .send(&rgba, width as u16, height as u16)
+                .wrap_err("Failed to stream frame")?;
+            if let Some(status_server) = &mut status_server {
+                metrics.clients = stream.clients();
+                status_server
+                    .serve(&metrics)
+                    .wrap_err("Status server failed")?;
+            }
+
+            next_frame += FRAME_PERIOD;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(wait) => std::thread::sleep(wait),
+                // Running late, do not try to catch up
+                None => {
+                    metrics.dropped_frames += 1;
+                    next_frame = Instant::now();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recorded or scripted input of the rom at `path`, or random input
+fn autoplay(opts: &AttractOpt, path: &Path, played: usize) -> Result<Autoplay> {
+    let recording = opts.recordings.as_ref().and_then(|dir| {
+        let name = dir.join(path.file_stem()?);
+        [RECORDING_EXTENSION, SCRIPT_EXTENSION]
+            .iter()
+            .map(|extension| name.with_extension(extension))
+            .find(|recording| recording.is_file())
+    });
+    match recording {
+        Some(recording) => {
+            let text = std::fs::read_to_string(&recording)
+                .wrap_err_with(|| format!("Failed to read {}", recording.display()))?;
+            let autoplay = match recording.extension().and_then(|e| e.to_str()) {
+                Some(SCRIPT_EXTENSION) => Autoplay::from_script(&text),
+                _ => Autoplay::parse(&text),
+            };
+            autoplay
+                .map_err(|line| eyre!("Invalid recording {} at line {}", recording.display(), line))
+        }
+        None => Ok(Autoplay::random(opts.seed.wrapping_add(played as u64))),
+    }
+}
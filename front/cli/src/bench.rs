@@ -0,0 +1,77 @@
+use chippy::bench::{Report, WORKLOAD};
+use eyre::{eyre, Result, WrapErr};
+use std::{path::PathBuf, time::Duration};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct BenchOpt {
+    /// Seconds every metric is measured for
+    #[structopt(long, default_value = "1")]
+    seconds: f64,
+
+    /// Print the report as JSON, to be saved as a baseline
+    #[structopt(long)]
+    json: bool,
+
+    /// Report of a previous run to compare against
+    #[structopt(long, parse(from_os_str))]
+    baseline: Option<PathBuf>,
+
+    /// Fail if a metric is more than this many percent slower than the baseline
+    #[structopt(long, default_value = "5")]
+    threshold: f64,
+
+    /// Rom to run, by default a built in loop of common instructions
+    #[structopt(name = "ROM", parse(from_os_str))]
+    rom: Option<PathBuf>,
+}
+
+/// Measure the interpreter throughput, optionally comparing it to a baseline report
+pub fn run(opts: &BenchOpt) -> Result<()> {
+    let baseline = match &opts.baseline {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).wrap_err("Failed to open baseline")?;
+            Some(Report::from_json(&text).map_err(|e| eyre!("Invalid baseline: {}", e))?)
+        }
+        None => None,
+    };
+    let rom = match &opts.rom {
+        Some(path) => chippy::rom::read(path, None).wrap_err("Failed to open rom")?,
+        None => WORKLOAD.to_vec(),
+    };
+
+    let report = Report::run(&rom, Duration::from_secs_f64(opts.seconds.max(0.0)));
+    if opts.json {
+        println!("{}", report.to_json());
+    }
+
+    let baseline = match baseline {
+        Some(baseline) => baseline,
+        None => {
+            if !opts.json {
+                for metric in report.metrics.iter() {
+                    println!("{:<22} {:>12}", metric.name, metric.value);
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let deltas = report.compare(&baseline);
+    let regressions: Vec<&str> = deltas
+        .iter()
+        .filter(|delta| delta.is_regression(opts.threshold))
+        .map(|delta| delta.name.as_str())
+        .collect();
+    for delta in deltas.iter() {
+        eprintln!("{}", delta);
+    }
+    match regressions.is_empty() {
+        true => Ok(()),
+        false => Err(eyre!(
+            "Slower than the baseline by more than {}%: {}",
+            opts.threshold,
+            regressions.join(", ")
+        )),
+    }
+}
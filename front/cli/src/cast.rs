@@ -0,0 +1,126 @@
+//! Recording of terminal sessions in the asciinema v2 format
+//! (https://github.com/asciinema/asciinema/blob/develop/doc/asciicast-v2.md)
+
+use eyre::{Result, WrapErr};
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub struct Recorder {
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, width: u16, height: u16) -> Result<Self> {
+        let file = File::create(path).wrap_err("Failed to create cast file")?;
+        let mut recorder = Self {
+            file: BufWriter::new(file),
+            start: Instant::now(),
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        writeln!(
+            recorder.file,
+            r#"{{"version": 2, "width": {}, "height": {}, "timestamp": {}, "env": {{"TERM": {}}}}}"#,
+            width,
+            height,
+            timestamp,
+            json_string(&std::env::var("TERM").unwrap_or_default())
+        )?;
+        Ok(recorder)
+    }
+
+    /// Record data written to the terminal
+    pub fn output(&mut self, data: &str) -> std::io::Result<()> {
+        self.event("o", data)
+    }
+
+    /// Record data typed by the user
+    pub fn input(&mut self, data: &str) -> std::io::Result<()> {
+        self.event("i", data)
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> std::io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(
+            self.file,
+            "[{:.6}, \"{}\", {}]",
+            time,
+            kind,
+            json_string(data)
+        )
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.file.flush();
+    }
+}
+
+/// Writer that forwards everything to `inner` and records each flushed batch of output
+pub struct TeeWriter<W: Write> {
+    inner: W,
+    recorder: Option<Rc<RefCell<Recorder>>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> TeeWriter<W> {
+    pub fn new(inner: W, recorder: Option<Rc<RefCell<Recorder>>>) -> Self {
+        Self {
+            inner,
+            recorder,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if self.recorder.is_some() {
+            self.buffer.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+        if let Some(recorder) = &self.recorder {
+            if !self.buffer.is_empty() {
+                recorder
+                    .borrow_mut()
+                    .output(&String::from_utf8_lossy(&self.buffer))?;
+                self.buffer.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\x7f' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,116 @@
+use chippy::{debug::trigger::Trigger, locale::Message};
+use eyre::{eyre, Result};
+use std::path::PathBuf;
+
+/// Commands typed on the debugger command line
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `break ADDR [if CONDITION]`
+    Break {
+        address: u16,
+        condition: Option<String>,
+    },
+    /// `delete [ADDR]`, deletes every breakpoint without an address
+    Delete(Option<u16>),
+    /// `trigger [NAME]`, toggles a trigger or lists the enabled ones without a name
+    Trigger(Option<Trigger>),
+    /// `watch EXPR|START..END`, lists the watches without an argument
+    Watch(Option<String>),
+    /// `unwatch [N]`, removes every watch without a number
+    Unwatch(Option<usize>),
+    /// `dumpmem START END FILE`, writes the memory from START up to END to a file
+    DumpMemory { start: u16, end: u16, path: PathBuf },
+    /// `loadmem ADDR FILE`, writes the bytes of a file to memory at ADDR
+    LoadMemory { address: u16, path: PathBuf },
+    /// `patch ADDR [HEX|ASM]`, writes hex bytes or assembly at ADDR. Without text the following
+    /// lines, typed or pasted, are written once an empty line ends them
+    Patch { address: u16, text: Option<String> },
+    /// `lastwrite ADDR`, steps back to the instruction that last changed the byte at ADDR
+    LastWrite(u16),
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command> {
+        let line = line.trim();
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+
+        match name {
+            "b" | "break" => {
+                let (address, condition) = match args.split_once(" if ") {
+                    Some((address, condition)) => (address, Some(condition.trim().to_string())),
+                    None => (args, None),
+                };
+                Ok(Command::Break {
+                    address: parse_address(address)?,
+                    condition,
+                })
+            }
+            "d" | "delete" => match args.is_empty() {
+                true => Ok(Command::Delete(None)),
+                false => Ok(Command::Delete(Some(parse_address(args)?))),
+            },
+            "t" | "trigger" => match args.is_empty() {
+                true => Ok(Command::Trigger(None)),
+                false => Ok(Command::Trigger(Some(
+                    args.parse().map_err(|e| eyre!("{}", e))?,
+                ))),
+            },
+            "w" | "watch" => match args.is_empty() {
+                true => Ok(Command::Watch(None)),
+                false => Ok(Command::Watch(Some(args.to_string()))),
+            },
+            "unwatch" => match args.is_empty() {
+                true => Ok(Command::Unwatch(None)),
+                false => Ok(Command::Unwatch(Some(
+                    args.parse()
+                        .map_err(|_| eyre!("Invalid watch number: {}", args))?,
+                ))),
+            },
+            "dumpmem" => match args.splitn(3, ' ').collect::<Vec<_>>().as_slice() {
+                [start, end, path] => Ok(Command::DumpMemory {
+                    start: parse_address(start)?,
+                    end: parse_end(end)?,
+                    path: PathBuf::from(path.trim()),
+                }),
+                _ => Err(eyre!("Usage: dumpmem START END FILE")),
+            },
+            "loadmem" => match args.split_once(' ') {
+                Some((address, path)) => Ok(Command::LoadMemory {
+                    address: parse_address(address)?,
+                    path: PathBuf::from(path.trim()),
+                }),
+                None => Err(eyre!("Usage: loadmem ADDR FILE")),
+            },
+            "patch" => {
+                let (address, text) = args.split_once(' ').unwrap_or((args, ""));
+                let text = text.trim();
+                Ok(Command::Patch {
+                    address: parse_address(address)?,
+                    text: (!text.is_empty()).then(|| text.to_string()),
+                })
+            }
+            "lastwrite" => Ok(Command::LastWrite(parse_address(args)?)),
+            "" => Err(eyre!("Empty command")),
+            _ => Err(eyre!("Unknown command: {}", name)),
+        }
+    }
+}
+
+/// Parse a hex address with or without the `0x` prefix
+pub fn parse_address(src: &str) -> Result<u16> {
+    let src = src.trim();
+    u16::from_str_radix(src.trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|address| *address < 0x1000)
+        .ok_or_else(|| eyre!(Message::InvalidAddress.format(&[&src])))
+}
+
+/// Parse the hex end of a range, which can be one past the last address
+fn parse_end(src: &str) -> Result<u16> {
+    let src = src.trim();
+    u16::from_str_radix(src.trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|address| *address <= 0x1000)
+        .ok_or_else(|| eyre!(Message::InvalidAddress.format(&[&src])))
+}
@@ -0,0 +1,283 @@
+use chippy::{
+    debug::Breakpoints,
+    emu::{instruction::Instruction, state::VmState},
+    locale::Message,
+};
+use crossterm::event::KeyCode;
+use std::collections::{BTreeMap, BTreeSet};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+const PROGRAM_START: u16 = 0x200;
+const LAST_ADDRESS: u16 = 0xFFE;
+const PAGE_SIZE: u16 = 16;
+
+/// Labels for the jump and call targets found in memory, keyed by address
+pub fn labels(memory: &[u8]) -> BTreeMap<u16, String> {
+    let mut labels = BTreeMap::new();
+    for pair in memory[PROGRAM_START as usize..].chunks_exact(2) {
+        match Instruction::parse(u16::from_be_bytes([pair[0], pair[1]])) {
+            Instruction::Call(addr) => {
+                labels.insert(addr, format!("sub_{:03X}", addr));
+            }
+            Instruction::Jump(addr) => {
+                labels
+                    .entry(addr)
+                    .or_insert_with(|| format!("lbl_{:03X}", addr));
+            }
+            _ => {}
+        }
+    }
+    labels
+}
+
+fn read_instruction(memory: &[u8], address: u16) -> Instruction {
+    let address = address as usize;
+    Instruction::parse(u16::from_be_bytes([memory[address], memory[address + 1]]))
+}
+
+/// Navigation state of the disassembly pane
+pub struct Disassembly {
+    cursor: u16,
+    follow_pc: bool,
+    history: Vec<u16>,
+    bookmarks: BTreeSet<u16>,
+    search: Option<String>,
+    pub message: Option<String>,
+}
+
+impl Disassembly {
+    pub fn new() -> Self {
+        Self {
+            cursor: PROGRAM_START,
+            follow_pc: true,
+            history: Vec::new(),
+            bookmarks: BTreeSet::new(),
+            search: None,
+            message: None,
+        }
+    }
+
+    pub fn cursor(&self) -> u16 {
+        self.cursor
+    }
+
+    /// Keep the cursor on the program counter unless the user navigated away
+    pub fn sync(&mut self, program_counter: u16) {
+        if self.follow_pc {
+            self.cursor = program_counter.min(LAST_ADDRESS);
+        }
+    }
+
+    /// Move the cursor to `address`, remembering the current location for `back`
+    pub fn goto(&mut self, address: u16) {
+        self.history.push(self.cursor);
+        self.cursor = address.min(LAST_ADDRESS);
+        self.follow_pc = false;
+    }
+
+    pub fn back(&mut self) {
+        if let Some(address) = self.history.pop() {
+            self.cursor = address;
+            self.follow_pc = false;
+        }
+    }
+
+    fn move_by(&mut self, offset: i32) {
+        let cursor = (self.cursor as i32 + offset).clamp(0, LAST_ADDRESS as i32);
+        self.cursor = cursor as u16;
+        self.follow_pc = false;
+    }
+
+    /// Follow the jump or call under the cursor
+    pub fn follow(&mut self, state: &VmState) {
+        match read_instruction(&state.memory, self.cursor) {
+            Instruction::Jump(addr) | Instruction::Call(addr) => self.goto(addr),
+            Instruction::JumpNPlusPC(addr) => self.goto(addr + state.registers[0] as u16),
+            _ => self.message = Some(Message::NotAJump.to_string()),
+        }
+    }
+
+    pub fn toggle_bookmark(&mut self) {
+        if !self.bookmarks.remove(&self.cursor) {
+            self.bookmarks.insert(self.cursor);
+        }
+    }
+
+    /// Go to the first bookmark after the cursor, wrapping around to the first one
+    pub fn next_bookmark(&mut self) {
+        let next = self
+            .bookmarks
+            .range(self.cursor + 1..)
+            .next()
+            .or_else(|| self.bookmarks.iter().next())
+            .copied();
+
+        match next {
+            Some(address) => self.goto(address),
+            None => self.message = Some(Message::NoBookmarks.to_string()),
+        }
+    }
+
+    /// Go to an address (hex with or without `0x`) or a label
+    pub fn search(&mut self, query: &str, state: &VmState) {
+        let query = query.trim();
+        let address = u16::from_str_radix(query.trim_start_matches("0x"), 16)
+            .ok()
+            .filter(|addr| *addr <= LAST_ADDRESS)
+            .or_else(|| {
+                labels(&state.memory)
+                    .into_iter()
+                    .find(|(_, label)| label.eq_ignore_ascii_case(query))
+                    .map(|(addr, _)| addr)
+            });
+
+        match address {
+            Some(address) => self.goto(address),
+            None => self.message = Some(Message::NotFound.format(&[&query])),
+        }
+    }
+
+    /// Handle a key press, returns true if the key was used
+    pub fn handle_key(&mut self, code: KeyCode, state: &VmState) -> bool {
+        if let Some(query) = &mut self.search {
+            match code {
+                KeyCode::Char(c) => query.push(c),
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Enter => {
+                    let query = query.clone();
+                    self.search = None;
+                    self.search(&query, state);
+                }
+                KeyCode::Esc => self.search = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        self.message = None;
+        match code {
+            KeyCode::Up => self.move_by(-2),
+            KeyCode::Down => self.move_by(2),
+            KeyCode::PageUp => self.move_by(-2 * PAGE_SIZE as i32),
+            KeyCode::PageDown => self.move_by(2 * PAGE_SIZE as i32),
+            KeyCode::Enter => self.follow(state),
+            KeyCode::Backspace => self.back(),
+            KeyCode::Char('/') => self.search = Some(String::new()),
+            KeyCode::Char('m') => self.toggle_bookmark(),
+            KeyCode::Char('\'') => self.next_bookmark(),
+            KeyCode::Char('g') => {
+                self.follow_pc = true;
+                self.sync(state.program_counter);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn widget<'a>(
+        &'a self,
+        state: &'a VmState,
+        breakpoints: &'a Breakpoints,
+        focused: bool,
+    ) -> DisassemblyWidget<'a> {
+        DisassemblyWidget {
+            disasm: self,
+            state,
+            breakpoints,
+            focused,
+        }
+    }
+
+    fn title(&self) -> String {
+        match &self.search {
+            Some(query) => Message::GoTo.format(&[query]),
+            None => match &self.message {
+                Some(message) => format!("{} - {}", Message::Disassembly, message),
+                None => Message::Disassembly.to_string(),
+            },
+        }
+    }
+}
+
+pub struct DisassemblyWidget<'a> {
+    disasm: &'a Disassembly,
+    state: &'a VmState,
+    breakpoints: &'a Breakpoints,
+    focused: bool,
+}
+
+impl<'a> Widget for DisassemblyWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let border = match self.focused {
+            true => Style::default().fg(Color::LightYellow),
+            false => Style::default().fg(Color::White),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(self.disasm.title());
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let labels = labels(&self.state.memory);
+        let rows = inner.height as i32;
+        let cursor = self.disasm.cursor as i32;
+        let start =
+            (cursor - (rows / 2) * 2).clamp(0, (LAST_ADDRESS as i32 - (rows - 1) * 2).max(0));
+
+        let lines: Vec<Spans> = (0..rows)
+            .map(|row| start + row * 2)
+            .filter(|addr| *addr <= LAST_ADDRESS as i32)
+            .map(|addr| {
+                let addr = addr as u16;
+                let pc_marker = match addr == self.state.program_counter {
+                    true => ">",
+                    false => " ",
+                };
+                let marker = match (
+                    self.breakpoints.get(addr),
+                    self.disasm.bookmarks.contains(&addr),
+                ) {
+                    (Some(breakpoint), _) if breakpoint.condition.is_some() => "?",
+                    (Some(_), _) => "B",
+                    (None, true) => "*",
+                    (None, false) => " ",
+                };
+                let label = labels
+                    .get(&addr)
+                    .map(|l| format!("{}:", l))
+                    .unwrap_or_default();
+                let text = format!(
+                    "{}{} {:03X} {:<9} {}",
+                    pc_marker,
+                    marker,
+                    addr,
+                    label,
+                    read_instruction(&self.state.memory, addr).to_asm()
+                );
+
+                let style = match addr == self.disasm.cursor {
+                    true => Style::default().add_modifier(Modifier::REVERSED),
+                    false if addr == self.state.program_counter => {
+                        Style::default().fg(Color::LightYellow)
+                    }
+                    false if self.breakpoints.get(addr).is_some() => {
+                        Style::default().fg(Color::LightRed)
+                    }
+                    false => Style::default(),
+                };
+                Spans::from(Span::styled(text, style))
+            })
+            .collect();
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
@@ -0,0 +1,84 @@
+use chippy::{emu::history::History, locale::Message};
+use crossterm::event::KeyCode;
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+/// Number of executed instructions recorded while debugging
+pub const HISTORY_SIZE: usize = 1024;
+const PAGE_SIZE: usize = 16;
+
+/// Scroll position of the history pane, counted in entries back from the most recent one
+pub struct HistoryView {
+    scroll: usize,
+}
+
+impl HistoryView {
+    pub fn new() -> Self {
+        Self { scroll: 0 }
+    }
+
+    /// Handle a key press, returns true if the key was used
+    pub fn handle_key(&mut self, code: KeyCode, history: &History) -> bool {
+        let last = history.len().saturating_sub(1);
+        self.scroll = match code {
+            KeyCode::Up => self.scroll.saturating_add(1).min(last),
+            KeyCode::Down => self.scroll.saturating_sub(1),
+            KeyCode::PageUp => self.scroll.saturating_add(PAGE_SIZE).min(last),
+            KeyCode::PageDown => self.scroll.saturating_sub(PAGE_SIZE),
+            KeyCode::Home => last,
+            KeyCode::End => 0,
+            _ => return false,
+        };
+        true
+    }
+
+    pub fn widget<'a>(&'a self, history: &'a History, focused: bool) -> HistoryWidget<'a> {
+        HistoryWidget {
+            view: self,
+            history,
+            focused,
+        }
+    }
+}
+
+pub struct HistoryWidget<'a> {
+    view: &'a HistoryView,
+    history: &'a History,
+    focused: bool,
+}
+
+impl<'a> Widget for HistoryWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let border = match self.focused {
+            true => Style::default().fg(Color::LightYellow),
+            false => Style::default().fg(Color::White),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(Message::History.format(&[&self.view.scroll, &self.history.len()]));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        // Most recent instruction at the top
+        let lines: Vec<String> = (self.view.scroll..)
+            .map_while(|back| self.history.get(back).map(|entry| (back, entry)))
+            .take(inner.height as usize)
+            .map(|(back, entry)| {
+                format!(
+                    "-{:<4} {:03X} {:04X}  {}",
+                    back,
+                    entry.address,
+                    entry.opcode,
+                    entry.instruction().to_asm()
+                )
+            })
+            .collect();
+
+        Paragraph::new(lines.join("\n")).render(inner, buf);
+    }
+}
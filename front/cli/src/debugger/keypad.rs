@@ -0,0 +1,88 @@
+use chippy::{emu::input::Input, locale::Message};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+/// Keys in the layout of the original COSMAC VIP keypad
+const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Number of steps a polled key stays highlighted
+const HEAT_MAX: u16 = 180;
+
+/// Tracks how recently the program polled each key
+pub struct KeypadHeat {
+    heat: [u16; 16],
+}
+
+impl KeypadHeat {
+    pub fn new() -> Self {
+        Self { heat: [0; 16] }
+    }
+
+    /// Heat up the keys polled since the last update and cool down the others
+    pub fn update(&mut self, input: &mut Input) {
+        for (heat, polled) in self.heat.iter_mut().zip(input.take_polled()) {
+            *heat = match polled {
+                true => HEAT_MAX,
+                false => heat.saturating_sub(1),
+            };
+        }
+    }
+
+    pub fn widget<'a>(&'a self, input: &'a Input) -> KeypadWidget<'a> {
+        KeypadWidget { heat: self, input }
+    }
+
+    fn color(&self, key: u8) -> Color {
+        match self.heat[key as usize] {
+            0 => Color::DarkGray,
+            heat if heat > HEAT_MAX * 2 / 3 => Color::LightRed,
+            heat if heat > HEAT_MAX / 3 => Color::Yellow,
+            _ => Color::Gray,
+        }
+    }
+}
+
+pub struct KeypadWidget<'a> {
+    heat: &'a KeypadHeat,
+    input: &'a Input,
+}
+
+impl<'a> Widget for KeypadWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(Message::Keypad.text());
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let lines: Vec<Spans> = LAYOUT
+            .iter()
+            .map(|row| {
+                let spans = row
+                    .iter()
+                    .map(|key| {
+                        let mut style = Style::default().fg(self.heat.color(*key));
+                        if self.input.is_pressed(*key) {
+                            style = style.add_modifier(Modifier::REVERSED);
+                        }
+                        Span::styled(format!(" {:X} ", key), style)
+                    })
+                    .collect::<Vec<_>>();
+                Spans::from(spans)
+            })
+            .collect();
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
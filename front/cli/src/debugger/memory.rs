@@ -0,0 +1,191 @@
+use chippy::{
+    debug::Inspect,
+    emu::{
+        state::{StateDiff, VmState},
+        vm::Vm,
+    },
+    locale::Message,
+};
+use crossterm::event::KeyCode;
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+const BYTES_PER_ROW: u16 = 16;
+const LAST_ADDRESS: u16 = 0xFFF;
+
+/// Hex editor state of the memory pane
+pub struct MemoryView {
+    cursor: u16,
+    /// High nibble typed for the byte under the cursor
+    pending: Option<u8>,
+    goto: Option<String>,
+    pub message: Option<String>,
+}
+
+impl MemoryView {
+    pub fn new() -> Self {
+        Self {
+            cursor: 0x200,
+            pending: None,
+            goto: None,
+            message: None,
+        }
+    }
+
+    fn move_by(&mut self, offset: i32) {
+        self.cursor = (self.cursor as i32 + offset).clamp(0, LAST_ADDRESS as i32) as u16;
+        self.pending = None;
+    }
+
+    fn goto(&mut self, query: &str) {
+        match u16::from_str_radix(query.trim().trim_start_matches("0x"), 16) {
+            Ok(address) if address <= LAST_ADDRESS => {
+                self.cursor = address;
+                self.pending = None;
+            }
+            _ => self.message = Some(Message::InvalidAddress.format(&[&query])),
+        }
+    }
+
+    /// Write one nibble at the cursor, moving to the next byte once both were typed
+    fn edit(&mut self, nibble: u8, vm: &mut Vm) {
+        match self.pending.take() {
+            None => self.pending = Some(nibble),
+            Some(high) => {
+                // The cursor never leaves memory
+                let _ = vm.write_memory(self.cursor, &[high << 4 | nibble]);
+                self.move_by(1);
+            }
+        }
+    }
+
+    /// Handle a key press, returns true if the key was used
+    pub fn handle_key(&mut self, code: KeyCode, vm: &mut Vm) -> bool {
+        if let Some(query) = &mut self.goto {
+            match code {
+                KeyCode::Char(c) => query.push(c),
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Enter => {
+                    let query = query.clone();
+                    self.goto = None;
+                    self.goto(&query);
+                }
+                KeyCode::Esc => self.goto = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        self.message = None;
+        match code {
+            KeyCode::Left => self.move_by(-1),
+            KeyCode::Right => self.move_by(1),
+            KeyCode::Up => self.move_by(-(BYTES_PER_ROW as i32)),
+            KeyCode::Down => self.move_by(BYTES_PER_ROW as i32),
+            KeyCode::PageUp => self.move_by(-16 * BYTES_PER_ROW as i32),
+            KeyCode::PageDown => self.move_by(16 * BYTES_PER_ROW as i32),
+            KeyCode::Char('/') => self.goto = Some(String::new()),
+            KeyCode::Char('i') => {
+                self.cursor = vm.index().min(LAST_ADDRESS);
+                self.pending = None;
+            }
+            KeyCode::Char(c) if c.is_ascii_hexdigit() => {
+                self.edit(c.to_digit(16).unwrap_or_default() as u8, vm)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn widget<'a>(
+        &'a self,
+        state: &'a VmState,
+        changes: &'a StateDiff,
+        focused: bool,
+    ) -> MemoryWidget<'a> {
+        MemoryWidget {
+            view: self,
+            state,
+            changes,
+            focused,
+        }
+    }
+
+    fn title(&self, state: &VmState) -> String {
+        match (&self.goto, &self.message) {
+            (Some(query), _) => Message::GoTo.format(&[query]),
+            (None, Some(message)) => format!("{} - {}", Message::Memory, message),
+            (None, None) => format!(
+                "{} - {:03X} - I {:03X}",
+                Message::Memory,
+                self.cursor,
+                state.index
+            ),
+        }
+    }
+}
+
+pub struct MemoryWidget<'a> {
+    view: &'a MemoryView,
+    state: &'a VmState,
+    changes: &'a StateDiff,
+    focused: bool,
+}
+
+impl<'a> Widget for MemoryWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let border = match self.focused {
+            true => Style::default().fg(Color::LightYellow),
+            false => Style::default().fg(Color::White),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(self.view.title(self.state));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let rows = inner.height;
+        let last_row = LAST_ADDRESS / BYTES_PER_ROW;
+        let cursor_row = self.view.cursor / BYTES_PER_ROW;
+        let first_row = cursor_row
+            .saturating_sub(rows / 2)
+            .min((last_row + 1).saturating_sub(rows));
+
+        let lines: Vec<Spans> = (first_row..=last_row)
+            .take(rows as usize)
+            .map(|row| {
+                let start = row * BYTES_PER_ROW;
+                let mut spans = vec![Span::raw(format!("{:03X} ", start))];
+                for address in start..start + BYTES_PER_ROW {
+                    let style = if address == self.view.cursor {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    } else if address == self.state.index {
+                        Style::default().fg(Color::Black).bg(Color::Cyan)
+                    } else if self.changes.memory.contains(&address) {
+                        Style::default().fg(Color::LightRed)
+                    } else {
+                        Style::default()
+                    };
+
+                    let byte = match (address == self.view.cursor, self.view.pending) {
+                        (true, Some(high)) => format!("{:X}_", high),
+                        _ => format!("{:02X}", self.state.memory[address as usize]),
+                    };
+                    spans.push(Span::raw(" "));
+                    spans.push(Span::styled(byte, style));
+                }
+                Spans::from(spans)
+            })
+            .collect();
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
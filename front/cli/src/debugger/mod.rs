@@ -0,0 +1,468 @@
+//! Interactive debugger view of the terminal frontend, enabled with `--debug`
+
+pub mod command;
+pub mod disasm;
+pub mod history;
+pub mod keypad;
+pub mod memory;
+pub mod watch;
+
+use crate::{render::detect::Capabilities, ui};
+use chippy::{
+    debug::{trigger::Triggers, watch::Watches, Breakpoints},
+    emu::{
+        compress::SnapshotHistory,
+        gpu,
+        state::{StateDiff, VmState},
+        vm::{Break, Vm},
+    },
+    locale::Message,
+    parser,
+};
+use command::Command;
+use crossterm::event::KeyCode;
+use disasm::Disassembly;
+use history::{HistoryView, HISTORY_SIZE};
+use keypad::KeypadHeat;
+use memory::MemoryView;
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+/// Number of instruction boundaries that can be stepped back over
+const STEP_BACK_SIZE: usize = 4096;
+const STEP_BACK_KEYFRAME_INTERVAL: usize = 64;
+
+/// Pane that receives the navigation keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Disassembly,
+    Memory,
+    History,
+}
+
+pub struct Debugger {
+    paused: bool,
+    step: bool,
+    focus: Focus,
+    /// State after the last executed step
+    last: VmState,
+    /// Changes made by the last executed step
+    changes: StateDiff,
+    /// States before each executed step, most recent last
+    previous: SnapshotHistory,
+    pub disasm: Disassembly,
+    pub memory: MemoryView,
+    pub keypad: KeypadHeat,
+    pub history: HistoryView,
+    pub breakpoints: Breakpoints,
+    pub triggers: Triggers,
+    pub watches: Watches,
+    /// Text typed on the command line while it is open
+    command: Option<String>,
+    /// Address and lines of a `patch` being typed or pasted
+    patch: Option<(u16, Vec<String>)>,
+    message: Option<String>,
+}
+
+impl Debugger {
+    /// New debugger for `vm`, the vm starts paused and records its instruction history
+    pub fn new(vm: &mut Vm) -> Self {
+        vm.set_history_capacity(HISTORY_SIZE);
+        Self {
+            paused: true,
+            step: false,
+            focus: Focus::Disassembly,
+            last: vm.snapshot(),
+            changes: StateDiff::default(),
+            previous: SnapshotHistory::new(STEP_BACK_SIZE, STEP_BACK_KEYFRAME_INTERVAL),
+            disasm: Disassembly::new(),
+            memory: MemoryView::new(),
+            keypad: KeypadHeat::new(),
+            history: HistoryView::new(),
+            breakpoints: Breakpoints::new(),
+            triggers: Triggers::new(),
+            watches: Watches::new(),
+            command: None,
+            patch: None,
+            message: None,
+        }
+    }
+
+    /// True if the vm should execute an instruction this frame
+    pub fn should_cycle(&mut self) -> bool {
+        !self.paused || std::mem::take(&mut self.step)
+    }
+
+    /// Update the views after the vm executed. `breakpoint` is true if it stopped on one of the
+    /// breakpoints instead, see `Frame::breakpoint`, then returns its address if its condition
+    /// holds and the debugger paused on it.
+    pub fn sync(&mut self, vm: &mut Vm, breakpoint: bool) -> Option<u16> {
+        self.keypad.update(&mut vm.input);
+        if breakpoint {
+            return self.break_hit(vm);
+        }
+        let state = vm.snapshot();
+        self.disasm.sync(state.program_counter);
+        self.changes = self.last.diff(&state);
+
+        if let Some(trigger) = self.triggers.hit(&self.last, &state) {
+            self.paused = true;
+            let address = format!("{:03X}", self.last.program_counter);
+            self.message = Some(Message::TriggerHit.format(&[&trigger, &address]));
+        }
+        self.previous.push(&self.last);
+        self.watches.update(&state);
+        self.last = state;
+        None
+    }
+
+    /// Pause on the breakpoint the vm stopped on, unless its condition is false. The vm executes
+    /// the instruction on the next cycle either way.
+    fn break_hit(&mut self, vm: &Vm) -> Option<u16> {
+        let address = match vm.break_hit()? {
+            Break::Breakpoint(address) => *address,
+            Break::Watchpoint { .. } => return None,
+        };
+        if !self.breakpoints.get(address)?.is_hit(vm) {
+            return None;
+        }
+        self.paused = true;
+        self.message = Some(Message::BreakpointHit.format(&[&format!("{:03X}", address)]));
+        Some(address)
+    }
+
+    /// Restore the state before the last executed step and pause
+    pub fn step_back(&mut self, vm: &mut Vm) {
+        self.paused = true;
+        match self.previous.pop() {
+            Some(state) => {
+                vm.restore(&state);
+                self.disasm.sync(state.program_counter);
+                self.changes = state.diff(&self.last);
+                self.watches.update(&state);
+                self.last = state;
+                self.message = None;
+            }
+            None => self.message = Some(Message::NoEarlierState.to_string()),
+        }
+    }
+
+    fn run_command(&mut self, line: &str, vm: &mut Vm) -> eyre::Result<String> {
+        let message = match Command::parse(line)? {
+            Command::Break { address, condition } => {
+                self.breakpoints.add(address, condition.as_deref())?;
+                vm.add_breakpoint(address);
+                Message::BreakpointSet.format(&[&format!("{:03X}", address)])
+            }
+            Command::Delete(Some(address)) => {
+                let hex = format!("{:03X}", address);
+                vm.remove_breakpoint(address);
+                match self.breakpoints.remove(address) {
+                    Some(_) => Message::BreakpointDeleted.format(&[&hex]),
+                    None => Message::NoBreakpoint.format(&[&hex]),
+                }
+            }
+            Command::Delete(None) => {
+                self.breakpoints.clear();
+                vm.clear_breakpoints();
+                Message::BreakpointsDeleted.to_string()
+            }
+            Command::Trigger(Some(trigger)) => match self.triggers.toggle(trigger) {
+                true => Message::TriggerEnabled.format(&[&trigger]),
+                false => Message::TriggerDisabled.format(&[&trigger]),
+            },
+            Command::Trigger(None) => {
+                let enabled: Vec<String> = self.triggers.iter().map(|t| t.to_string()).collect();
+                match enabled.is_empty() {
+                    true => Message::NoTriggers.to_string(),
+                    false => Message::Triggers.format(&[&enabled.join(", ")]),
+                }
+            }
+            Command::Watch(Some(src)) => {
+                let watch = self.watches.add(&src)?.to_string();
+                self.watches.update(&self.last);
+                Message::Watching.format(&[&watch])
+            }
+            Command::Watch(None) => match self.watches.len() {
+                0 => Message::NoWatches.to_string(),
+                len => Message::WatchCount.format(&[&len]),
+            },
+            Command::Unwatch(Some(index)) => match self.watches.remove(index) {
+                Some(watch) => Message::WatchRemoved.format(&[&watch]),
+                None => Message::NoWatch.format(&[&index]),
+            },
+            Command::Unwatch(None) => {
+                self.watches.clear();
+                Message::WatchesRemoved.to_string()
+            }
+            Command::DumpMemory { start, end, path } => {
+                let bytes = vm.dump_memory(start..end)?;
+                std::fs::write(&path, &bytes)?;
+                Message::WroteBytes.format(&[&bytes.len(), &path.display()])
+            }
+            Command::LoadMemory { address, path } => {
+                let bytes = std::fs::read(&path)?;
+                vm.write_memory(address, &bytes)?;
+                self.last = vm.snapshot();
+                self.watches.update(&self.last);
+                Message::LoadedBytes.format(&[&bytes.len(), &format!("{:03X}", address)])
+            }
+            Command::Patch {
+                address,
+                text: Some(text),
+            } => self.patch(address, &text, vm)?,
+            Command::Patch {
+                address,
+                text: None,
+            } => {
+                self.patch = Some((address, Vec::new()));
+                Message::Patching.format(&[&format!("{:03X}", address)])
+            }
+            Command::LastWrite(address) => self.last_write(address, vm),
+        };
+        Ok(message)
+    }
+
+    /// Step back to the state before the instruction that last changed memory at `address`,
+    /// found in the step back history
+    fn last_write(&mut self, address: u16, vm: &mut Vm) -> String {
+        let hex = format!("{:03X}", address);
+        let back = match self.previous.last_memory_change(address, &self.last) {
+            Some(back) => back,
+            None => return Message::NoChange.format(&[&hex, &self.previous.len()]),
+        };
+        for _ in 0..back {
+            self.previous.pop();
+        }
+        self.step_back(vm);
+        let pc = format!("{:03X}", self.last.program_counter);
+        Message::LastChange.format(&[&hex, &pc, &(back + 1)])
+    }
+
+    /// Write hex bytes or assembly at `address`, see `parser::patch_bytes`
+    fn patch(&mut self, address: u16, text: &str, vm: &mut Vm) -> eyre::Result<String> {
+        let bytes = parser::patch_bytes(text, address)?;
+        vm.write_memory(address, &bytes)?;
+        self.last = vm.snapshot();
+        self.watches.update(&self.last);
+        Ok(Message::Patched.format(&[&bytes.len(), &format!("{:03X}", address)]))
+    }
+
+    /// Add a line to the patch being typed, an empty line writes it
+    fn patch_line(&mut self, line: String, vm: &mut Vm) -> eyre::Result<Option<String>> {
+        if let Some((_, lines)) = &mut self.patch {
+            if !line.trim().is_empty() {
+                lines.push(line);
+                return Ok(None);
+            }
+        }
+        match self.patch.take() {
+            Some((address, lines)) => self.patch(address, &lines.join("\n"), vm).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn toggle_breakpoint(&mut self, address: u16, vm: &mut Vm) {
+        if self.breakpoints.remove(address).is_some() {
+            vm.remove_breakpoint(address);
+        } else {
+            // An unconditional breakpoint can not fail to parse
+            let _ = self.breakpoints.add(address, None);
+            vm.add_breakpoint(address);
+        }
+    }
+
+    /// Handle a key press, returns true if the key was used by the debugger
+    pub fn handle_key(&mut self, code: KeyCode, vm: &mut Vm) -> bool {
+        if let Some(line) = &mut self.command {
+            match code {
+                KeyCode::Char(c) => line.push(c),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                KeyCode::Enter => {
+                    let line = line.clone();
+                    self.command = None;
+                    let result = match self.patch.is_some() {
+                        true => self.patch_line(line, vm).transpose(),
+                        false => Some(self.run_command(&line, vm)),
+                    };
+                    match result {
+                        Some(Ok(message)) => self.message = Some(message),
+                        Some(Err(err)) => self.message = Some(err.to_string()),
+                        None => (),
+                    }
+                    if self.patch.is_some() {
+                        self.command = Some(String::new());
+                    }
+                }
+                KeyCode::Esc => {
+                    self.command = None;
+                    self.patch = None;
+                }
+                _ => {}
+            }
+            return true;
+        }
+
+        let used = match self.focus {
+            Focus::Disassembly => self.disasm.handle_key(code, &vm.snapshot()),
+            Focus::Memory => self.memory.handle_key(code, vm),
+            Focus::History => self.history.handle_key(code, vm.history()),
+        };
+        if used {
+            return true;
+        }
+
+        match code {
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Disassembly => Focus::Memory,
+                    Focus::Memory => Focus::History,
+                    Focus::History => Focus::Disassembly,
+                }
+            }
+            KeyCode::Char(':') => {
+                self.command = Some(String::new());
+                self.message = None;
+            }
+            KeyCode::Char('x') => self.toggle_breakpoint(self.disasm.cursor(), vm),
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('s') => {
+                self.paused = true;
+                self.step = true;
+            }
+            KeyCode::Char('S') => self.step_back(vm),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn draw<B: Backend>(
+        &self,
+        f: &mut Frame<B>,
+        vm: &Vm,
+        caps: &Capabilities,
+        status: &ui::Status,
+    ) {
+        let state = vm.snapshot();
+        let main_block = Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::LightYellow))
+            .title(match self.paused {
+                true => Message::Paused.text(),
+                false => Message::Running.text(),
+            });
+        let inner = main_block.inner(f.size());
+        f.render_widget(main_block, f.size());
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Min(0), Constraint::Length(1)])
+            .split(inner);
+
+        let status_line = Paragraph::new(status.to_string())
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Right);
+        f.render_widget(status_line, rows[1]);
+
+        let command_line = match (&self.command, &self.message) {
+            (Some(line), Some(message)) if line.is_empty() && self.patch.is_some() => {
+                format!("{}  _", message)
+            }
+            (Some(line), _) => match &self.patch {
+                Some((address, lines)) => format!("{:03X}+{}> {}_", address, lines.len(), line),
+                None => format!(":{}_", line),
+            },
+            (None, Some(message)) => message.clone(),
+            (None, None) => String::new(),
+        };
+        f.render_widget(Paragraph::new(command_line), rows[1]);
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Length(gpu::SCREEN_WIDTH as u16 + 2),
+                Constraint::Min(0),
+            ])
+            .split(rows[0]);
+
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(gpu::SCREEN_HEIGHT as u16 + 2),
+                Constraint::Min(0),
+            ])
+            .split(columns[0]);
+
+        f.render_widget(ui::display(&vm.gpu, caps), left[0]);
+        let below_display = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(6), Constraint::Min(0)])
+            .split(left[1]);
+        let bottom_left = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Min(0), Constraint::Length(14)])
+            .split(below_display[0]);
+
+        f.render_widget(registers(&state), bottom_left[0]);
+        f.render_widget(self.keypad.widget(&vm.input), bottom_left[1]);
+        f.render_widget(watch::widget(&self.watches), below_display[1]);
+        let right = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+                Constraint::Min(0),
+            ])
+            .split(columns[1]);
+
+        f.render_widget(
+            self.disasm
+                .widget(&state, &self.breakpoints, self.focus == Focus::Disassembly),
+            right[0],
+        );
+        f.render_widget(
+            self.memory
+                .widget(&state, &self.changes, self.focus == Focus::Memory),
+            right[1],
+        );
+        f.render_widget(
+            self.history
+                .widget(vm.history(), self.focus == Focus::History),
+            right[2],
+        );
+    }
+}
+
+fn registers(state: &VmState) -> Paragraph<'static> {
+    let mut lines = vec![format!(
+        "PC {:03X}  I {:03X}  SP {:X}  DT {:02X}  ST {:02X}",
+        state.program_counter,
+        state.index,
+        state.stack_pointer,
+        state.delay_timer,
+        state.sound_timer
+    )];
+    for (row, values) in state.registers.chunks(8).enumerate() {
+        let line = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("V{:X} {:02X}", row * 8 + i, v))
+            .collect::<Vec<_>>()
+            .join(" ");
+        lines.push(line);
+    }
+
+    Paragraph::new(lines.join("\n")).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(Message::Registers.text()),
+    )
+}
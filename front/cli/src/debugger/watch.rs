@@ -0,0 +1,37 @@
+use chippy::{debug::watch::Watches, locale::Message};
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+
+/// Pane listing the watches, values that changed on the last step are highlighted
+pub fn widget(watches: &Watches) -> Paragraph<'static> {
+    let lines: Vec<Spans> = watches
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let mut spans = vec![Span::raw(format!("{} {}", i, entry.watch))];
+            if let Some(start) = entry.start {
+                spans.push(Span::raw(format!(" @{:03X}", start)));
+            }
+            spans.push(Span::raw(":"));
+            for (value, changed) in entry.values.iter().zip(entry.changed.iter()) {
+                let style = match changed {
+                    true => Style::default().fg(Color::LightRed),
+                    false => Style::default(),
+                };
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(format!("{:02X}", value), style));
+            }
+            Spans::from(spans)
+        })
+        .collect();
+
+    Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(Message::Watches.text()),
+    )
+}
@@ -0,0 +1,33 @@
+use chippy::rom::{diff, patch::Patch};
+use eyre::{Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct DiffOpt {
+    /// Print a patch that turns the old rom into the new one instead of the differences
+    #[structopt(long)]
+    patch: bool,
+
+    #[structopt(name = "OLD", parse(from_os_str))]
+    old: PathBuf,
+
+    #[structopt(name = "NEW", parse(from_os_str))]
+    new: PathBuf,
+}
+
+/// Print the instructions that differ between two roms
+pub fn run(opts: &DiffOpt) -> Result<()> {
+    let old = std::fs::read(&opts.old).wrap_err("Failed to open old rom")?;
+    let new = std::fs::read(&opts.new).wrap_err("Failed to open new rom")?;
+
+    if opts.patch {
+        print!("{}", Patch::between(&old, &new));
+        return Ok(());
+    }
+
+    for change in diff::diff(&old, &new) {
+        println!("{}", change);
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use chippy::{emu::instruction::Instruction, parser};
+use eyre::{Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct DisasmOpt {
+    /// Write source that assembles back into the exact same rom instead of an annotated listing
+    #[structopt(long)]
+    exact: bool,
+
+    #[structopt(name = "ROM", parse(from_os_str))]
+    rom: PathBuf,
+}
+
+/// Print the instructions of a rom
+pub fn run(opts: &DisasmOpt) -> Result<()> {
+    let rom = std::fs::read(&opts.rom).wrap_err("Failed to open rom")?;
+    if opts.exact {
+        println!("{}", parser::disassemble_exact(&rom));
+        return Ok(());
+    }
+
+    for (i, word) in rom.chunks(2).enumerate() {
+        let address = 0x200 + i * 2;
+        match word {
+            [high, low] => {
+                let opcode = u16::from_be_bytes([*high, *low]);
+                let asm = Instruction::parse(opcode).to_asm();
+                println!("{:03X}  {:04X}  {}", address, opcode, asm);
+            }
+            _ => println!("{:03X}  {:02X}", address, word[0]),
+        }
+    }
+    Ok(())
+}
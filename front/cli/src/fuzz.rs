@@ -0,0 +1,72 @@
+use chippy::{autoplay, emu::frame::DEFAULT_CYCLES_PER_FRAME, fuzz::Fuzzer};
+use eyre::{eyre, Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Extension of the recordings written for the findings, replayed by `chippy attract`
+const RECORDING_EXTENSION: &str = "keys";
+
+#[derive(Debug, StructOpt)]
+pub struct FuzzOpt {
+    /// Number of key sequences to play
+    #[structopt(long, default_value = "10000")]
+    runs: usize,
+
+    /// Frames of input per key sequence
+    #[structopt(long, default_value = "600")]
+    frames: usize,
+
+    /// Instructions run per frame
+    #[structopt(long)]
+    ipf: Option<usize>,
+
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Directory the recordings of the crashing inputs are written to
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(name = "ROM", parse(from_os_str))]
+    rom: PathBuf,
+}
+
+/// Mutate key sequences to execute as much of a rom as possible, reporting the inputs that crash
+/// it
+pub fn run(opts: &FuzzOpt) -> Result<()> {
+    let rom = chippy::rom::read(&opts.rom, None).wrap_err("Failed to open rom")?;
+    if let Some(output) = &opts.output {
+        std::fs::create_dir_all(output).wrap_err("Failed to create output directory")?;
+    }
+
+    // Panics are reported as findings, the default hook would print every one of them
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut fuzzer = Fuzzer::new(rom, opts.seed)
+        .frames(opts.frames)
+        .cycles_per_frame(opts.ipf.unwrap_or(DEFAULT_CYCLES_PER_FRAME));
+    for _ in 0..opts.runs {
+        let finding = match fuzzer.step() {
+            Some(finding) => finding,
+            None => continue,
+        };
+        println!("run {}: {}", fuzzer.runs(), finding);
+        if let Some(output) = &opts.output {
+            let number = fuzzer.findings().len();
+            let path = output.join(format!("finding-{}.{}", number, RECORDING_EXTENSION));
+            std::fs::write(&path, autoplay::write_recording(&finding.input))
+                .wrap_err("Failed to write recording")?;
+        }
+    }
+    let _ = std::panic::take_hook();
+
+    println!(
+        "{} runs covered {} addresses with {} inputs kept",
+        fuzzer.runs(),
+        fuzzer.covered(),
+        fuzzer.corpus_len()
+    );
+    match fuzzer.findings().len() {
+        0 => Ok(()),
+        findings => Err(eyre!("Found {} crashing inputs", findings)),
+    }
+}
@@ -0,0 +1,25 @@
+use chippy::parser::syntax;
+use eyre::{Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct GenSyntaxOpt {
+    /// Directory the definitions are written to
+    #[structopt(short, long, default_value = ".", parse(from_os_str))]
+    output: PathBuf,
+}
+
+/// Write the TextMate and Tree-sitter syntax highlighting definitions of the assembly language
+pub fn run(opts: &GenSyntaxOpt) -> Result<()> {
+    for file in syntax::files() {
+        let path = opts.output.join(file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).wrap_err("Failed to create output directory")?;
+        }
+        std::fs::write(&path, file.contents)
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use chippy::{
+    emu::profile::Profile,
+    emu::quirks::Quirks,
+    emu::vm::{StopReason, SysPolicy, TimerClock, Vm},
+    exit::ExitCode,
+    render::{self, Palette},
+};
+use eyre::{eyre, Result, WrapErr};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+/// Screenshot pixels per side of a chip8 pixel, high resolution pixels are half as large
+const SCREENSHOT_SCALE: usize = 8;
+
+#[derive(Debug, StructOpt)]
+pub struct HeadlessOpt {
+    /// Process exit code when the rom executes exit (00FD), a number or a register vX to exit
+    /// with its value
+    #[structopt(long, default_value = "0")]
+    exit_code: ExitCode,
+
+    /// Fail if the rom is still running after this many frames
+    #[structopt(long)]
+    max_frames: Option<usize>,
+
+    /// Instructions run per frame
+    #[structopt(long, default_value = "11")]
+    ipf: usize,
+
+    /// Print the display when the rom ends
+    #[structopt(long)]
+    print_display: bool,
+
+    /// Write a png of every Nth frame, numbered by frame
+    #[structopt(long, value_name = "N")]
+    screenshot_every: Option<usize>,
+
+    /// Print the last N executed instructions when the rom fails or runs too long
+    #[structopt(long, value_name = "N")]
+    trace: Option<usize>,
+
+    /// Run with a behaviour of another interpreter, vf-reset for the COSMAC VIP, can be repeated
+    #[structopt(long, possible_values = Quirks::NAMES)]
+    quirk: Vec<String>,
+
+    /// Fail when the rom calls a machine code routine with sys (0nnn) instead of ignoring it
+    #[structopt(long)]
+    trap_sys: bool,
+
+    /// Print the N most executed addresses and the instruction mix when the rom ends
+    #[structopt(long, value_name = "N")]
+    profile: Option<usize>,
+
+    /// Directory of the screenshots
+    #[structopt(long, default_value = "screenshots", parse(from_os_str))]
+    screenshot_dir: PathBuf,
+
+    #[structopt(name = "ROM", parse(from_os_str))]
+    rom: PathBuf,
+}
+
+/// Run a rom as fast as possible without a display and exit with the code it reports
+pub fn run(opts: &HeadlessOpt) -> Result<()> {
+    let rom = std::fs::read(&opts.rom).wrap_err("Failed to open rom")?;
+    let mut vm = Vm::new()
+        .with_timer_clock(TimerClock::Realtime)
+        .with_history(opts.trace.unwrap_or(0));
+    vm.set_profiling(opts.profile.is_some());
+    vm.set_quirks(Quirks::from_names(opts.quirk.iter().map(String::as_str)).0);
+    if opts.trap_sys {
+        vm.set_sys_policy(SysPolicy::Trap);
+    }
+    vm.try_load(&rom)?;
+
+    if opts.screenshot_every == Some(0) {
+        return Err(eyre!("--screenshot-every must be at least 1"));
+    }
+    if opts.screenshot_every.is_some() {
+        std::fs::create_dir_all(&opts.screenshot_dir)
+            .wrap_err("Failed to create screenshot directory")?;
+    }
+
+    let result = run_frames(&mut vm, opts);
+    if let (Some(count), Some(profile)) = (opts.profile, vm.profile()) {
+        print_profile(profile, count);
+    }
+    if let Err(err) = result {
+        for (address, opcode, instruction) in vm.trace() {
+            eprintln!("{:03X}  {:04X}  {}", address, opcode, instruction.to_asm());
+        }
+        eprintln!("{}", vm);
+        return Err(err);
+    }
+
+    if opts.print_display {
+        println!("{}", vm.gpu);
+    }
+    if vm.stop_reason() == Some(StopReason::Exit) {
+        std::process::exit(opts.exit_code.code(&vm));
+    }
+    Ok(())
+}
+
+fn run_frames(vm: &mut Vm, opts: &HeadlessOpt) -> Result<()> {
+    let mut frames = 0;
+    for frame in vm.frames().cycles_per_frame(opts.ipf) {
+        let frame = frame?;
+        frames += 1;
+        if opts
+            .screenshot_every
+            .is_some_and(|every| frames % every == 0)
+        {
+            let path = opts.screenshot_dir.join(format!("frame-{:06}.png", frames));
+            write_png(&path, &frame.display)?;
+        }
+        if Some(frames) == opts.max_frames {
+            return Err(eyre!("Rom still running after {} frames", frames));
+        }
+    }
+    if let Some(StopReason::Sys(addr)) = vm.stop_reason() {
+        return Err(eyre!("Rom called the machine code routine at {:03X}", addr));
+    }
+    Ok(())
+}
+
+fn print_profile(profile: &Profile, count: usize) {
+    eprintln!("{} instructions", profile.total());
+    for (address, hits) in profile.hottest(count) {
+        let share = hits as f64 / profile.total() as f64 * 100.0;
+        eprintln!("{:03X}  {:>10}  {:5.1}%", address, hits, share);
+    }
+    for (class, name, share) in profile.mix() {
+        eprintln!("{:X}nnn {:<6} {:5.1}%", class, name, share * 100.0);
+    }
+}
+
+/// Write the display as an RGBA png
+fn write_png(path: &Path, display: &[bool]) -> Result<()> {
+    let file =
+        File::create(path).wrap_err_with(|| format!("Failed to create {}", path.display()))?;
+    let scale = render::fit_scale(display.len(), SCREENSHOT_SCALE);
+    let (width, height) = render::display_size(display.len());
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        (width * scale) as u32,
+        (height * scale) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| {
+            writer.write_image_data(&render::to_rgba(display, scale, Palette::default()))
+        })
+        .wrap_err("Failed to write png")
+}
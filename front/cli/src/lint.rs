@@ -0,0 +1,67 @@
+use chippy::{
+    lint::{self, Finding, Severity, Target},
+    parser::assembler,
+};
+use eyre::{eyre, Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct LintOpt {
+    /// Interpreter the program should run on
+    #[structopt(long, possible_values = Target::VARIANTS)]
+    target: Target,
+
+    /// Rom, or assembly source with an asm extension
+    #[structopt(name = "PROGRAM", parse(from_os_str))]
+    program: PathBuf,
+}
+
+/// Print the instructions of a program that are unsupported or quirk dependent on the target,
+/// failing if any is unsupported
+pub fn run(opts: &LintOpt) -> Result<()> {
+    let path = opts.program.display();
+    let findings = match opts.program.extension().and_then(|ext| ext.to_str()) {
+        Some("asm") => {
+            let source =
+                std::fs::read_to_string(&opts.program).wrap_err("Failed to open source file")?;
+            let assembly = assembler::assemble(&source)?;
+            let code = assembly.lines.keys().copied().collect();
+            let findings = lint::lint(&assembly.bytes, &code, opts.target);
+            for finding in findings.iter() {
+                let line = assembly.lines[&finding.address];
+                print_finding(&format!("{}:{}", path, line + 1), finding);
+            }
+            findings
+        }
+        _ => {
+            let rom = std::fs::read(&opts.program).wrap_err("Failed to open rom")?;
+            let findings = lint::lint_rom(&rom, opts.target);
+            for finding in findings.iter() {
+                print_finding(&path.to_string(), finding);
+            }
+            findings
+        }
+    };
+
+    let unsupported = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Unsupported)
+        .count();
+    match unsupported {
+        0 => Ok(()),
+        _ => Err(eyre!(
+            "{} instructions are not supported by {}",
+            unsupported,
+            opts.target
+        )),
+    }
+}
+
+fn print_finding(location: &str, finding: &Finding) {
+    let severity = match finding.severity {
+        Severity::Unsupported => "error",
+        Severity::Quirk => "warning",
+    };
+    println!("{}: {}: {}", severity, location, finding);
+}
@@ -1,15 +1,54 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 
-use chippy::emu::{
-    gpu,
-    input::Key,
-    vm::{ProgramState, Vm},
+use cast::{Recorder, TeeWriter};
+use chippy::{
+    debug::{
+        events::{Event, EventKind, EventLog},
+        Inspect,
+    },
+    emu::{
+        dump::{self, Dump},
+        frame::DEFAULT_CYCLES_PER_FRAME,
+        gpu,
+        input::Key,
+        pacing::Pacer,
+        quirks::Quirks,
+        rewind::Rewinder,
+        speed::SpeedRamp,
+        state::VmState,
+        vm::{ProgramState, SoundEvent, StopReason, TimerClock, Vm},
+    },
+    exit::ExitCode,
+    locale::{self, Language, Message},
+    netplay::{Follower, Host, InputFrame, VoteServer},
+    render::Palette,
+    rom::{
+        database::{self, RomDatabase},
+        header::RomHeader,
+    },
+    score::{self, HighScores, ScoreLocation},
+    status::{Metrics, StatusServer},
+    storage::Storage,
+    video::VideoRecorder,
+    wav::WavRecorder,
+};
+use crossterm::{
+    cursor::MoveTo,
+    event::KeyCode,
+    execute,
+    style::Print,
+    terminal::{Clear, ClearType},
 };
-use crossterm::event::KeyCode;
-use eyre::{Result, WrapErr};
+use debugger::Debugger;
+use eyre::{eyre, Result, WrapErr};
+use render::{detect::Capabilities, Renderer};
+use slots::SaveSlots;
 use std::{
-    path::PathBuf,
+    cell::RefCell,
+    io::Stdout,
+    path::{Path, PathBuf},
+    rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -25,29 +64,509 @@ use tui::{
     widgets::{Block, BorderType, Borders},
     Frame, Terminal,
 };
+mod asm;
+#[cfg(feature = "stream")]
+mod attract;
+mod bench;
+mod cast;
+mod debugger;
+mod diff;
+mod disasm;
+mod fuzz;
+mod gen_syntax;
+mod headless;
+mod lint;
+mod map;
+mod patch;
+mod render;
+mod repl;
+mod slots;
+mod soak;
+mod sprite;
+mod suite;
 mod ui;
 
-type Term = tui::terminal::Terminal<tui::backend::CrosstermBackend<std::io::Stdout>>;
+// Rewind history of 60 seconds at 60 fps
+const REWIND_INTERVAL: usize = 6;
+const REWIND_CAPACITY: usize = 600;
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+/// Size of a chip8 pixel in the streamed frames, high resolution pixels are half as large
+#[cfg(feature = "stream")]
+const STREAM_SCALE: usize = 8;
+/// Size of a chip8 pixel in recorded videos, high resolution pixels are half as large
+const VIDEO_SCALE: usize = 8;
+const SCORES_FILE: &str = "chippy-scores.txt";
+const ROM_DATABASE_FILE: &str = "chippy-roms.txt";
+
+type Term = tui::terminal::Terminal<tui::backend::CrosstermBackend<TeeWriter<Stdout>>>;
+
+const HOTKEYS: &str = "HOTKEYS:
+    0-9, a-f     Chip8 keypad
+    [ ]          Select previous/next save slot
+    F5           Save state to the current slot
+    F9           Load state from the current slot
+    Backspace    Rewind
+    q, Esc       Quit
+
+DEBUGGER HOTKEYS (--debug):
+    Space        Pause/resume
+    s            Step one instruction
+    S            Step back one instruction
+    Up, Down     Move the disassembly cursor
+    PgUp, PgDn   Move the disassembly cursor a page
+    Enter        Follow the jump or call under the cursor
+    Backspace    Return to the previous location
+    /            Go to an address or label
+    m            Toggle a bookmark at the cursor
+    '            Go to the next bookmark
+    g            Go back to the program counter
+    x            Toggle a breakpoint at the cursor
+    :            Open the command line
+    Tab          Switch between the disassembly, memory and history panes
+
+MEMORY PANE:
+    Arrows       Move the cursor
+    PgUp, PgDn   Move the cursor 256 bytes
+    0-9, a-f     Overwrite the byte under the cursor
+    /            Go to an address
+    i            Go to the address in the I register
+
+COMMANDS:
+    break ADDR [if CONDITION]    Break at ADDR, optionally only if CONDITION is true,
+                                 for example `break 2A4 if v3 == 0x1F && I > 0x300`
+    delete [ADDR]                Delete the breakpoint at ADDR or all breakpoints
+    trigger [NAME]               Toggle breaking on an event or list the enabled triggers:
+                                   draw       any draw instruction
+                                   collision  a draw instruction that sets vf
+                                   sound      the sound timer starts
+                                   key        the program checks or waits for a key
+
+HISTORY PANE:
+    Up, Down     Scroll through the executed instructions
+    PgUp, PgDn   Scroll a page
+    Home, End    Go to the oldest/most recent instruction";
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "chippy")]
+#[structopt(
+    name = "chippy",
+    after_help = HOTKEYS,
+    setting = structopt::clap::AppSettings::SubcommandsNegateReqs
+)]
 struct Opt {
     /// Set fps
     #[structopt(short, long, default_value = "60")]
     fps: usize,
 
-    #[structopt(name = "FILE")]
-    filepath: PathBuf,
+    /// Instructions run per frame, 11 matches the COSMAC VIP and 30 suits SUPER-CHIP games.
+    /// Defaults to the speed given by the header file of the rom, or 11.
+    #[structopt(long)]
+    ipf: Option<usize>,
+
+    /// Instructions run per second, overrides --ipf
+    #[structopt(long, value_name = "IPS")]
+    speed: Option<f64>,
+
+    /// Exit with this code when the rom executes exit (00FD), a number or a register vX to exit
+    /// with its value
+    #[structopt(long)]
+    exit_code: Option<ExitCode>,
+
+    /// Language of the status bar and debugger, defaults to the language of the system
+    #[structopt(long, possible_values = Language::VARIANTS)]
+    lang: Option<Language>,
+
+    /// Renderer used to draw the display, picks the best one the terminal supports by default
+    #[structopt(long, possible_values = Renderer::VARIANTS)]
+    renderer: Option<Renderer>,
+
+    /// Use this renderer even if the terminal does not seem to support it
+    #[structopt(long, possible_values = Renderer::VARIANTS, conflicts_with = "renderer")]
+    force_renderer: Option<Renderer>,
+
+    /// Size in terminal pixels of a chip8 pixel for image renderers
+    #[structopt(long, default_value = "8")]
+    scale: usize,
+
+    /// Terminal cells per chip8 pixel in each direction for the block renderer, larger blocks
+    /// are easier to see
+    #[structopt(long, default_value = "1", value_name = "CELLS")]
+    block_scale: u16,
+
+    /// Colors of set and unset pixels as ON:OFF hex colors or a preset name, used by the block
+    /// renderer on truecolor terminals and for recorded and streamed frames. The yellow,
+    /// inverted, white and sky presets have a high contrast. Defaults to the palette of the rom
+    /// header.
+    #[structopt(long)]
+    palette: Option<Palette>,
+
+    /// Write the score and status messages to stderr as plain lines when they change, for
+    /// screen readers. Redirect stderr to a file or pipe to read them.
+    #[structopt(long)]
+    screen_reader: bool,
+
+    /// Ring the terminal bell when the buzzer starts
+    #[structopt(long)]
+    bell: bool,
+
+    /// Record the session as an asciinema v2 cast file
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Record the buzzer of the session as a WAV file, in sync with --record
+    #[structopt(long, parse(from_os_str))]
+    record_audio: Option<PathBuf>,
+
+    /// Record the display and buzzer as a video with ffmpeg, the format is picked from the
+    /// extension (mp4, webm, ...)
+    #[structopt(long, parse(from_os_str))]
+    record_video: Option<PathBuf>,
+
+    /// Write the machine state and the last executed instructions to this file if the program
+    /// fails
+    #[structopt(long, parse(from_os_str))]
+    crash_dump: Option<PathBuf>,
+
+    /// Write draw, beep, key poll, halt and breakpoint events to this file as JSON lines
+    #[structopt(long, parse(from_os_str))]
+    events: Option<PathBuf>,
+
+    /// Directory for save state slots, defaults to the directory of the rom
+    #[structopt(long, parse(from_os_str))]
+    state_dir: Option<PathBuf>,
+
+    /// Run with a behaviour of another interpreter, vf-reset for the COSMAC VIP, can be repeated
+    #[structopt(long, possible_values = Quirks::NAMES)]
+    quirk: Vec<String>,
+
+    /// Start paused in the debugger view
+    #[structopt(long)]
+    debug: bool,
+
+    /// Pin an expression or memory range START..END in the debugger, can be repeated
+    #[structopt(long, value_name = "EXPR")]
+    watch: Vec<String>,
+
+    /// Apply an IPS, BPS or chippy text patch to the rom before running it
+    #[structopt(long, parse(from_os_str))]
+    patch: Option<PathBuf>,
+
+    /// Memory holding the score of the game as ADDRESS:LEN[:bcd|binary], the best score is
+    /// kept for every rom
+    #[structopt(long)]
+    score: Option<ScoreLocation>,
+
+    /// Find the score by playing the rom with random keys before starting, unless --score is
+    /// given
+    #[structopt(long)]
+    detect_score: bool,
+
+    /// File storing the best scores, defaults to chippy-scores.txt in the state directory
+    #[structopt(long, parse(from_os_str))]
+    scores_file: Option<PathBuf>,
+
+    /// Rom database naming the keys of every rom, shown in the status bar. Defaults to
+    /// chippy-roms.txt next to the rom.
+    #[structopt(long, parse(from_os_str))]
+    rom_database: Option<PathBuf>,
+
+    /// Wait for a follower on ADDR and mirror the keypad to it (experimental)
+    #[structopt(long, value_name = "ADDR")]
+    host: Option<String>,
+
+    /// Follow the session of a host on ADDR, local keys are ignored (experimental)
+    #[structopt(long, value_name = "ADDR", conflicts_with = "host")]
+    follow: Option<String>,
+
+    /// Let clients connecting to ADDR vote for the key to press, one hex digit per line
+    #[structopt(long, value_name = "ADDR", conflicts_with = "follow")]
+    votes: Option<String>,
+
+    /// Frames in a voting window, the key with the most votes is held for the next window
+    #[structopt(long, default_value = "30")]
+    vote_window: usize,
+
+    /// Serve the display as an MJPEG stream over HTTP on ADDR, for capture in streaming software
+    #[cfg(feature = "stream")]
+    #[structopt(long, value_name = "ADDR")]
+    stream: Option<String>,
+
+    /// Serve the uptime, clients, rom checksum, speed and dropped frames over HTTP on ADDR, as
+    /// JSON or in the Prometheus text format at /metrics
+    #[structopt(long, value_name = "ADDR")]
+    status: Option<String>,
+
+    /// Rom to load from a zip archive, by default the only rom in the archive
+    #[structopt(long)]
+    entry: Option<String>,
+
+    #[structopt(name = "FILE", parse(from_os_str))]
+    filepath: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    tool: Option<Tool>,
+}
+
+#[derive(Debug, StructOpt)]
+struct DebugOpt {
+    /// Crash dump written by --crash-dump
+    #[structopt(long, parse(from_os_str))]
+    dump: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+enum Tool {
+    /// Assemble a source file into a rom
+    Asm(asm::AsmOpt),
+    /// Play the roms of a playlist in turn with recorded or random input, streaming the display
+    #[cfg(feature = "stream")]
+    Attract(attract::AttractOpt),
+    /// Measure the interpreter throughput and compare it to a previous run
+    Bench(bench::BenchOpt),
+    /// Open a crash dump in the debugger
+    Debug(DebugOpt),
+    /// Print the instructions that differ between two roms
+    Diff(diff::DiffOpt),
+    /// Print the instructions of a rom
+    Disasm(disasm::DisasmOpt),
+    /// Search for key sequences that crash a rom, guided by the addresses they execute
+    Fuzz(fuzz::FuzzOpt),
+    /// Write syntax highlighting definitions of the assembly language for editors
+    GenSyntax(gen_syntax::GenSyntaxOpt),
+    /// Run a rom without a display, exiting with the code it reports with exit (00FD)
+    Headless(headless::HeadlessOpt),
+    /// Flag the instructions of a rom that another interpreter does not run or runs differently
+    Lint(lint::LintOpt),
+    /// Chart where the code, data and padding of an assembly source end up in memory
+    Map(map::MapOpt),
+    /// Apply a patch to a rom
+    Patch(patch::PatchOpt),
+    /// Assemble and execute instructions interactively
+    Repl,
+    /// Run random roms on many vms for hours, checking the machine invariants
+    Soak(soak::SoakOpt),
+    /// Convert a monochrome png image into sprite data
+    Sprite(sprite::SpriteOpt),
+    /// Run the test suites of roms, see `chippy::rom::suite` for the format
+    Test(suite::TestOpt),
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
     let opts = Opt::from_args();
+    locale::set_language(opts.lang.unwrap_or_else(Language::from_env));
+    let mut dump = None;
+    if let Some(tool) = &opts.tool {
+        match tool {
+            Tool::Asm(asm_opts) => return asm::run(asm_opts),
+            #[cfg(feature = "stream")]
+            Tool::Attract(attract_opts) => return attract::run(attract_opts),
+            Tool::Bench(bench_opts) => return bench::run(bench_opts),
+            Tool::Debug(debug_opts) => {
+                let bytes = std::fs::read(&debug_opts.dump).wrap_err("Failed to open dump")?;
+                let decoded = Dump::decode(&bytes).ok_or_else(|| eyre!("Invalid crash dump"))?;
+                dump = Some((debug_opts.dump.clone(), decoded));
+            }
+            Tool::Diff(diff_opts) => return diff::run(diff_opts),
+            Tool::Disasm(disasm_opts) => return disasm::run(disasm_opts),
+            Tool::Fuzz(fuzz_opts) => return fuzz::run(fuzz_opts),
+            Tool::GenSyntax(gen_syntax_opts) => return gen_syntax::run(gen_syntax_opts),
+            Tool::Headless(headless_opts) => return headless::run(headless_opts),
+            Tool::Lint(lint_opts) => return lint::run(lint_opts),
+            Tool::Map(map_opts) => return map::run(map_opts),
+            Tool::Patch(patch_opts) => return patch::run(patch_opts),
+            Tool::Repl => return repl::run(),
+            Tool::Soak(soak_opts) => return soak::run(soak_opts),
+            Tool::Sprite(sprite_opts) => return sprite::run(sprite_opts),
+            Tool::Test(test_opts) => return suite::run(test_opts),
+        }
+    }
+    let debug = opts.debug || dump.is_some();
+
+    let filepath = match &dump {
+        Some((path, _)) => path.clone(),
+        None => opts
+            .filepath
+            .clone()
+            .ok_or_else(|| eyre!(Message::NoRomFile))?,
+    };
+    let caps = Capabilities::detect();
+    let renderer = match (opts.force_renderer, opts.renderer) {
+        // The debugger panes are only drawn by the block renderer
+        _ if debug => Renderer::Blocks,
+        (Some(forced), _) => forced,
+        (None, Some(requested)) => {
+            let renderer = caps.fallback(requested);
+            if renderer != requested {
+                eprintln!(
+                    "Renderer {:?} is not supported by this terminal, using {:?}",
+                    requested, renderer
+                );
+            }
+            renderer
+        }
+        (None, None) => caps.best_renderer(),
+    };
 
-    let bytes = std::fs::read(&opts.filepath).wrap_err("Failed to open c8 file")?;
-    let mut vm = Vm::new();
-    vm.load(bytes);
+    let mut vm = Vm::new().with_timer_clock(TimerClock::Realtime);
+    let mut score_location = opts.score;
+    let checksum = match &dump {
+        Some((_, dump)) => {
+            dump.restore(&mut vm);
+            dump.rom_checksum
+        }
+        None => {
+            let mut bytes = chippy::rom::read(&filepath, opts.entry.as_deref())
+                .wrap_err(Message::OpenRomFailed)?;
+            if let Some(patch) = &opts.patch {
+                let patch = std::fs::read(patch).wrap_err("Failed to open patch file")?;
+                bytes = chippy::rom::apply_patch(&bytes, &patch)?;
+            }
+            let checksum = chippy::rom::checksum(&bytes);
+            if score_location.is_none() && opts.detect_score {
+                score_location = score::detect(&bytes, checksum as u64);
+                match score_location {
+                    Some(location) => eprintln!("Found the score at {}", location),
+                    None => eprintln!("No score found, the rom does not draw one with the font"),
+                }
+            }
+            vm.try_load(&bytes)?;
+            checksum
+        }
+    };
+    if opts.crash_dump.is_some() {
+        vm.set_history_capacity(dump::TRACE_SIZE);
+    }
+    let header = RomHeader::load_for(&filepath)
+        .wrap_err(Message::ReadHeaderFailed)?
+        .unwrap_or_default();
+    let names = header.quirks.iter().chain(opts.quirk.iter());
+    let (quirks, unsupported) = Quirks::from_names(names.map(String::as_str));
+    if !unsupported.is_empty() {
+        eprintln!(
+            "Ignoring the quirks of the rom, they are not supported: {}",
+            unsupported.join(", ")
+        );
+    }
+    vm.set_quirks(quirks);
+    let rom_database = RomDatabase::load(opts.rom_database.clone().unwrap_or_else(|| {
+        filepath
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+            .join(ROM_DATABASE_FILE)
+    }))
+    .wrap_err(Message::ReadDatabaseFailed)?;
+    // The keypad is mapped to the keys of the same hex digit
+    let key_hints = database::key_hints(&rom_database.key_labels(checksum, &header), |key| {
+        format!("{:x}", key)
+    });
+
+    let scores_file = opts.scores_file.clone().unwrap_or_else(|| {
+        opts.state_dir
+            .clone()
+            .or_else(|| filepath.parent().map(Path::to_path_buf))
+            .unwrap_or_default()
+            .join(SCORES_FILE)
+    });
+    let mut high_scores = match score_location {
+        Some(_) => HighScores::load(&scores_file).wrap_err("Failed to read high scores")?,
+        None => HighScores::new(),
+    };
+    let mut new_high_score = false;
+
+    let mut host = match &opts.host {
+        Some(addr) => {
+            println!("Waiting for a follower on {}", addr);
+            Some(Host::listen(addr).wrap_err("Failed to host the session")?)
+        }
+        None => None,
+    };
+    let mut follower = match &opts.follow {
+        Some(addr) => Some(Follower::connect(addr).wrap_err("Failed to join the session")?),
+        None => None,
+    };
+
+    let mut vote_server = match &opts.votes {
+        Some(addr) => Some(
+            VoteServer::bind(addr, opts.vote_window).wrap_err("Failed to start the vote server")?,
+        ),
+        None => None,
+    };
+
+    #[cfg(feature = "stream")]
+    let mut stream = match &opts.stream {
+        Some(addr) => Some(
+            chippy::stream::MjpegServer::bind(addr)
+                .wrap_err("Failed to start the stream server")?,
+        ),
+        None => None,
+    };
+
+    let mut status_server = match &opts.status {
+        Some(addr) => Some(StatusServer::bind(addr).wrap_err("Failed to start the status server")?),
+        None => None,
+    };
+    let mut metrics = Metrics::new(checksum);
+
+    let mut audio = match opts.record_audio.is_some() || opts.record_video.is_some() {
+        true => Some(WavRecorder::new(opts.fps as u32)),
+        false => None,
+    };
+    let mut video = match &opts.record_video {
+        Some(path) => {
+            let (width, height) = (
+                gpu::SCREEN_WIDTH * VIDEO_SCALE,
+                gpu::SCREEN_HEIGHT * VIDEO_SCALE,
+            );
+            let recorder = VideoRecorder::start(path, opts.fps as u32, width, height)
+                .wrap_err("Failed to start ffmpeg")?;
+            Some(recorder)
+        }
+        None => None,
+    };
+
+    let mut events = match &opts.events {
+        Some(path) => {
+            let file = std::fs::File::create(path).wrap_err("Failed to create event log")?;
+            Some(EventLog::new(std::io::BufWriter::new(file)))
+        }
+        None => None,
+    };
+
+    let mut slots = SaveSlots::new(&filepath, opts.state_dir.clone(), checksum);
+    // The RPL flags of the HP48 roms are kept between runs, a crash dump has its own
+    if dump.is_none() {
+        if let Some(flags) = slots
+            .storage()
+            .load_flags(checksum)
+            .wrap_err("Failed to read flags")?
+        {
+            vm.set_flags(&flags);
+        }
+    }
+    let flags_before = vm.flags().to_vec();
+    let mut rewind = Rewinder::new(REWIND_INTERVAL, REWIND_CAPACITY);
+    // State before the last frame, only captured while logging events
+    let mut before = VmState::default();
+    let mut message: Option<(String, Instant)> = dump
+        .as_ref()
+        .map(|(_, dump)| (dump.error.clone(), Instant::now()));
+    let mut frame_count = 0usize;
+    let mut cycle_count = 0u64;
+    let mut debugger = match debug {
+        true => Some(Debugger::new(&mut vm)),
+        false => None,
+    };
+    if let Some(debugger) = &mut debugger {
+        for watch in opts.watch.iter() {
+            debugger
+                .watches
+                .add(watch)
+                .map_err(|e| eyre!("Invalid watch {}: {}", watch, e))?;
+        }
+        debugger.watches.update(&vm);
+    }
 
     // Because the parent thread that is spawning this thread is the main one we dont have to join
     // it at the end of the program. As it is the end of the program it will be terminated.
@@ -64,64 +583,343 @@ fn main() -> Result<()> {
         ctrlc_running_handle.store(false, Ordering::SeqCst);
     })?;
 
-    let mut term = create_terminal()?;
+    let recorder = match &opts.record {
+        Some(path) => {
+            let (width, height) = crossterm::terminal::size()?;
+            Some(Rc::new(RefCell::new(Recorder::create(
+                path, width, height,
+            )?)))
+        }
+        None => None,
+    };
+
+    let mut term = create_terminal(TeeWriter::new(std::io::stdout(), recorder.clone()))?;
+    let mut stdout = TeeWriter::new(std::io::stdout(), recorder.clone());
+    if renderer != Renderer::Blocks {
+        execute!(stdout, Clear(ClearType::All))?;
+    }
 
-    let frame = Duration::from_millis((1000 / opts.fps) as u64);
+    let mut pacer = Pacer::with_fps(opts.fps as u32);
+    let frame_period = Duration::from_secs(1) / opts.fps.max(1) as u32;
+    let ipf = opts
+        .ipf
+        .or_else(|| header.cycles_per_frame())
+        .unwrap_or(DEFAULT_CYCLES_PER_FRAME);
+    // Colors of the block renderer and of the recorded and streamed frames
+    let look = ui::Look {
+        palette: opts.palette.or(header.palette),
+        scale: opts.block_scale,
+    };
+    let palette = look.palette.unwrap_or_default();
+    let mut announcement = String::new();
+    let mut governor = SpeedRamp::new(opts.speed.unwrap_or((ipf * opts.fps) as f64));
     while running.load(Ordering::SeqCst) {
-        let now = Instant::now();
+        let mut redraw = false;
 
         vm.input.clear();
         while let Ok(event) = rx.try_recv() {
             match event {
-                crossterm::event::Event::Key(key) => match key.code {
-                    KeyCode::Esc => running.store(false, Ordering::SeqCst),
-                    KeyCode::Char('q') => running.store(false, Ordering::SeqCst),
-                    KeyCode::Char('0') => vm.input.key_down(Key::Zero),
-                    KeyCode::Char('1') => vm.input.key_down(Key::One),
-                    KeyCode::Char('2') => vm.input.key_down(Key::Two),
-                    KeyCode::Char('3') => vm.input.key_down(Key::Three),
-                    KeyCode::Char('4') => vm.input.key_down(Key::Four),
-                    KeyCode::Char('5') => vm.input.key_down(Key::Five),
-                    KeyCode::Char('6') => vm.input.key_down(Key::Six),
-                    KeyCode::Char('7') => vm.input.key_down(Key::Seven),
-                    KeyCode::Char('8') => vm.input.key_down(Key::Eight),
-                    KeyCode::Char('9') => vm.input.key_down(Key::Nine),
-                    KeyCode::Char('a') => vm.input.key_down(Key::A),
-                    KeyCode::Char('b') => vm.input.key_down(Key::B),
-                    KeyCode::Char('c') => vm.input.key_down(Key::C),
-                    KeyCode::Char('d') => vm.input.key_down(Key::D),
-                    KeyCode::Char('e') => vm.input.key_down(Key::E),
-                    KeyCode::Char('f') => vm.input.key_down(Key::F),
-                    KeyCode::Char(_) => {}
-                    _ => {}
-                },
+                crossterm::event::Event::Key(key) => {
+                    if let Some(recorder) = &recorder {
+                        record_key(&mut recorder.borrow_mut(), key.code)?;
+                    }
+
+                    if let Some(debugger) = &mut debugger {
+                        if debugger.handle_key(key.code, &mut vm) {
+                            redraw = true;
+                            continue;
+                        }
+                    }
+
+                    match key.code {
+                        KeyCode::Esc => running.store(false, Ordering::SeqCst),
+                        KeyCode::Char('q') => running.store(false, Ordering::SeqCst),
+                        KeyCode::Char('0') => vm.input.key_down(Key::Zero),
+                        KeyCode::Char('1') => vm.input.key_down(Key::One),
+                        KeyCode::Char('2') => vm.input.key_down(Key::Two),
+                        KeyCode::Char('3') => vm.input.key_down(Key::Three),
+                        KeyCode::Char('4') => vm.input.key_down(Key::Four),
+                        KeyCode::Char('5') => vm.input.key_down(Key::Five),
+                        KeyCode::Char('6') => vm.input.key_down(Key::Six),
+                        KeyCode::Char('7') => vm.input.key_down(Key::Seven),
+                        KeyCode::Char('8') => vm.input.key_down(Key::Eight),
+                        KeyCode::Char('9') => vm.input.key_down(Key::Nine),
+                        KeyCode::Char('a') => vm.input.key_down(Key::A),
+                        KeyCode::Char('b') => vm.input.key_down(Key::B),
+                        KeyCode::Char('c') => vm.input.key_down(Key::C),
+                        KeyCode::Char('d') => vm.input.key_down(Key::D),
+                        KeyCode::Char('e') => vm.input.key_down(Key::E),
+                        KeyCode::Char('f') => vm.input.key_down(Key::F),
+                        KeyCode::Char('[') => slots.previous(),
+                        KeyCode::Char(']') => slots.next(),
+                        KeyCode::F(5) => {
+                            let text = match slots.save(&vm) {
+                                Ok(()) => Message::SavedSlot.format(&[&slots.current()]),
+                                Err(err) => err.to_string(),
+                            };
+                            message = Some((text, Instant::now()));
+                        }
+                        KeyCode::F(9) => {
+                            let text = match slots.load(&mut vm) {
+                                Ok(()) => Message::LoadedSlot.format(&[&slots.current()]),
+                                Err(err) => err.to_string(),
+                            };
+                            message = Some((text, Instant::now()));
+                        }
+                        KeyCode::Backspace => {
+                            rewind.rewind(&mut vm, REWIND_INTERVAL);
+                        }
+                        KeyCode::Char(_) => {}
+                        _ => {}
+                    }
+                    redraw = true;
+                }
                 _ => {}
             }
         }
 
-        match vm.cycle() {
-            ProgramState::Continue => {}
-            ProgramState::Stop => running.store(false, Ordering::SeqCst),
+        let cycles = match &mut debugger {
+            // Step one instruction at a time so that the triggers and step back see every one
+            Some(debugger) => debugger.should_cycle() as usize,
+            None => governor.advance(frame_period),
+        };
+
+        if cycles > 0 {
+            if let Some(vote_server) = &mut vote_server {
+                vote_server
+                    .update(&mut vm.input)
+                    .wrap_err("Vote server failed")?;
+            }
+            if let Some(host) = &mut host {
+                host.send(InputFrame::capture(frame_count as u64, &vm.input))
+                    .wrap_err("Lost the follower")?;
+            }
+            if let Some(follower) = &mut follower {
+                match follower.recv().wrap_err("Lost the host")? {
+                    Some(input) => input.apply(&mut vm.input),
+                    None => break,
+                }
+            }
+
+            if events.is_some() {
+                vm.snapshot_into(&mut before);
+            }
+            let address = vm.program_counter();
+            let stopped = match vm
+                .frames()
+                .cycles_per_frame(cycles)
+                .period(frame_period)
+                .next()
+            {
+                Some(Ok(frame)) => {
+                    if !frame.breakpoint {
+                        cycle_count += cycles as u64;
+                    }
+                    frame.breakpoint
+                }
+                None => {
+                    if let Some(events) = &mut events {
+                        events.write(&Event {
+                            kind: EventKind::Halt,
+                            cycle: cycle_count,
+                            frame: frame_count as u64,
+                            address,
+                        })?;
+                    }
+                    running.store(false, Ordering::SeqCst);
+                    false
+                }
+                Some(Err(error)) => {
+                    crossterm::terminal::disable_raw_mode().unwrap();
+                    if let Some(path) = &opts.crash_dump {
+                        std::fs::write(path, Dump::capture(&vm, checksum, error).encode())
+                            .wrap_err("Failed to write crash dump")?;
+                        eprintln!(
+                            "Wrote a crash dump to {0}, inspect it with `chippy debug --dump {0}`",
+                            path.display()
+                        );
+                    }
+                    return Err(error.into());
+                }
+            };
+
+            if let Some(events) = &mut events {
+                events.step(cycle_count, frame_count as u64, &before, &vm)?;
+            }
+
+            rewind.record(&vm);
+            frame_count += 1;
+
+            if let Some(location) = &score_location {
+                new_high_score |= high_scores.submit(checksum, location.read(&vm));
+            }
+
+            if let Some(debugger) = &mut debugger {
+                let breakpoint = debugger.sync(&mut vm, stopped);
+                if let (Some(events), Some(address)) = (&mut events, breakpoint) {
+                    events.write(&Event {
+                        kind: EventKind::Breakpoint,
+                        cycle: cycle_count,
+                        frame: frame_count as u64,
+                        address,
+                    })?;
+                }
+                redraw = true;
+            }
+        } else if debugger.is_none() {
+            // The timers run on while the speed is too low to run an instruction every frame
+            vm.tick(frame_period);
+        }
+
+        if vm.take_sound_event() == Some(SoundEvent::Started) && opts.bell {
+            execute!(stdout, Print('\x07'))?;
+        }
+        if let Some(audio) = &mut audio {
+            audio.push(vm.sound_active());
+        }
+        if let Some(video) = &mut video {
+            let display = vm.gpu.display();
+            let scale = chippy::render::fit_scale(display.len(), VIDEO_SCALE);
+            let frame = chippy::render::to_rgba(display, scale, palette);
+            video
+                .push(&frame)
+                .wrap_err("Failed to record video frame")?;
         }
 
-        if vm.gpu.pending_draw {
-            term.draw(|f| ui::draw(f, &vm.gpu))?;
+        if let Some((_, shown)) = &message {
+            if shown.elapsed() > MESSAGE_DURATION {
+                message = None;
+                redraw = true;
+            }
+        }
+
+        let status = ui::Status {
+            keys: &key_hints,
+            slot: slots.current(),
+            rewind: rewind.len(),
+            message: message.as_ref().map(|(text, _)| text.as_str()),
+            score: score_location.map(|location| {
+                let best = high_scores.best(checksum).unwrap_or(0);
+                (location.read(&vm), best)
+            }),
+        };
+        if opts.screen_reader {
+            let current = status.announcement();
+            if current != announcement {
+                if !current.is_empty() {
+                    eprintln!("{}", current);
+                }
+                announcement = current;
+            }
+        }
+
+        if vm.gpu.pending_draw || redraw {
+            match renderer {
+                Renderer::Blocks => {
+                    match &debugger {
+                        Some(debugger) => term.draw(|f| debugger.draw(f, &vm, &caps, &status))?,
+                        None => term.draw(|f| ui::draw(f, &vm.gpu, &caps, &status, look))?,
+                    };
+                }
+                Renderer::Sixel => {
+                    render::sixel::draw(&mut stdout, &vm.gpu, opts.scale, caps.multiplexer)?
+                }
+                Renderer::Kitty => {
+                    render::kitty::draw(&mut stdout, &vm.gpu, opts.scale, caps.multiplexer)?
+                }
+            }
+            if renderer != Renderer::Blocks {
+                draw_status_line(&mut stdout, &status)?;
+            }
             vm.gpu.pending_draw = false;
+
+            #[cfg(feature = "stream")]
+            if let Some(stream) = &mut stream {
+                let display = vm.gpu.display();
+                let scale = chippy::render::fit_scale(display.len(), STREAM_SCALE);
+                let frame = chippy::render::to_rgba(display, scale, palette);
+                let (width, height) = (vm.gpu.width() * scale, vm.gpu.height() * scale);
+                stream
+                    .send(&frame, width as u16, height as u16)
+                    .wrap_err("Failed to stream frame")?;
+            }
         }
 
-        if let Some(remaining) = frame.checked_sub(now.elapsed()) {
-            std::thread::sleep(remaining);
+        if let Some(status_server) = &mut status_server {
+            metrics.frame(cycles);
+            metrics.dropped_frames = pacer.dropped();
+            metrics.clients =
+                host.is_some() as usize + vote_server.as_ref().map_or(0, VoteServer::voters);
+            #[cfg(feature = "stream")]
+            {
+                metrics.clients += stream.as_ref().map_or(0, |stream| stream.clients());
+            }
+            status_server
+                .serve(&metrics)
+                .wrap_err("Status server failed")?;
         }
+
+        pacer.wait();
     }
 
     crossterm::terminal::disable_raw_mode().unwrap();
 
+    if let (Some(audio), Some(path)) = (&audio, &opts.record_audio) {
+        let file = std::fs::File::create(path).wrap_err("Failed to create audio recording")?;
+        audio
+            .write(std::io::BufWriter::new(file))
+            .wrap_err("Failed to write audio recording")?;
+    }
+    if let Some(video) = video {
+        video
+            .finish(audio.as_ref())
+            .wrap_err("Failed to write video")?;
+    }
+    if let Some(events) = &mut events {
+        events.flush().wrap_err("Failed to write event log")?;
+    }
+    if vm.flags() != flags_before.as_slice() {
+        slots
+            .storage()
+            .save_flags(checksum, vm.flags())
+            .wrap_err("Failed to write flags")?;
+    }
+    if new_high_score {
+        high_scores
+            .save(&scores_file)
+            .wrap_err("Failed to write high scores")?;
+    }
+    if let (Some(exit_code), Some(StopReason::Exit)) = (&opts.exit_code, vm.stop_reason()) {
+        std::process::exit(exit_code.code(&vm));
+    }
     Ok(())
 }
 
-fn create_terminal() -> Result<Term> {
-    let stdout = std::io::stdout();
-    let backend = tui::backend::CrosstermBackend::new(stdout);
+fn draw_status_line<W: std::io::Write>(out: &mut W, status: &ui::Status) -> Result<()> {
+    let (_, height) = crossterm::terminal::size()?;
+    execute!(
+        out,
+        MoveTo(0, height.saturating_sub(1)),
+        Clear(ClearType::CurrentLine),
+        Print(status.to_string())
+    )?;
+    Ok(())
+}
+
+fn record_key(recorder: &mut Recorder, code: KeyCode) -> Result<()> {
+    let data = match code {
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Esc => "\x1b".to_string(),
+        KeyCode::Enter => "\r".to_string(),
+        KeyCode::Tab => "\t".to_string(),
+        KeyCode::Backspace => "\x7f".to_string(),
+        _ => return Ok(()),
+    };
+    recorder.input(&data).wrap_err("Failed to record key")
+}
+
+fn create_terminal(writer: TeeWriter<Stdout>) -> Result<Term> {
+    let backend = tui::backend::CrosstermBackend::new(writer);
     let something = tui::terminal::Terminal::new(backend).wrap_err("Failed to create terminal");
     something
 }
@@ -0,0 +1,65 @@
+use chippy::parser::{
+    assembler::{self, Syntax},
+    map::{MemoryMap, RegionKind},
+    report::PROGRAM_SPACE,
+};
+use eyre::{Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// First address of the chart
+const CHART_START: usize = 0x200;
+
+#[derive(Debug, StructOpt)]
+pub struct MapOpt {
+    /// Enable structured control flow (loop/again, if/then, while)
+    #[structopt(long)]
+    structured: bool,
+
+    /// Bytes of memory per row of the chart
+    #[structopt(long, default_value = "256")]
+    row: usize,
+
+    /// Characters per row of the chart
+    #[structopt(long, default_value = "64")]
+    width: usize,
+
+    /// List every region below the chart
+    #[structopt(long)]
+    regions: bool,
+
+    #[structopt(name = "SOURCE", parse(from_os_str))]
+    source: PathBuf,
+}
+
+/// Print where the code, data and padding of an assembled program are in memory
+pub fn run(opts: &MapOpt) -> Result<()> {
+    let source = std::fs::read_to_string(&opts.source).wrap_err("Failed to open source file")?;
+    let syntax = match opts.structured {
+        true => Syntax::Structured,
+        false => Syntax::Raw,
+    };
+    let map = MemoryMap::new(&assembler::assemble_with(&source, syntax)?);
+
+    let (row, width) = (opts.row.max(1), opts.width.max(1));
+    for start in (CHART_START..CHART_START + PROGRAM_SPACE).step_by(row) {
+        println!("{:03X} |{}|", start, map.bar(start as u16, row, width));
+    }
+
+    let kinds = [
+        RegionKind::Code,
+        RegionKind::Data,
+        RegionKind::Padding,
+        RegionKind::Free,
+    ];
+    let legend: Vec<String> = kinds
+        .iter()
+        .map(|kind| format!("'{}' {}: {} bytes", kind.symbol(), kind, map.total(*kind)))
+        .collect();
+    println!("\n{}", legend.join(", "));
+
+    if opts.regions {
+        print!("\n{}", map);
+    }
+    Ok(())
+}
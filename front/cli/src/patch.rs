@@ -0,0 +1,31 @@
+use chippy::rom::patch::Patch;
+use eyre::{Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct PatchOpt {
+    /// Output rom file, defaults to overwriting the rom
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(name = "ROM", parse(from_os_str))]
+    rom: PathBuf,
+
+    /// Patch in the format printed by `chippy diff --patch`
+    #[structopt(name = "PATCH", parse(from_os_str))]
+    patch: PathBuf,
+}
+
+/// Apply a patch to a rom file
+pub fn run(opts: &PatchOpt) -> Result<()> {
+    let mut rom = std::fs::read(&opts.rom).wrap_err("Failed to open rom")?;
+    let patch: Patch = std::fs::read_to_string(&opts.patch)
+        .wrap_err("Failed to open patch")?
+        .parse()?;
+    patch.apply(&mut rom)?;
+
+    let output = opts.output.as_ref().unwrap_or(&opts.rom);
+    std::fs::write(output, rom).wrap_err("Failed to write rom")?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use super::Renderer;
+use std::env;
+
+/// Terminal multiplexers that need graphics escape sequences wrapped to pass them through to the
+/// outer terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Multiplexer {
+    /// Requires `set -g allow-passthrough on` on tmux 3.3 and later
+    Tmux,
+    Screen,
+}
+
+// GNU screen truncates DCS strings longer than this
+const SCREEN_CHUNK_SIZE: usize = 768;
+
+impl Multiplexer {
+    /// Wrap a single escape sequence so the multiplexer passes it through untouched
+    pub fn wrap(&self, sequence: &str) -> String {
+        match self {
+            Multiplexer::Tmux => {
+                format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+            }
+            Multiplexer::Screen => {
+                let mut out = String::with_capacity(sequence.len() + 32);
+                let mut rest = sequence;
+                while !rest.is_empty() {
+                    let mut end = rest.len().min(SCREEN_CHUNK_SIZE);
+                    while !rest.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    let (chunk, tail) = rest.split_at(end);
+                    out.push_str(&format!("\x1bP{}\x1b\\", chunk));
+                    rest = tail;
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Wrap `sequence` for the multiplexer if there is one
+pub fn passthrough(sequence: &str, multiplexer: Option<Multiplexer>) -> String {
+    match multiplexer {
+        Some(m) => m.wrap(sequence),
+        None => sequence.to_string(),
+    }
+}
+
+/// Display features of the terminal, detected from the environment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    pub truecolor: bool,
+    pub sixel: bool,
+    pub kitty: bool,
+    /// Block characters are single width and can be displayed
+    pub unicode: bool,
+    pub multiplexer: Option<Multiplexer>,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+
+        let multiplexer = if env::var("TMUX").is_ok() || program == "tmux" {
+            Some(Multiplexer::Tmux)
+        } else if env::var("STY").is_ok() || term.starts_with("screen") {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        };
+
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .map(|name| var(name))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        Self {
+            truecolor: matches!(var("COLORTERM").as_str(), "truecolor" | "24bit"),
+            sixel: matches!(
+                term.as_str(),
+                "foot" | "foot-extra" | "mlterm" | "yaft-256color" | "contour"
+            ) || matches!(
+                program.as_str(),
+                "WezTerm" | "mintty" | "iTerm.app" | "contour"
+            ),
+            kitty: term == "xterm-kitty"
+                || program == "WezTerm"
+                || env::var("KITTY_WINDOW_ID").is_ok(),
+            unicode: locale.contains("utf-8") || locale.contains("utf8"),
+            multiplexer,
+        }
+    }
+
+    pub fn supports(&self, renderer: Renderer) -> bool {
+        match renderer {
+            Renderer::Blocks => true,
+            Renderer::Sixel => self.sixel,
+            Renderer::Kitty => self.kitty,
+        }
+    }
+
+    /// The best renderer the terminal supports
+    pub fn best_renderer(&self) -> Renderer {
+        [Renderer::Kitty, Renderer::Sixel]
+            .iter()
+            .copied()
+            .find(|r| self.supports(*r))
+            .unwrap_or(Renderer::Blocks)
+    }
+
+    /// Use `renderer` if supported, otherwise the best supported one
+    pub fn fallback(&self, renderer: Renderer) -> Renderer {
+        match self.supports(renderer) {
+            true => renderer,
+            false => self.best_renderer(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use super::detect::{passthrough, Multiplexer};
+use chippy::{emu::gpu::Gpu, render};
+use crossterm::{cursor::MoveTo, queue};
+use eyre::Result;
+use std::io::Write;
+
+// Image id used for the display so each frame replaces the previous one
+const IMAGE_ID: u32 = 1;
+// Maximum payload size of a single graphics command
+const CHUNK_SIZE: usize = 4096;
+
+const OFF_COLOR: [u8; 3] = [0x00, 0x00, 0x00];
+const ON_COLOR: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+/// Draw the display as a kitty image at the top left corner of the terminal
+pub fn draw<W: Write>(
+    out: &mut W,
+    gpu: &Gpu,
+    scale: usize,
+    multiplexer: Option<Multiplexer>,
+) -> Result<()> {
+    queue!(out, MoveTo(0, 0))?;
+    for command in encode(gpu, scale) {
+        out.write_all(passthrough(&command, multiplexer).as_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Encode the display as kitty graphics commands where each chip8 pixel is `scale` x `scale`
+/// pixels, high resolution pixels are half as large. The previous image is deleted and the new one is transmitted and displayed. Each
+/// command is a separate escape sequence.
+pub fn encode(gpu: &Gpu, scale: usize) -> Vec<String> {
+    let scale = render::fit_scale(gpu.display().len(), scale.max(1));
+    let width = gpu.width() * scale;
+    let height = gpu.height() * scale;
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            match gpu.get(x / scale, y / scale) {
+                true => rgb.extend_from_slice(&ON_COLOR),
+                false => rgb.extend_from_slice(&OFF_COLOR),
+            }
+        }
+    }
+
+    let payload = base64(&rgb);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(CHUNK_SIZE).collect();
+
+    // q=2 suppresses the terminal responses that would otherwise show up as input
+    let mut out = vec![format!("\x1b_Ga=d,d=i,i={},q=2\x1b\\", IMAGE_ID)];
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = (index + 1 < chunks.len()) as u8;
+        let control = match index {
+            0 => format!(
+                "a=T,f=24,s={},v={},i={},C=1,q=2,m={}",
+                width, height, IMAGE_ID, more
+            ),
+            _ => format!("m={}", more),
+        };
+        out.push(format!(
+            "\x1b_G{};{}\x1b\\",
+            control,
+            std::str::from_utf8(chunk).expect("base64 is ascii")
+        ));
+    }
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(TABLE[((n >> (18 - i * 6)) & 0x3F) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
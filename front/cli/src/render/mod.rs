@@ -0,0 +1,33 @@
+use std::str::FromStr;
+
+pub mod detect;
+pub mod kitty;
+pub mod sixel;
+
+/// How the display is drawn to the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    /// Unicode block characters drawn through tui
+    Blocks,
+    /// Sixel images for terminals that support them (xterm, mlterm, foot)
+    Sixel,
+    /// Kitty graphics protocol images (kitty, wezterm)
+    Kitty,
+}
+
+impl Renderer {
+    pub const VARIANTS: &'static [&'static str] = &["blocks", "sixel", "kitty"];
+}
+
+impl FromStr for Renderer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blocks" => Ok(Renderer::Blocks),
+            "sixel" => Ok(Renderer::Sixel),
+            "kitty" => Ok(Renderer::Kitty),
+            _ => Err(format!("Unknown renderer: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use super::detect::{passthrough, Multiplexer};
+use chippy::{emu::gpu::Gpu, render};
+use crossterm::{cursor::MoveTo, queue};
+use eyre::Result;
+use std::io::Write;
+
+// Colors are defined as rgb percentages
+const OFF_COLOR: (u8, u8, u8) = (0, 0, 0);
+const ON_COLOR: (u8, u8, u8) = (100, 100, 100);
+
+/// Draw the display as a sixel image at the top left corner of the terminal
+pub fn draw<W: Write>(
+    out: &mut W,
+    gpu: &Gpu,
+    scale: usize,
+    multiplexer: Option<Multiplexer>,
+) -> Result<()> {
+    queue!(out, MoveTo(0, 0))?;
+    out.write_all(passthrough(&encode(gpu, scale), multiplexer).as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Encode the display as a sixel image where each chip8 pixel is `scale` x `scale` pixels, high
+/// resolution pixels are half as large
+pub fn encode(gpu: &Gpu, scale: usize) -> String {
+    let scale = render::fit_scale(gpu.display().len(), scale.max(1));
+    let width = gpu.width() * scale;
+    let height = gpu.height() * scale;
+
+    let mut out = String::new();
+    // DCS with a 1:1 aspect ratio and raster attributes for the image size
+    out.push_str(&format!("\x1bP0;1;0q\"1;1;{};{}", width, height));
+    for (index, (r, g, b)) in [OFF_COLOR, ON_COLOR].iter().enumerate() {
+        out.push_str(&format!("#{};2;{};{};{}", index, r, g, b));
+    }
+
+    for band in (0..height).step_by(6) {
+        for (color, value) in [false, true].iter().enumerate() {
+            out.push_str(&format!("#{}", color));
+
+            let sixels = (0..width).map(|x| {
+                let bits = (0..6)
+                    .filter(|bit| band + bit < height)
+                    .filter(|bit| gpu.get(x / scale, (band + bit) / scale) == *value)
+                    .fold(0u8, |bits, bit| bits | (1 << bit));
+                (63 + bits) as char
+            });
+            push_run_length(&mut out, sixels);
+
+            // Return to the start of the band for the next color
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_run_length<I: Iterator<Item = char>>(out: &mut String, chars: I) {
+    let mut chars = chars.peekable();
+    while let Some(c) = chars.next() {
+        let mut count = 1;
+        while chars.peek() == Some(&c) {
+            chars.next();
+            count += 1;
+        }
+
+        match count {
+            1..=3 => (0..count).for_each(|_| out.push(c)),
+            _ => out.push_str(&format!("!{}{}", count, c)),
+        }
+    }
+}
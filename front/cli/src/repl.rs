@@ -0,0 +1,120 @@
+use chippy::{
+    emu::{state::VmState, vm::Vm},
+    parser,
+};
+use eyre::{eyre, Result, WrapErr};
+use std::io::{BufRead, Write};
+
+const HELP: &str = "Type an instruction to assemble and execute it, for example `ld v0, 0x05`.
+    .regs       Print every register
+    .display    Print the display
+    .reset      Reset the machine
+    .help       Print this help
+    .quit       Exit the repl";
+
+/// Interactive playground that assembles and executes one line at a time
+pub fn run() -> Result<()> {
+    let mut vm = Vm::new();
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+
+    println!("{}", HELP);
+    loop {
+        print!("{:03X}> ", vm.snapshot().program_counter);
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+
+        match line.trim() {
+            "" => {}
+            ".q" | ".quit" => break,
+            ".h" | ".help" => println!("{}", HELP),
+            ".r" | ".regs" => print_registers(&vm.snapshot()),
+            ".d" | ".display" => println!("{}", vm.gpu),
+            ".reset" => vm.reset(),
+            line => {
+                if let Err(err) = execute(&mut vm, line) {
+                    println!("{:?}", err);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Assemble `line`, write it at the program counter and execute it, printing what changed
+fn execute(vm: &mut Vm, line: &str) -> Result<()> {
+    let instructions = parser::from_asm(line).wrap_err("Failed to assemble")?;
+    for instruction in instructions {
+        let mut before = vm.snapshot();
+        let pc = before.program_counter as usize;
+        before
+            .memory
+            .get_mut(pc..pc + 2)
+            .ok_or_else(|| eyre!("Program counter out of memory: {:03X}", pc))?
+            .copy_from_slice(&instruction.to_u16().to_be_bytes());
+        vm.restore(&before);
+
+        vm.cycle().wrap_err("Failed to execute")?;
+        let after = vm.snapshot();
+        print_changes(&before, &after);
+        if after.display != before.display {
+            println!("{}", vm.gpu);
+        }
+    }
+    Ok(())
+}
+
+fn print_changes(before: &VmState, after: &VmState) {
+    let diff = before.diff(after);
+    let mut changes: Vec<String> = diff
+        .registers
+        .iter()
+        .map(|r| format!("v{:x} = {:02X}", r, after.registers[*r as usize]))
+        .collect();
+    if diff.index {
+        changes.push(format!("i = {:03X}", after.index));
+    }
+    if diff.stack {
+        changes.push(format!("sp = {:X}", after.stack_pointer));
+    }
+    if after.delay_timer != before.delay_timer {
+        changes.push(format!("dt = {:02X}", after.delay_timer));
+    }
+    if after.sound_timer != before.sound_timer {
+        changes.push(format!("st = {:02X}", after.sound_timer));
+    }
+    changes.extend(
+        diff.memory
+            .iter()
+            .map(|a| format!("[{:03X}] = {:02X}", a, after.memory[*a as usize])),
+    );
+
+    if !changes.is_empty() {
+        println!("{}", changes.join("  "));
+    }
+}
+
+fn print_registers(state: &VmState) {
+    println!(
+        "pc {:03X}  i {:03X}  sp {:X}  dt {:02X}  st {:02X}",
+        state.program_counter,
+        state.index,
+        state.stack_pointer,
+        state.delay_timer,
+        state.sound_timer
+    );
+    for (row, values) in state.registers.chunks(8).enumerate() {
+        let line = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("v{:x} {:02X}", row * 8 + i, v))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,80 @@
+use chippy::{
+    emu::{state::VmState, vm::Vm},
+    storage::{FileStorage, Storage},
+};
+use eyre::{Result, WrapErr};
+use std::path::{Path, PathBuf};
+
+pub const SLOT_COUNT: usize = 10;
+
+/// Numbered save states of a rom, kept in a `FileStorage` under the checksum of the rom
+pub struct SaveSlots {
+    storage: FileStorage,
+    checksum: u32,
+    /// Name of the rom, the save states used to be stored as `<rom name>.state<slot>`
+    name: String,
+    current: usize,
+}
+
+impl SaveSlots {
+    /// Slots for `rom`, stored in `dir` or next to the rom if no directory is given
+    pub fn new(rom: &Path, dir: Option<PathBuf>, checksum: u32) -> Self {
+        let dir = dir
+            .or_else(|| rom.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let name = rom
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "rom".to_string());
+
+        Self {
+            storage: FileStorage::new(dir),
+            checksum,
+            name,
+            current: 0,
+        }
+    }
+
+    /// Storage of the save states, which also keeps the other data of the rom
+    pub fn storage(&mut self) -> &mut FileStorage {
+        &mut self.storage
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % SLOT_COUNT;
+    }
+
+    pub fn previous(&mut self) {
+        self.current = (self.current + SLOT_COUNT - 1) % SLOT_COUNT;
+    }
+
+    pub fn save(&mut self, vm: &Vm) -> Result<()> {
+        self.storage
+            .save_state(self.checksum, self.current as u8, &vm.snapshot())
+            .wrap_err("Failed to write save state")
+    }
+
+    pub fn load(&self, vm: &mut Vm) -> Result<()> {
+        let state = match self
+            .storage
+            .load_state(self.checksum, self.current as u8)
+            .wrap_err("Failed to read save state")?
+        {
+            Some(state) => state,
+            None => {
+                let legacy = self
+                    .storage
+                    .dir()
+                    .join(format!("{}.state{}", self.name, self.current));
+                let bytes = std::fs::read(legacy).wrap_err("Failed to read save state")?;
+                VmState::decode(&bytes).wrap_err("Invalid save state")?
+            }
+        };
+        vm.restore(&state);
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use chippy::soak::{self, Failure};
+use eyre::{eyre, Result, WrapErr};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+/// Time between two progress lines
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, StructOpt)]
+pub struct SoakOpt {
+    /// Number of vms run concurrently, defaults to the number of cpus
+    #[structopt(short, long)]
+    threads: Option<usize>,
+
+    /// Hours to run for
+    #[structopt(long, default_value = "1")]
+    hours: f64,
+
+    /// Instructions executed per rom
+    #[structopt(long, default_value = "100000")]
+    cycles: usize,
+
+    /// First seed, roms use consecutive seeds from it
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Only run the rom of this seed, to reproduce a failure
+    #[structopt(long, conflicts_with = "seed")]
+    replay: Option<u64>,
+
+    /// Directory the roms of the failed seeds are written to
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+/// Run random roms on many vms, reporting the seeds that break an invariant
+pub fn run(opts: &SoakOpt) -> Result<()> {
+    if let Some(seed) = opts.replay {
+        return match soak::run_seed(seed, opts.cycles) {
+            Ok(cycles) => {
+                println!("seed {} ran {} cycles without failure", seed, cycles);
+                Ok(())
+            }
+            Err(failure) => Err(eyre!("{}", failure)),
+        };
+    }
+
+    let threads = opts.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1)
+    });
+    let deadline = Instant::now() + Duration::from_secs_f64(opts.hours.max(0.0) * 3600.0);
+    let next_seed = Arc::new(AtomicU64::new(opts.seed));
+    let roms = Arc::new(AtomicUsize::new(0));
+    let failures: Arc<Mutex<Vec<Failure>>> = Arc::default();
+
+    // Panics are reported as failures, the default hook would print every one of them
+    std::panic::set_hook(Box::new(|_| {}));
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let (next_seed, roms, failures) = (next_seed.clone(), roms.clone(), failures.clone());
+            let cycles = opts.cycles;
+            std::thread::spawn(move || {
+                while Instant::now() < deadline {
+                    let seed = next_seed.fetch_add(1, Ordering::Relaxed);
+                    if let Err(failure) = soak::run_seed(seed, cycles) {
+                        failures.lock().unwrap().push(failure);
+                    }
+                    roms.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    while Instant::now() < deadline {
+        std::thread::sleep(PROGRESS_INTERVAL.min(deadline - Instant::now()));
+        println!(
+            "{:>8.0}s  {} roms  {} failures",
+            start.elapsed().as_secs_f64(),
+            roms.load(Ordering::Relaxed),
+            failures.lock().unwrap().len()
+        );
+    }
+    for worker in workers {
+        worker.join().map_err(|_| eyre!("Soak worker stopped"))?;
+    }
+    let _ = std::panic::take_hook();
+
+    let mut failures = failures.lock().unwrap();
+    failures.sort_by_key(|failure| failure.seed);
+    for failure in failures.iter() {
+        println!("{}", failure);
+        if let Some(dir) = &opts.output {
+            let path = dir.join(format!("soak-{}.ch8", failure.seed));
+            std::fs::write(path, soak::random_rom(failure.seed))
+                .wrap_err("Failed to write failed rom")?;
+        }
+    }
+    match failures.len() {
+        0 => Ok(()),
+        count => Err(eyre!(
+            "{} of {} roms failed, rerun one with --replay SEED",
+            count,
+            roms.load(Ordering::Relaxed)
+        )),
+    }
+}
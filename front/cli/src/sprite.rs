@@ -0,0 +1,96 @@
+use chippy::sprite;
+use eyre::{eyre, Result, WrapErr};
+use std::{fs::File, io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct SpriteOpt {
+    /// Write raw sprite bytes instead of assembler `db` blocks
+    #[structopt(long)]
+    raw: bool,
+
+    /// Set pixels that are darker than the threshold instead of lighter
+    #[structopt(long)]
+    invert: bool,
+
+    /// Brightness from 0 to 255 above which a pixel is set
+    #[structopt(long, default_value = "128")]
+    threshold: u8,
+
+    /// Label of the sprite data, defaults to the image file name
+    #[structopt(long)]
+    name: Option<String>,
+
+    /// Output file, defaults to stdout
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(name = "IMAGE", parse(from_os_str))]
+    image: PathBuf,
+}
+
+/// Convert a png image into sprite data
+pub fn run(opts: &SpriteOpt) -> Result<()> {
+    let (width, height, pixels) = read_png(opts)?;
+    let sprites = sprite::from_bitmap(width, height, &pixels);
+
+    let data = match opts.raw {
+        true => sprite::to_bytes(&sprites),
+        false => {
+            let name = match &opts.name {
+                Some(name) => name.clone(),
+                None => opts
+                    .image
+                    .file_stem()
+                    .map(|stem| {
+                        stem.to_string_lossy()
+                            .replace(|c: char| !c.is_alphanumeric(), "_")
+                    })
+                    .unwrap_or_else(|| "sprite".to_string()),
+            };
+            sprite::to_asm(&sprites, &name).into_bytes()
+        }
+    };
+
+    match &opts.output {
+        Some(path) => std::fs::write(path, data).wrap_err("Failed to write sprite file")?,
+        None => std::io::stdout().write_all(&data)?,
+    }
+    Ok(())
+}
+
+/// Decode the image into a monochrome bitmap, transparent pixels are never set
+fn read_png(opts: &SpriteOpt) -> Result<(usize, usize, Vec<bool>)> {
+    let file = File::open(&opts.image).wrap_err("Failed to open image")?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().wrap_err("Failed to read png")?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .wrap_err("Failed to decode png")?;
+
+    let samples = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+    if width == 0 || height == 0 {
+        return Err(eyre!("Image is empty"));
+    }
+
+    let pixels = buffer[..info.buffer_size()]
+        .chunks(info.line_size)
+        .flat_map(|line| line[..width * samples].chunks(samples))
+        .map(|pixel| {
+            let (brightness, alpha) = match pixel {
+                [gray] => (*gray as u32, 255),
+                [gray, alpha] => (*gray as u32, *alpha),
+                [r, g, b] => ((*r as u32 * 3 + *g as u32 * 6 + *b as u32) / 10, 255),
+                [r, g, b, alpha] => ((*r as u32 * 3 + *g as u32 * 6 + *b as u32) / 10, *alpha),
+                _ => (0, 0),
+            };
+            let light = brightness >= opts.threshold as u32;
+            alpha >= 128 && light != opts.invert
+        })
+        .collect();
+
+    Ok((width, height, pixels))
+}
@@ -0,0 +1,51 @@
+use chippy::rom::suite::Suite;
+use eyre::{eyre, Result, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct TestOpt {
+    /// Only run the tests whose name contains this text
+    #[structopt(long)]
+    filter: Option<String>,
+
+    /// Test suite files
+    #[structopt(name = "SUITE", parse(from_os_str), required = true)]
+    suites: Vec<PathBuf>,
+}
+
+/// Run the test suites of roms, failing if any test fails
+pub fn run(opts: &TestOpt) -> Result<()> {
+    let (mut passed, mut failed) = (0, 0);
+    for path in opts.suites.iter() {
+        let suite = Suite::load(path)
+            .wrap_err_with(|| format!("Failed to read test suite {}", path.display()))?;
+        let program = suite
+            .program_bytes()
+            .wrap_err_with(|| format!("Failed to load the program of {}", path.display()))?;
+
+        let filter = opts.filter.as_deref().unwrap_or_default();
+        for test in suite.tests.iter().filter(|test| test.name.contains(filter)) {
+            let failures = test.run(&program);
+            match failures.is_empty() {
+                true => {
+                    passed += 1;
+                    println!("test {}::{} ... ok", path.display(), test.name);
+                }
+                false => {
+                    failed += 1;
+                    println!("test {}::{} ... FAILED", path.display(), test.name);
+                    for failure in failures {
+                        println!("    {}", failure);
+                    }
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    match failed {
+        0 => Ok(()),
+        _ => Err(eyre!("{} tests failed", failed)),
+    }
+}
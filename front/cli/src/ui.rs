@@ -1,4 +1,9 @@
-use chippy::emu::gpu::{self, Gpu};
+use crate::render::detect::Capabilities;
+use chippy::{
+    emu::gpu::{self, Gpu},
+    locale::Message,
+    render::Palette,
+};
 use eyre::Result;
 use tui::{
     backend::Backend,
@@ -13,14 +18,102 @@ const PIXEL_HIGHT: u16 = 1;
 const GRID_WIDTH: u16 = gpu::SCREEN_WIDTH as u16 * PIXEL_WIDTH;
 const GRID_HEIGHT: u16 = gpu::SCREEN_HEIGHT as u16 * PIXEL_HIGHT;
 
+/// Frontend state shown in the status bar
+pub struct Status<'a> {
+    /// What the keys do in the rom, empty when unknown
+    pub keys: &'a str,
+    pub slot: usize,
+    /// Number of rewind snapshots available
+    pub rewind: usize,
+    pub message: Option<&'a str>,
+    /// Current and best score when a score location is configured
+    pub score: Option<(u32, u32)>,
+}
+
+impl<'a> Status<'a> {
+    /// The parts of the status worth announcing to a screen reader: the score and the message,
+    /// without the counters that change on their own
+    pub fn announcement(&self) -> String {
+        let score = self
+            .score
+            .map(|(score, best)| Message::StatusScore.format(&[&score, &best]));
+        let parts: Vec<&str> = score.as_deref().into_iter().chain(self.message).collect();
+        parts.join(" | ")
+    }
+}
+
+/// How the block renderer draws the display
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Look {
+    /// Colors of the pixels on truecolor terminals, the terminal colors otherwise
+    pub palette: Option<Palette>,
+    /// Terminal cells per chip8 pixel in each direction, at least 1
+    pub scale: u16,
+}
+
+impl<'a> std::fmt::Display for Status<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.keys.is_empty() {
+            write!(f, "{} | ", self.keys)?;
+        }
+        write!(
+            f,
+            "{} | {}",
+            Message::StatusSlot.format(&[&self.slot]),
+            Message::StatusRewind.format(&[&self.rewind])
+        )?;
+        if let Some((score, best)) = self.score {
+            write!(f, " | {}", Message::StatusScore.format(&[&score, &best]))?;
+        }
+        if let Some(message) = self.message {
+            write!(f, " | {}", message)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Ui<'a> {
     gpu: &'a Gpu,
     block: Option<Block<'a>>,
+    pixel: &'a str,
+    color: Color,
+    background: Color,
+    scale: u16,
 }
 
 impl<'a> Ui<'a> {
     pub fn new(gpu: &'a Gpu) -> Self {
-        Self { gpu, block: None }
+        Self {
+            gpu,
+            block: None,
+            pixel: "█",
+            color: Color::White,
+            background: Color::Reset,
+            scale: 1,
+        }
+    }
+
+    /// Text drawn for a set pixel
+    pub fn pixel(mut self, pixel: &'a str) -> Ui<'a> {
+        self.pixel = pixel;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Ui<'a> {
+        self.color = color;
+        self
+    }
+
+    /// Color behind the pixels
+    pub fn background(mut self, background: Color) -> Ui<'a> {
+        self.background = background;
+        self
+    }
+
+    /// Draw every pixel as `scale` by `scale` cells
+    pub fn scale(mut self, scale: u16) -> Ui<'a> {
+        self.scale = scale.max(1);
+        self
     }
 
     pub fn block(mut self, block: Block<'a>) -> Ui<'a> {
@@ -40,40 +133,61 @@ impl<'a> Widget for Ui<'a> {
             None => area,
         };
 
-        for y in 0..gpu::SCREEN_HEIGHT {
-            for x in 0..gpu::SCREEN_WIDTH {
-                let pixel = self.gpu.get(x, y);
-                let text = match pixel {
-                    true => "█",
+        let style = Style::default().fg(self.color).bg(self.background);
+        for yy in final_area.top()..final_area.bottom() {
+            for xx in final_area.left()..final_area.right() {
+                let x = ((xx - final_area.x) / self.scale) as usize;
+                let y = ((yy - final_area.y) / self.scale) as usize;
+                if x >= gpu::SCREEN_WIDTH || y >= gpu::SCREEN_HEIGHT {
+                    continue;
+                }
+                // The grid has a cell per pixel of the 64x32 view of high resolution displays
+                let text = match self.gpu.memory[y * gpu::SCREEN_WIDTH + x] {
+                    true => self.pixel,
                     false => " ",
                     // false => "·",
                 };
-                let xx = final_area.x + x as u16;
-                let yy = final_area.y + y as u16;
-                buf.set_string(xx, yy, text, Style::default().fg(Color::White));
+                buf.set_string(xx, yy, text, style);
             }
         }
     }
 }
 
-pub fn draw<B: Backend>(f: &mut Frame<B>, gpu: &Gpu) {
+pub fn draw<B: Backend>(
+    f: &mut Frame<B>,
+    gpu: &Gpu,
+    caps: &Capabilities,
+    status: &Status,
+    look: Look,
+) {
+    let scale = look.scale.max(1);
+    let (grid_width, grid_height) = (GRID_WIDTH * scale, GRID_HEIGHT * scale);
     let main_block = Block::default()
         .borders(Borders::ALL)
         .style(Style::default().fg(Color::LightYellow))
         .title("Chippy");
+    let inner = main_block.inner(f.size());
     f.render_widget(main_block, f.size());
 
+    if inner.height > 0 {
+        let status_area = Rect::new(inner.x, inner.y + inner.height - 1, inner.width, 1);
+        let status_line = Paragraph::new(status.to_string())
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Right);
+        f.render_widget(status_line, status_area);
+    }
+
     let vertical_padding_block_height =
-        f.size().height.checked_sub(GRID_HEIGHT).unwrap_or_default() / 2;
+        f.size().height.checked_sub(grid_height).unwrap_or_default() / 2;
 
     let horizontal_padding_block_width =
-        f.size().width.checked_sub(GRID_WIDTH).unwrap_or_default() / 2;
+        f.size().width.checked_sub(grid_width).unwrap_or_default() / 2;
 
     let v_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(vec![
             Constraint::Min(vertical_padding_block_height),
-            Constraint::Length(GRID_HEIGHT + 2),
+            Constraint::Length(grid_height + 2),
             Constraint::Min(vertical_padding_block_height),
         ])
         .split(f.size());
@@ -82,15 +196,31 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, gpu: &Gpu) {
         .direction(Direction::Horizontal)
         .constraints(vec![
             Constraint::Min(horizontal_padding_block_width),
-            Constraint::Length(GRID_WIDTH + 2),
+            Constraint::Length(grid_width + 2),
             Constraint::Min(horizontal_padding_block_width),
         ])
         .split(v_layout[1]);
 
-    let ui = Ui::new(gpu).block(
+    let mut ui = display(gpu, caps).scale(scale);
+    if let (Some(palette), true) = (look.palette, caps.truecolor) {
+        let rgb = |[r, g, b, _]: [u8; 4]| Color::Rgb(r, g, b);
+        ui = ui.color(rgb(palette.on)).background(rgb(palette.off));
+    }
+    f.render_widget(ui, h_layout[1]);
+}
+
+/// Display widget styled for the capabilities of the terminal
+pub fn display<'a>(gpu: &'a Gpu, caps: &Capabilities) -> Ui<'a> {
+    let mut ui = Ui::new(gpu).block(
         Block::default()
             .borders(Borders::ALL)
             .style(Style::default().fg(Color::White)),
     );
-    f.render_widget(ui, h_layout[1]);
+    if !caps.unicode {
+        ui = ui.pixel("#");
+    }
+    if caps.truecolor {
+        ui = ui.color(Color::Rgb(0xCD, 0xCE, 0xCF));
+    }
+    ui
 }
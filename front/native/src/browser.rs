@@ -0,0 +1,128 @@
+use chippy::{
+    emu::gpu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    render::Palette,
+    rom::catalog::{Catalog, CatalogEntry},
+};
+use winit::event::VirtualKeyCode;
+
+/// Size of a thumbnail pixel in window pixels
+const THUMBNAIL_SCALE: usize = 2;
+/// Space around every thumbnail, the selection border is drawn in it
+const MARGIN: usize = 8;
+const CELL_WIDTH: usize = SCREEN_WIDTH * THUMBNAIL_SCALE + MARGIN * 2;
+const CELL_HEIGHT: usize = SCREEN_HEIGHT * THUMBNAIL_SCALE + MARGIN * 2;
+
+const BACKGROUND: [u8; 4] = [0x10, 0x17, 0x20, 0xFF];
+const SELECTED: [u8; 4] = [0x81, 0xB2, 0x9A, 0xFF];
+
+/// Grid of rom thumbnails, moved around with the arrow keys.
+pub struct Browser {
+    catalog: Catalog,
+    selected: usize,
+    /// First visible row
+    scroll: usize,
+}
+
+impl Browser {
+    pub fn new(catalog: Catalog) -> Self {
+        Self {
+            catalog,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&CatalogEntry> {
+        self.catalog.entries.get(self.selected)
+    }
+
+    /// Title of the window while browsing
+    pub fn title(&self) -> String {
+        match self.selected() {
+            Some(entry) => format!(
+                "Chippy - {} ({} bytes, crc32 {:08x})",
+                entry.name, entry.size, entry.crc32
+            ),
+            None => "Chippy - no roms found".to_string(),
+        }
+    }
+
+    /// Move the selection, returns true if the selected rom changed
+    pub fn key(&mut self, keycode: VirtualKeyCode, width: usize) -> bool {
+        let columns = columns(width);
+        let last = self.catalog.entries.len().saturating_sub(1);
+        let selected = match keycode {
+            VirtualKeyCode::Left => self.selected.saturating_sub(1),
+            VirtualKeyCode::Right => (self.selected + 1).min(last),
+            VirtualKeyCode::Up => self.selected.saturating_sub(columns),
+            VirtualKeyCode::Down => (self.selected + columns).min(last),
+            VirtualKeyCode::Home => 0,
+            VirtualKeyCode::End => last,
+            _ => self.selected,
+        };
+        let changed = selected != self.selected;
+        self.selected = selected;
+        changed
+    }
+
+    pub fn draw(&mut self, frame: &mut [u8], width: usize, height: usize) {
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&BACKGROUND);
+        }
+
+        let palette = Palette::default();
+        let columns = columns(width);
+        let rows = (height / CELL_HEIGHT).max(1);
+        let row = self.selected / columns;
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + rows {
+            self.scroll = row + 1 - rows;
+        }
+
+        let visible = self
+            .catalog
+            .entries
+            .iter()
+            .enumerate()
+            .skip(self.scroll * columns)
+            .take(rows * columns);
+        for (index, entry) in visible {
+            let cell = index - self.scroll * columns;
+            let left = (cell % columns) * CELL_WIDTH;
+            let top = (cell / columns) * CELL_HEIGHT;
+            if index == self.selected {
+                fill(
+                    frame,
+                    width,
+                    left + 2,
+                    top + 2,
+                    CELL_WIDTH - 4,
+                    CELL_HEIGHT - 4,
+                    SELECTED,
+                );
+            }
+            for (i, on) in entry.thumbnail.iter().enumerate() {
+                let x = left + MARGIN + (i % SCREEN_WIDTH) * THUMBNAIL_SCALE;
+                let y = top + MARGIN + (i / SCREEN_WIDTH) * THUMBNAIL_SCALE;
+                let color = if *on { palette.on } else { palette.off };
+                fill(frame, width, x, y, THUMBNAIL_SCALE, THUMBNAIL_SCALE, color);
+            }
+        }
+    }
+}
+
+fn columns(width: usize) -> usize {
+    (width / CELL_WIDTH).max(1)
+}
+
+/// Fill a rectangle of the frame, clipped to the frame
+fn fill(frame: &mut [u8], width: usize, x: usize, y: usize, w: usize, h: usize, color: [u8; 4]) {
+    let height = frame.len() / 4 / width;
+    for row in y..(y + h).min(height) {
+        for column in x..(x + w).min(width) {
+            let index = (row * width + column) * 4;
+            frame[index..index + 4].copy_from_slice(&color);
+        }
+    }
+}
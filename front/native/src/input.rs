@@ -55,3 +55,34 @@ pub fn to_emu_key(keycode: &VirtualKeyCode, mapping: KeyMapping) -> Option<Key>
         },
     }
 }
+
+/// Keys bound by one of the mappings, with the name shown to the player
+const KEY_NAMES: [(VirtualKeyCode, &str); 18] = [
+    (VirtualKeyCode::Key1, "1"),
+    (VirtualKeyCode::Key2, "2"),
+    (VirtualKeyCode::Key3, "3"),
+    (VirtualKeyCode::Key4, "4"),
+    (VirtualKeyCode::Q, "Q"),
+    (VirtualKeyCode::W, "W"),
+    (VirtualKeyCode::E, "E"),
+    (VirtualKeyCode::R, "R"),
+    (VirtualKeyCode::A, "A"),
+    (VirtualKeyCode::S, "S"),
+    (VirtualKeyCode::D, "D"),
+    (VirtualKeyCode::F, "F"),
+    (VirtualKeyCode::P, "P"),
+    (VirtualKeyCode::T, "T"),
+    (VirtualKeyCode::Z, "Z"),
+    (VirtualKeyCode::X, "X"),
+    (VirtualKeyCode::C, "C"),
+    (VirtualKeyCode::V, "V"),
+];
+
+/// Name of the keyboard key mapped to the chip8 key `key`
+pub fn key_name(key: u8, mapping: KeyMapping) -> String {
+    KEY_NAMES
+        .iter()
+        .find(|(keycode, _)| to_emu_key(keycode, mapping).map(|key| key as u8) == Some(key))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("{:X}", key))
+}
@@ -1,67 +1,340 @@
 #![allow(unused_variables)]
 
-use chippy::emu::{self, input::Key, vm::Vm};
+use browser::Browser;
+use chippy::{
+    emu::{
+        self,
+        frame::DEFAULT_CYCLES_PER_FRAME,
+        input::Key,
+        quirks::Quirks,
+        speed::SpeedRamp,
+        vm::{ProgramState, StopReason, TimerClock, Vm},
+    },
+    exit::ExitCode,
+    locale::{self, Language, Message},
+    render::{self, Blend, FrameBlender, Palette},
+    rom::{
+        catalog::Catalog,
+        database::{self, RomDatabase},
+        header::RomHeader,
+        playlist::Playlist,
+    },
+    score::{HighScores, ScoreLocation},
+    video::VideoRecorder,
+    wav::WavRecorder,
+};
 use emu::gpu;
 use eyre::{eyre, Result, WrapErr};
-use log::error;
+use log::{error, warn};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
 use winit::{
-    dpi::{LogicalSize, PhysicalSize},
+    dpi::LogicalSize,
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Fullscreen, WindowBuilder},
 };
 
+mod browser;
 mod input;
 
+/// Size of a chip8 pixel in logical pixels, for the initial window size
 const PIXEL_SIZE: u32 = 16;
+/// Size of the rom browser buffer and initial logical size of the window
+const BROWSER_WIDTH: u32 = gpu::SCREEN_WIDTH as u32 * PIXEL_SIZE;
+const BROWSER_HEIGHT: u32 = gpu::SCREEN_HEIGHT as u32 * PIXEL_SIZE;
+/// Frames per second the instructions per frame of --ipf are counted in
+const FPS: usize = 60;
+/// Buffer pixels per side of a 64x32 pixel, so that high resolution pixels are a buffer pixel
+const DISPLAY_SCALE: usize = gpu::HIRES_WIDTH / gpu::SCREEN_WIDTH;
+/// Size of a chip8 pixel in the streamed frames, high resolution pixels are half as large
+#[cfg(feature = "stream")]
+const STREAM_SCALE: usize = 8;
+/// Size of a chip8 pixel in recorded videos, high resolution pixels are half as large
+const VIDEO_SCALE: usize = 8;
+/// Frame rate of recorded videos, one frame per update of the window
+const VIDEO_FPS: u32 = 60;
+/// Speed multiplier while Tab is held
+const FAST_FORWARD: f64 = 4.0;
+/// Time taken to reach the fast forward speed and to come back from it
+const SPEED_RAMP: Duration = Duration::from_millis(250);
+/// Buffer columns between the two displays of --compare
+const COMPARE_GAP: usize = 2 * DISPLAY_SCALE;
+const COMPARE_GAP_COLOR: [u8; 4] = [0x40, 0x40, 0x40, 0xFF];
+/// Instructions run at most per update, so a stalled window does not try to catch up for seconds
+const MAX_CYCLES_PER_UPDATE: usize = 1000;
+const SCORES_FILE: &str = "chippy-scores.txt";
+const ROM_DATABASE_FILE: &str = "chippy-roms.txt";
 
-fn update_buffer(gpu: &gpu::Gpu, frame: &mut [u8]) {
-    let mut index = 0;
-    let width = gpu::SCREEN_WIDTH * PIXEL_SIZE as usize;
-    for pixel in frame.chunks_exact_mut(4) {
-        let x = (index % width) / 16;
-        let y = (index / width) / 16;
-        let state = gpu.get(x, y);
-
-        let value = match state {
-            true => [0xCD, 0xCE, 0xCF, 0xFF],
-            false => [0x19, 0x23, 0x30, 0xFF],
-        };
-
-        pixel.copy_from_slice(&value);
-        index += 1;
-    }
+#[derive(Debug, StructOpt)]
+#[structopt(name = "chippy-native")]
+struct Opt {
+    /// Apply an IPS, BPS or chippy text patch to the rom before running it
+    #[structopt(long, parse(from_os_str))]
+    patch: Option<PathBuf>,
+
+    /// Memory holding the score of the game as ADDRESS:LEN[:bcd|binary], the best score is
+    /// kept for every rom and shown in the window title
+    #[structopt(long)]
+    score: Option<ScoreLocation>,
+
+    /// File storing the best scores, defaults to chippy-scores.txt next to the roms
+    #[structopt(long, parse(from_os_str))]
+    scores_file: Option<PathBuf>,
+
+    /// Serve the display as an MJPEG stream over HTTP on ADDR, for capture in streaming software
+    #[cfg(feature = "stream")]
+    #[structopt(long, value_name = "ADDR")]
+    stream: Option<String>,
+
+    /// Record the display and buzzer as a video with ffmpeg, the format is picked from the
+    /// extension (mp4, webm, ...)
+    #[structopt(long, parse(from_os_str))]
+    record_video: Option<PathBuf>,
+
+    /// Colors of set and unset pixels as ON:OFF hex colors or a preset name, used instead of the
+    /// palette of the rom database. The yellow, inverted, white and sky presets have a high
+    /// contrast. F9 cycles through the palettes.
+    #[structopt(long)]
+    palette: Option<Palette>,
+
+    /// Rom database giving the palettes and key labels of every rom, defaults to
+    /// chippy-roms.txt next to the roms. F1 shows the keys of the rom in the window title.
+    #[structopt(long, parse(from_os_str))]
+    rom_database: Option<PathBuf>,
+
+    /// Blend every frame with the previous one to hide flickering sprites
+    #[structopt(long, default_value = "none", possible_values = Blend::VARIANTS)]
+    blend: Blend,
+
+    /// Exit with this code when the rom executes exit (00FD), a number or a register vX to exit
+    /// with its value
+    #[structopt(long)]
+    exit_code: Option<ExitCode>,
+
+    /// Language of the window title, defaults to the language of the system
+    #[structopt(long, possible_values = Language::VARIANTS)]
+    lang: Option<Language>,
+
+    /// Keep running while the window is in the background instead of pausing
+    #[structopt(long)]
+    run_unfocused: bool,
+
+    /// Instructions run per frame, 11 matches the COSMAC VIP and 30 suits SUPER-CHIP games.
+    /// Defaults to the speed given by the header file of the rom, or 11.
+    #[structopt(long)]
+    ipf: Option<usize>,
+
+    /// Instructions run per second, overrides --ipf
+    #[structopt(long, value_name = "IPS")]
+    speed: Option<f64>,
+
+    /// Run a second rom given as --compare=PATH, such as another version of the rom, next to the
+    /// first one with the same input. A bare --compare runs the rom itself again, to compare the
+    /// quirks of --compare-quirk.
+    #[structopt(
+        long,
+        require_equals = true,
+        value_name = "PATH",
+        conflicts_with = "kiosk"
+    )]
+    compare: Option<Option<PathBuf>>,
+
+    /// Quirk of the compared rom on top of the quirks of the header, can be repeated
+    #[structopt(
+        long,
+        possible_values = Quirks::NAMES,
+        number_of_values = 1,
+        requires = "compare"
+    )]
+    compare_quirk: Vec<String>,
+
+    /// Rom to load from a zip archive, by default the only rom in the archive
+    #[structopt(long)]
+    entry: Option<String>,
+
+    /// Borderless fullscreen without a cursor, playing the roms of FILE in turn. FILE is a
+    /// directory or a playlist file with one rom per line.
+    #[structopt(long)]
+    kiosk: bool,
+
+    /// Seconds without input before the kiosk moves to the next rom
+    #[structopt(long, default_value = "120", value_name = "SECS")]
+    kiosk_idle: u64,
+
+    /// Rom to run, or a directory of roms to pick from
+    #[structopt(name = "FILE", parse(from_os_str))]
+    filepath: PathBuf,
 }
 
 fn main() -> Result<()> {
     env_logger::init();
 
-    let size = PhysicalSize::new(
-        gpu::SCREEN_WIDTH as u32 * PIXEL_SIZE,
-        gpu::SCREEN_HEIGHT as u32 * PIXEL_SIZE,
-    );
-
-    let scale_factor = 1.0;
     let mapping = input::KeyMapping::default();
 
-    let romfile = std::env::args()
-        .nth(1)
-        .ok_or(eyre!("Missing rom file in arguments"))?;
-    let bytes = std::fs::read(romfile).wrap_err("Failed to open c8 file")?;
-    let mut vm = Vm::new();
-    vm.load(bytes);
+    let opts = Opt::from_args();
+    locale::set_language(opts.lang.unwrap_or_else(Language::from_env));
+    let mut vm = Vm::new().with_timer_clock(TimerClock::Realtime);
+    let mut browser = None;
+    let mut checksum = 0;
+    let mut playlist = None;
+    let mut header = RomHeader::default();
+    let mut rom = Vec::new();
+    if opts.kiosk {
+        let mut roms = Playlist::load(&opts.filepath).wrap_err("Failed to read the playlist")?;
+        let (loaded, bytes) =
+            load_playlist_rom(&mut roms).ok_or_else(|| eyre!("No rom to play"))?;
+        checksum = chippy::rom::checksum(&bytes);
+        header = roms.current().map(read_header).unwrap_or_default();
+        vm = loaded;
+        vm.set_quirks(quirks_of(&header));
+        playlist = Some(roms);
+    } else if opts.filepath.is_dir() {
+        let catalog = Catalog::scan(&opts.filepath).wrap_err("Failed to list rom directory")?;
+        browser = Some(Browser::new(catalog));
+    } else {
+        let mut bytes = chippy::rom::read(&opts.filepath, opts.entry.as_deref())
+            .wrap_err(Message::OpenRomFailed)?;
+        if let Some(patch) = &opts.patch {
+            let patch = std::fs::read(patch).wrap_err("Failed to open patch file")?;
+            bytes = chippy::rom::apply_patch(&bytes, &patch)?;
+        }
+        checksum = chippy::rom::checksum(&bytes);
+        header = read_header(&opts.filepath);
+        vm.try_load(&bytes)?;
+        vm.set_quirks(quirks_of(&header));
+        rom = bytes;
+    }
+    let mut playing = browser.is_none();
+    let mut compare = match &opts.compare {
+        Some(_) if browser.is_some() => {
+            return Err(eyre!("--compare needs a rom, not a directory"))
+        }
+        Some(path) => {
+            let bytes = match path {
+                Some(path) => {
+                    chippy::rom::read(path, None).wrap_err("Failed to open the rom to compare")?
+                }
+                None => rom,
+            };
+            let names = header.quirks.iter().chain(opts.compare_quirk.iter());
+            let mut other = Vm::new().with_timer_clock(TimerClock::Realtime);
+            other.try_load(&bytes)?;
+            other.set_quirks(Quirks::from_names(names.map(String::as_str)).0);
+            Some(other)
+        }
+        None => None,
+    };
+
+    let rom_dir = match opts.filepath.is_dir() {
+        true => Some(opts.filepath.as_path()),
+        false => opts.filepath.parent(),
+    }
+    .map(Path::to_path_buf)
+    .unwrap_or_default();
+    let scores_file = opts
+        .scores_file
+        .clone()
+        .unwrap_or_else(|| rom_dir.join(SCORES_FILE));
+    let mut high_scores = match opts.score {
+        Some(_) => HighScores::load(&scores_file).wrap_err("Failed to read high scores")?,
+        None => HighScores::new(),
+    };
+    let mut new_high_score = false;
+
+    let rom_database = RomDatabase::load(
+        opts.rom_database
+            .clone()
+            .unwrap_or_else(|| rom_dir.join(ROM_DATABASE_FILE)),
+    )
+    .wrap_err(Message::ReadDatabaseFailed)?;
+    let key_database = rom_database.clone();
+    // Palettes of the current rom, the override first and then the palette of the rom header
+    let palette_override = opts.palette;
+    let palettes_of = move |checksum: u32, header: &RomHeader| {
+        let mut palettes = rom_database.palettes(checksum);
+        for palette in header.palette.iter().chain(palette_override.iter()) {
+            palettes.retain(|other| other != palette);
+            palettes.insert(0, *palette);
+        }
+        palettes
+    };
+    // What the keys do in the current rom, shown in the title while F1 is held
+    let key_hints_of = move |checksum: u32, header: &RomHeader| {
+        let labels = key_database.key_labels(checksum, header);
+        database::key_hints(&labels, |key| input::key_name(key, mapping))
+    };
+    let mut key_hints = key_hints_of(checksum, &header);
+    let mut palettes = palettes_of(checksum, &header);
+    let mut palette = palettes[0];
+    // Instructions per second of the current rom, the command line overrides the rom header
+    let (speed_override, ipf_override) = (opts.speed, opts.ipf);
+    let speed_of = move |header: &RomHeader| {
+        let ipf = ipf_override
+            .or_else(|| header.cycles_per_frame())
+            .unwrap_or(DEFAULT_CYCLES_PER_FRAME);
+        speed_override.unwrap_or((ipf * FPS) as f64)
+    };
+
+    #[cfg(feature = "stream")]
+    let mut stream = match &opts.stream {
+        Some(addr) => Some(
+            chippy::stream::MjpegServer::bind(addr)
+                .wrap_err("Failed to start the stream server")?,
+        ),
+        None => None,
+    };
+
+    let mut video = match &opts.record_video {
+        Some(path) => {
+            let (width, height) = (
+                gpu::SCREEN_WIDTH * VIDEO_SCALE,
+                gpu::SCREEN_HEIGHT * VIDEO_SCALE,
+            );
+            let recorder = VideoRecorder::start(path, VIDEO_FPS, width, height)
+                .wrap_err("Failed to start ffmpeg")?;
+            Some((recorder, WavRecorder::new(VIDEO_FPS)))
+        }
+        None => None,
+    };
+
+    let mut blender = FrameBlender::new(opts.blend);
+    let mut intensity = [0; gpu::HIRES_WIDTH * gpu::HIRES_HEIGHT];
+    let mut compare_blender = FrameBlender::new(opts.blend);
+    let mut compare_intensity = [0; gpu::HIRES_WIDTH * gpu::HIRES_HEIGHT];
+    let mut base_speed = speed_of(&header);
+    let mut speed = SpeedRamp::new(base_speed);
+    let mut last_update = Instant::now();
+    let mut last_input = Instant::now();
+    let kiosk_idle = Duration::from_secs(opts.kiosk_idle);
 
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
-        .with_inner_size(size.to_logical::<f64>(1.0))
-        .with_title("Chippy")
-        .build(&event_loop)
-        .unwrap();
+    let mut builder = WindowBuilder::new()
+        .with_inner_size(LogicalSize::new(
+            BROWSER_WIDTH * (1 + compare.is_some() as u32),
+            BROWSER_HEIGHT,
+        ))
+        .with_title(title(&header));
+    if opts.kiosk {
+        builder = builder
+            .with_decorations(false)
+            .with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+    let window = builder.build(&event_loop).unwrap();
+    window.set_cursor_visible(!opts.kiosk);
+    if let Some(browser) = &browser {
+        window.set_title(&browser.title());
+    }
 
+    // The buffer is scaled to the surface by a whole factor, the surface follows the window size
+    let mut buffer = buffer_size(playing, compare.is_some());
     let mut pixels = {
         let size = window.inner_size();
         let surface_texture = pixels::SurfaceTexture::new(size.width, size.height, &window);
-        pixels::Pixels::new(size.width, size.height, surface_texture)?
+        pixels::Pixels::new(buffer.0, buffer.1, surface_texture)?
     };
 
     event_loop.run(move |event, _, control_flow| {
@@ -84,7 +357,14 @@ fn main() -> Result<()> {
                         ..
                     },
                 ..
-            } => *control_flow = ControlFlow::Exit,
+            } => match &browser {
+                // Back to the rom list
+                Some(browser) if playing => {
+                    playing = false;
+                    window.set_title(&browser.title());
+                }
+                _ => *control_flow = ControlFlow::Exit,
+            },
             Event::WindowEvent {
                 event:
                     WindowEvent::KeyboardInput {
@@ -98,6 +378,75 @@ fn main() -> Result<()> {
                     },
                 ..
             } => {
+                if let (Some(browser), false) = (&mut browser, playing) {
+                    if state != ElementState::Pressed {
+                        return;
+                    }
+                    if keycode == VirtualKeyCode::Return {
+                        let entry = match browser.selected() {
+                            Some(entry) => entry,
+                            None => return,
+                        };
+                        let mut loaded = Vm::new().with_timer_clock(TimerClock::Realtime);
+                        let read = chippy::rom::read(&entry.path, None)
+                            .map_err(|e| e.to_string())
+                            .and_then(|bytes| match loaded.try_load(&bytes) {
+                                Ok(_) => Ok(bytes),
+                                Err(e) => Err(e.to_string()),
+                            });
+                        match read {
+                            Ok(bytes) => {
+                                checksum = chippy::rom::checksum(&bytes);
+                                header = read_header(&entry.path);
+                                palettes = palettes_of(checksum, &header);
+                                palette = palettes[0];
+                                key_hints = key_hints_of(checksum, &header);
+                                base_speed = speed_of(&header);
+                                speed = SpeedRamp::new(base_speed);
+                                vm = loaded;
+                                vm.set_quirks(quirks_of(&header));
+                                playing = true;
+                                let name = header.title.as_deref().unwrap_or(&entry.name);
+                                window.set_title(&format!("Chippy - {}", name));
+                            }
+                            Err(e) => error!("Failed to load {}: {}", entry.path.display(), e),
+                        }
+                    } else if browser.key(keycode, BROWSER_WIDTH as usize) {
+                        window.set_title(&browser.title());
+                    }
+                    return;
+                }
+
+                last_input = Instant::now();
+                if keycode == VirtualKeyCode::F9 && state == ElementState::Pressed {
+                    let next = palettes
+                        .iter()
+                        .position(|other| *other == palette)
+                        .unwrap_or(0)
+                        + 1;
+                    palette = palettes[next % palettes.len()];
+                }
+
+                if keycode == VirtualKeyCode::F1 {
+                    match state {
+                        ElementState::Pressed if key_hints.is_empty() => {
+                            window.set_title(&format!("Chippy - {}", Message::NoKeyLabels))
+                        }
+                        ElementState::Pressed => window.set_title(&key_hints),
+                        ElementState::Released => window.set_title(&title(&header)),
+                    }
+                }
+
+                if keycode == VirtualKeyCode::Tab {
+                    let target = match state {
+                        ElementState::Pressed => base_speed * FAST_FORWARD,
+                        ElementState::Released => base_speed,
+                    };
+                    if target != speed.target() {
+                        speed.ramp_to(target, SPEED_RAMP);
+                    }
+                }
+
                 // Handle keystate
                 if let Some(key) = input::to_emu_key(&keycode, mapping) {
                     match state {
@@ -106,32 +455,170 @@ fn main() -> Result<()> {
                     };
                 }
             }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } if !opts.run_unfocused => {
+                vm.set_paused(!focused);
+                if let Some(other) = &mut compare {
+                    other.set_paused(!focused);
+                }
+            }
             Event::WindowEvent {
                 event: WindowEvent::Resized(size),
                 ..
             } => {
                 pixels.resize_surface(size.width, size.height);
             }
-            // Event::WindowEvent {
-            //     event:
-            //         WindowEvent::ScaleFactorChanged {
-            //             scale_factor,
-            //             new_inner_size,
-            //         },
-            //     ..
-            // } => {
-            //     pixels.resize_surface(new_inner_size .width, new_inner_size .height);
-            // }
+            Event::WindowEvent {
+                event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                ..
+            } => {
+                pixels.resize_surface(new_inner_size.width, new_inner_size.height);
+            }
             Event::MainEventsCleared => {
-                match vm.cycle() {
-                    emu::vm::ProgramState::Continue => {}
-                    emu::vm::ProgramState::Stop => *control_flow = ControlFlow::Exit,
+                let now = Instant::now();
+                let elapsed = now - last_update;
+                last_update = now;
+                if !playing {
+                    window.request_redraw();
+                    return;
+                }
+
+                let cycles = speed.advance(elapsed).min(MAX_CYCLES_PER_UPDATE);
+                let state = (0..cycles)
+                    .map(|_| vm.cycle().unwrap_or_else(ProgramState::Error))
+                    .find(|state| {
+                        !matches!(
+                            state,
+                            ProgramState::Continue
+                                | ProgramState::Halt
+                                | ProgramState::WaitingForKey
+                        )
+                    })
+                    .unwrap_or(ProgramState::Continue);
+                if let ProgramState::Error(e) = state {
+                    error!("The program failed: {}", e);
+                }
+                let ended = matches!(state, ProgramState::Halted(_) | ProgramState::Error(_));
+                vm.tick(elapsed);
+                if let Some(other) = &mut compare {
+                    other.input.keys = vm.input.keys;
+                    for _ in 0..cycles {
+                        match other.cycle() {
+                            Ok(ProgramState::Halted(_)) => break,
+                            Ok(_) => (),
+                            Err(e) => {
+                                error!("The compared program failed: {}", e);
+                                other.request_stop();
+                            }
+                        }
+                    }
+                    other.tick(elapsed);
+                }
+                match state {
+                    ProgramState::Continue
+                    | ProgramState::Halt
+                    | ProgramState::BreakpointHit
+                    | ProgramState::WaitingForKey => {
+                        if let Some(location) = &opts.score {
+                            let score = location.read(&vm);
+                            if high_scores.submit(checksum, score) {
+                                new_high_score = true;
+                                let best = Message::BestScore.format(&[&score]);
+                                window.set_title(&format!("Chippy - {}", best));
+                            }
+                        }
+                    }
+                    // The kiosk moves on to the next rom below
+                    ProgramState::Halted(_) | ProgramState::Error(_) if playlist.is_some() => (),
+                    ProgramState::Halted(_) | ProgramState::Error(_) => match &browser {
+                        Some(browser) => {
+                            playing = false;
+                            window.set_title(&browser.title());
+                        }
+                        None => *control_flow = ControlFlow::Exit,
+                    },
+                }
+
+                if let Some(playlist) = &mut playlist {
+                    if ended || last_input.elapsed() >= kiosk_idle {
+                        playlist.advance();
+                        if let Some((loaded, bytes)) = load_playlist_rom(playlist) {
+                            checksum = chippy::rom::checksum(&bytes);
+                            header = playlist.current().map(read_header).unwrap_or_default();
+                            palettes = palettes_of(checksum, &header);
+                            palette = palettes[0];
+                            key_hints = key_hints_of(checksum, &header);
+                            base_speed = speed_of(&header);
+                            speed = SpeedRamp::new(base_speed);
+                            vm = loaded;
+                            vm.set_quirks(quirks_of(&header));
+                            window.set_title(&title(&header));
+                        }
+                        last_input = Instant::now();
+                    }
+                }
+
+                if let Some((recorder, audio)) = &mut video {
+                    let display = vm.gpu.display();
+                    let scale = render::fit_scale(display.len(), VIDEO_SCALE);
+                    let frame = render::to_rgba(display, scale, palette);
+                    if let Err(e) = recorder.push(&frame) {
+                        error!("Failed to record video frame: {}", e);
+                    }
+                    audio.push(vm.sound_active());
                 }
 
                 window.request_redraw();
             }
             Event::RedrawEventsCleared => {
-                update_buffer(&vm.gpu, pixels.get_frame());
+                let size = buffer_size(playing, compare.is_some());
+                if size != buffer {
+                    buffer = size;
+                    pixels.resize_buffer(size.0, size.1);
+                }
+
+                match &mut browser {
+                    Some(browser) if !playing => {
+                        browser.draw(pixels.get_frame(), buffer.0 as usize, buffer.1 as usize)
+                    }
+                    _ => {
+                        let display = vm.gpu.display();
+                        let intensity = &mut intensity[..display.len()];
+                        blender.blend_into(display, intensity);
+                        match &compare {
+                            Some(other) => {
+                                let display = other.gpu.display();
+                                let compare_intensity = &mut compare_intensity[..display.len()];
+                                compare_blender.blend_into(display, compare_intensity);
+                                draw_side_by_side(
+                                    intensity,
+                                    compare_intensity,
+                                    palette,
+                                    pixels.get_frame(),
+                                );
+                            }
+                            None => render::draw_intensity_rgba(
+                                intensity,
+                                render::fit_scale(intensity.len(), DISPLAY_SCALE),
+                                palette,
+                                pixels.get_frame(),
+                            ),
+                        }
+                    }
+                }
+
+                #[cfg(feature = "stream")]
+                if let Some(stream) = &mut stream {
+                    let display = vm.gpu.display();
+                    let scale = render::fit_scale(display.len(), STREAM_SCALE);
+                    let frame = render::to_rgba(display, scale, palette);
+                    let (width, height) = (vm.gpu.width() * scale, vm.gpu.height() * scale);
+                    if let Err(e) = stream.send(&frame, width as u16, height as u16) {
+                        error!("Failed to stream frame: {}", e);
+                    }
+                }
 
                 if pixels
                     .render()
@@ -139,10 +626,110 @@ fn main() -> Result<()> {
                     .is_err()
                 {
                     *control_flow = ControlFlow::Exit;
-                    return;
+                }
+            }
+            Event::LoopDestroyed => {
+                if new_high_score {
+                    if let Err(e) = high_scores.save(&scores_file) {
+                        error!("Failed to write high scores: {}", e);
+                    }
+                }
+                if let Some((recorder, audio)) = video.take() {
+                    if let Err(e) = recorder.finish(Some(&audio)) {
+                        error!("Failed to write video: {}", e);
+                    }
+                }
+                if let (Some(exit_code), Some(StopReason::Exit)) =
+                    (&opts.exit_code, vm.stop_reason())
+                {
+                    std::process::exit(exit_code.code(&vm));
                 }
             }
             _ => (),
         }
     });
 }
+
+/// Load the current rom of the playlist, moving past the roms that can not be read or loaded
+fn load_playlist_rom(playlist: &mut Playlist) -> Option<(Vm, Vec<u8>)> {
+    for _ in 0..playlist.len() {
+        let path = playlist.current()?;
+        let mut vm = Vm::new().with_timer_clock(TimerClock::Realtime);
+        match chippy::rom::read(path, None) {
+            Ok(bytes) => match vm.try_load(&bytes) {
+                Ok(_) => return Some((vm, bytes)),
+                Err(e) => error!("Failed to load {}: {}", path.display(), e),
+            },
+            Err(e) => error!("Failed to open {}: {}", path.display(), e),
+        }
+        playlist.advance();
+    }
+    None
+}
+
+/// Header of the rom at `path`, an empty header when the rom has none or it can not be read
+fn read_header(path: &Path) -> RomHeader {
+    let header = RomHeader::load_for(path)
+        .unwrap_or_else(|e| {
+            error!("Failed to read the header of {}: {}", path.display(), e);
+            None
+        })
+        .unwrap_or_default();
+    let (_, unsupported) = Quirks::from_names(header.quirks.iter().map(String::as_str));
+    if !unsupported.is_empty() {
+        warn!(
+            "Ignoring the quirks of {}, they are not supported: {}",
+            path.display(),
+            unsupported.join(", ")
+        );
+    }
+    header
+}
+
+fn quirks_of(header: &RomHeader) -> Quirks {
+    Quirks::from_names(header.quirks.iter().map(String::as_str)).0
+}
+
+/// Window title naming the rom when its header gives a title
+fn title(header: &RomHeader) -> String {
+    match &header.title {
+        Some(title) => format!("Chippy - {}", title),
+        None => "Chippy".to_string(),
+    }
+}
+
+/// Size of the pixel buffer: the chip8 display at the high resolution while playing, two of
+/// them side by side when comparing, the rom browser otherwise
+fn buffer_size(playing: bool, compare: bool) -> (u32, u32) {
+    match (playing, compare) {
+        (true, false) => (gpu::HIRES_WIDTH as u32, gpu::HIRES_HEIGHT as u32),
+        (true, true) => (
+            (2 * gpu::HIRES_WIDTH + COMPARE_GAP) as u32,
+            gpu::HIRES_HEIGHT as u32,
+        ),
+        (false, _) => (BROWSER_WIDTH, BROWSER_HEIGHT),
+    }
+}
+
+/// Draw the pixel intensities of two displays next to each other into a buffer of the size
+/// given by `buffer_size` when comparing
+fn draw_side_by_side(left: &[u8], right: &[u8], palette: Palette, buffer: &mut [u8]) {
+    let row = gpu::HIRES_WIDTH * 4;
+    let gap = COMPARE_GAP * 4;
+    let mut half = vec![0; row * gpu::HIRES_HEIGHT];
+    for (intensity, offset) in [(left, 0), (right, row + gap)].iter() {
+        let scale = render::fit_scale(intensity.len(), DISPLAY_SCALE);
+        render::draw_intensity_rgba(intensity, scale, palette, &mut half);
+        for (line, pixels) in buffer
+            .chunks_exact_mut(2 * row + gap)
+            .zip(half.chunks_exact(row))
+        {
+            line[*offset..*offset + row].copy_from_slice(pixels);
+        }
+    }
+    for line in buffer.chunks_exact_mut(2 * row + gap) {
+        for pixel in line[row..row + gap].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&COMPARE_GAP_COLOR);
+        }
+    }
+}